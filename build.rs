@@ -0,0 +1,21 @@
+//! Generates the C header for the `capi` feature.
+
+#[cfg(feature = "capi")]
+fn main() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let config =
+        cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir))
+            .expect("could not read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/capi.rs", dir))
+        .generate()
+        .expect("could not generate C header")
+        .write_to_file(format!("{}/rustventure.h", out));
+}
+
+#[cfg(not(feature = "capi"))]
+fn main() {}
@@ -2,20 +2,70 @@
 //! directory tree.
 
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
 use yaml_rust::{Yaml, YamlLoader};
 
-use crate::scene::Scene;
+use crate::ambient::{self, Event};
+use crate::clock::Clock;
+use crate::item::{self, Item};
+use crate::npc::{self, Npc};
+use crate::output::Stylesheet;
+use crate::scene::{Action, Scene};
+use crate::warning::Warning;
 
-#[derive(Debug, PartialEq, Eq)]
+/// Keys `about.yaml` may contain.
+const ABOUT_KEYS: &[&str] = &[
+    "name",
+    "author",
+    "version",
+    "prompt",
+    "variables",
+    "show_exits",
+    "tolerate_typos",
+    "pronouns",
+    "chain_commands",
+    "abbreviations",
+    "commands",
+    "styles",
+    "start",
+    "cover",
+    "banner",
+    "clock",
+    "events",
+    "language",
+    "known_words",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Adventure {
     name: String,
     author: String,
     version: Option<String>,
+    prompt: Option<String>,
+    variables: BTreeMap<String, String>,
+    items: BTreeMap<String, Item>,
+    npcs: BTreeMap<String, Npc>,
+    show_exits: bool,
+    tolerate_typos: bool,
+    pronouns: bool,
+    chain_commands: bool,
+    abbreviations: BTreeMap<String, String>,
+    /// Global commands: keyword -> effect
+    commands: BTreeMap<String, String>,
+    styles: Stylesheet,
     start: PathBuf,
+    cover: Option<PathBuf>,
+    banner: bool,
+    clock: Option<Clock>,
+    events: Vec<Event>,
+    language: Option<String>,
+    known_words: Vec<String>,
 }
 
 // Maybe these macros aren't necessary, but they are good practice. :D
@@ -32,6 +82,183 @@ macro_rules! get_optional_field {
     }};
 }
 
+/// Convert scalar YAML values to strings, so numbers and booleans
+/// can be written without quotes.
+fn scalar_string(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Load the optional data file `name` (with `.yaml` or `.yml`
+/// extension) next to `about`, or return an empty map if there is
+/// none.
+fn load_optional<T, F>(
+    about: &Path,
+    name: &str,
+    load: F,
+) -> Result<BTreeMap<String, T>, Box<dyn Error>>
+where
+    F: Fn(&Path) -> Result<BTreeMap<String, T>, Box<dyn Error>>,
+{
+    for ext in ["yaml", "yml"] {
+        let path = about.with_file_name(format!("{}.{}", name, ext));
+        if path.is_file() {
+            return load(&path);
+        }
+    }
+    Ok(BTreeMap::new())
+}
+
+/// Default abbreviations of common commands.
+pub const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("x", "examine"),
+    ("i", "inventory"),
+    ("n", "go north"),
+    ("s", "go south"),
+    ("e", "go east"),
+    ("w", "go west"),
+    ("ne", "go northeast"),
+    ("nw", "go northwest"),
+    ("se", "go southeast"),
+    ("sw", "go southwest"),
+    ("u", "go up"),
+    ("d", "go down"),
+];
+
+/// [`ABBREVIATIONS`] as a map.
+pub fn default_abbreviations() -> BTreeMap<String, String> {
+    ABBREVIATIONS
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Parse the `abbreviations` hash and merge it into the defaults, an
+/// empty value removes an abbreviation.
+fn parse_abbreviations(
+    about: &Hash,
+) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let mut abbreviations = default_abbreviations();
+    let hash = match about.get(&Yaml::from_str("abbreviations")) {
+        Some(Yaml::Hash(h)) => h,
+        _ => return Ok(abbreviations),
+    };
+    for (short, long) in hash {
+        let short = scalar_string(short).ok_or("invalid abbreviation")?;
+        match long {
+            Yaml::Null => abbreviations.remove(&short),
+            long => abbreviations.insert(
+                short.clone(),
+                scalar_string(long)
+                    .ok_or(format!("invalid abbreviation: {}", short))?,
+            ),
+        };
+    }
+    abbreviations.retain(|_, long| !long.is_empty());
+    Ok(abbreviations)
+}
+
+/// Action line of the global command `keyword`.
+fn command_action(keyword: &str, effect: &str) -> String {
+    format!("!kw:{} -> {}", keyword, effect)
+}
+
+/// Parse the `commands` hash, mapping keywords to effects like in
+/// actions, e.g. `pray: print You feel calmer. [if has(amulet)]`.
+fn parse_commands(
+    about: &Hash,
+) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let hash = match about.get(&Yaml::from_str("commands")) {
+        Some(Yaml::Hash(h)) => h,
+        Some(_) => return Err("invalid commands, must be a hash".into()),
+        None => return Ok(BTreeMap::new()),
+    };
+    let mut commands = BTreeMap::new();
+    for (keyword, effect) in hash {
+        let keyword = scalar_string(keyword).ok_or("invalid command")?;
+        let effect = effect
+            .as_str()
+            .ok_or(format!("invalid command: {}", keyword))?;
+        Action::new(&command_action(&keyword, effect))
+            .map_err(|e| format!("invalid command {}: {}", keyword, e))?;
+        commands.insert(keyword, effect.to_string());
+    }
+    Ok(commands)
+}
+
+/// Parse the `styles` hash, mapping style names to attribute lists.
+fn parse_styles(about: &Hash) -> Result<Stylesheet, Box<dyn Error>> {
+    let mut styles = Stylesheet::default();
+    let hash = match about.get(&Yaml::from_str("styles")) {
+        Some(Yaml::Hash(h)) => h,
+        _ => return Ok(styles),
+    };
+    for (name, attrs) in hash {
+        let name = name.as_str().ok_or("invalid style name")?;
+        let attrs = match attrs {
+            Yaml::Array(a) => a
+                .iter()
+                .filter_map(|a| a.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            other => other
+                .as_str()
+                .ok_or(format!("invalid style: {}", name))?
+                .to_string(),
+        };
+        styles.add(name, &attrs)?;
+    }
+    Ok(styles)
+}
+
+/// Parse the optional `clock` hash with `start` time, duration of a
+/// `turn`, and additional `periods` of the day. An empty hash enables
+/// the clock with default settings.
+fn parse_clock(about: &Hash) -> Result<Option<Clock>, Box<dyn Error>> {
+    let hash = match about.get(&Yaml::from_str("clock")) {
+        Some(Yaml::Hash(h)) => h,
+        Some(Yaml::Null) | Some(Yaml::Boolean(true)) => {
+            return Ok(Some(Clock::default()))
+        }
+        Some(Yaml::Boolean(false)) | None => return Ok(None),
+        Some(_) => return Err("invalid clock, must be hash".into()),
+    };
+    let mut clock = Clock::default();
+    if let Some(start) = hash.get(&Yaml::from_str("start")) {
+        clock
+            .set_start(&scalar_string(start).ok_or("invalid clock start")?)?;
+    }
+    if let Some(turn) = hash.get(&Yaml::from_str("turn")) {
+        clock.set_turn(&scalar_string(turn).ok_or("invalid clock turn")?)?;
+    }
+    if let Some(Yaml::Hash(periods)) = hash.get(&Yaml::from_str("periods")) {
+        for (name, range) in periods {
+            let name = name.as_str().ok_or("invalid period name")?;
+            let range =
+                range.as_str().ok_or(format!("invalid period: {}", name))?;
+            clock.add_period(name, range)?;
+        }
+    }
+    Ok(Some(clock))
+}
+
+/// Parse the optional `known_words` list, words the spell-check
+/// accepts in addition to the dictionary.
+fn parse_known_words(about: &Hash) -> Result<Vec<String>, Box<dyn Error>> {
+    match about.get(&Yaml::from_str("known_words")) {
+        Some(Yaml::Array(words)) => words
+            .iter()
+            .map(|w| scalar_string(w).ok_or("invalid known word".into()))
+            .collect(),
+        Some(_) => Err("invalid known_words, must be a list".into()),
+        None => Ok(Vec::new()),
+    }
+}
+
 macro_rules! get_field {
     ($hash:ident, $field:ident) => {
         get_optional_field!($hash, $field).ok_or(stringify!(missing $field))
@@ -42,18 +269,69 @@ impl TryFrom<&Path> for Adventure {
     type Error = Box<dyn Error>;
 
     fn try_from(p: &Path) -> Result<Self, Self::Error> {
+        Ok(Adventure::read(p)?.0)
+    }
+}
+
+impl Adventure {
+    /// Read the adventure from its `about.yaml` file `p`, warning
+    /// about unknown keys and missing files.
+    fn read(p: &Path) -> Result<(Adventure, Vec<Warning>), Box<dyn Error>> {
+        enter_span!("load_adventure", path = %p.display());
         let s = fs::read_to_string(p)?;
         let docs = YamlLoader::load_from_str(&s)?;
         let about = docs
-            .get(0)
+            .first()
             .ok_or("no data in file")?
             .as_hash()
             .ok_or("invalid data, must be hash")?;
 
-        Ok(Adventure {
+        let mut warnings: Vec<Warning> = about
+            .keys()
+            .filter(|k| !k.as_str().is_some_and(|k| ABOUT_KEYS.contains(&k)))
+            .map(|k| {
+                let key =
+                    scalar_string(k).unwrap_or_else(|| format!("{:?}", k));
+                Warning::new(p, format!("unknown key: {}", key))
+            })
+            .collect();
+        let adventure = Adventure {
             name: get_field!(about, name)?,
             author: get_field!(about, author)?,
             version: get_optional_field!(about, version),
+            prompt: get_optional_field!(about, prompt),
+            variables: about
+                .get(&Yaml::from_str("variables"))
+                .and_then(|v| v.as_hash())
+                .map(|vars| {
+                    vars.iter()
+                        .filter_map(|(k, v)| {
+                            Some((scalar_string(k)?, scalar_string(v)?))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            items: load_optional(p, "items", item::load)?,
+            npcs: load_optional(p, "npcs", npc::load)?,
+            show_exits: about
+                .get(&Yaml::from_str("show_exits"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            tolerate_typos: about
+                .get(&Yaml::from_str("tolerate_typos"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            pronouns: about
+                .get(&Yaml::from_str("pronouns"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            chain_commands: about
+                .get(&Yaml::from_str("chain_commands"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            abbreviations: parse_abbreviations(about)?,
+            commands: parse_commands(about)?,
+            styles: parse_styles(about)?,
             start: {
                 let mut path = p.to_path_buf();
                 path.set_file_name(
@@ -62,7 +340,26 @@ impl TryFrom<&Path> for Adventure {
                 );
                 path
             },
-        })
+            cover: get_optional_field!(about, cover)
+                .map(|c| p.with_file_name(c)),
+            banner: about
+                .get(&Yaml::from_str("banner"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            clock: parse_clock(about)?,
+            events: about
+                .get(&Yaml::from_str("events"))
+                .map_or(Ok(Vec::new()), ambient::from_yaml)?,
+            language: get_optional_field!(about, language),
+            known_words: parse_known_words(about)?,
+        };
+        if let Some(cover) = adventure.cover().filter(|c| !c.is_file()) {
+            warnings.push(Warning::new(
+                p,
+                format!("missing cover art: {}", cover.display()),
+            ));
+        }
+        Ok((adventure, warnings))
     }
 }
 
@@ -81,6 +378,122 @@ impl fmt::Display for Adventure {
 }
 
 impl Adventure {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Default prompt for the adventure, if it doesn't use the
+    /// engine default.
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// Item definitions from `items.yaml`, by id.
+    pub fn items(&self) -> &BTreeMap<String, Item> {
+        &self.items
+    }
+
+    /// NPC definitions from `npcs.yaml`, by id.
+    pub fn npcs(&self) -> &BTreeMap<String, Npc> {
+        &self.npcs
+    }
+
+    /// Whether to list exits after scene descriptions.
+    pub fn show_exits(&self) -> bool {
+        self.show_exits
+    }
+
+    /// Whether keyword actions accept input with a typo, within edit
+    /// distance 1.
+    pub fn tolerate_typos(&self) -> bool {
+        self.tolerate_typos
+    }
+
+    /// Whether "it" and "them" in input refer to the last thing the
+    /// player handled.
+    pub fn pronouns(&self) -> bool {
+        self.pronouns
+    }
+
+    /// Whether players may chain commands in one line with "then",
+    /// "and", or ".".
+    pub fn chain_commands(&self) -> bool {
+        self.chain_commands
+    }
+
+    /// Abbreviations for the first word of commands, the defaults
+    /// merged with those of the adventure.
+    pub fn abbreviations(&self) -> &BTreeMap<String, String> {
+        &self.abbreviations
+    }
+
+    /// Commands available in every scene, as keyword actions. Actions
+    /// of the current scene take precedence over them, and they take
+    /// precedence over built-in commands.
+    pub fn commands(&self) -> Vec<Action> {
+        self.commands
+            .iter()
+            .filter_map(|(k, e)| Action::new(&command_action(k, e)).ok())
+            .collect()
+    }
+
+    /// Text styles the adventure defines for markup in scenes.
+    pub fn styles(&self) -> &Stylesheet {
+        &self.styles
+    }
+
+    /// Initial values of game variables.
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    /// Directory containing the adventure.
+    pub fn dir(&self) -> &Path {
+        self.start.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Path of the start scene file.
+    pub fn start_path(&self) -> &Path {
+        &self.start
+    }
+
+    /// Cover art file (usually `cover.txt`, or `cover.ans` with ANSI
+    /// colors) to show when the adventure starts.
+    pub fn cover(&self) -> Option<&Path> {
+        self.cover.as_deref()
+    }
+
+    /// Whether to show the name in large letters when the adventure
+    /// starts.
+    pub fn banner(&self) -> bool {
+        self.banner
+    }
+
+    /// Settings of the in-game clock, if the adventure uses one.
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    /// Ambient events that may happen after turns.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Language of the adventure text, as the name of a Hunspell
+    /// dictionary like `en_US`, to spell-check it.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Words the spell-check accepts, like names.
+    pub fn known_words(&self) -> &[String] {
+        &self.known_words
+    }
+
     /// Load the start scene of the adventure, consuming `self` to
     /// avoid copying the `PathBuf`.
     pub fn start(self) -> Result<Scene, Box<dyn Error>> {
@@ -88,6 +501,35 @@ impl Adventure {
     }
 }
 
+/// Path of the `about.yaml` file for `path`, which may be the
+/// adventure directory or the file itself.
+pub(crate) fn about_file(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        let yaml = path.join("about.yaml");
+        if yaml.exists() {
+            yaml
+        } else {
+            path.join("about.yml")
+        }
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Load the adventure in `path`, which may be the adventure directory
+/// or its `about.yaml` file.
+pub fn load(path: &Path) -> Result<Adventure, Box<dyn Error>> {
+    Adventure::try_from(about_file(path).as_path())
+}
+
+/// Load the adventure in `path` like [`load`], also returning likely
+/// mistakes in `about.yaml`, like unknown keys.
+pub fn load_with_warnings(
+    path: &Path,
+) -> Result<(Adventure, Vec<Warning>), Box<dyn Error>> {
+    Adventure::read(&about_file(path))
+}
+
 /// Find adventures inside the given `dir`. Assumes that every
 /// directory containing an `about.yaml` or `about.yml` file is an
 /// adventure.
@@ -111,6 +553,7 @@ pub fn search(dir: &Path) -> Result<Vec<Adventure>, Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture::TempAdventure;
 
     fn kitten_adventure() -> Adventure {
         let start: PathBuf =
@@ -121,7 +564,24 @@ mod tests {
             name: "A cuddly kitten".to_string(),
             author: "Fiona".to_string(),
             version: Some("1.0".to_string()),
+            prompt: None,
+            variables: BTreeMap::new(),
+            items: BTreeMap::new(),
+            npcs: BTreeMap::new(),
+            show_exits: false,
+            tolerate_typos: false,
+            pronouns: false,
+            chain_commands: false,
+            abbreviations: default_abbreviations(),
+            commands: BTreeMap::new(),
+            styles: Stylesheet::default(),
             start,
+            cover: None,
+            banner: false,
+            clock: None,
+            events: Vec::new(),
+            language: None,
+            known_words: Vec::new(),
         }
     }
 
@@ -150,15 +610,101 @@ mod tests {
             name: "Test Adventure".to_string(),
             author: "Me".to_string(),
             version: None,
+            prompt: None,
+            variables: BTreeMap::new(),
+            items: BTreeMap::new(),
+            npcs: BTreeMap::new(),
+            show_exits: false,
+            tolerate_typos: false,
+            pronouns: false,
+            chain_commands: false,
+            abbreviations: default_abbreviations(),
+            commands: BTreeMap::new(),
+            styles: Stylesheet::default(),
             start: PathBuf::from("test.scene"),
+            cover: None,
+            banner: false,
+            clock: None,
+            events: Vec::new(),
+            language: None,
+            known_words: Vec::new(),
         };
         assert_eq!(format!("{}", about), "\"Test Adventure\" by Me");
     }
 
+    #[test]
+    fn load_variables() {
+        let fixture = TempAdventure::new("variables", &[]);
+        let dir = fixture.dir();
+        let path = dir.join("about.yaml");
+        fs::write(
+            &path,
+            "name: Test\nauthor: Me\nvariables:\n  lives: 3\n  dark: no\n\
+             tolerate_typos: true\ncover: cover.ans\n\
+             abbreviations:\n  l: look\n  d: ~\n  x: inspect\n\
+             commands:\n  pray: print You feel calmer.\n\
+             clock:\n  start: 21:00\n  turn: 5m\n",
+        )
+        .unwrap();
+        let (about, warnings) = Adventure::read(&path).unwrap();
+        fs::write(&path, "name: Test\nauthor: Me\ncommands:\n  pray: ~\n")
+            .unwrap();
+        let invalid = Adventure::read(&path);
+        assert_eq!(invalid.unwrap_err().to_string(), "invalid command: pray");
+        assert_eq!(
+            warnings.iter().map(|w| &w.message).collect::<Vec<_>>(),
+            [&format!(
+                "missing cover art: {}",
+                dir.join("cover.ans").display()
+            )]
+        );
+        assert!(about.tolerate_typos());
+        let abbreviations = about.abbreviations();
+        assert_eq!(abbreviations["l"], "look");
+        assert_eq!(abbreviations["x"], "inspect");
+        assert_eq!(abbreviations["n"], "go north");
+        assert!(!abbreviations.contains_key("d"));
+        let commands = about.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].keyword(), Some("pray"));
+        let clock = about.clock().unwrap();
+        assert_eq!(
+            (clock.turn(), clock.time(0).to_string()),
+            (5, "21:00".into())
+        );
+        assert_eq!(about.cover(), Some(dir.join("cover.ans").as_path()));
+        assert_eq!(
+            about.variables().iter().collect::<Vec<_>>(),
+            [
+                (&"dark".to_string(), &"no".to_string()),
+                (&"lives".to_string(), &"3".to_string())
+            ]
+        );
+    }
+
     #[test]
     fn search_adventure() {
         let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         let adventures = search(&dir as &Path).unwrap();
         assert_eq!(adventures, vec![kitten_adventure()]);
     }
+
+    #[test]
+    fn serialize_adventure() {
+        let mut about = kitten_adventure();
+        about.items.insert("yarn".to_string(), Item::new("yarn"));
+        about.clock = Some(Clock::default());
+        about
+            .events
+            .push(Event::new("Purr.", 5).with_tags(&["home"]));
+        let json = serde_json::to_value(&about).unwrap();
+        assert_eq!(json["name"], "A cuddly kitten");
+        assert_eq!(json["version"], "1.0");
+        assert_eq!(json["items"]["yarn"]["name"], "yarn");
+        assert_eq!(json["clock"]["start"], 480);
+        assert_eq!(json["clock"]["periods"]["night"]["from"], 1320);
+        assert_eq!(json["events"][0]["tags"][0], "home");
+        let back: Adventure = serde_json::from_value(json).unwrap();
+        assert_eq!(back, about);
+    }
 }
@@ -0,0 +1,143 @@
+//! Ambient events: text printed at random after turns, like weather
+//! or background noises. Adventures declare them in the `events`
+//! list of `about.yaml`, each with the percent chance to happen per
+//! turn, and optionally the scenes or scene tags it can happen in:
+//!
+//! ```yaml
+//! events:
+//!   - text: A cold wind blows.
+//!     chance: 10
+//!     scenes: [field]
+//!     tags: [forest]
+//!   - text: You hear a distant bell.
+//!     chance: 2
+//! ```
+//!
+//! At most one event happens per turn, earlier events win.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use yaml_rust::Yaml;
+
+use crate::scene::Scene;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    text: String,
+    /// Chance to happen per turn in percent
+    chance: u32,
+    /// Scenes the event can happen in
+    scenes: Vec<String>,
+    /// Tags of scenes the event can happen in. Without scenes and
+    /// tags it can happen anywhere.
+    tags: Vec<String>,
+}
+
+fn strings(value: &Yaml) -> Vec<&str> {
+    match value {
+        Yaml::Array(a) => a.iter().filter_map(|s| s.as_str()).collect(),
+        Yaml::String(s) => vec![s.as_str()],
+        _ => Vec::new(),
+    }
+}
+
+impl Event {
+    pub fn new(text: &str, chance: u32) -> Event {
+        Event {
+            text: text.to_string(),
+            chance,
+            scenes: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Limit the event to the given scenes.
+    pub fn in_scenes(mut self, scenes: &[&str]) -> Event {
+        self.scenes = scenes.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Limit the event to scenes with one of the given tags, in
+    /// addition to the scenes set with [`in_scenes`](Event::in_scenes).
+    pub fn with_tags(mut self, tags: &[&str]) -> Event {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Chance of the event to happen per turn, in percent.
+    pub fn chance(&self) -> u32 {
+        self.chance
+    }
+
+    /// Whether the event can happen in `scene`.
+    pub fn applies(&self, scene: &Scene) -> bool {
+        (self.scenes.is_empty() && self.tags.is_empty())
+            || self.scenes.iter().any(|s| s == scene.name())
+            || self.tags.iter().any(|t| scene.meta().has_tag(t))
+    }
+
+    fn from_yaml(def: &Yaml) -> Result<Event, Box<dyn Error>> {
+        let text = def["text"].as_str().ok_or("event without text")?;
+        let chance = def["chance"]
+            .as_i64()
+            .filter(|c| (1..=100).contains(c))
+            .ok_or(format!("event needs a chance of 1 to 100: {}", text))?;
+        Ok(Event::new(text, chance as u32)
+            .in_scenes(&strings(&def["scenes"]))
+            .with_tags(&strings(&def["tags"])))
+    }
+}
+
+/// Read the `events` list of `about.yaml`.
+pub fn from_yaml(list: &Yaml) -> Result<Vec<Event>, Box<dyn Error>> {
+    match list {
+        Yaml::Array(a) => a.iter().map(Event::from_yaml).collect(),
+        Yaml::BadValue | Yaml::Null => Ok(Vec::new()),
+        _ => Err("invalid events, must be list".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn parse_events() {
+        let docs = YamlLoader::load_from_str(
+            "- text: A cold wind blows.\n  chance: 10\n  scenes: [field]\n\
+             \x20 tags: forest\n\
+             - text: A bell rings.\n  chance: 2\n",
+        )
+        .unwrap();
+        let events = from_yaml(&docs[0]).unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::new("A cold wind blows.", 10)
+                    .in_scenes(&["field"])
+                    .with_tags(&["forest"]),
+                Event::new("A bell rings.", 2),
+            ]
+        );
+        let scene = |name: &str, text| {
+            Scene::parse(PathBuf::from(format!("{}.scene", name)), text)
+                .unwrap()
+        };
+        assert!(!events[0].applies(&scene("cave", "A cave.\n")));
+        assert!(events[0].applies(&scene("field", "A field.\n")));
+        assert!(events[0]
+            .applies(&scene("glade", "@tags: forest, outdoors\nA glade.\n")));
+        assert!(events[1].applies(&scene("cave", "A cave.\n")));
+
+        let docs =
+            YamlLoader::load_from_str("- text: Rain.\n  chance: 200\n")
+                .unwrap();
+        assert!(from_yaml(&docs[0]).is_err());
+    }
+}
@@ -0,0 +1,515 @@
+//! Opt-in analytics for playtests: the engine reports scene changes
+//! and the end of the game to an [`Analytics`] implementation, so
+//! authors can find out where players get stuck. `rustventure
+//! --analytics <file>` records them with [`FileLog`], and `rustventure
+//! analyze` turns a directory of such logs into a [`Report`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::scene::graph::SceneGraph;
+
+/// Scenes reached by fewer than this share of the playtests are
+/// flagged as rarely reached.
+pub const RARE: f64 = 0.1;
+
+/// File in the adventure directory that `--author-feedback` appends
+/// unmatched input to.
+pub const UNMATCHED_LOG: &str = "unmatched.log";
+
+/// Receives game events, set with
+/// [`Engine::set_analytics`](crate::engine::Engine::set_analytics).
+/// The default methods do nothing. Errors are logged, they don't
+/// interrupt the game.
+pub trait Analytics: fmt::Debug {
+    /// The player left the scene `from` for `to` on turn `turn`.
+    fn scene_change(
+        &mut self,
+        _from: &str,
+        _to: &str,
+        _turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// The game ended in `scene` on turn `turn`.
+    fn ending(
+        &mut self,
+        _scene: &str,
+        _turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// The player entered `input` in `scene` on turn `turn`, and it
+    /// matched neither an action nor a built-in command.
+    fn unmatched(
+        &mut self,
+        _scene: &str,
+        _input: &str,
+        _turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Analytics that record nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoAnalytics;
+
+impl Analytics for NoAnalytics {}
+
+/// One recorded event, a line in the log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    /// `scene_change`, `ending`, or `unmatched`
+    pub event: String,
+    /// Scene the event happened in
+    pub scene: String,
+    /// Scene the player moved to, for scene changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Input of the player, for unmatched input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    pub turn: u64,
+}
+
+impl Record {
+    fn new(event: &str, scene: &str, turn: u64) -> Self {
+        Record {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            event: event.to_string(),
+            scene: scene.to_string(),
+            target: None,
+            input: None,
+            turn,
+        }
+    }
+}
+
+/// Quote a CSV field if necessary.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Appends events to a file, as CSV if the file name ends in `.csv`,
+/// otherwise as JSON lines:
+///
+/// ```json
+/// {"time":1700000000,"event":"scene_change","scene":"field","target":"cave","turn":3}
+/// {"time":1700000042,"event":"ending","scene":"cave","turn":9}
+/// ```
+#[derive(Debug)]
+pub struct FileLog {
+    file: File,
+    csv: bool,
+}
+
+impl FileLog {
+    pub fn open(path: &Path) -> Result<FileLog, Box<dyn Error>> {
+        let csv = path.extension().is_some_and(|e| e == "csv");
+        let mut file =
+            OpenOptions::new().create(true).append(true).open(path)?;
+        if csv && file.metadata()?.len() == 0 {
+            writeln!(file, "time,event,scene,target,input,turn")?;
+        }
+        Ok(FileLog { file, csv })
+    }
+
+    fn write(&mut self, record: Record) -> Result<(), Box<dyn Error>> {
+        // One write per line, so lines stay whole if the game crashes.
+        let line = if self.csv {
+            format!(
+                "{},{},{},{},{},{}\n",
+                record.time,
+                record.event,
+                csv_field(&record.scene),
+                csv_field(record.target.as_deref().unwrap_or_default()),
+                csv_field(record.input.as_deref().unwrap_or_default()),
+                record.turn
+            )
+        } else {
+            format!("{}\n", serde_json::to_string(&record)?)
+        };
+        self.file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Analytics for FileLog {
+    fn scene_change(
+        &mut self,
+        from: &str,
+        to: &str,
+        turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut record = Record::new("scene_change", from, turn);
+        record.target = Some(to.to_string());
+        self.write(record)
+    }
+
+    fn ending(
+        &mut self,
+        scene: &str,
+        turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write(Record::new("ending", scene, turn))
+    }
+
+    fn unmatched(
+        &mut self,
+        scene: &str,
+        input: &str,
+        turn: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut record = Record::new("unmatched", scene, turn);
+        record.input = Some(input.to_string());
+        self.write(record)
+    }
+}
+
+/// Append `unmatched` input by scene (see
+/// [`Engine::unmatched`](crate::engine::Engine::unmatched)) to the
+/// file `path`, one `scene: input` line each, so authors see what
+/// players tried.
+pub fn append_unmatched(
+    path: &Path,
+    unmatched: &BTreeMap<String, Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut text = String::new();
+    for (scene, inputs) in unmatched {
+        for input in inputs {
+            text.push_str(&format!("{}: {}\n", scene, input));
+        }
+    }
+    if !text.is_empty() {
+        let mut file =
+            OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Read the JSON lines logs in `dir`, split into playtests: a log
+/// gets a new playtest after each `ending`.
+pub fn read_logs(dir: &Path) -> Result<Vec<Vec<Record>>, Box<dyn Error>> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|f| f.extension().is_some_and(|e| e == "jsonl"));
+    files.sort();
+    let mut playtests = Vec::new();
+    for file in files {
+        let mut current = Vec::new();
+        for (n, line) in fs::read_to_string(&file)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(line).map_err(|e| {
+                format!("{}: line {}: {}", file.display(), n + 1, e)
+            })?;
+            let end = record.event == "ending";
+            current.push(record);
+            if end {
+                playtests.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.is_empty() {
+            playtests.push(current);
+        }
+    }
+    Ok(playtests)
+}
+
+/// What the playtests did in one scene.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneStats {
+    pub name: String,
+    /// How often players entered the scene
+    pub visits: usize,
+    /// Number of playtests that reached the scene
+    pub playtests: usize,
+    /// Turns spent in the scene, for visits with a known start
+    turns: u64,
+    timed_visits: usize,
+    /// Inputs that matched nothing, with how often they were tried
+    pub failed: BTreeMap<String, usize>,
+    /// Whether the adventure has the scene
+    pub known: bool,
+}
+
+impl SceneStats {
+    /// Average number of turns players spent in the scene.
+    pub fn average_turns(&self) -> Option<f64> {
+        (self.timed_visits > 0)
+            .then(|| self.turns as f64 / self.timed_visits as f64)
+    }
+
+    /// The `n` most common failed inputs, with their counts.
+    pub fn common_failed(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut failed: Vec<(&str, usize)> =
+            self.failed.iter().map(|(i, c)| (i.as_str(), *c)).collect();
+        failed.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        failed.truncate(n);
+        failed
+    }
+}
+
+/// Per-scene summary of playtest logs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub playtests: usize,
+    /// Scenes of the adventure in breadth first order, then scenes
+    /// that only appear in the logs
+    pub scenes: Vec<SceneStats>,
+}
+
+impl Report {
+    /// Summarize `playtests` (see [`read_logs`]) of the adventure
+    /// with the scene `graph`.
+    pub fn new(graph: &SceneGraph, playtests: &[Vec<Record>]) -> Report {
+        let mut report = Report {
+            playtests: playtests.len(),
+            scenes: graph
+                .scenes()
+                .map(|s| SceneStats {
+                    name: s.name().to_string(),
+                    known: true,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        for playtest in playtests {
+            let mut reached = BTreeSet::new();
+            // The first scene of a playtest was entered at an unknown
+            // turn, the game may have continued from a save.
+            let mut entered = None;
+            if let Some(first) = playtest.first() {
+                report.scene(&first.scene).visits += 1;
+                reached.insert(first.scene.as_str());
+            }
+            for record in playtest {
+                let stats = report.scene(&record.scene);
+                match (record.event.as_str(), &record.input) {
+                    ("unmatched", Some(input)) => {
+                        let input = input.trim().to_lowercase();
+                        *stats.failed.entry(input).or_default() += 1;
+                    }
+                    ("scene_change" | "ending", _) => {
+                        if let Some(start) = entered {
+                            stats.turns += record.turn.saturating_sub(start);
+                            stats.timed_visits += 1;
+                        }
+                        if let Some(target) = &record.target {
+                            report.scene(target).visits += 1;
+                            reached.insert(target.as_str());
+                            entered = Some(record.turn);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            for name in reached {
+                report.scene(name).playtests += 1;
+            }
+        }
+        report
+    }
+
+    /// Statistics of the scene `name`, added if it isn't there yet.
+    fn scene(&mut self, name: &str) -> &mut SceneStats {
+        let i = match self.scenes.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.scenes.push(SceneStats {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.scenes.len() - 1
+            }
+        };
+        &mut self.scenes[i]
+    }
+
+    /// Scenes of the adventure reached by fewer than [`RARE`] of the
+    /// playtests, including those never reached.
+    pub fn rarely_reached(&self) -> Vec<&SceneStats> {
+        let limit = self.playtests as f64 * RARE;
+        self.scenes
+            .iter()
+            .filter(|s| s.known && (s.playtests as f64) < limit)
+            .collect()
+    }
+}
+
+/// Singular or plural of a counted word.
+fn count(n: usize, word: &str) -> String {
+    match n {
+        1 => format!("1 {}", word),
+        n => format!("{} {}s", n, word),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}\n", count(self.playtests, "playtest"))?;
+        for s in &self.scenes {
+            write!(f, "{}", s.name)?;
+            if !s.known {
+                write!(f, " (not in the adventure)")?;
+            }
+            if s.visits == 0 {
+                writeln!(f, ": never reached")?;
+                continue;
+            }
+            write!(
+                f,
+                ": {} in {}",
+                count(s.visits, "visit"),
+                count(s.playtests, "playtest")
+            )?;
+            match s.average_turns() {
+                Some(t) => writeln!(f, ", {:.1} turns on average", t)?,
+                None => writeln!(f)?,
+            }
+            let failed: Vec<String> = s
+                .common_failed(5)
+                .into_iter()
+                .map(|(input, n)| format!("{} ({})", input, n))
+                .collect();
+            if !failed.is_empty() {
+                writeln!(f, "  failed inputs: {}", failed.join(", "))?;
+            }
+        }
+        let rare: Vec<&str> = self
+            .rarely_reached()
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        if !rare.is_empty() {
+            writeln!(f, "\nRarely reached: {}", rare.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+    use crate::scene::Scene;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn file_log() {
+        let fixture = TempAdventure::new("analytics", &[]);
+        let jsonl = fixture.path("log.jsonl");
+        let csv = fixture.path("log.csv");
+        for path in [&jsonl, &csv] {
+            let mut log = FileLog::open(path).unwrap();
+            log.scene_change("field", "cave, dark", 3).unwrap();
+            log.unmatched("cave, dark", "light", 8).unwrap();
+            log.ending("cave, dark", 9).unwrap();
+        }
+        let jsonl_text = fs::read_to_string(&jsonl).unwrap();
+        let csv_text = fs::read_to_string(&csv).unwrap();
+
+        let records: Vec<Record> = jsonl_text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].target.as_deref(), Some("cave, dark"));
+        assert_eq!(
+            (records[2].event.as_str(), records[2].turn),
+            ("ending", 9)
+        );
+        let lines: Vec<&str> = csv_text.lines().collect();
+        assert_eq!(lines[0], "time,event,scene,target,input,turn");
+        assert!(lines[1].ends_with(",scene_change,field,\"cave, dark\",,3"));
+        assert!(lines[2].ends_with(",unmatched,\"cave, dark\",,light,8"));
+        assert!(lines[3].ends_with(",ending,\"cave, dark\",,,9"));
+    }
+
+    #[test]
+    fn report() {
+        let path: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources", "kitten.scene"]
+                .iter()
+                .collect();
+        let graph = SceneGraph::crawl(Scene::load(path).unwrap());
+        let record = |event: &str, scene: &str, other: &str, turn| {
+            let mut r = Record::new(event, scene, turn);
+            match event {
+                "scene_change" => r.target = Some(other.to_string()),
+                "unmatched" => r.input = Some(other.to_string()),
+                _ => {}
+            }
+            r
+        };
+        let playtests = vec![
+            vec![
+                record("unmatched", "kitten", "Sing", 1),
+                record("scene_change", "kitten", "cuddle_cat", 2),
+                record("scene_change", "cuddle_cat", "kitten", 5),
+                record("unmatched", "kitten", "sing", 6),
+                record("ending", "kitten", "", 7),
+            ],
+            vec![
+                record("unmatched", "kitten", "dance", 1),
+                record("ending", "kitten", "", 1),
+            ],
+        ];
+        let report = Report::new(&graph, &playtests);
+        assert_eq!(report.playtests, 2);
+        let kitten = &report.scenes[0];
+        assert_eq!((kitten.visits, kitten.playtests), (3, 2));
+        assert_eq!(kitten.average_turns(), Some(2.0));
+        assert_eq!(kitten.common_failed(1), [("sing", 2)]);
+        let cuddle = &report.scenes[1];
+        assert_eq!((cuddle.visits, cuddle.playtests), (1, 1));
+        assert_eq!(cuddle.average_turns(), Some(3.0));
+        assert!(report.rarely_reached().is_empty());
+        assert_eq!(
+            report.to_string(),
+            "2 playtests\n\n\
+             kitten: 3 visits in 2 playtests, 2.0 turns on average\n  \
+             failed inputs: sing (2), dance (1)\n\
+             cuddle_cat: 1 visit in 1 playtest, 3.0 turns on average\n"
+        );
+    }
+
+    #[test]
+    fn unmatched_log() {
+        let fixture = TempAdventure::new("unmatched", &[]);
+        let path = fixture.path("unmatched.log");
+        let unmatched = BTreeMap::from([
+            ("cave".to_string(), vec!["light".to_string()]),
+            (
+                "field".to_string(),
+                vec!["dig".to_string(), "fly".to_string()],
+            ),
+        ]);
+        append_unmatched(&path, &unmatched).unwrap();
+        append_unmatched(&path, &BTreeMap::new()).unwrap();
+        append_unmatched(&path, &unmatched).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "cave: light\nfield: dig\nfield: fly\n".repeat(2));
+    }
+}
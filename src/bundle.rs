@@ -0,0 +1,198 @@
+//! Standalone bundles for distributing a game, e.g. on itch.io or
+//! Steam: `rustventure bundle` copies the rustventure binary and the
+//! adventure into one directory, with a `launcher.yaml` that makes
+//! the binary start the adventure when run without arguments:
+//!
+//! ```yaml
+//! adventure: adventure
+//! args: [--typewriter, "40"]
+//! ```
+//!
+//! The `args` are command line options for the game.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+use crate::adventure;
+
+/// File name of the launcher configuration, next to the binary.
+pub const LAUNCHER: &str = "launcher.yaml";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Launcher {
+    /// Adventure directory
+    adventure: PathBuf,
+    /// Command line options to use
+    args: Vec<String>,
+}
+
+impl Launcher {
+    /// Load the launcher configuration at `path`, the adventure is
+    /// relative to its directory.
+    pub fn load(path: &Path) -> Result<Launcher, Box<dyn Error>> {
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?)?;
+        let doc = docs.first().ok_or("no data in launcher")?;
+        let adventure = doc["adventure"]
+            .as_str()
+            .ok_or("launcher without adventure")?;
+        let args = match &doc["args"] {
+            Yaml::Array(a) => a
+                .iter()
+                .map(|a| a.as_str().map(|a| a.to_string()))
+                .collect::<Option<_>>()
+                .ok_or("launcher arguments must be strings")?,
+            _ => Vec::new(),
+        };
+        Ok(Launcher {
+            adventure: path
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(adventure),
+            args,
+        })
+    }
+
+    /// Load the launcher configuration next to the running binary,
+    /// if there is one. Fails if the file exists but can't be loaded.
+    pub fn find() -> Result<Option<Launcher>, Box<dyn Error>> {
+        let Some(path) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join(LAUNCHER)))
+            .filter(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+        Launcher::load(&path)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn adventure(&self) -> &Path {
+        &self.adventure
+    }
+
+    /// Command line arguments to start the game with, including the
+    /// adventure.
+    pub fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> =
+            self.args.iter().map(OsString::from).collect();
+        args.push(self.adventure.clone().into_os_string());
+        args
+    }
+}
+
+/// Copy the directory `from` to `to`, skipping hidden files.
+fn copy_dir(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Default name of the bundle directory for `name` on this platform,
+/// like `kitten-linux-x86_64`.
+pub fn default_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!(
+        "{}-{}-{}",
+        name.to_lowercase(),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )
+}
+
+/// Bundle the adventure in `path` into the directory `output`, with
+/// a copy of the running binary, passing `args` to it when launched.
+/// Returns the path of the binary in the bundle.
+pub fn bundle(
+    path: &Path,
+    output: &Path,
+    args: &[String],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let adventure = adventure::load(path)?;
+    let dir = adventure.dir().canonicalize()?;
+    if std::path::absolute(output)?.starts_with(&dir) {
+        return Err("the bundle cannot be inside the adventure".into());
+    }
+    copy_dir(&dir, &output.join("adventure"))?;
+
+    let exe = std::env::current_exe()?;
+    let target = output.join(exe.file_name().ok_or("unnamed binary")?);
+    fs::copy(&exe, &target)?;
+
+    let mut doc = Hash::new();
+    doc.insert(
+        Yaml::String("adventure".to_string()),
+        Yaml::String("adventure".to_string()),
+    );
+    if !args.is_empty() {
+        doc.insert(
+            Yaml::String("args".to_string()),
+            Yaml::Array(args.iter().cloned().map(Yaml::String).collect()),
+        );
+    }
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&Yaml::Hash(doc))?;
+    out.push('\n');
+    fs::write(output.join(LAUNCHER), out)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn bundle_adventure() {
+        let resources: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources"].iter().collect();
+        let fixture = TempAdventure::new("bundle", &[]);
+        let dir = fixture.path("bundle");
+        let args = ["--typewriter".to_string(), "40".to_string()];
+        let exe = bundle(&resources, &dir, &args).unwrap();
+        let launcher = Launcher::load(&dir.join(LAUNCHER));
+        let copied = dir.join("adventure").join("kitten.scene").is_file();
+        let inside = bundle(&resources, &resources.join("out"), &[]);
+
+        assert!(exe.starts_with(&dir));
+        assert!(copied);
+        let launcher = launcher.unwrap();
+        assert_eq!(launcher.adventure(), dir.join("adventure"));
+        assert_eq!(
+            launcher.args(),
+            [
+                "--typewriter",
+                "40",
+                dir.join("adventure").to_str().unwrap()
+            ]
+            .map(OsString::from)
+        );
+        assert!(inside.is_err());
+        assert_eq!(
+            default_name("A cuddly kitten"),
+            format!(
+                "a-cuddly-kitten-{}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            )
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Cache of `check` results for scene files, so repeated checks only
+//! parse files that changed since the last run. Files are identified
+//! by their absolute path, changes by a hash of their content. Caches
+//! written by another version of rustventure are discarded, as the
+//! checks may have changed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::warning::Warning;
+
+/// Results of checking a scene file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCheck {
+    /// Hash of the file content, see [`hash`]
+    pub hash: u64,
+    pub warnings: Vec<Warning>,
+    /// Names of the scenes the scene can change to
+    pub links: Vec<String>,
+    /// Words of the scene text, for the spell-check
+    pub words: Vec<String>,
+    /// NPCs whose state the scene changes
+    pub npcs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckCache {
+    /// Where to save the cache, if anywhere
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Version of rustventure that wrote the cache
+    #[serde(default)]
+    version: String,
+    files: BTreeMap<PathBuf, FileCheck>,
+}
+
+impl Default for CheckCache {
+    fn default() -> Self {
+        CheckCache {
+            path: None,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl CheckCache {
+    /// Default location of the cache file, in the user's cache
+    /// directory.
+    pub fn default_path() -> Option<PathBuf> {
+        let cache = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache"))
+            })?;
+        Some(cache.join("rustventure").join("check.json"))
+    }
+
+    /// Load the cache from `path`. If the file doesn't exist yet,
+    /// can't be read as a cache, or is from another version an empty
+    /// cache is returned, and will be written on save.
+    pub fn load(path: PathBuf) -> Result<CheckCache, Box<dyn Error>> {
+        let mut cache = match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s)
+                .ok()
+                .filter(|c: &CheckCache| {
+                    c.version == env!("CARGO_PKG_VERSION")
+                })
+                .unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                CheckCache::default()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        cache.path = Some(path);
+        Ok(cache)
+    }
+
+    /// Write the cache to its file, if it has one.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Cached results for `file`, if its content still has the same
+    /// `hash`.
+    pub fn get(&self, file: &Path, hash: u64) -> Option<&FileCheck> {
+        self.files.get(file).filter(|c| c.hash == hash)
+    }
+
+    pub fn insert(&mut self, file: PathBuf, check: FileCheck) {
+        self.files.insert(file, check);
+    }
+}
+
+/// Hash `data` with 64 bit FNV-1a. Unlike the hashers in `std` the
+/// result is the same across Rust versions and platforms.
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn fnv_hash() {
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let fixture = TempAdventure::new("cache", &[]);
+        let path = fixture.path("cache/check.json");
+        let check = FileCheck {
+            hash: hash(b"Dust.\n"),
+            warnings: vec![Warning::new(
+                Path::new("/a/attic.scene"),
+                "empty description".to_string(),
+            )],
+            links: vec!["hall".to_string()],
+            words: vec!["Dust".to_string()],
+            npcs: Vec::new(),
+        };
+        let mut cache = CheckCache::load(path.clone()).unwrap();
+        cache.insert(PathBuf::from("/a/attic.scene"), check.clone());
+        cache.save().unwrap();
+        let loaded = CheckCache::load(path.clone());
+        let json = fs::read_to_string(&path).unwrap().replace(
+            &format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION")),
+            "\"version\":\"0.0.1\"",
+        );
+        fs::write(&path, json).unwrap();
+        let old = CheckCache::load(path.clone());
+        fs::write(&path, "not json").unwrap();
+        let broken = CheckCache::load(path.clone());
+
+        let loaded = loaded.unwrap();
+        let attic = Path::new("/a/attic.scene");
+        assert_eq!(loaded.get(attic, check.hash), Some(&check));
+        assert_eq!(loaded.get(attic, hash(b"Cobwebs.\n")), None);
+        assert!(old.unwrap().files.is_empty());
+        assert!(broken.unwrap().files.is_empty());
+    }
+}
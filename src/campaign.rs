@@ -0,0 +1,146 @@
+//! Campaigns link adventures into a series, so one adventure can hand
+//! off to the next with `!kw:leave -> adventure garden`, optionally
+//! naming the scene to start in: `-> adventure garden gate`.
+//!
+//! The adventures of a campaign share a directory, which contains the
+//! `campaign.yaml` manifest. It lists the adventures by name with
+//! their directories, and which state carries over between them:
+//!
+//! ```yaml
+//! name: The Kitten Saga
+//! adventures:
+//!   box: episode1
+//!   garden: episode2
+//! carry:
+//!   variables: [score]
+//!   inventory: true
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::adventure::{self, Adventure};
+
+/// File name of the campaign manifest.
+pub const MANIFEST: &str = "campaign.yaml";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Campaign {
+    name: String,
+    /// Directory containing the manifest
+    dir: PathBuf,
+    /// Adventure directories by name, relative to `dir`
+    adventures: BTreeMap<String, PathBuf>,
+    /// Variables to carry over to the next adventure
+    variables: Vec<String>,
+    /// Whether the player keeps the inventory
+    inventory: bool,
+}
+
+impl Campaign {
+    /// Load the campaign manifest at `path`.
+    pub fn load(path: &Path) -> Result<Campaign, Box<dyn Error>> {
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?)?;
+        let doc = docs.first().ok_or("no data in campaign")?;
+        let name = doc["name"].as_str().ok_or("campaign without name")?;
+        let adventures = doc["adventures"]
+            .as_hash()
+            .ok_or("campaign without adventures")?
+            .iter()
+            .map(|(k, v)| match (k.as_str(), v.as_str()) {
+                (Some(k), Some(v)) => Ok((k.to_string(), PathBuf::from(v))),
+                _ => Err(format!("invalid campaign adventure: {:?}", k)),
+            })
+            .collect::<Result<_, _>>()?;
+        let variables = match &doc["carry"]["variables"] {
+            Yaml::Array(a) => a
+                .iter()
+                .filter_map(|v| v.as_str().map(|v| v.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Campaign {
+            name: name.to_string(),
+            dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            adventures,
+            variables,
+            inventory: doc["carry"]["inventory"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Find the campaign the adventure in `dir` belongs to: the
+    /// manifest must be in the parent directory and list it.
+    pub fn find(dir: &Path) -> Result<Option<Campaign>, Box<dyn Error>> {
+        let dir = match dir.as_os_str().is_empty() {
+            true => Path::new(".").canonicalize()?,
+            false => dir.canonicalize()?,
+        };
+        let Some(manifest) = dir.parent().map(|p| p.join(MANIFEST)) else {
+            return Ok(None);
+        };
+        if !manifest.is_file() {
+            return Ok(None);
+        }
+        let campaign = Campaign::load(&manifest)?;
+        let listed = campaign.adventures.values().any(|a| {
+            campaign.dir.join(a).canonicalize().is_ok_and(|a| a == dir)
+        });
+        Ok(listed.then_some(campaign))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Variables that carry over to the next adventure.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Whether the player keeps the inventory in the next adventure.
+    pub fn inventory(&self) -> bool {
+        self.inventory
+    }
+
+    /// Load the adventure called `name` in the manifest.
+    pub fn adventure(&self, name: &str) -> Result<Adventure, Box<dyn Error>> {
+        let dir = self.adventures.get(name).ok_or(format!(
+            "no adventure {} in campaign {}",
+            name, self.name
+        ))?;
+        adventure::load(&self.dir.join(dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn find_campaign() {
+        let fixture = TempAdventure::new(
+            "campaign",
+            &[
+                ("episode1/about.yaml", "name: Box\nauthor: Fiona\n"),
+                ("other/about.yaml", "name: Other\nauthor: Fiona\n"),
+                (
+                    MANIFEST,
+                    "name: Saga\nadventures:\n  box: episode1\n\
+                     carry:\n  variables: [score]\n",
+                ),
+            ],
+        );
+        let campaign = Campaign::find(&fixture.path("episode1"));
+        let other = Campaign::find(&fixture.path("other"));
+
+        let campaign = campaign.unwrap().unwrap();
+        assert_eq!(campaign.name(), "Saga");
+        assert_eq!(campaign.variables(), ["score"]);
+        assert!(!campaign.inventory());
+        assert!(other.unwrap().is_none());
+    }
+}
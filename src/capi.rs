@@ -0,0 +1,242 @@
+//! C API to embed the engine in game frameworks written in C or C++,
+//! enabled with the `capi` feature. Building with the feature
+//! generates the header `rustventure.h` in the build's `OUT_DIR`.
+//!
+//! ```c
+//! RvEngine *engine = rv_engine_new(text, strlen(text));
+//! rv_engine_add_scene(engine, "cave", cave, strlen(cave));
+//! puts(rv_engine_output(engine));
+//! if (rv_engine_step(engine, "go north") != 0)
+//!     fprintf(stderr, "%s", rv_engine_output(engine));
+//! rv_engine_free(engine);
+//! ```
+//!
+//! Strings returned by the functions belong to the engine, and stay
+//! valid until the next call with the same engine. Panics don't
+//! unwind into C, the functions fail instead.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::slice;
+
+use crate::engine::Engine;
+use crate::scene::{ReadProvider, Scene};
+
+/// Scene text by path, shared with the scene provider.
+type Scenes = Rc<RefCell<HashMap<PathBuf, String>>>;
+
+/// An engine running one game, with the output of the last call.
+pub struct RvEngine {
+    engine: Engine,
+    scenes: Scenes,
+    output: CString,
+}
+
+impl RvEngine {
+    fn set_output(&mut self, mut output: Vec<u8>) {
+        output.retain(|b| *b != 0);
+        self.output = CString::new(output).unwrap_or_default();
+    }
+}
+
+/// Run `f`, returning `failed` if it panics.
+fn guarded<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+/// Read `len` bytes of UTF-8 text at `text`.
+unsafe fn text<'a>(text: *const u8, len: usize) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    std::str::from_utf8(slice::from_raw_parts(text, len)).ok()
+}
+
+/// Create an engine starting in the scene in the `len` bytes at
+/// `text`. The output holds the description of the scene. Returns
+/// NULL if the scene is invalid.
+///
+/// # Safety
+///
+/// `text` must point to at least `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rv_engine_new(
+    text: *const u8,
+    len: usize,
+) -> *mut RvEngine {
+    let Some(text) = self::text(text, len) else {
+        return std::ptr::null_mut();
+    };
+    guarded(std::ptr::null_mut(), || {
+        let Ok(scene) = Scene::parse(PathBuf::from("start.scene"), text)
+        else {
+            return std::ptr::null_mut();
+        };
+        let scenes = Scenes::default();
+        let mut engine = Engine::new(scene);
+        let shared = Rc::clone(&scenes);
+        engine.set_scene_provider(ReadProvider::new(move |path: &Path| {
+            shared
+                .borrow()
+                .get(path)
+                .cloned()
+                .ok_or(io::ErrorKind::NotFound.into())
+        }));
+        let mut output = Vec::new();
+        if engine.describe(&mut output).is_err() {
+            return std::ptr::null_mut();
+        }
+        let mut engine = RvEngine {
+            engine,
+            scenes,
+            output: CString::default(),
+        };
+        engine.set_output(output);
+        Box::into_raw(Box::new(engine))
+    })
+}
+
+/// Add the scene called `name`, with the `len` bytes at `text`, for
+/// the player to move to. Returns 0 on success, or -1 if the name or
+/// text is invalid.
+///
+/// # Safety
+///
+/// `engine` must come from [`rv_engine_new`], `name` must be a NUL
+/// terminated string, and `text` must point to at least `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rv_engine_add_scene(
+    engine: *mut RvEngine,
+    name: *const c_char,
+    text: *const u8,
+    len: usize,
+) -> c_int {
+    let (Some(engine), false) = (engine.as_mut(), name.is_null()) else {
+        return -1;
+    };
+    let (Ok(name), Some(text)) =
+        (CStr::from_ptr(name).to_str(), self::text(text, len))
+    else {
+        return -1;
+    };
+    engine
+        .scenes
+        .borrow_mut()
+        .insert(PathBuf::from(format!("{}.scene", name)), text.to_string());
+    0
+}
+
+/// Process one line of player input. Returns 0 on success, or -1 on
+/// error, then the output holds the error message.
+///
+/// # Safety
+///
+/// `engine` must come from [`rv_engine_new`], and `input` must be a
+/// NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rv_engine_step(
+    engine: *mut RvEngine,
+    input: *const c_char,
+) -> c_int {
+    let (Some(engine), false) = (engine.as_mut(), input.is_null()) else {
+        return -1;
+    };
+    let input = CStr::from_ptr(input).to_string_lossy();
+    let mut output = Vec::new();
+    let res = guarded(Err("the engine panicked".into()), || {
+        engine.engine.step(&input, &mut output)
+    });
+    match res {
+        Ok(()) => {
+            engine.set_output(output);
+            0
+        }
+        Err(err) => {
+            engine.set_output(format!("Error: {}\n", err).into_bytes());
+            -1
+        }
+    }
+}
+
+/// Output of the last call with `engine`, as a NUL terminated UTF-8
+/// string.
+///
+/// # Safety
+///
+/// `engine` must come from [`rv_engine_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rv_engine_output(
+    engine: *const RvEngine,
+) -> *const c_char {
+    match engine.as_ref() {
+        Some(engine) => engine.output.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Free `engine`, NULL is ignored.
+///
+/// # Safety
+///
+/// `engine` must come from [`rv_engine_new`], and must not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rv_engine_free(engine: *mut RvEngine) {
+    if !engine.is_null() {
+        let engine = Box::from_raw(engine);
+        guarded((), || drop(engine));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(engine: *const RvEngine) -> String {
+        unsafe { CStr::from_ptr(rv_engine_output(engine)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn play() {
+        let start = "A field.\n!kw:north -> scene cave\n";
+        let cave = "A dark cave.\n";
+        unsafe {
+            let engine = rv_engine_new(start.as_ptr(), start.len());
+            assert!(!engine.is_null());
+            assert_eq!(output(engine), "A field.\n");
+            assert_eq!(
+                rv_engine_add_scene(
+                    engine,
+                    c"cave".as_ptr(),
+                    cave.as_ptr(),
+                    cave.len()
+                ),
+                0
+            );
+            assert_eq!(rv_engine_step(engine, c"north".as_ptr()), 0);
+            assert_eq!(output(engine), "A dark cave.\n");
+            rv_engine_free(engine);
+
+            let invalid = [0xff, 0xfe];
+            assert!(rv_engine_new(invalid.as_ptr(), invalid.len()).is_null());
+            assert_eq!(
+                rv_engine_step(std::ptr::null_mut(), c"".as_ptr()),
+                -1
+            );
+        }
+    }
+
+    #[test]
+    fn panics() {
+        assert_eq!(guarded(-1, || panic!("engine bug")), -1);
+        assert_eq!(guarded(-1, || 0), 0);
+    }
+}
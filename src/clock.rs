@@ -0,0 +1,201 @@
+//! An optional in-game clock, for adventures where the time of day
+//! matters. The clock advances by a fixed duration per turn and with
+//! `advance` effects, e.g. `!kw:sleep -> advance 8h You sleep.`
+//!
+//! Conditions can check the time of day with `[if time in night]`,
+//! using a period name or a range like `time in 08:00-12:00`. Scene
+//! text can show the time with `${time}` and the day with `${day}`.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+const DAY: u64 = 24 * 60;
+
+/// Parse a time of day like `08:30` into minutes after midnight.
+fn parse_time(s: &str) -> Result<u32, Box<dyn Error>> {
+    let (h, m) = s.trim().split_once(':').ok_or("time must be HH:MM")?;
+    let (h, m): (u32, u32) = (h.parse()?, m.parse()?);
+    if h > 23 || m > 59 {
+        return Err(format!("invalid time: {}", s).into());
+    }
+    Ok(h * 60 + m)
+}
+
+/// Parse a duration like `2h`, `30m`, or `1d6h` into minutes.
+pub fn parse_duration(s: &str) -> Result<u64, Box<dyn Error>> {
+    lazy_static! {
+        static ref DURATION_RE: Regex =
+            Regex::new(r"^(?:(\d+)d)?(?:(\d+)h)?(?:(\d+)m)?$").unwrap();
+    }
+    let c = DURATION_RE
+        .captures(s)
+        .filter(|_| !s.is_empty())
+        .ok_or(format!("invalid duration: {}", s))?;
+    let mut minutes = 0;
+    for (i, unit) in [(1, DAY), (2, 60), (3, 1)] {
+        if let Some(n) = c.get(i) {
+            minutes += n.as_str().parse::<u64>()? * unit;
+        }
+    }
+    Ok(minutes)
+}
+
+/// A part of the day. If `to` is before `from` the period lasts over
+/// midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Period {
+    from: u32,
+    to: u32,
+}
+
+impl Period {
+    /// Parse a range like `22:00-06:00`, the end is exclusive.
+    pub fn parse(s: &str) -> Result<Period, Box<dyn Error>> {
+        let (from, to) =
+            s.split_once('-').ok_or(format!("invalid period: {}", s))?;
+        Ok(Period {
+            from: parse_time(from)?,
+            to: parse_time(to)?,
+        })
+    }
+
+    /// Whether `minute` (after midnight) is within the period.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.from <= self.to {
+            (self.from..self.to).contains(&minute)
+        } else {
+            minute >= self.from || minute < self.to
+        }
+    }
+}
+
+/// The time shown by a [`Clock`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    /// Day of the game, starting with 1
+    pub day: u64,
+    /// Minutes after midnight
+    pub minute: u32,
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minute / 60, self.minute % 60)
+    }
+}
+
+/// Clock settings of an adventure. The elapsed time is part of the
+/// game state, so it is saved with the game.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clock {
+    /// Minute of the first day the game starts at
+    start: u32,
+    /// Minutes passing with each turn
+    turn: u64,
+    periods: BTreeMap<String, Period>,
+}
+
+impl Default for Clock {
+    /// Start at 08:00, time passes only with `advance` effects.
+    fn default() -> Self {
+        let periods = [
+            ("night", "22:00-06:00"),
+            ("morning", "06:00-12:00"),
+            ("afternoon", "12:00-18:00"),
+            ("evening", "18:00-22:00"),
+        ]
+        .into_iter()
+        .map(|(n, p)| (n.to_string(), Period::parse(p).unwrap()))
+        .collect();
+        Clock {
+            start: 8 * 60,
+            turn: 0,
+            periods,
+        }
+    }
+}
+
+impl Clock {
+    /// Set the time of day the game starts at, like `08:00`.
+    pub fn set_start(&mut self, start: &str) -> Result<(), Box<dyn Error>> {
+        self.start = parse_time(start)?;
+        Ok(())
+    }
+
+    /// Set how much time passes with each turn, like `5m`.
+    pub fn set_turn(&mut self, turn: &str) -> Result<(), Box<dyn Error>> {
+        self.turn = parse_duration(turn)?;
+        Ok(())
+    }
+
+    /// Minutes passing with each turn.
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+
+    /// Add or replace a named period, e.g. `dawn` as `05:00-07:00`.
+    /// There are default periods `night`, `morning`, `afternoon`, and
+    /// `evening`.
+    pub fn add_period(
+        &mut self,
+        name: &str,
+        range: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.periods.insert(name.to_string(), Period::parse(range)?);
+        Ok(())
+    }
+
+    /// The time after `elapsed` minutes of play.
+    pub fn time(&self, elapsed: u64) -> Time {
+        let now = u64::from(self.start) + elapsed;
+        Time {
+            day: now / DAY + 1,
+            minute: (now % DAY) as u32,
+        }
+    }
+
+    /// Whether the time after `elapsed` minutes is in `period`, a
+    /// period name or a range. Unknown periods never match.
+    pub fn is_in(&self, elapsed: u64, period: &str) -> bool {
+        let minute = self.time(elapsed).minute;
+        match self.periods.get(period) {
+            Some(p) => p.contains(minute),
+            None => Period::parse(period).is_ok_and(|p| p.contains(minute)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("2h").unwrap(), 120);
+        assert_eq!(parse_duration("1d6h30m").unwrap(), 1830);
+        assert_eq!(parse_duration("45m").unwrap(), 45);
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2 hours").is_err());
+    }
+
+    #[test]
+    fn time_of_day() {
+        let mut clock = Clock::default();
+        clock.set_start("21:30").unwrap();
+        clock.add_period("dawn", "05:00-07:00").unwrap();
+        assert_eq!(clock.time(0).to_string(), "21:30");
+        assert!(clock.is_in(0, "evening"));
+        assert!(clock.is_in(30, "night"));
+        let time = clock.time(8 * 60);
+        assert_eq!((time.day, time.to_string()), (2, "05:30".to_string()));
+        assert!(clock.is_in(8 * 60, "night"));
+        assert!(clock.is_in(8 * 60, "dawn"));
+        assert!(clock.is_in(8 * 60, "05:00-06:00"));
+        assert!(!clock.is_in(8 * 60, "noon"));
+        assert!(clock.set_start("25:00").is_err());
+    }
+}
@@ -0,0 +1,366 @@
+//! Subcommands of the command line interface for tasks other than
+//! playing an adventure.
+
+use clap::{Subcommand, ValueEnum};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::adventure;
+use crate::analytics::{self, Report};
+use crate::bundle;
+use crate::cache::CheckCache;
+use crate::diagnose;
+use crate::scene::graph::SceneGraph;
+use crate::{epub, gamebook, migrate, pool, site, syntax, testing, twee};
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert a story from another format into an adventure
+    #[clap(subcommand)]
+    Import(Import),
+    /// Convert an adventure into another format
+    #[clap(subcommand)]
+    Export(Export),
+    /// Run the tests written in scene files
+    Test {
+        /// Adventure directory, its about.yaml, or a single scene file
+        #[clap(default_value = ".")]
+        path: PathBuf,
+        /// Show how many actions of each scene the tests triggered
+        #[clap(long)]
+        coverage: bool,
+    },
+    /// Load an adventure and all its scenes, and list likely mistakes
+    Check {
+        /// Adventure directory, its about.yaml, or a single scene file
+        #[clap(default_value = ".")]
+        path: PathBuf,
+        /// Number of scene files to load in parallel, defaults to the
+        /// number of CPUs
+        #[clap(short, long)]
+        jobs: Option<usize>,
+        /// Parse all scene files, instead of only those that changed
+        /// since the last check
+        #[clap(long)]
+        no_cache: bool,
+    },
+    /// Rewrite the scene files of an adventure in the newest scene
+    /// format
+    Migrate {
+        /// Adventure directory, its about.yaml, or a single scene file
+        #[clap(default_value = ".")]
+        path: PathBuf,
+        /// Only show the changes, without writing them
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Report errors, warnings, and actions of one scene file, for
+    /// editor plugins
+    Diagnose {
+        /// Scene file to check
+        file: PathBuf,
+        #[clap(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+    /// Package the adventure with this binary into a directory to
+    /// distribute, which starts the adventure when run
+    Bundle {
+        /// Adventure directory or its about.yaml file
+        #[clap(default_value = ".")]
+        adventure: PathBuf,
+        /// Directory to write the bundle to, defaults to one named
+        /// after the adventure and platform
+        #[clap(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        /// Options to start the game with, after `--`, e.g.
+        /// `-- --typewriter 40`
+        #[clap(last = true)]
+        args: Vec<String>,
+    },
+    /// Print syntax highlighting definitions for scene files
+    Syntax {
+        #[clap(long, value_enum, default_value = "textmate")]
+        format: SyntaxFormat,
+    },
+    /// Summarize playtest logs recorded with `--analytics`: visits,
+    /// turns spent, and failed inputs of each scene
+    Analyze {
+        /// Directory with the JSON lines logs
+        logs: PathBuf,
+        /// Adventure directory, its about.yaml, or a single scene file
+        #[clap(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Print the shortest sequence of inputs reaching a scene from the
+    /// start, one per line
+    Walkthrough {
+        /// Name of the scene to reach
+        ending: String,
+        /// Adventure directory, its about.yaml, or a single scene file
+        #[clap(default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+/// Output format of `diagnose`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One line per error or warning
+    Text,
+    /// All diagnostics including action spans as one JSON object
+    Json,
+}
+
+/// Output format of `syntax`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SyntaxFormat {
+    /// TextMate grammar, as used by VS Code and many other editors
+    Textmate,
+    /// Lists of the words of the grammar, as JSON
+    Words,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Import {
+    /// Import a Twee 3 file, the text format of Twine stories
+    Twee {
+        /// Twee file to import
+        file: PathBuf,
+        /// Directory to write the adventure to, defaults to the
+        /// name of the Twee file without extension
+        #[clap(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+    /// Import a story written in Ink (experimental, only a subset of
+    /// Ink is supported)
+    #[cfg(feature = "ink")]
+    Ink {
+        /// Ink file to import
+        file: PathBuf,
+        /// Directory to write the adventure to, defaults to the
+        /// name of the Ink file without extension
+        #[clap(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Export {
+    /// Export to a Twee 3 file, for editing in Twine
+    Twee {
+        /// Adventure directory or its about.yaml file
+        #[clap(default_value = ".")]
+        adventure: PathBuf,
+        /// File to write to, defaults to standard output
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Export to a static web site with a page for each scene, for
+    /// proofreading
+    Html {
+        /// Directory to write the pages to
+        dir: PathBuf,
+        /// Adventure directory or its about.yaml file
+        #[clap(default_value = ".")]
+        adventure: PathBuf,
+    },
+    /// Export to an EPUB e-book with a chapter for each scene, for
+    /// reading offline
+    Epub {
+        /// File to write the book to
+        file: PathBuf,
+        /// Adventure directory or its about.yaml file
+        #[clap(default_value = ".")]
+        adventure: PathBuf,
+    },
+    /// Export to a PDF gamebook with numbered sections ("turn to 47"),
+    /// for printing
+    Pdf {
+        /// File to write the gamebook to
+        file: PathBuf,
+        /// Adventure directory or its about.yaml file
+        #[clap(default_value = ".")]
+        adventure: PathBuf,
+    },
+}
+
+/// Write an imported story from `file` to `dir`, or a directory next
+/// to the file if not given.
+fn import_story<W: Write>(
+    story: &twee::Story,
+    file: &Path,
+    dir: Option<PathBuf>,
+    output: &mut W,
+) -> Result<(), Box<dyn Error>> {
+    let dir = dir.unwrap_or_else(|| file.with_extension(""));
+    if dir == file {
+        return Err("cannot derive output directory, use --output".into());
+    }
+    let written = twee::import(story, &dir)?;
+    writeln!(
+        output,
+        "Imported {} passages to {}",
+        written.len() - 1,
+        dir.display()
+    )?;
+    Ok(())
+}
+
+impl Command {
+    /// Run the command, writing messages for the user to `output`.
+    pub fn run<W: Write>(self, output: &mut W) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Import(Import::Twee { file, output: dir }) => {
+                let story = twee::parse(&fs::read_to_string(&file)?)?;
+                import_story(&story, &file, dir, output)?;
+            }
+            #[cfg(feature = "ink")]
+            Command::Import(Import::Ink { file, output: dir }) => {
+                let story = crate::ink::parse(&fs::read_to_string(&file)?)?;
+                import_story(&story, &file, dir, output)?;
+            }
+            Command::Export(Export::Twee {
+                adventure,
+                output: file,
+            }) => {
+                let twee = twee::export(adventure::load(&adventure)?)?;
+                match file {
+                    Some(f) => fs::write(f, twee)?,
+                    None => write!(output, "{}", twee)?,
+                }
+            }
+            Command::Export(Export::Html { dir, adventure }) => {
+                let pages = site::export(adventure::load(&adventure)?, &dir)?;
+                writeln!(
+                    output,
+                    "Exported {} scenes to {}",
+                    pages.len() - 1,
+                    dir.display()
+                )?;
+            }
+            Command::Export(Export::Epub { file, adventure }) => {
+                epub::write(&adventure, &file)?;
+                writeln!(output, "Exported the book to {}", file.display())?;
+            }
+            Command::Export(Export::Pdf { file, adventure }) => {
+                gamebook::write(&adventure, &file)?;
+                writeln!(
+                    output,
+                    "Exported the gamebook to {}",
+                    file.display()
+                )?;
+            }
+            Command::Test { path, coverage } => {
+                let summary = testing::run(&path, output)?;
+                if coverage {
+                    writeln!(output, "\nCoverage:")?;
+                    for c in &summary.coverage {
+                        writeln!(output, "{}", c)?;
+                    }
+                    writeln!(output)?;
+                }
+                writeln!(
+                    output,
+                    "{} passed, {} failed",
+                    summary.passed, summary.failed
+                )?;
+                if summary.failed > 0 {
+                    return Err(
+                        format!("{} tests failed", summary.failed).into()
+                    );
+                }
+            }
+            Command::Check {
+                path,
+                jobs,
+                no_cache,
+            } => {
+                let jobs = jobs.unwrap_or_else(pool::default_workers);
+                let mut cache = match CheckCache::default_path() {
+                    Some(p) if !no_cache => CheckCache::load(p)?,
+                    _ => CheckCache::default(),
+                };
+                let warnings = testing::check(&path, jobs, &mut cache)?;
+                cache.save()?;
+                for w in &warnings {
+                    writeln!(output, "Warning: {}", w)?;
+                }
+                writeln!(output, "{} warnings", warnings.len())?;
+            }
+            Command::Migrate { path, dry_run } => {
+                let migrations = migrate::migrate(&path, dry_run)?;
+                for m in &migrations {
+                    if dry_run {
+                        write!(output, "{}", m)?;
+                    } else {
+                        writeln!(output, "Migrated {}", m.path.display())?;
+                    }
+                }
+                let done = if dry_run { "to migrate" } else { "migrated" };
+                writeln!(
+                    output,
+                    "{} scene files {}",
+                    migrations.len(),
+                    done
+                )?;
+            }
+            Command::Diagnose { file, format } => {
+                let diagnostics = diagnose::diagnose(&file)?;
+                if format == Format::Json {
+                    let json = serde_json::to_string_pretty(&diagnostics)?;
+                    writeln!(output, "{}", json)?;
+                    return Ok(());
+                }
+                for e in &diagnostics.errors {
+                    writeln!(output, "{}: error: {}", file.display(), e)?;
+                }
+                for w in &diagnostics.warnings {
+                    writeln!(output, "{}: warning: {}", file.display(), w)?;
+                }
+                if !diagnostics.errors.is_empty() {
+                    return Err("the scene has errors".into());
+                }
+            }
+            Command::Bundle {
+                adventure,
+                output: dir,
+                args,
+            } => {
+                let dir = match dir {
+                    Some(d) => d,
+                    None => PathBuf::from(bundle::default_name(
+                        adventure::load(&adventure)?.name(),
+                    )),
+                };
+                let exe = bundle::bundle(&adventure, &dir, &args)?;
+                writeln!(
+                    output,
+                    "Bundled the adventure, run {}",
+                    exe.display()
+                )?;
+            }
+            Command::Syntax { format } => {
+                let json = match format {
+                    SyntaxFormat::Textmate => syntax::textmate(),
+                    SyntaxFormat::Words => {
+                        serde_json::to_value(syntax::words())?
+                    }
+                };
+                writeln!(output, "{}", serde_json::to_string_pretty(&json)?)?;
+            }
+            Command::Analyze { logs, path } => {
+                let graph = SceneGraph::crawl(testing::start_scene(&path)?);
+                let playtests = analytics::read_logs(&logs)?;
+                write!(output, "{}", Report::new(&graph, &playtests))?;
+            }
+            Command::Walkthrough { ending, path } => {
+                for step in testing::walkthrough(&path, &ending)? {
+                    writeln!(output, "{}", step)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Conditions that decide whether an action is available.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+
+use crate::expr::Expr;
+
+/// Words starting the simple (non-expression) conditions, besides
+/// comparisons with `==` and `!=`.
+pub const KEYWORDS: &[&str] = &["has", "not", "scene has", "time in", "is"];
+
+/// Access to the game state needed to check conditions.
+pub trait Context {
+    fn variable(&self, name: &str) -> Option<&str>;
+    fn has(&self, item: &str) -> bool;
+    fn npc_state(&self, npc: &str) -> Option<&str>;
+    /// Number of times the player entered `scene`.
+    fn visits(&self, scene: &str) -> u32;
+    /// Whether the current scene has `tag`.
+    fn scene_has(&self, _tag: &str) -> bool {
+        false
+    }
+    /// Whether the in-game clock shows a time in `period`, always
+    /// `false` without a clock.
+    fn time_in(&self, _period: &str) -> bool {
+        false
+    }
+}
+
+/// A condition as written in action lines, e.g. `[if has key]`.
+///
+/// Conditions using `&&`, `||`, `<`, `>`, or parentheses are parsed
+/// as [expressions](crate::expr), e.g.
+/// `[if has(key) && visits(cave) > 2]`. Note that in expressions
+/// text must be quoted: `mood == "happy"`. Conditions that aren't
+/// valid expressions fall back to the simple grammar, so values like
+/// `face == >.<` keep working.
+///
+/// # Examples
+///
+/// ```
+/// use rustventure::condition::Condition;
+/// let c = Condition::parse("not mood == grumpy").unwrap();
+/// assert_eq!(format!("{}", c), "not mood == grumpy");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// The player carries the item
+    Has(String),
+    /// The variable has the value
+    Equals(String, String),
+    /// The variable does not have the value (or is unset)
+    NotEquals(String, String),
+    /// The NPC is in the state, written `guard is alerted`
+    Is(String, String),
+    /// The current scene has the tag, written `scene has outdoors`
+    SceneHas(String),
+    /// The in-game clock shows a time in the period, written
+    /// `time in night` or `time in 08:00-12:00`
+    TimeIn(String),
+    Not(Box<Condition>),
+    Expr(Expr),
+}
+
+impl Condition {
+    pub fn parse(s: &str) -> Result<Condition, Box<dyn Error>> {
+        let s = s.trim();
+        if ["&&", "||", "<", ">", "("].iter().any(|op| s.contains(op)) {
+            match Expr::parse(s) {
+                Ok(e) => Ok(Condition::Expr(e)),
+                // Report the expression error if it isn't a simple
+                // condition either.
+                Err(e) => Condition::parse_simple(s).map_err(|_| e),
+            }
+        } else {
+            Condition::parse_simple(s)
+        }
+    }
+
+    /// Parse a condition without expressions.
+    fn parse_simple(s: &str) -> Result<Condition, Box<dyn Error>> {
+        if let Some(rest) = s.strip_prefix("not ") {
+            Ok(Condition::Not(Box::new(Condition::parse(rest)?)))
+        } else if let Some(item) = s.strip_prefix("has ") {
+            Ok(Condition::Has(item.trim().to_string()))
+        } else if let Some(tag) = s.strip_prefix("scene has ") {
+            Ok(Condition::SceneHas(tag.trim().to_string()))
+        } else if let Some(period) = s.strip_prefix("time in ") {
+            Ok(Condition::TimeIn(period.trim().to_string()))
+        } else if let Some((var, value)) = s.split_once("!=") {
+            Ok(Condition::NotEquals(
+                var.trim().to_string(),
+                value.trim().to_string(),
+            ))
+        } else if let Some((var, value)) = s.split_once("==") {
+            Ok(Condition::Equals(
+                var.trim().to_string(),
+                value.trim().to_string(),
+            ))
+        } else if let Some((npc, state)) = s.split_once(" is ") {
+            Ok(Condition::Is(
+                npc.trim().to_string(),
+                state.trim().to_string(),
+            ))
+        } else {
+            Err(format!("invalid condition: {}", s).into())
+        }
+    }
+
+    pub fn check(&self, ctx: &dyn Context) -> bool {
+        match self {
+            Condition::Has(item) => ctx.has(item),
+            Condition::Equals(var, value) => {
+                ctx.variable(var) == Some(value.as_str())
+            }
+            Condition::NotEquals(var, value) => {
+                ctx.variable(var) != Some(value.as_str())
+            }
+            Condition::Is(npc, state) => {
+                ctx.npc_state(npc) == Some(state.as_str())
+            }
+            Condition::SceneHas(tag) => ctx.scene_has(tag),
+            Condition::TimeIn(period) => ctx.time_in(period),
+            Condition::Not(c) => !c.check(ctx),
+            Condition::Expr(e) => e.check(ctx),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Has(item) => write!(f, "has {}", item),
+            Condition::Equals(var, value) => {
+                write!(f, "{} == {}", var, value)
+            }
+            Condition::NotEquals(var, value) => {
+                write!(f, "{} != {}", var, value)
+            }
+            Condition::Is(npc, state) => write!(f, "{} is {}", npc, state),
+            Condition::SceneHas(tag) => write!(f, "scene has {}", tag),
+            Condition::TimeIn(period) => write!(f, "time in {}", period),
+            Condition::Not(c) => write!(f, "not {}", c),
+            Condition::Expr(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Conditions serialize as written in scenes, e.g. `has key`.
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        Condition::parse(&s).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::GameState;
+
+    impl Context for GameState {
+        fn variable(&self, name: &str) -> Option<&str> {
+            self.get(name)
+        }
+
+        fn has(&self, item: &str) -> bool {
+            GameState::has(self, item)
+        }
+
+        fn npc_state(&self, npc: &str) -> Option<&str> {
+            self.npc(npc)
+        }
+
+        fn visits(&self, scene: &str) -> u32 {
+            GameState::visits(self, scene)
+        }
+    }
+
+    #[test]
+    fn check_conditions() {
+        let mut state = GameState::default();
+        state.set("mood", "happy");
+        state.give("key");
+        state.set_npc("guard", "asleep");
+        state.set("face", ">.<");
+        state.set("sign", "this is fine");
+        let check = |s| Condition::parse(s).unwrap().check(&state);
+        assert!(check("has key"));
+        assert!(!check("has yarn"));
+        assert!(check("not has yarn"));
+        assert!(check("mood == happy"));
+        assert!(check("mood != grumpy"));
+        assert!(check("hunger != high"));
+        assert!(!check("not mood == happy"));
+        assert!(check("guard is asleep"));
+        assert!(!check("guard is alerted"));
+        assert!(!check("cat is asleep"));
+        // Without a clock there is no time of day, and the game state
+        // knows no scene.
+        assert!(!check("time in night"));
+        assert!(!check("scene has outdoors"));
+        assert!(check("has(key) && mood == \"happy\""));
+        assert!(!check(
+            "(has(yarn) || mood == \"happy\") && visits(box) > 0"
+        ));
+        // Values that look like expressions, or contain " is ".
+        assert!(check("face == >.<"));
+        assert!(check("not face == (o_o)"));
+        assert!(check("sign == this is fine"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid condition: purr")]
+    fn invalid_condition() {
+        Condition::parse("purr").unwrap();
+    }
+
+    #[test]
+    fn serialize_condition() {
+        for text in ["not has key", "has(lamp) || visits(cave) > 0"] {
+            let c = Condition::parse(text).unwrap();
+            let json = serde_json::to_string(&c).unwrap();
+            assert_eq!(serde_json::from_str::<Condition>(&json).unwrap(), c);
+        }
+        assert_eq!(
+            serde_json::to_string(&Condition::Has("key".to_string()))
+                .unwrap(),
+            r#""has key""#
+        );
+        assert!(serde_json::from_str::<Condition>(r#""a < (""#).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Console setup for Windows terminals: the classic console host
+//! shows ANSI escape sequences literally and uses a legacy code page,
+//! unless told otherwise. Other platforms need no setup.
+
+/// Prepare the console for colors and UTF-8 text. Returns `false` if
+/// the console does not support escape sequences, e.g. on Windows
+/// versions before 10.
+#[cfg(not(windows))]
+pub fn setup() -> bool {
+    true
+}
+
+#[cfg(windows)]
+pub fn setup() -> bool {
+    use std::ffi::c_void;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    const CP_UTF8: u32 = 65001;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+        fn SetConsoleCP(code_page: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+
+    let handle = io::stdout().as_raw_handle();
+    // SAFETY: the calls only change settings of the console attached
+    // to the process, and fail harmlessly if there is none.
+    unsafe {
+        SetConsoleCP(CP_UTF8);
+        SetConsoleOutputCP(CP_UTF8);
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) == 0 {
+            // Not a console, e.g. redirected output.
+            return true;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || SetConsoleMode(
+                handle,
+                mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+            ) != 0
+    }
+}
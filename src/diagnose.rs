@@ -0,0 +1,185 @@
+//! Diagnostics for a single scene file, for editor plugins: parse
+//! errors, warnings, and where the actions are. `rustventure diagnose
+//! --format json` prints them as JSON:
+//!
+//! ```json
+//! {
+//!   "file": "cave.scene",
+//!   "errors": [],
+//!   "warnings": [{"line": null, "message": "empty description"}],
+//!   "actions": [
+//!     {"line": 2, "start": 1, "end": 23, "source": "out",
+//!      "kind": "change", "target": "field"}
+//!   ]
+//! }
+//! ```
+//!
+//! Lines and columns count from 1, columns in characters, `end` is
+//! exclusive.
+
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::preprocess::preprocess_lines;
+use crate::scene::{Action, Scene};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Line of the problem, if known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic from an error message, taking the line
+    /// number from a `line N:` prefix.
+    fn from_message(message: &str) -> Diagnostic {
+        let line = message
+            .strip_prefix("line ")
+            .and_then(|m| m.split_once(": "));
+        match line.and_then(|(n, m)| Some((n.parse().ok()?, m))) {
+            Some((line, message)) => Diagnostic {
+                line: Some(line),
+                message: message.to_string(),
+            },
+            None => Diagnostic {
+                line: None,
+                message: message.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Location and summary of an action in the scene file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ActionSpan {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    /// Keyword or regular expression of the action
+    pub source: String,
+    /// Kind of the effect, see
+    /// [`Effect::kind`](crate::scene::Effect::kind)
+    pub kind: &'static str,
+    pub target: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostics {
+    pub file: PathBuf,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    pub actions: Vec<ActionSpan>,
+}
+
+/// Read the scene file at `path` and collect diagnostics. Only
+/// failing to read the file is an error.
+pub fn diagnose(path: &Path) -> Result<Diagnostics, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut diagnostics = Diagnostics {
+        file: path.to_path_buf(),
+        errors: Vec::new(),
+        warnings: Vec::new(),
+        actions: Vec::new(),
+    };
+    let format = match Scene::parse_with_warnings(path.to_path_buf(), &text) {
+        Ok((scene, warnings)) => {
+            diagnostics.warnings = warnings
+                .iter()
+                .map(|w| Diagnostic::from_message(&w.message))
+                .collect();
+            scene.format()
+        }
+        Err(e) => {
+            diagnostics
+                .errors
+                .push(Diagnostic::from_message(&e.to_string()));
+            1
+        }
+    };
+
+    // Macro errors are already reported above.
+    let lines = preprocess_lines(&text).unwrap_or_default();
+    let original: Vec<&str> = text.lines().collect();
+    for (n, line) in lines {
+        let Ok(action) = Action::parse(line.trim(), format) else {
+            continue;
+        };
+        let raw = original.get(n - 1).copied().unwrap_or_default();
+        let indent = raw.chars().take_while(|c| c.is_whitespace()).count();
+        diagnostics.actions.push(ActionSpan {
+            line: n,
+            start: indent + 1,
+            end: raw.trim_end().chars().count() + 1,
+            source: action.source().to_string(),
+            kind: action.effect().kind(),
+            target: action.effect().target().map(|t| t.to_string()),
+        });
+    }
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn diagnose_scene() {
+        let fixture = TempAdventure::new(
+            "diagnose",
+            &[(
+                "cave.scene",
+                "A cave.\n!define OUT scene field\n  !kw:out -> !use OUT\n\
+                 !kw:out -> print Again?\n",
+            )],
+        );
+        let path = fixture.path("cave.scene");
+        let ok = diagnose(&path).unwrap();
+        fs::write(&path, "A cave.\n!kw:out -> !use NOPE\n").unwrap();
+        let broken = diagnose(&path).unwrap();
+
+        assert!(ok.errors.is_empty());
+        assert_eq!(
+            ok.warnings,
+            [Diagnostic {
+                line: Some(4),
+                message: "unreachable action, keyword out is already \
+                          taken on line 3"
+                    .to_string()
+            }]
+        );
+        assert_eq!(
+            ok.actions[0],
+            ActionSpan {
+                line: 3,
+                start: 3,
+                end: 22,
+                source: "out".to_string(),
+                kind: "change",
+                target: Some("field".to_string()),
+            }
+        );
+        assert_eq!(ok.actions[1].line, 4);
+        assert_eq!(
+            broken.errors,
+            [Diagnostic {
+                line: Some(2),
+                message: "undefined macro: NOPE".to_string()
+            }]
+        );
+        assert!(broken.actions.is_empty());
+    }
+}
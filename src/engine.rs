@@ -0,0 +1,3433 @@
+//! The game engine: keeps track of the current scene and processes
+//! player input.
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::adventure::{self, Adventure};
+use crate::ambient::Event;
+use crate::analytics::{Analytics, NoAnalytics};
+use crate::campaign::Campaign;
+use crate::clock::{Clock, Time};
+use crate::condition::Context;
+use crate::frame::Frame;
+use crate::item::Item;
+use crate::matcher::{Matcher, RegexMatcher};
+use crate::npc::Npc;
+use crate::output::{
+    self, Decorator, Profile, Stylesheet, TextFilter, Undecorated,
+};
+use crate::profile::PlayerProfile;
+use crate::prompt::Prompter;
+use crate::save::{self, SaveGame, SaveStore, SAVE_VERSION};
+use crate::scene::{Action, Effect, FileProvider, Scene, SceneProvider};
+use crate::state::{GameState, Location};
+
+/// Prompt used if neither adventure nor scene define one.
+pub const DEFAULT_PROMPT: &str = "> ";
+/// Number of commands to keep in the history.
+pub const HISTORY_LENGTH: usize = 100;
+/// Default size limit of the transcript in bytes.
+pub const TRANSCRIPT_QUOTA: usize = 64 * 1024;
+/// Save name used if the player doesn't give one.
+pub const QUICKSAVE: &str = "quicksave";
+/// Save name used when the game ends, to continue later.
+pub const AUTOSAVE: &str = "autosave";
+/// Words in input that refer to the last thing the player handled,
+/// if enabled with [`Engine::set_pronouns`].
+pub const PRONOUNS: &[&str] = &["it", "them"];
+
+#[derive(Debug)]
+pub struct Engine {
+    /// Name of the adventure, empty when playing a single scene
+    adventure: String,
+    adventure_dir: Option<PathBuf>,
+    campaign: Option<Campaign>,
+    /// Name of the start scene, to resume at if a save's scene is gone
+    start: String,
+    scene: Scene,
+    prompt: String,
+    output_profile: Profile,
+    decorator: Box<dyn Decorator>,
+    player_profile: PlayerProfile,
+    history: VecDeque<String>,
+    transcript: String,
+    transcript_quota: usize,
+    state: GameState,
+    /// Item definitions of the adventure
+    items: BTreeMap<String, Item>,
+    /// NPC definitions of the adventure
+    npcs: BTreeMap<String, Npc>,
+    styles: Stylesheet,
+    /// List exits after scene descriptions
+    show_exits: bool,
+    /// In-game clock, if the adventure uses one
+    clock: Option<Clock>,
+    /// Ambient events that may happen after turns
+    events: Vec<Event>,
+    /// Descriptions of items seen in scenes so far
+    item_descriptions: BTreeMap<String, String>,
+    /// Indices of the actions triggered so far, by scene
+    triggered: BTreeMap<String, BTreeSet<usize>>,
+    /// Inputs that matched nothing so far, by scene
+    unmatched: BTreeMap<String, Vec<String>>,
+    /// Whether to replace pronouns in input with the referent
+    pronouns: bool,
+    /// Whether to split input into several commands
+    chain_commands: bool,
+    /// Expansions of abbreviated first words of commands
+    abbreviations: BTreeMap<String, String>,
+    /// Commands of the adventure available in every scene
+    commands: Vec<Action>,
+    /// The last thing the player referred to
+    referent: Option<String>,
+    matcher: Box<dyn Matcher>,
+    scenes: Box<dyn SceneProvider>,
+    /// Wrap adventure text to this many columns
+    wrap: Option<usize>,
+    /// Whether to show text styles
+    color: bool,
+    /// Typewriter speed of the output, if it can be changed
+    text_speed: Option<Arc<AtomicU32>>,
+    /// Steps turning adventure text into output
+    filters: Vec<Box<dyn TextFilter>>,
+    saves: Option<Box<dyn SaveStore>>,
+    analytics: Box<dyn Analytics>,
+    /// State at the last `debug state` command, if debug commands
+    /// are enabled
+    debug: Option<GameState>,
+    /// Whether the idle effect of the scene fired since the last
+    /// input
+    idle_fired: bool,
+}
+
+impl Engine {
+    pub fn new(scene: Scene) -> Engine {
+        let mut engine = Engine {
+            adventure: String::new(),
+            adventure_dir: None,
+            campaign: None,
+            start: scene.name().to_string(),
+            scene: Scene::default(),
+            prompt: DEFAULT_PROMPT.to_string(),
+            output_profile: Profile::default(),
+            decorator: Box::new(Undecorated),
+            player_profile: PlayerProfile::default(),
+            history: VecDeque::new(),
+            transcript: String::new(),
+            transcript_quota: TRANSCRIPT_QUOTA,
+            state: GameState::default(),
+            items: BTreeMap::new(),
+            npcs: BTreeMap::new(),
+            styles: Stylesheet::default(),
+            show_exits: false,
+            clock: None,
+            events: Vec::new(),
+            item_descriptions: BTreeMap::new(),
+            triggered: BTreeMap::new(),
+            unmatched: BTreeMap::new(),
+            pronouns: false,
+            chain_commands: false,
+            abbreviations: adventure::default_abbreviations(),
+            commands: Vec::new(),
+            referent: None,
+            matcher: Box::new(RegexMatcher::default()),
+            scenes: Box::new(FileProvider),
+            wrap: None,
+            color: true,
+            text_speed: None,
+            filters: output::default_filters(),
+            saves: None,
+            analytics: Box::new(NoAnalytics),
+            debug: None,
+            idle_fired: false,
+        };
+        engine.set_seed(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+        );
+        engine.enter(scene);
+        engine
+    }
+
+    /// Switch to `scene` as a new visit, remembering the descriptions
+    /// of items declared in it.
+    fn enter(&mut self, scene: Scene) {
+        for i in scene.items() {
+            if !i.description().is_empty() {
+                self.item_descriptions
+                    .entry(i.id().to_string())
+                    .or_insert_with(|| i.description().to_string());
+            }
+        }
+        self.state.visit(scene.name());
+        self.arrive(scene);
+    }
+
+    /// Make `scene` the current scene, also when returning to it
+    /// without a new visit.
+    fn arrive(&mut self, scene: Scene) {
+        if !self.scene.name().is_empty() {
+            let turn = self.state.turns();
+            logged(self.analytics.scene_change(
+                self.scene.name(),
+                scene.name(),
+                turn,
+            ));
+        }
+        self.scene = scene;
+        self.idle_fired = false;
+    }
+
+    /// Report the end of the game to the analytics.
+    pub fn finish(&mut self) {
+        let turn = self.state.turns();
+        logged(self.analytics.ending(self.scene.name(), turn));
+    }
+
+    /// Move the player to `scene` directly, without an action leading
+    /// there and without describing it.
+    pub fn goto(&mut self, scene: Scene) {
+        self.enter(scene);
+    }
+
+    /// Inputs that matched neither an action nor a built-in command,
+    /// by scene, in the order the player entered them.
+    pub fn unmatched(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.unmatched
+    }
+
+    /// Indices of the actions that have been triggered, by scene.
+    pub fn triggered(&self) -> &BTreeMap<String, BTreeSet<usize>> {
+        &self.triggered
+    }
+
+    /// Current state of an NPC.
+    pub fn npc_state(&self, npc: &str) -> Option<&str> {
+        self.state
+            .npc(npc)
+            .or_else(|| self.npcs.get(npc).map(|n| n.initial()))
+    }
+
+    /// Name of an item to show to the player.
+    pub fn item_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.items.get(id).map_or(id, |i| i.name())
+    }
+
+    /// Find the id of the item the player means by `s`, using the
+    /// item definitions. Returns `s` if no definition matches.
+    pub fn resolve_item<'a>(&'a self, s: &'a str) -> &'a str {
+        self.items
+            .values()
+            .find(|i| i.matches(s))
+            .map_or(s, |i| i.id())
+    }
+
+    /// Names of `items`, separated by commas.
+    fn item_list(&self, items: &[&str]) -> String {
+        items
+            .iter()
+            .map(|i| self.item_name(i))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Labels of the exits of the current scene that are available,
+    /// or at least have an alternative effect (like locked doors).
+    pub fn exits(&self) -> Vec<&str> {
+        let mut exits = Vec::new();
+        for a in self.scene.exits() {
+            let available = a.otherwise().is_some()
+                || a.condition().is_none_or(|c| c.check(self));
+            if let Some(label) = a.exit_label().filter(|_| available) {
+                if !exits.contains(&label) {
+                    exits.push(label);
+                }
+            }
+        }
+        exits
+    }
+
+    /// Items in the current scene, either lying around or inside the
+    /// given container.
+    pub fn items_in(&self, container: Option<&str>) -> Vec<&str> {
+        let name = self.scene.name();
+        let mut items: Vec<&str> = self
+            .scene
+            .items()
+            .iter()
+            .filter(|i| {
+                i.container() == container
+                    && self.state.location(i.id()).is_none()
+            })
+            .map(|i| i.id())
+            .collect();
+        items.extend(self.state.items().filter_map(|(i, l)| match l {
+            Location::Scene {
+                scene,
+                container: c,
+            } if scene == name && c.as_deref() == container => Some(i),
+            _ => None,
+        }));
+        items
+    }
+
+    /// Create an engine for the adventure, starting at its start
+    /// scene.
+    pub fn from_adventure(
+        adventure: Adventure,
+    ) -> Result<Engine, Box<dyn Error>> {
+        let start = Scene::load(adventure.start_path().to_path_buf())?;
+        Engine::start_adventure(adventure, start)
+    }
+
+    /// Like [`from_adventure`](Engine::from_adventure), but skip
+    /// malformed action lines in scenes with a warning, see
+    /// [`Scene::parse_lenient`].
+    pub fn from_adventure_lenient(
+        adventure: Adventure,
+    ) -> Result<Engine, Box<dyn Error>> {
+        let start =
+            Scene::load_lenient(adventure.start_path().to_path_buf())?;
+        Engine::start_adventure(adventure, start)
+    }
+
+    fn start_adventure(
+        adventure: Adventure,
+        start: Scene,
+    ) -> Result<Engine, Box<dyn Error>> {
+        let mut engine = Engine::new(start);
+        engine.campaign = Campaign::find(adventure.dir())?;
+        engine.set_adventure(adventure);
+        Ok(engine)
+    }
+
+    /// Use the settings of `adventure`, and set its initial values
+    /// of variables that aren't set yet.
+    fn set_adventure(&mut self, adventure: Adventure) {
+        self.adventure = adventure.name().to_string();
+        self.adventure_dir = Some(adventure.dir().to_path_buf());
+        self.start = adventure
+            .start_path()
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.prompt =
+            adventure.prompt().unwrap_or(DEFAULT_PROMPT).to_string();
+        for (k, v) in adventure.variables() {
+            if self.state.get(k).is_none() {
+                self.state.set(k, v);
+            }
+        }
+        self.items = adventure.items().clone();
+        self.npcs = adventure.npcs().clone();
+        self.show_exits = adventure.show_exits();
+        self.pronouns = adventure.pronouns();
+        self.chain_commands = adventure.chain_commands();
+        self.abbreviations = adventure.abbreviations().clone();
+        self.commands = adventure.commands();
+        let matcher = RegexMatcher::default();
+        if adventure.tolerate_typos() {
+            self.set_matcher(matcher.tolerance(1));
+        } else {
+            self.set_matcher(matcher);
+        }
+        self.styles = adventure.styles().clone();
+        self.clock = adventure.clock().cloned();
+        self.events = adventure.events().to_vec();
+    }
+
+    /// Continue with the adventure `name` of the campaign, in `scene`
+    /// or its start scene. State the campaign doesn't carry over is
+    /// reset.
+    fn change_adventure<W: Write>(
+        &mut self,
+        name: &str,
+        scene: Option<&str>,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let campaign = self
+            .campaign
+            .as_ref()
+            .ok_or("the adventure is not part of a campaign")?;
+        let adventure = campaign.adventure(name)?;
+        let state = self
+            .state
+            .carry_over(campaign.variables(), campaign.inventory());
+        let start = match scene {
+            Some(s) => adventure.dir().join(format!("{}.scene", s)),
+            None => adventure.start_path().to_path_buf(),
+        };
+        let start = if self.scene.lenient() {
+            Scene::load_lenient(start)?
+        } else {
+            Scene::load(start)?
+        };
+        info!(adventure = name, "changing adventure");
+        self.state = state;
+        self.set_adventure(adventure);
+        self.triggered.clear();
+        self.item_descriptions.clear();
+        self.enter(start);
+        self.output_profile
+            .scene_change(output, self.scene.name())?;
+        self.decorator.scene_change(output, self.scene.name())?;
+        self.write_description(output)
+    }
+
+    /// Name of the adventure being played, empty when playing a
+    /// single scene file.
+    pub fn adventure(&self) -> &str {
+        &self.adventure
+    }
+
+    /// Directory of the adventure being played, `None` when playing a
+    /// single scene file.
+    pub fn adventure_dir(&self) -> Option<&Path> {
+        self.adventure_dir.as_deref()
+    }
+
+    /// The campaign the adventure is part of, if any.
+    pub fn campaign(&self) -> Option<&Campaign> {
+        self.campaign.as_ref()
+    }
+
+    /// Enable the `save` and `restore` commands, keeping saves in
+    /// `store`.
+    pub fn set_save_store<S: SaveStore + 'static>(&mut self, store: S) {
+        self.saves = Some(Box::new(store));
+    }
+
+    /// Save the current game.
+    pub fn save_game(&self) -> SaveGame {
+        SaveGame {
+            version: SAVE_VERSION,
+            adventure: self.adventure.clone(),
+            scene: self.scene.reference(),
+            state: self.state.clone(),
+            content: self.content_hash(),
+        }
+    }
+
+    /// Hash of the adventure's files, or the scene files next to the
+    /// current scene when playing a single scene. `None` if the
+    /// scenes don't come from the file system.
+    fn content_hash(&self) -> Option<u64> {
+        let dir = match &self.adventure_dir {
+            Some(d) => d.as_path(),
+            None => self.scene.path().parent()?,
+        };
+        save::content_hash(dir).ok()
+    }
+
+    /// Save the game as `name` in the save store. Does nothing if
+    /// there is no store.
+    pub fn save_as(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let save = self.save_game();
+        match &mut self.saves {
+            Some(store) => {
+                enter_span!("save", name);
+                store.save(name, &save)?;
+                info!(scene = %save.scene, "game saved");
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Continue the game saved as `name` in the save store, see
+    /// [`restore`](Engine::restore).
+    pub fn restore_from(
+        &mut self,
+        name: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        enter_span!("restore", name);
+        let save = self
+            .saves
+            .as_ref()
+            .ok_or("saving is not available")?
+            .load(name)?;
+        self.restore(save)
+    }
+
+    /// Continue a saved game. Returns notes for the player if the
+    /// adventure changed since the game was saved. If the scene of
+    /// the save is gone then, the game continues at the start scene.
+    pub fn restore(
+        &mut self,
+        save: SaveGame,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        if save.adventure != self.adventure {
+            return Err(format!(
+                "the save is from a different adventure: {}",
+                save.adventure
+            )
+            .into());
+        }
+        let mut notes = Vec::new();
+        let changed = save.content.is_some_and(|c| {
+            self.content_hash().is_some_and(|current| current != c)
+        });
+        if changed {
+            warn!(scene = %save.scene, "adventure changed since the save");
+            notes.push(
+                "The adventure has changed since this game was saved, \
+                 some things may not be as you left them."
+                    .to_string(),
+            );
+        }
+        let scene = match self.scenes.load_next(&self.scene, &save.scene) {
+            Ok(scene) => scene,
+            Err(_) if changed => {
+                let start = self.start.clone();
+                let scene = self.scenes.load_next(&self.scene, &start)?;
+                notes.push(
+                    "The place you saved in no longer exists, continuing \
+                     at the start."
+                        .to_string(),
+                );
+                scene
+            }
+            Err(e) => return Err(e),
+        };
+        self.enter(scene);
+        self.state = save.state;
+        info!(scene = %save.scene, "game restored");
+        Ok(notes)
+    }
+
+    /// Ask whether to save before quitting, e.g. after the player
+    /// pressed Ctrl-C, and save as [`QUICKSAVE`] if they agree. Does
+    /// nothing if saving is not available.
+    pub fn confirm_save(
+        &mut self,
+        prompter: &mut dyn Prompter,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.saves.is_none() {
+            return Ok(());
+        }
+        let question = "Save before quitting?";
+        let yes = prompter.confirm(question)?;
+        self.record(
+            format!("{} {}\n", question, if yes { "yes" } else { "no" })
+                .as_bytes(),
+        );
+        if yes {
+            let mut out = prompter.output();
+            let mut rec = Recorder::new(&mut out);
+            self.save_command("save", QUICKSAVE, &mut rec)?;
+            let copy = rec.copy;
+            self.record(&copy);
+        }
+        Ok(())
+    }
+
+    pub fn set_output_profile(&mut self, profile: Profile) {
+        self.output_profile = profile;
+    }
+
+    /// Report game events to `analytics`, e.g. a
+    /// [`FileLog`](crate::analytics::FileLog) for playtests.
+    pub fn set_analytics<A: Analytics + 'static>(&mut self, analytics: A) {
+        self.analytics = Box::new(analytics);
+    }
+
+    /// Add announcements to the output, e.g.
+    /// [`Echo`](crate::output::Echo).
+    pub fn set_decorator<D: Decorator + 'static>(&mut self, decorator: D) {
+        self.decorator = Box::new(decorator);
+    }
+
+    pub fn set_player_profile(&mut self, profile: PlayerProfile) {
+        self.player_profile = profile;
+    }
+
+    pub fn player_profile(&self) -> &PlayerProfile {
+        &self.player_profile
+    }
+
+    pub fn player_profile_mut(&mut self) -> &mut PlayerProfile {
+        &mut self.player_profile
+    }
+
+    /// Wrap adventure text to lines of at most `width` columns, or
+    /// not at all if `None`.
+    pub fn set_wrap(&mut self, width: Option<usize>) {
+        self.wrap = width;
+    }
+
+    /// Replace [`PRONOUNS`] in input with the last thing the player
+    /// referred to: the item of the last item command, or the
+    /// capture group named `it` of the last action triggered, e.g.
+    /// `!regex:^pet (?P<it>cat|dog)$ -> ...`.
+    pub fn set_pronouns(&mut self, pronouns: bool) {
+        self.pronouns = pronouns;
+    }
+
+    /// Replace the abbreviations of commands, by default
+    /// [`ABBREVIATIONS`](crate::adventure::ABBREVIATIONS).
+    pub fn set_abbreviations(
+        &mut self,
+        abbreviations: BTreeMap<String, String>,
+    ) {
+        self.abbreviations = abbreviations;
+    }
+
+    /// Let players chain commands like "open door then north", see
+    /// [`step`](Engine::step). Off by default, because keywords may
+    /// contain the separators.
+    pub fn set_chain_commands(&mut self, chain: bool) {
+        self.chain_commands = chain;
+    }
+
+    /// Show text styles, or remove them if `false`.
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// Let the player change the typewriter speed of the output, see
+    /// [`Typewriter::speed`](crate::output::Typewriter::speed).
+    pub fn set_text_speed(&mut self, speed: Arc<AtomicU32>) {
+        self.text_speed = Some(speed);
+    }
+
+    /// Seed the random number generator used for random responses.
+    /// By default it is seeded from the current time.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.state.seed(seed);
+    }
+
+    /// Let `players` players take turns in the same game, each with
+    /// their own inventory and variables, see
+    /// [`GameState::set_players`]. After each turn the engine says
+    /// whose turn is next.
+    pub fn set_players(&mut self, players: usize) {
+        self.state.set_players(players);
+    }
+
+    /// Number of the player whose turn it is, counting from 1.
+    pub fn current_player(&self) -> usize {
+        self.state.player() + 1
+    }
+
+    /// Enable commands for adventure authors, like `debug state`.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug.then(|| self.state.snapshot());
+    }
+
+    /// Enable the in-game clock, or disable it with `None`.
+    pub fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+    }
+
+    /// Set the ambient events that may happen after turns.
+    pub fn set_events(&mut self, events: Vec<Event>) {
+        self.events = events;
+    }
+
+    /// Current time of the in-game clock, `None` if the adventure
+    /// doesn't use one.
+    pub fn time(&self) -> Option<Time> {
+        self.clock.as_ref().map(|c| c.time(self.state.elapsed()))
+    }
+
+    /// Replace the default [`RegexMatcher`] that decides which
+    /// actions player input triggers.
+    pub fn set_matcher<M: Matcher + 'static>(&mut self, matcher: M) {
+        self.matcher = Box::new(matcher);
+    }
+
+    /// Replace the default [`FileProvider`] that loads the scenes
+    /// the player moves to.
+    pub fn set_scene_provider<P: SceneProvider + 'static>(
+        &mut self,
+        provider: P,
+    ) {
+        self.scenes = Box::new(provider);
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    /// Get the value of a variable. Variables set by the current
+    /// scene override the game state.
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.scene
+            .variables()
+            .get(name)
+            .map(|v| v.as_str())
+            .or_else(|| self.state.get(name))
+    }
+
+    /// Replace `${name}` references in `text` with variable values.
+    /// With a clock `${time}` and `${day}` show the in-game time, and
+    /// `${current_player}` the number of the player whose turn it is,
+    /// unless there are variables with these names. References to
+    /// unknown variables are left alone, so they are easy to spot.
+    pub fn interpolate<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        lazy_static! {
+            static ref VAR_RE: Regex = Regex::new(r"\$\{(\w+)\}").unwrap();
+        }
+        VAR_RE.replace_all(text, |c: &Captures| {
+            if let Some(value) = self.variable(&c[1]) {
+                return value.to_string();
+            }
+            match (&c[1], self.time()) {
+                ("time", Some(t)) => t.to_string(),
+                ("day", Some(t)) => t.day.to_string(),
+                ("current_player", _) => self.current_player().to_string(),
+                _ => c[0].to_string(),
+            }
+        })
+    }
+
+    /// The prompt to show: The current scene can override the
+    /// adventure prompt.
+    pub fn prompt(&self) -> Cow<'_, str> {
+        self.interpolate(self.scene.prompt().unwrap_or(&self.prompt))
+    }
+
+    /// Commands entered so far, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(|s| s.as_str())
+    }
+
+    /// Pass `text` through the text filters, by default replacing
+    /// variable references, applying the adventure's text styles, and
+    /// wrapping it if enabled.
+    pub fn render(&self, text: &str) -> String {
+        self.filters
+            .iter()
+            .fold(text.to_string(), |text, f| f.filter(self, text))
+    }
+
+    /// Add `filter` at the end of the text pipeline, see
+    /// [`render`](Engine::render).
+    pub fn add_filter(&mut self, filter: Box<dyn TextFilter>) {
+        self.filters.push(filter);
+    }
+
+    /// Replace all text filters, the defaults are
+    /// [`output::default_filters`].
+    pub fn set_filters(&mut self, filters: Vec<Box<dyn TextFilter>>) {
+        self.filters = filters;
+    }
+
+    /// Text styles of the adventure.
+    pub fn styles(&self) -> &Stylesheet {
+        &self.styles
+    }
+
+    pub fn output_profile(&self) -> Profile {
+        self.output_profile
+    }
+
+    /// Whether text styles are shown.
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// Width to wrap text to, if any.
+    pub fn wrap(&self) -> Option<usize> {
+        self.wrap
+    }
+
+    /// Write a line of adventure text.
+    fn say<W: Write>(
+        &self,
+        output: &mut W,
+        text: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        writeln!(output, "{}", self.render(text))?;
+        Ok(())
+    }
+
+    /// Recent output of the engine, including prompts and player
+    /// input, limited to the transcript quota.
+    pub fn transcript(&self) -> &str {
+        &self.transcript
+    }
+
+    /// Set the size limit of the transcript in bytes.
+    pub fn set_transcript_quota(&mut self, bytes: usize) {
+        self.transcript_quota = bytes;
+        self.record(b"");
+    }
+
+    /// Add output to the transcript, dropping old lines if it gets
+    /// too long.
+    fn record(&mut self, text: &[u8]) {
+        self.transcript.push_str(&String::from_utf8_lossy(text));
+        if self.transcript.len() > self.transcript_quota {
+            let mut cut = self.transcript.len() - self.transcript_quota;
+            while !self.transcript.is_char_boundary(cut) {
+                cut += 1;
+            }
+            // Prefer cutting at a line break, unless that would drop
+            // everything.
+            if let Some(i) = self.transcript[cut..].find('\n') {
+                if cut + i + 1 < self.transcript.len() {
+                    cut += i + 1;
+                }
+            }
+            self.transcript.drain(..cut);
+        }
+    }
+
+    /// Write the description of the current scene.
+    pub fn describe<W: Write>(
+        &mut self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut rec = Recorder::new(output);
+        let res = self.write_description(&mut rec);
+        let copy = rec.copy;
+        self.record(&copy);
+        res
+    }
+
+    fn write_description<W: Write>(
+        &self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        write!(output, "{}", self.render(&self.scene.to_string()))?;
+        let items = self.items_in(None);
+        if !items.is_empty() {
+            writeln!(output, "You can see: {}.", self.item_list(&items))?;
+        }
+        if self.show_exits {
+            let exits = self.exits();
+            if !exits.is_empty() {
+                writeln!(output, "Exits: {}.", exits.join(", "))?;
+            }
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    /// The description of the current scene as an HTML page, with
+    /// the text styles of the adventure, e.g. for promotional
+    /// material. See [`output::html`].
+    pub fn scene_html(&mut self) -> Result<String, Box<dyn Error>> {
+        // Style tags must stay in the text, and wrapping is up to the
+        // browser.
+        let filters = std::mem::replace(
+            &mut self.filters,
+            vec![Box::new(output::Interpolate)],
+        );
+        let mut text = Vec::new();
+        let res = self.write_description(&mut text);
+        self.filters = filters;
+        res?;
+        let title = match self.adventure.as_str() {
+            "" => self.scene.name().to_string(),
+            a => format!("{}: {}", a, self.scene.name()),
+        };
+        let body =
+            output::html::to_html(&String::from_utf8(text)?, &self.styles);
+        Ok(output::html::page(&title, &body, &self.styles))
+    }
+
+    /// Write the prompt, formatted for the output profile.
+    pub fn write_prompt<W: Write>(
+        &mut self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        write!(output, "{}", self.formatted_prompt()?)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    /// The prompt formatted for the output profile, to show before
+    /// reading input. It is recorded in the transcript.
+    pub fn formatted_prompt(&mut self) -> Result<String, Box<dyn Error>> {
+        let mut prompt = Vec::new();
+        self.output_profile.prompt(&mut prompt, &self.prompt())?;
+        self.record(&prompt);
+        Ok(String::from_utf8(prompt)?)
+    }
+
+    /// Process one line of player input, writing any resulting
+    /// output.
+    ///
+    /// `again` (or `g`) repeats the previous command, then player
+    /// aliases and abbreviations like `x` for `examine` are expanded.
+    /// `go <exit>` takes the exit with that label. Actions of the
+    /// current scene take precedence over the commands of the
+    /// adventure, see [`Adventure::commands`], and both over built-in
+    /// commands, so adventures are free to use any keywords they
+    /// like. A trailing line break is ignored,
+    /// including the `\r\n` of Windows terminals.
+    ///
+    /// If enabled with [`set_chain_commands`](Engine::set_chain_commands)
+    /// the input may contain several commands separated by "then",
+    /// "and", or ".", unless an action matches the whole input. They
+    /// run in order until one fails or the scene changes.
+    ///
+    /// The output of a step ends with a flush, so front ends can
+    /// handle it as one block, e.g. when skipping an animation.
+    pub fn step<W: Write>(
+        &mut self,
+        input: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let input = input.trim_end_matches(['\n', '\r']);
+        self.record(format!("{}\n", input).as_bytes());
+        self.idle_fired = false;
+        let turns = self.state.turns();
+        let mut rec = Recorder::new(output);
+        let res = self
+            .process_chain(input, &mut rec)
+            .and_then(|_| self.pass_turn(turns, &mut rec))
+            .and_then(|_| Ok(rec.flush()?));
+        let copy = rec.copy;
+        self.record(&copy);
+        res
+    }
+
+    /// In hot-seat mode pass the turn to the next player if the input
+    /// counted as a turn, i.e. the turn count changed from `turns`.
+    fn pass_turn<W: Write>(
+        &mut self,
+        turns: u64,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.state.players() < 2 || self.state.turns() == turns {
+            return Ok(());
+        }
+        self.state.next_player();
+        trace!(player = self.current_player(), "next player");
+        writeln!(
+            output,
+            "Player {}, it's your turn.",
+            self.current_player()
+        )?;
+        Ok(())
+    }
+
+    /// How long the player may stay idle before the `@idle` effect
+    /// of the current scene happens, see [`idle`](Engine::idle).
+    /// `None` if the scene has none, or it already happened since the
+    /// last input.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self.idle_fired {
+            true => None,
+            false => self.scene.idle().map(|(d, _)| d),
+        }
+    }
+
+    /// Apply the `@idle` effect of the current scene, for interactive
+    /// front ends to call when the player didn't enter anything for
+    /// [`idle_timeout`](Engine::idle_timeout). It happens at most
+    /// once between inputs.
+    pub fn idle<W: Write>(
+        &mut self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some((_, effect)) = self.scene.idle() else {
+            return Ok(());
+        };
+        if self.idle_fired {
+            return Ok(());
+        }
+        let effect = effect.clone();
+        self.idle_fired = true;
+        debug!(scene = self.scene.name(), "player is idle");
+        // The idle effect counts as the action after the last one.
+        let index = self.scene.actions().len();
+        let mut rec = Recorder::new(output);
+        let res = self
+            .apply(index, effect, &mut rec)
+            .and_then(|_| Ok(rec.flush()?));
+        let copy = rec.copy;
+        self.record(&copy);
+        res
+    }
+
+    /// Describe the current scene like [`describe`](Engine::describe),
+    /// as the [`Frame`] for the start of the game.
+    pub fn start_frame(&mut self) -> Result<Frame, Box<dyn Error>> {
+        let mut output = Vec::new();
+        self.describe(&mut output)?;
+        let output = String::from_utf8(output)?;
+        Ok(self.frame_of(String::new(), output.clone(), Some(output)))
+    }
+
+    /// Process one line of input like [`step`](Engine::step), and
+    /// collect everything it produced in a [`Frame`].
+    pub fn frame(&mut self, input: &str) -> Result<Frame, Box<dyn Error>> {
+        let before = self.state.clone();
+        let scene = self.scene.reference();
+        let mut output = Vec::new();
+        self.step(input, &mut output)?;
+        let description = if self.scene.reference() != scene {
+            let mut d = Vec::new();
+            self.write_description(&mut d)?;
+            Some(String::from_utf8(d)?)
+        } else {
+            None
+        };
+        let mut frame = self.frame_of(
+            input.trim_end_matches(['\n', '\r']).to_string(),
+            String::from_utf8(output)?,
+            description,
+        );
+        frame.changes = before.diff(&self.state);
+        Ok(frame)
+    }
+
+    fn frame_of(
+        &self,
+        input: String,
+        output: String,
+        description: Option<String>,
+    ) -> Frame {
+        Frame {
+            input,
+            output,
+            scene: self.scene.reference(),
+            description,
+            prompt: self.prompt().into_owned(),
+            exits: self
+                .scene
+                .exits()
+                .filter_map(|a| a.exit_label().map(|l| l.to_string()))
+                .collect(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Process the commands in `input`, see [`step`](Engine::step).
+    fn process_chain<W: Write>(
+        &mut self,
+        input: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        lazy_static! {
+            static ref SEPARATOR_RE: Regex =
+                Regex::new(r"\s+(?:then|and)\s+|\.").unwrap();
+        }
+        let commands: Vec<&str> = match self.chain_commands {
+            true => SEPARATOR_RE
+                .split(input)
+                .map(|c| c.trim())
+                .filter(|c| !c.is_empty())
+                .collect(),
+            false => Vec::new(),
+        };
+        // Keywords containing a separator still work.
+        if commands.len() < 2 || self.find_effect(input).is_some() {
+            self.process(input, output)?;
+            return Ok(());
+        }
+        for command in commands {
+            let scene = self.scene.name().to_string();
+            if !self.process(command, output)? || self.scene.name() != scene {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process one command, returns whether it succeeded: it
+    /// triggered an action whose condition was met, or a built-in
+    /// command.
+    fn process<W: Write>(
+        &mut self,
+        input: &str,
+        output: &mut W,
+    ) -> Result<bool, Box<dyn Error>> {
+        // Scenes may use the words themselves, like abbreviations.
+        let repeat = (input == "again" || input == "g")
+            && self.find_effect(input).is_none();
+        let input = if repeat {
+            match self.history.back() {
+                Some(last) => last.clone(),
+                None => {
+                    writeln!(output, "There is nothing to repeat.")?;
+                    return Ok(false);
+                }
+            }
+        } else {
+            if !input.is_empty() {
+                if self.history.len() == HISTORY_LENGTH {
+                    self.history.pop_front();
+                }
+                self.history.push_back(input.to_string());
+            }
+            input.to_string()
+        };
+        let input = self.player_profile.expand(&input).into_owned();
+        let input = self.expand_abbreviation(&input).into_owned();
+        let input = match self.pronouns {
+            true => self.resolve_pronouns(&input).into_owned(),
+            false => input,
+        };
+        trace!(input, scene = self.scene.name(), "processing input");
+        if let Some(cmd) = input.strip_prefix('@') {
+            if self.debug.is_some() {
+                self.author_command(cmd, output)?;
+                return Ok(true);
+            }
+        }
+        if !input.is_empty() {
+            self.state.tick();
+            if let Some(clock) = &self.clock {
+                self.state.advance(clock.turn());
+            }
+        }
+        let found = self.find_effect(&input).or_else(|| {
+            let label = input.strip_prefix("go ")?.trim();
+            self.find_effect_by(|a| a.exit_label() == Some(label))
+        });
+        let global = match found {
+            None => self.global_command(&input),
+            Some(_) => None,
+        };
+        let success = match (found, global) {
+            (Some((i, effect, fired)), _) => {
+                self.triggered
+                    .entry(self.scene.name().to_string())
+                    .or_default()
+                    .insert(i);
+                if fired {
+                    self.state.fire(self.scene.name(), i);
+                }
+                if let Effect::Change(target) = &effect {
+                    self.state.add_exit(self.scene.name(), &input, target);
+                }
+                self.decorator.command(output, &input)?;
+                if self.pronouns {
+                    let it = self.scene.actions()[i]
+                        .expression()
+                        .captures(&input)
+                        .and_then(|c| c.name("it"))
+                        .map(|m| m.as_str().to_string());
+                    self.referent = it.or(self.referent.take());
+                }
+                self.apply(i, effect, output)?;
+                fired
+            }
+            (None, Some((i, effect))) => {
+                trace!(command = i, "global command matched");
+                self.decorator.command(output, &input)?;
+                // Indexes past the scene actions keep the state of
+                // alternative responses apart.
+                let index = usize::MAX - i;
+                self.apply(index, effect, output)?;
+                true
+            }
+            (None, None) => {
+                trace!("no action matched");
+                let builtin = self.builtin(&input, output)?;
+                if !builtin && !input.is_empty() {
+                    self.unmatched
+                        .entry(self.scene.name().to_string())
+                        .or_default()
+                        .push(input.clone());
+                    let turn = self.state.turns();
+                    logged(self.analytics.unmatched(
+                        self.scene.name(),
+                        &input,
+                        turn,
+                    ));
+                    let actions = self.scene.actions();
+                    if let Some(s) = self.matcher.suggest(actions, &input) {
+                        writeln!(output, "Did you mean '{}'?", s)?;
+                    }
+                }
+                builtin || input.is_empty()
+            }
+        };
+        if !input.is_empty() {
+            self.ambient(output)?;
+        }
+        Ok(success)
+    }
+
+    /// Expand an abbreviation in the first word of `input`, unless an
+    /// action matches the input as it is.
+    fn expand_abbreviation<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let (first, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let Some(long) = self.abbreviations.get(first) else {
+            return Cow::Borrowed(input);
+        };
+        if self.find_effect(input).is_some() {
+            return Cow::Borrowed(input);
+        }
+        match rest {
+            "" => Cow::Owned(long.clone()),
+            rest => Cow::Owned(format!("{} {}", long, rest)),
+        }
+    }
+
+    /// Replace [`PRONOUNS`] in `input` with the last thing the player
+    /// referred to, if there is one.
+    fn resolve_pronouns<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let Some(referent) = &self.referent else {
+            return Cow::Borrowed(input);
+        };
+        if !input.split_whitespace().any(|w| PRONOUNS.contains(&w)) {
+            return Cow::Borrowed(input);
+        }
+        let words: Vec<&str> = input
+            .split_whitespace()
+            .map(|w| if PRONOUNS.contains(&w) { referent } else { w })
+            .collect();
+        trace!(input, referent, "resolved pronouns");
+        Cow::Owned(words.join(" "))
+    }
+
+    /// Roll for the ambient events of the current scene, and show the
+    /// first one that happens.
+    fn ambient<W: Write>(
+        &mut self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut text = None;
+        for e in self.events.iter().filter(|e| e.applies(&self.scene)) {
+            if self.state.random(100) < e.chance() as usize {
+                trace!(
+                    scene = self.scene.name(),
+                    event = e.text(),
+                    "ambient event"
+                );
+                text = Some(e.text().to_string());
+                break;
+            }
+        }
+        match text {
+            Some(text) => self.say(output, &text),
+            None => Ok(()),
+        }
+    }
+
+    /// Find the effect of the first action matching `input` that is
+    /// either available or has an alternative effect. Returns the
+    /// index of the action along with the effect, and whether it is
+    /// the action's own effect (so the action fires). Approximate
+    /// matches are considered only if there is no exact one.
+    fn find_effect(&self, input: &str) -> Option<(usize, Effect, bool)> {
+        self.find_effect_by(|a| self.matcher.matches(a, input))
+            .or_else(|| {
+                self.find_effect_by(|a| {
+                    self.matcher.matches_approximately(a, input)
+                })
+            })
+    }
+
+    /// Number and effect of the global command of the adventure
+    /// matching `input`, or the alternative effect if its condition
+    /// isn't met.
+    fn global_command(&self, input: &str) -> Option<(usize, Effect)> {
+        let (i, a) = self
+            .commands
+            .iter()
+            .enumerate()
+            .find(|(_, a)| self.matcher.matches(a, input))?;
+        let effect = match a.condition() {
+            Some(c) if !c.check(self) => a.otherwise()?,
+            _ => a.effect(),
+        };
+        Some((i, effect.clone()))
+    }
+
+    fn find_effect_by<F: Fn(&Action) -> bool>(
+        &self,
+        matches: F,
+    ) -> Option<(usize, Effect, bool)> {
+        let scene = self.scene.name();
+        self.scene
+            .actions()
+            .iter()
+            .enumerate()
+            .filter(|(i, a)| {
+                if a.once() && self.state.last_fired(scene, *i).is_some() {
+                    trace!(scene, action = i, "skipped, fired once already");
+                    return false;
+                }
+                let matched = matches(a);
+                trace!(
+                    scene,
+                    action = i,
+                    expression = a.expression().as_str(),
+                    matched,
+                    "tested action"
+                );
+                matched
+            })
+            .find_map(|(i, a)| {
+                let cooling = self
+                    .state
+                    .last_fired(scene, i)
+                    .is_some_and(|t| self.state.turns() - t <= a.cooldown());
+                match a.condition() {
+                    _ if cooling => {
+                        trace!(scene, action = i, "cooling down");
+                        a.cooling().map(|e| (e, false))
+                    }
+                    Some(c) if !c.check(self) => {
+                        trace!(
+                            scene,
+                            action = i,
+                            condition = %c,
+                            "condition not met"
+                        );
+                        a.otherwise().map(|e| (e, false))
+                    }
+                    _ => Some((a.effect(), true)),
+                }
+                .map(|(e, fired)| (i, e.clone(), fired))
+            })
+    }
+
+    /// Change to the scene `target` and describe it, if the player
+    /// meets its entry condition. Returns whether the scene changed.
+    fn change_scene<W: Write>(
+        &mut self,
+        target: &str,
+        output: &mut W,
+    ) -> Result<bool, Box<dyn Error>> {
+        let next = self.scenes.load_next(&self.scene, target)?;
+        if let Some((c, text)) = next.entry_condition() {
+            if !c.check(self) {
+                let text = text.unwrap_or("You can't go there.");
+                self.say(output, text)?;
+                return Ok(false);
+            }
+        }
+        self.enter(next);
+        self.output_profile
+            .scene_change(output, self.scene.name())?;
+        self.decorator.scene_change(output, self.scene.name())?;
+        self.write_description(output)?;
+        Ok(true)
+    }
+
+    /// Apply `effect` of action number `index` in the current scene.
+    fn apply<W: Write>(
+        &mut self,
+        index: usize,
+        effect: Effect,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        trace!(
+            scene = self.scene.name(),
+            action = index,
+            ?effect,
+            "applying effect"
+        );
+        match effect {
+            Effect::Output(s) => self.say(output, &s)?,
+            Effect::Cycle(responses) => {
+                let n = self.state.respond(self.scene.name(), index);
+                self.say(output, &responses[n % responses.len()])?;
+            }
+            Effect::Random(responses) => {
+                let n = self.state.random(responses.len());
+                self.say(output, &responses[n])?;
+            }
+            Effect::Change(s) => {
+                self.change_scene(&s, output)?;
+            }
+            Effect::PushScene(s) => {
+                let current = self.scene.reference();
+                if self.change_scene(&s, output)? {
+                    self.state.push_scene(current);
+                }
+            }
+            Effect::PopScene => match self.state.scene_stack().last() {
+                Some(reference) => {
+                    // Returning is not a new visit. Keep the scene on
+                    // the stack until it is loaded.
+                    let scene =
+                        self.scenes.load_next(&self.scene, reference)?;
+                    self.state.pop_scene();
+                    self.arrive(scene);
+                    self.output_profile
+                        .scene_change(output, self.scene.name())?;
+                    self.decorator.scene_change(output, self.scene.name())?;
+                    self.write_description(output)?;
+                }
+                None => self.say(output, "There is nowhere to return to.")?,
+            },
+            Effect::Give(item, msg) => {
+                self.state.give(&item);
+                let msg = msg.unwrap_or_else(|| {
+                    format!("Got {}.", self.item_name(&item))
+                });
+                self.say(output, &msg)?;
+            }
+            Effect::Take(item, msg) => {
+                self.state.take(&item);
+                let msg = msg.unwrap_or_else(|| {
+                    format!("Lost {}.", self.item_name(&item))
+                });
+                self.say(output, &msg)?;
+            }
+            #[cfg(feature = "rhai")]
+            Effect::Script(file) => {
+                let path = self.scene.path().with_file_name(file);
+                let outcome = crate::script::run(&path, &mut self.state)?;
+                for line in outcome.output {
+                    self.say(output, &line)?;
+                }
+                if let Some(scene) = outcome.scene {
+                    self.apply(index, Effect::Change(scene), output)?;
+                }
+            }
+            Effect::ChangeAdventure(name, scene) => {
+                self.change_adventure(&name, scene.as_deref(), output)?
+            }
+            Effect::Advance(minutes, msg) => {
+                self.state.advance(minutes);
+                if let Some(msg) = msg {
+                    self.say(output, &msg)?;
+                }
+            }
+            Effect::Npc(npc, state, msg) => match self.npcs.get(&npc) {
+                // `check` reports these, don't end the game for a typo
+                None => {
+                    warn!(npc, "unknown NPC");
+                }
+                Some(def) => {
+                    let current =
+                        self.npc_state(&npc).unwrap_or(def.initial());
+                    if def.can_change(current, &state) {
+                        self.state.set_npc(&npc, &state);
+                        if let Some(msg) = msg {
+                            self.say(output, &msg)?;
+                        }
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Handle built-in commands, returns `false` if `input` isn't
+    /// one.
+    fn builtin<W: Write>(
+        &mut self,
+        input: &str,
+        output: &mut W,
+    ) -> Result<bool, Box<dyn Error>> {
+        let (cmd, args) = input.split_once(' ').unwrap_or((input, ""));
+        let item_command = matches!(cmd, "examine" | "take" | "drop" | "put");
+        if self.pronouns && item_command {
+            let item = args.split(" from ").next().unwrap_or_default();
+            let item = item.split(" in ").next().unwrap_or_default().trim();
+            if !item.is_empty() {
+                self.referent = Some(self.resolve_item(item).to_string());
+            }
+        }
+        match cmd {
+            "alias" => self.alias(args.trim(), output)?,
+            "options" => self.options(args.trim(), output)?,
+            "inventory" => {
+                let items: Vec<&str> = self.state.inventory().collect();
+                if items.is_empty() {
+                    writeln!(output, "You are empty-handed.")?;
+                } else {
+                    writeln!(
+                        output,
+                        "You are carrying: {}",
+                        self.item_list(&items)
+                    )?;
+                }
+            }
+            "examine" => self.examine(args.trim(), output)?,
+            "take" => self.take_item(args.trim(), output)?,
+            "drop" => self.drop_item(args.trim(), output)?,
+            "put" => self.put_item(args.trim(), output)?,
+            "map" => self.map(output)?,
+            "exits" => {
+                let exits = self.exits();
+                if exits.is_empty() {
+                    writeln!(output, "There are no obvious exits.")?;
+                } else {
+                    writeln!(output, "Exits: {}.", exits.join(", "))?;
+                }
+            }
+            "history" => {
+                for (i, line) in self.history.iter().enumerate() {
+                    writeln!(output, "{}: {}", i + 1, line)?;
+                }
+            }
+            "save" | "restore" | "saves" => {
+                self.save_command(cmd, args.trim(), output)?
+            }
+            "debug" if self.debug.is_some() => {
+                self.debug_command(args.trim(), output)?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The `save [name]`, `restore [name]`, and `saves` commands.
+    /// Errors of the save store are shown to the player instead of
+    /// ending the game.
+    fn save_command<W: Write>(
+        &mut self,
+        cmd: &str,
+        name: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = if name.is_empty() { QUICKSAVE } else { name };
+        let store = match &self.saves {
+            Some(s) => s,
+            None => {
+                writeln!(output, "Saving is not available.")?;
+                return Ok(());
+            }
+        };
+        let res = match cmd {
+            "save" => {
+                self.save_as(name).map(|_| format!("Saved as {}.", name))
+            }
+            "saves" => store.list().map(|saves| {
+                if saves.is_empty() {
+                    "There are no saved games.".to_string()
+                } else {
+                    format!("Saved games: {}", saves.join(", "))
+                }
+            }),
+            _ => match self.restore_from(name) {
+                Ok(notes) => {
+                    for note in notes {
+                        writeln!(output, "{}", note)?;
+                    }
+                    self.write_description(output)?;
+                    return Ok(());
+                }
+                Err(e) => Err(e),
+            },
+        };
+        match res {
+            Ok(msg) => writeln!(output, "{}", msg)?,
+            Err(e) => writeln!(output, "Sorry, that didn't work: {}", e)?,
+        }
+        Ok(())
+    }
+
+    /// The `debug state` command: show the game state, and what
+    /// changed since the last time.
+    fn debug_command<W: Write>(
+        &mut self,
+        args: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        if args != "state" {
+            writeln!(output, "Unknown debug command: {}", args)?;
+            return Ok(());
+        }
+        let s = &self.state;
+        writeln!(
+            output,
+            "Scene: {}, turn {}",
+            self.scene.reference(),
+            s.turns()
+        )?;
+        if let Some(time) = self.time() {
+            writeln!(output, "Time: day {}, {}", time.day, time)?;
+        }
+        if !s.scene_stack().is_empty() {
+            writeln!(output, "Scene stack: {}", s.scene_stack().join(", "))?;
+        }
+        for (name, value) in s.variables() {
+            writeln!(output, "Variable {} = {}", name, value)?;
+        }
+        for p in 0..s.players() {
+            for (name, value) in s.player_variables(p).into_iter().flatten() {
+                writeln!(
+                    output,
+                    "Variable {} = {} (player {})",
+                    name,
+                    value,
+                    p + 1
+                )?;
+            }
+        }
+        for (item, location) in s.items() {
+            writeln!(output, "Item {}: {}", item, location)?;
+        }
+        for (npc, state) in s.npcs() {
+            writeln!(output, "NPC {}: {}", npc, state)?;
+        }
+        let visits: Vec<String> =
+            s.visited().map(|(s, n)| format!("{} {}", s, n)).collect();
+        writeln!(output, "Visits: {}", visits.join(", "))?;
+        let last = self.debug.replace(s.snapshot()).unwrap_or_default();
+        let changes = last.diff(s);
+        if !changes.is_empty() {
+            writeln!(output, "Changes since the last check:")?;
+            for c in changes {
+                writeln!(output, "  {}", c)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Commands for adventure authors, starting with `@` so they
+    /// can't conflict with adventure keywords: `@goto <scene>`,
+    /// `@set var=value`, `@give item`, `@reload`, `@export file.html`
+    /// (see [`scene_html`](Engine::scene_html)), and `@state` (the
+    /// same as `debug state`). They don't count as turns.
+    fn author_command<W: Write>(
+        &mut self,
+        cmd: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let (cmd, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        let args = args.trim();
+        match (cmd, args) {
+            ("goto", scene) if !scene.is_empty() => {
+                match self.scenes.load_next(&self.scene, scene) {
+                    Ok(next) => {
+                        self.enter(next);
+                        self.write_description(output)?;
+                    }
+                    Err(e) => {
+                        writeln!(output, "Cannot load {}: {}", scene, e)?
+                    }
+                }
+            }
+            ("set", assignment) => match assignment.split_once('=') {
+                Some((var, value)) if !var.trim().is_empty() => {
+                    let (var, value) = (var.trim(), value.trim());
+                    self.state.set(var, value);
+                    writeln!(output, "Set {} = {}.", var, value)?;
+                }
+                _ => writeln!(output, "Usage: @set var=value")?,
+            },
+            ("give", item) if !item.is_empty() => {
+                self.state.give(item);
+                writeln!(output, "Gave {}.", item)?;
+            }
+            ("reload", "") => {
+                let reference = self.scene.reference();
+                match self.scenes.load_next(&self.scene, &reference) {
+                    // Replace the scene without counting a visit
+                    Ok(scene) => {
+                        self.scene = scene;
+                        self.write_description(output)?;
+                    }
+                    Err(e) => writeln!(output, "Cannot reload: {}", e)?,
+                }
+            }
+            ("state", "") => self.debug_command("state", output)?,
+            ("export", file) if !file.is_empty() => {
+                match std::fs::write(file, self.scene_html()?) {
+                    Ok(()) => {
+                        writeln!(output, "Exported the scene to {}.", file)?
+                    }
+                    Err(e) => writeln!(output, "Cannot export: {}", e)?,
+                }
+            }
+            _ => writeln!(output, "Unknown author command: @{}", cmd)?,
+        }
+        Ok(())
+    }
+
+    /// The `map` command: list visited scenes and known exits.
+    fn map<W: Write>(&self, output: &mut W) -> Result<(), Box<dyn Error>> {
+        for (scene, _) in self.state.visited() {
+            if scene == self.scene.name() {
+                writeln!(output, "{} (you are here)", scene)?;
+            } else {
+                writeln!(output, "{}", scene)?;
+            }
+            for (input, target) in self.state.known_exits(scene) {
+                writeln!(output, "  {} -> {}", input, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The `examine` command: describe an item the player carries or
+    /// can see, or a container and its contents.
+    fn examine<W: Write>(
+        &self,
+        item: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let item = self.resolve_item(item);
+        if item.is_empty() {
+            writeln!(output, "Examine what?")?;
+        } else if let Some(desc) = self.scene.containers().get(item) {
+            if !desc.is_empty() {
+                writeln!(output, "{}", desc)?;
+            }
+            let contents = self.items_in(Some(item));
+            if contents.is_empty() {
+                writeln!(output, "It is empty.")?;
+            } else {
+                writeln!(
+                    output,
+                    "It contains: {}.",
+                    self.item_list(&contents)
+                )?;
+            }
+        } else if self.state.has(item) || self.items_in(None).contains(&item)
+        {
+            let desc = self
+                .items
+                .get(item)
+                .map(|i| i.description())
+                .filter(|d| !d.is_empty())
+                .or_else(|| {
+                    self.item_descriptions.get(item).map(|d| d.as_str())
+                });
+            match desc {
+                Some(desc) => writeln!(output, "{}", desc)?,
+                None => writeln!(
+                    output,
+                    "You see nothing special about the {}.",
+                    self.item_name(item)
+                )?,
+            }
+        } else {
+            writeln!(output, "You can't see any {} here.", item)?;
+        }
+        Ok(())
+    }
+
+    /// The `take` command: `take <item>` picks up an item lying in
+    /// the scene, `take <item> from <container>` gets it out of a
+    /// container.
+    fn take_item<W: Write>(
+        &mut self,
+        args: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let (item, container) = match args.split_once(" from ") {
+            Some((i, c)) => (i.trim(), Some(c.trim())),
+            None => (args, None),
+        };
+        if item.is_empty() {
+            writeln!(output, "Take what?")?;
+            return Ok(());
+        }
+        if let Some(c) = container {
+            if !self.scene.containers().contains_key(c) {
+                writeln!(output, "You can't see any {} here.", c)?;
+                return Ok(());
+            }
+        }
+        let item = self.resolve_item(item).to_string();
+        let name = self.item_name(&item).to_string();
+        if self.state.has(&item) {
+            writeln!(output, "You already have the {}.", name)?;
+        } else if self.items_in(container).contains(&item.as_str()) {
+            self.state.give(&item);
+            writeln!(output, "Taken: {}.", name)?;
+        } else if let Some(c) = container {
+            writeln!(output, "There is no {} in the {}.", name, c)?;
+        } else {
+            writeln!(output, "You can't see any {} here.", name)?;
+        }
+        Ok(())
+    }
+
+    /// The `drop` command: leave an item in the current scene.
+    fn drop_item<W: Write>(
+        &mut self,
+        item: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let item = self.resolve_item(item).to_string();
+        let name = self.item_name(&item).to_string();
+        if item.is_empty() {
+            writeln!(output, "Drop what?")?;
+        } else if self.state.has(&item) {
+            let location = Location::Scene {
+                scene: self.scene.name().to_string(),
+                container: None,
+            };
+            self.state.place(&item, location);
+            writeln!(output, "Dropped: {}.", name)?;
+        } else {
+            writeln!(output, "You don't have any {}.", name)?;
+        }
+        Ok(())
+    }
+
+    /// The `put` command: `put <item> in <container>`.
+    fn put_item<W: Write>(
+        &mut self,
+        args: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        let (item, container) = match args.split_once(" in ") {
+            Some((i, c)) if !i.trim().is_empty() && !c.trim().is_empty() => {
+                (i.trim(), c.trim())
+            }
+            _ => {
+                writeln!(output, "Put what in what?")?;
+                return Ok(());
+            }
+        };
+        let item = self.resolve_item(item).to_string();
+        let name = self.item_name(&item).to_string();
+        if !self.scene.containers().contains_key(container) {
+            writeln!(output, "You can't see any {} here.", container)?;
+        } else if self.state.has(&item) {
+            let location = Location::Scene {
+                scene: self.scene.name().to_string(),
+                container: Some(container.to_string()),
+            };
+            self.state.place(&item, location);
+            writeln!(output, "You put the {} in the {}.", name, container)?;
+        } else {
+            writeln!(output, "You don't have any {}.", name)?;
+        }
+        Ok(())
+    }
+
+    /// The `alias` command: list aliases without arguments, define
+    /// one with `alias n = go north`, remove it with `alias n =`.
+    fn alias<W: Write>(
+        &mut self,
+        args: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        if args.is_empty() {
+            if self.player_profile.aliases().is_empty() {
+                writeln!(output, "No aliases defined.")?;
+            }
+            for (k, v) in self.player_profile.aliases() {
+                writeln!(output, "{} = {}", k, v)?;
+            }
+            return Ok(());
+        }
+        let (name, command) = match args.split_once('=') {
+            Some((n, c))
+                if !n.trim().is_empty() && !n.trim().contains(' ') =>
+            {
+                (n.trim(), c.trim())
+            }
+            _ => {
+                writeln!(output, "Usage: alias <name> = <command>")?;
+                return Ok(());
+            }
+        };
+        if command.is_empty() {
+            if self.player_profile.remove_alias(name).is_some() {
+                writeln!(output, "Removed alias {}.", name)?;
+            } else {
+                writeln!(output, "There is no alias {}.", name)?;
+            }
+        } else {
+            self.player_profile.set_alias(name, command);
+            writeln!(output, "Alias {} = {}", name, command)?;
+        }
+        self.save_profile(output)
+    }
+
+    /// Save the player profile after the player changed it. Failing to
+    /// save is reported, but the game goes on with the changes.
+    fn save_profile<W: Write>(
+        &self,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Err(e) = self.player_profile.save() {
+            writeln!(output, "Could not save your settings: {}", e)?;
+        }
+        Ok(())
+    }
+
+    /// The `options` command: show or change output settings, which
+    /// are stored in the player profile.
+    fn options<W: Write>(
+        &mut self,
+        args: &str,
+        output: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        fn show(n: Option<usize>) -> String {
+            n.map_or("off".to_string(), |n| n.to_string())
+        }
+        let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+        let value = value.trim();
+        // Some(None) switches a setting off
+        let number = match value {
+            "off" | "0" => Some(None),
+            v => v.parse::<usize>().ok().map(Some),
+        };
+        let options = self.player_profile.options_mut();
+        match (name, value, number) {
+            ("", _, _) => {
+                let speed = self
+                    .text_speed
+                    .as_ref()
+                    .map(|s| s.load(Ordering::SeqCst) as usize);
+                match speed {
+                    Some(s) => writeln!(
+                        output,
+                        "speed: {}",
+                        show(Some(s).filter(|s| *s > 0))
+                    )?,
+                    None => writeln!(output, "speed: not available")?,
+                }
+                let color = if self.color { "on" } else { "off" };
+                writeln!(output, "color: {}", color)?;
+                writeln!(output, "width: {}", show(self.wrap))?;
+                writeln!(
+                    output,
+                    "Change a setting with \"options <name> <value>\"."
+                )?;
+                return Ok(());
+            }
+            ("speed", _, Some(n)) => {
+                let Some(speed) = &self.text_speed else {
+                    writeln!(
+                        output,
+                        "The text speed can't be changed here."
+                    )?;
+                    return Ok(());
+                };
+                let cps = n.map_or(0, |n| n.min(u32::MAX as usize) as u32);
+                speed.store(cps, Ordering::SeqCst);
+                options.speed = Some(cps).filter(|n| *n > 0);
+                writeln!(output, "Text speed: {}", show(n))?;
+            }
+            ("color", "on" | "off", _) => {
+                self.color = value == "on";
+                options.color = self.color;
+                writeln!(output, "Colors: {}", value)?;
+            }
+            ("width", _, Some(n)) => {
+                self.wrap = n;
+                options.width = n;
+                writeln!(output, "Width: {}", show(n))?;
+            }
+            _ => {
+                writeln!(
+                    output,
+                    "Usage: options [speed <cps>|color <on|off>|width <columns>]"
+                )?;
+                return Ok(());
+            }
+        }
+        self.save_profile(output)
+    }
+}
+
+/// Log errors of the analytics, they must not interrupt the game.
+fn logged(res: Result<(), Box<dyn Error>>) {
+    if let Err(_e) = res {
+        warn!(error = %_e, "could not record analytics");
+    }
+}
+
+/// Writer that keeps a copy of everything written, for the
+/// transcript.
+struct Recorder<'a, W: Write> {
+    inner: &'a mut W,
+    copy: Vec<u8>,
+}
+
+impl<'a, W: Write> Recorder<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        Recorder {
+            inner,
+            copy: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Recorder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.copy.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Context for Engine {
+    fn variable(&self, name: &str) -> Option<&str> {
+        Engine::variable(self, name)
+    }
+
+    fn has(&self, item: &str) -> bool {
+        self.state.has(item)
+    }
+
+    fn npc_state(&self, npc: &str) -> Option<&str> {
+        Engine::npc_state(self, npc)
+    }
+
+    fn visits(&self, scene: &str) -> u32 {
+        self.state.visits(scene)
+    }
+
+    fn scene_has(&self, tag: &str) -> bool {
+        self.scene.meta().has_tag(tag)
+    }
+
+    fn time_in(&self, period: &str) -> bool {
+        self.clock
+            .as_ref()
+            .is_some_and(|c| c.is_in(self.state.elapsed(), period))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+    use crate::prompt::Scripted;
+    use crate::save::FsStore;
+    use crate::state::Change;
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    fn kitten_engine() -> Engine {
+        let path: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources", "kitten.scene"]
+                .iter()
+                .collect();
+        Engine::new(Scene::load(path).unwrap())
+    }
+
+    fn steps(engine: &mut Engine, inputs: &[&str]) -> String {
+        let mut output = Vec::new();
+        for i in inputs {
+            engine.step(i, &mut output).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn alias() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["alias", "alias m = meow", "m", "alias"]),
+            "No aliases defined.\nAlias m = meow\n\"Meow!\" =^.^=\nm = meow\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["alias m =", "m", "alias m"]),
+            "Removed alias m.\nUsage: alias <name> = <command>\n"
+        );
+
+        // A profile that can't be written doesn't end the game.
+        let fixture = TempAdventure::new("engine-alias", &[]);
+        let path = fixture.path("profile.yaml");
+        engine.set_player_profile(PlayerProfile::load(path.clone()).unwrap());
+        std::fs::create_dir(&path).unwrap();
+        let out = steps(&mut engine, &["alias m = meow", "m"]);
+        assert!(
+            out.starts_with("Alias m = meow\nCould not save your settings: "),
+            "{}",
+            out
+        );
+        assert!(out.ends_with("\n\"Meow!\" =^.^=\n"), "{}", out);
+    }
+
+    #[test]
+    fn options() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["options", "options speed 40"]),
+            "speed: not available\ncolor: on\nwidth: off\n\
+             Change a setting with \"options <name> <value>\".\n\
+             The text speed can't be changed here.\n"
+        );
+        let speed = Arc::new(AtomicU32::new(0));
+        engine.set_text_speed(Arc::clone(&speed));
+        assert_eq!(
+            steps(
+                &mut engine,
+                &[
+                    "options speed 40",
+                    "options color off",
+                    "options width 30"
+                ]
+            ),
+            "Text speed: 40\nColors: off\nWidth: 30\n"
+        );
+        assert_eq!(speed.load(Ordering::SeqCst), 40);
+        let options = engine.player_profile().options();
+        assert_eq!((options.speed, options.color), (Some(40), false));
+        assert_eq!(options.width, Some(30));
+        assert_eq!(
+            steps(&mut engine, &["options width off", "options color blue"]),
+            "Width: off\n\
+             Usage: options [speed <cps>|color <on|off>|width <columns>]\n"
+        );
+
+        // The setting still applies if it can't be saved.
+        let fixture = TempAdventure::new("engine-options", &[]);
+        let path = fixture.path("profile.yaml");
+        engine.set_player_profile(PlayerProfile::load(path.clone()).unwrap());
+        std::fs::create_dir(&path).unwrap();
+        let out = steps(&mut engine, &["options color on"]);
+        assert!(
+            out.starts_with("Colors: on\nCould not save your settings: "),
+            "{}",
+            out
+        );
+        assert!(engine.color());
+    }
+
+    #[test]
+    fn echo() {
+        let mut engine = kitten_engine();
+        engine.set_decorator(output::Echo);
+        assert_eq!(
+            steps(&mut engine, &["meow", "sing", "hug"]),
+            "(You meow.)\n\"Meow!\" =^.^=\n(You hug.)\n\
+             \u{2014} New location: Cuddle cat \u{2014}\n\
+             *purr*\nThere's a kitten purring in your arms!\n"
+        );
+    }
+
+    /// Analytics that record events as text.
+    #[derive(Debug, Default)]
+    struct Events(Rc<RefCell<Vec<String>>>);
+
+    impl Analytics for Events {
+        fn scene_change(
+            &mut self,
+            from: &str,
+            to: &str,
+            turn: u64,
+        ) -> Result<(), Box<dyn Error>> {
+            let event = format!("{} -> {} ({})", from, to, turn);
+            self.0.borrow_mut().push(event);
+            Ok(())
+        }
+
+        fn ending(
+            &mut self,
+            scene: &str,
+            turn: u64,
+        ) -> Result<(), Box<dyn Error>> {
+            self.0
+                .borrow_mut()
+                .push(format!("end {} ({})", scene, turn));
+            Ok(())
+        }
+
+        fn unmatched(
+            &mut self,
+            scene: &str,
+            input: &str,
+            _turn: u64,
+        ) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(format!("{}: {}?", scene, input));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn analytics() {
+        let mut engine = kitten_engine();
+        let events = Events::default();
+        let recorded = Rc::clone(&events.0);
+        engine.set_analytics(events);
+        steps(&mut engine, &["meow", "sing", "hug"]);
+        engine.finish();
+        assert_eq!(engine.unmatched()["kitten"], ["sing"]);
+        assert_eq!(
+            *recorded.borrow(),
+            [
+                "kitten: sing?",
+                "kitten -> cuddle_cat (3)",
+                "end cuddle_cat (3)"
+            ]
+        );
+    }
+
+    #[test]
+    fn pronouns() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "@item: yarn = A ball of yarn.\n\
+                 @container: basket = A wicker basket.\n\
+                 A cozy room.\n\
+                 !regex:^pet (?P<it>cat|dog)$ -> print Purr.\n\
+                 !kw:feed dog -> print Woof!\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            steps(&mut engine, &["examine yarn", "take it"]),
+            "A ball of yarn.\nYou can't see any it here.\n"
+        );
+        engine.set_pronouns(true);
+        assert_eq!(
+            steps(
+                &mut engine,
+                &[
+                    "examine yarn",
+                    "take it",
+                    "examine basket",
+                    "put yarn in it",
+                    "pet dog"
+                ]
+            ),
+            "A ball of yarn.\nTaken: yarn.\nA wicker basket.\n\
+             It is empty.\nYou put the yarn in the basket.\nPurr.\n"
+        );
+        assert_eq!(steps(&mut engine, &["feed it"]), "Woof!\n");
+    }
+
+    #[test]
+    fn chain_commands() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "A cozy room.\n\
+                 !kw:meow -> print Meow!\n\
+                 !kw:purr -> print Purr.\n\
+                 !kw:sit and wait -> print You wait.\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(steps(&mut engine, &["meow then purr"]), "");
+        engine.set_chain_commands(true);
+        assert_eq!(
+            steps(
+                &mut engine,
+                &["meow then purr. meow", "meow and sing and purr", "."]
+            ),
+            "Meow!\nPurr.\nMeow!\nMeow!\n"
+        );
+        assert_eq!(steps(&mut engine, &["sit and wait"]), "You wait.\n");
+        let mut engine = kitten_engine();
+        engine.set_chain_commands(true);
+        assert_eq!(
+            steps(&mut engine, &["hug then meow"]),
+            "*purr*\nThere's a kitten purring in your arms!\n"
+        );
+    }
+
+    #[test]
+    fn abbreviations() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "@item: yarn = A ball of yarn.\n\
+                 A cozy room.\n\
+                 !kw:e -> print Eek!\n\
+                 !kw[exit]:north -> print You walk north.\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            steps(&mut engine, &["x yarn", "e", "i", "n"]),
+            "A ball of yarn.\nEek!\nYou are empty-handed.\nYou walk north.\n"
+        );
+        engine.set_abbreviations(BTreeMap::new());
+        assert_eq!(steps(&mut engine, &["i"]), "Did you mean 'e'?\n");
+    }
+
+    #[test]
+    fn adventure_commands() {
+        let fixture = TempAdventure::new(
+            "commands",
+            &[
+                (
+                    "about.yaml",
+                    "name: Temple\nauthor: Fiona\ncommands:\n  \
+                     pray: 'print [\"You feel blessed.\", \
+                     \"Nothing happens.\"]'\n  \
+                     inventory: print Your pockets are deep.\n",
+                ),
+                ("start.scene", "A hall.\n!door:in -> altar\n"),
+                (
+                    "altar.scene",
+                    "An altar.\n!kw:pray -> print The gods listen.\n\
+                     !kw:leave -> start\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let adventure = crate::adventure::load(dir);
+        let mut engine = Engine::from_adventure(adventure.unwrap()).unwrap();
+        let out =
+            steps(&mut engine, &["pray", "pray", "inventory", "in", "pray"]);
+
+        assert_eq!(
+            out,
+            "You feel blessed.\nNothing happens.\nYour pockets are deep.\n\
+             An altar.\nThe gods listen.\n"
+        );
+    }
+
+    #[test]
+    fn hot_seat() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "@format: 2\n@prompt: Player ${current_player}> \n\
+                 A cozy room.\n\
+                 !kw:beg -> give treat [if not has treat | Not again.]\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(engine.prompt(), "Player 1> ");
+        engine.set_players(2);
+        assert_eq!(
+            steps(&mut engine, &["beg", "", "inventory", "beg", "beg"]),
+            "Got treat.\nPlayer 2, it's your turn.\n\
+             You are empty-handed.\nPlayer 1, it's your turn.\n\
+             Not again.\nPlayer 2, it's your turn.\n\
+             Got treat.\nPlayer 1, it's your turn.\n"
+        );
+        assert_eq!(engine.current_player(), 1);
+        assert_eq!(engine.prompt(), "Player 1> ");
+        assert_eq!(engine.state().inventory().count(), 0);
+        assert_eq!(
+            engine.state().location("treat"),
+            Some(&Location::Player(1))
+        );
+    }
+
+    #[test]
+    fn history() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["again", "meow", "", "g", "history"]),
+            "There is nothing to repeat.\n\"Meow!\" =^.^=\n\"Meow!\" =^.^=\n\
+             1: meow\n2: history\n"
+        );
+        assert_eq!(engine.history().collect::<Vec<_>>(), ["meow", "history"]);
+
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("rope.scene"),
+                "A rope.\n!kw:pull -> print Ding!\n!kw:again -> print Dong!\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            steps(&mut engine, &["pull", "again", "g"]),
+            "Ding!\nDong!\nDong!\n"
+        );
+    }
+
+    #[test]
+    fn locked_door() {
+        let path: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources", "door.scene"]
+                .iter()
+                .collect();
+        let mut engine = Engine::new(
+            Scene::parse(
+                path,
+                "@format: 2\nA door.\n\
+                 !door:open -> cuddle_cat [needs key | The door is locked.]\n\
+                 !kw:take key -> give key [if not has key]\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            steps(
+                &mut engine,
+                &["open", "inventory", "take key", "inventory"]
+            ),
+            "The door is locked.\nYou are empty-handed.\nGot key.\n\
+             You are carrying: key\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["take key"]),
+            "You already have the key.\n"
+        );
+        steps(&mut engine, &["open"]);
+        assert_eq!(engine.scene().name(), "cuddle_cat");
+    }
+
+    #[test]
+    fn items() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "@item: yarn = A ball of yarn.\n\
+                 @container: basket = A wicker basket.\n\
+                 @item: treat in basket\n\
+                 A cozy room.\n",
+            )
+            .unwrap(),
+        );
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "A cozy room.\nYou can see: yarn.\n"
+        );
+        assert_eq!(
+            steps(
+                &mut engine,
+                &[
+                    "examine yarn",
+                    "examine basket",
+                    "take treat",
+                    "take treat from basket",
+                    "examine treat",
+                    "put treat in box",
+                    "take yarn",
+                    "put yarn in basket",
+                    "examine basket",
+                    "drop treat",
+                    "inventory",
+                ]
+            ),
+            "A ball of yarn.\n\
+             A wicker basket.\nIt contains: treat.\n\
+             You can't see any treat here.\n\
+             Taken: treat.\n\
+             You see nothing special about the treat.\n\
+             You can't see any box here.\n\
+             Taken: yarn.\n\
+             You put the yarn in the basket.\n\
+             A wicker basket.\nIt contains: yarn.\n\
+             Dropped: treat.\n\
+             You are empty-handed.\n"
+        );
+        assert_eq!(engine.items_in(None), ["treat"]);
+    }
+
+    #[test]
+    fn item_definitions() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("room.scene"),
+                "@format: 2\n@item: yarn = A ball of yarn.\nA cozy room.\n\
+                 !kw:beg -> give treat\n",
+            )
+            .unwrap(),
+        );
+        engine.items = crate::item::parse(
+            "yarn:\n  name: red yarn\n  aliases: ball\n\
+             treat:\n  name: fish treat\n  description: Smells fishy.\n",
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "A cozy room.\nYou can see: red yarn.\n"
+        );
+        assert_eq!(
+            steps(
+                &mut engine,
+                &["examine ball", "take red yarn", "beg", "examine treat"]
+            ),
+            "A ball of yarn.\nTaken: red yarn.\nGot fish treat.\nSmells fishy.\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["inventory"]),
+            "You are carrying: fish treat, red yarn\n"
+        );
+    }
+
+    #[test]
+    fn npc_states() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("gate.scene"),
+                "@format: 2\nA guard stands at the gate.\n\
+                 !kw:sneak -> print You slip past. [if guard is asleep]\n\
+                 !kw:sneak -> print The guard blocks the way.\n\
+                 !kw:shout -> npc guard -> alerted The guard jumps up!\n\
+                 !kw:sing -> npc guard -> asleep The guard falls asleep.\n\
+                 !kw:pet -> npc cat -> happy The cat purrs.\n",
+            )
+            .unwrap(),
+        );
+        engine.npcs = crate::npc::parse(
+            "guard:\n  initial: asleep\n  states:\n    \
+             asleep: [alerted]\n    alerted: [asleep]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps(&mut engine, &["sneak", "shout", "shout", "sneak", "sing"]),
+            "You slip past.\nThe guard jumps up!\n\
+             The guard blocks the way.\nThe guard falls asleep.\n"
+        );
+        assert_eq!(engine.npc_state("guard"), Some("asleep"));
+        // NPCs missing from npcs.yaml are left alone.
+        assert_eq!(steps(&mut engine, &["pet"]), "");
+        assert_eq!(engine.npc_state("cat"), None);
+    }
+
+    #[test]
+    fn map() {
+        let mut engine = kitten_engine();
+        steps(&mut engine, &["hug", "release", "cuddle"]);
+        assert_eq!(engine.state().visits("kitten"), 2);
+        assert_eq!(engine.state().visits("cuddle_cat"), 2);
+        assert_eq!(
+            steps(&mut engine, &["map"]),
+            "cuddle_cat (you are here)\n  release -> kitten\n\
+             kitten\n  cuddle -> cuddle_cat\n  hug -> cuddle_cat\n"
+        );
+    }
+
+    #[test]
+    fn exits() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("hall.scene"),
+                "@format: 2\nA hall.\n\
+                 !kw[exit]:north -> scene garden\n\
+                 !door:east -> vault [needs key]\n\
+                 !kw[exit]:up -> scene attic [if ladder == placed]\n\
+                 !kw:meow -> print Echo!\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(steps(&mut engine, &["exits"]), "Exits: north, east.\n");
+        engine.state.set("ladder", "placed");
+        engine.show_exits = true;
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "A hall.\nExits: north, east, up.\n"
+        );
+        assert_eq!(
+            steps(&mut kitten_engine(), &["exits"]),
+            "There are no obvious exits.\n"
+        );
+    }
+
+    #[test]
+    fn styled_output() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("den.scene"),
+                "A {danger}dragon{/danger} sleeps.\n\
+                 !kw:poke -> print The dragon {danger}wakes{/danger}!\n",
+            )
+            .unwrap(),
+        );
+        engine.styles.add("danger", "red").unwrap();
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "A \x1b[0;31mdragon\x1b[0m sleeps.\n"
+        );
+        engine.set_output_profile(Profile::ScreenReader);
+        assert_eq!(steps(&mut engine, &["poke"]), "The dragon wakes!\n");
+    }
+
+    #[test]
+    fn text_filters() {
+        #[derive(Debug)]
+        struct Count;
+
+        impl TextFilter for Count {
+            fn filter(&self, engine: &Engine, text: String) -> String {
+                format!("{}[{}]", text.trim_end(), engine.state.turns())
+            }
+        }
+
+        let mut engine = kitten_engine();
+        engine.state.set("name", "Mia");
+        engine.set_filters(vec![Box::new(output::Markup)]);
+        assert_eq!(engine.render("${name}"), "${name}");
+        engine.set_filters(output::default_filters());
+        engine.add_filter(Box::new(Count));
+        assert_eq!(engine.render("Hi ${name}!"), "Hi Mia![0]");
+    }
+
+    #[test]
+    fn wrapped_output() {
+        let mut engine = kitten_engine();
+        engine.set_wrap(Some(20));
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "There's a little\nkitten in front of\nyou!\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["pet"]),
+            "The kitten rubs\ntheir head on your\nhand.\n"
+        );
+    }
+
+    #[test]
+    fn transcript() {
+        let mut engine = kitten_engine();
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        engine.write_prompt(&mut out).unwrap();
+        engine.step("meow", &mut out).unwrap();
+        engine.write_prompt(&mut out).unwrap();
+        assert_eq!(
+            engine.transcript(),
+            "There's a little kitten in front of you!\n> meow\n\
+             \"Meow!\" =^.^=\n> "
+        );
+        engine.set_transcript_quota(20);
+        assert_eq!(engine.transcript(), "\"Meow!\" =^.^=\n> ");
+        engine.step("hug", &mut out).unwrap();
+        assert_eq!(engine.transcript(), "rring in your arms!\n");
+        // The quota may end in the middle of a character.
+        engine.set_transcript_quota(5);
+        engine.record("Grüße\n".as_bytes());
+        assert_eq!(engine.transcript(), "ße\n");
+        engine.record("Tschüß\n".as_bytes());
+        assert_eq!(engine.transcript(), "üß\n");
+    }
+
+    #[test]
+    fn prompt_override() {
+        let mut engine = kitten_engine();
+        assert_eq!(engine.prompt(), DEFAULT_PROMPT);
+        engine.prompt = "Meow? ".to_string();
+        assert_eq!(engine.prompt(), "Meow? ");
+        engine.scene = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@prompt: Purr? \nA kitten looks at you.\n",
+        )
+        .unwrap();
+        assert_eq!(engine.prompt(), "Purr? ");
+    }
+
+    #[test]
+    fn variables() {
+        let mut engine = kitten_engine();
+        engine.state.set("mood", "curious");
+        engine.state.set("lives", "9");
+        engine.prompt =
+            "[${lives} lives, ${mood}, ${unknown}] > ".to_string();
+        assert_eq!(engine.prompt(), "[9 lives, curious, ${unknown}] > ");
+        engine.scene = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@set mood=sleepy\nThe kitten yawns.\n",
+        )
+        .unwrap();
+        assert_eq!(engine.prompt(), "[9 lives, sleepy, ${unknown}] > ");
+        assert_eq!(engine.state().get("mood"), Some("curious"));
+    }
+
+    #[test]
+    fn custom_matcher() {
+        #[derive(Debug)]
+        struct IgnoreCase;
+
+        impl Matcher for IgnoreCase {
+            fn matches(&self, action: &Action, input: &str) -> bool {
+                action.expression().is_match(&input.to_lowercase())
+            }
+        }
+
+        let mut engine = kitten_engine();
+        assert_ne!(steps(&mut engine, &["MEOW"]), "\"Meow!\" =^.^=\n");
+        engine.set_matcher(IgnoreCase);
+        assert_eq!(steps(&mut engine, &["MEOW"]), "\"Meow!\" =^.^=\n");
+    }
+
+    #[test]
+    fn suggestions() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["mwow", "bark", ""]),
+            "Did you mean 'meow'?\n"
+        );
+        engine.set_matcher(RegexMatcher::default().suggest_distance(0));
+        assert_eq!(steps(&mut engine, &["mwow"]), "");
+    }
+
+    #[test]
+    fn tolerate_typos() {
+        let mut engine = kitten_engine();
+        engine.set_matcher(RegexMatcher::default().tolerance(1));
+        engine.scene = Scene::parse(
+            PathBuf::from("test.scene"),
+            "A kitten.\n!kw:pet -> print *purr*\n!kw:pat -> print Pat pat.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps(&mut engine, &["pot", "pat", "mwow"]),
+            "*purr*\nPat pat.\n"
+        );
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["save"]),
+            "Saving is not available.\n"
+        );
+
+        let fixture = TempAdventure::new("engine-saves", &[]);
+        engine.set_save_store(FsStore::new(fixture.path("saves")));
+        let out = steps(
+            &mut engine,
+            &["saves", "hug", "save", "save ../x", "release", "restore"],
+        );
+        let saves = steps(&mut engine, &["saves"]);
+        let missing = steps(&mut engine, &["restore nothing"]);
+        assert_eq!(
+            out,
+            "There are no saved games.\n\
+             *purr*\nThere's a kitten purring in your arms!\n\
+             Saved as quicksave.\n\
+             Sorry, that didn't work: invalid save name: ../x\n\
+             There's a little kitten in front of you!\n\
+             *purr*\nThere's a kitten purring in your arms!\n"
+        );
+        assert_eq!(engine.scene().name(), "cuddle_cat");
+        assert_eq!(engine.state().visits("kitten"), 1);
+        assert_eq!(saves, "Saved games: quicksave\n");
+        assert_eq!(
+            missing,
+            "Sorry, that didn't work: no save named nothing\n"
+        );
+    }
+
+    #[test]
+    fn confirm_save() {
+        let mut engine = kitten_engine();
+        let mut prompter = Scripted::new(&["n", "yes"]);
+        engine.confirm_save(&mut prompter).unwrap();
+        assert!(prompter.prompts.is_empty());
+
+        let fixture = TempAdventure::new("engine-quit", &[]);
+        engine.set_save_store(FsStore::new(fixture.path("saves")));
+        engine.confirm_save(&mut prompter).unwrap();
+        let none = steps(&mut engine, &["saves"]);
+        engine.confirm_save(&mut prompter).unwrap();
+        let saves = steps(&mut engine, &["saves"]);
+        assert_eq!(prompter.prompts.len(), 2);
+        assert_eq!(
+            String::from_utf8(prompter.output).unwrap(),
+            "Saved as quicksave.\n"
+        );
+        assert_eq!(none, "There are no saved games.\n");
+        assert_eq!(saves, "Saved games: quicksave\n");
+        assert!(engine
+            .transcript()
+            .contains("quitting? yes\nSaved as quicksave.\n"));
+    }
+
+    #[test]
+    fn once_and_cooldown() {
+        let mut engine = kitten_engine();
+        engine.scene = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@format: 2\nA kitten with a key.\n\
+             !kw[once]:take key -> give key A key!\n\
+             !kw:take key -> print There is no more key.\n\
+             !kw:pet -> print *purr* [cooldown 2 | The kitten needs a break.]\n\
+             !kw:meow -> print Meow! [cooldown 1]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps(
+                &mut engine,
+                &["take key", "take key", "pet", "pet", "meow", "pet"]
+            ),
+            "A key!\nThere is no more key.\n*purr*\n\
+             The kitten needs a break.\nMeow!\n*purr*\n"
+        );
+        assert_eq!(steps(&mut engine, &["meow", "meow"]), "Meow!\n");
+        assert_eq!(engine.state().turns(), 8);
+        assert_eq!(engine.state().last_fired("test", 0), Some(1));
+        assert_eq!(engine.state().last_fired("test", 1), Some(2));
+
+        // Fired actions are part of saved games.
+        let json = engine.save_game().to_json().unwrap();
+        assert_eq!(SaveGame::from_json(&json).unwrap(), engine.save_game());
+    }
+
+    #[test]
+    fn alternative_responses() {
+        let mut engine = kitten_engine();
+        engine.scene = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@format: 2\nA kitten.\n\
+             !kw:meow -> print [\"Meow!\", \"Mrrp?\", \"*ignores you*\"]\n\
+             !kw:pet -> print random [\"*purr*\", \"*purr, purr*\", \"Mew.\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps(&mut engine, &["meow", "meow", "meow", "meow"]),
+            "Meow!\nMrrp?\n*ignores you*\nMeow!\n"
+        );
+
+        // The same seed gives the same responses, also after
+        // restoring a game.
+        engine.set_seed(7);
+        let save = engine.save_game();
+        let pets = ["pet"; 8];
+        let first = steps(&mut engine, &pets);
+        engine.state = save.state;
+        assert_eq!(steps(&mut engine, &pets), first);
+        let responses: BTreeSet<&str> = first.lines().collect();
+        assert!(responses.len() > 1);
+        assert!(responses.is_subset(&BTreeSet::from([
+            "*purr*",
+            "*purr, purr*",
+            "Mew."
+        ])));
+    }
+
+    #[test]
+    fn scene_parameters() {
+        let fixture = TempAdventure::new(
+            "engine-params",
+            &[
+                (
+                    "street.scene",
+                    "A street with shops.\n\
+                     !kw:bakery -> scene shop?item=bread&price=2\n\
+                     !kw:smithy -> scene shop?item=sword\n",
+                ),
+                (
+                    "shop.scene",
+                    "@format: 2\n@set price=10\n\
+                     You can buy a ${item} for ${price} coins.\n\
+                     !kw:buy -> print A fine ${item}! [if item == sword]\n\
+                     !kw:leave -> scene street\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let mut engine =
+            Engine::new(Scene::load(dir.join("street.scene")).unwrap());
+        let out = steps(&mut engine, &["bakery", "buy", "leave", "smithy"]);
+        let save = engine.save_game();
+        let left = steps(&mut engine, &["leave"]);
+        engine.restore(save.clone()).unwrap();
+        let bought = steps(&mut engine, &["buy"]);
+        assert_eq!(
+            out,
+            "You can buy a bread for 2 coins.\n\
+             A street with shops.\n\
+             You can buy a sword for 10 coins.\n"
+        );
+        assert_eq!(save.scene, "shop?item=sword");
+        assert_eq!(left, "A street with shops.\n");
+        assert_eq!(engine.scene().params().len(), 1);
+        assert_eq!(bought, "A fine sword!\n");
+    }
+
+    #[test]
+    fn idle_effect() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("kitten.scene"),
+                "@format: 2\n\
+                 @idle: 20 -> print [\"The kitten yawns.\", \"Zzz.\"]\n\
+                 A kitten.\n!kw:pet -> print Purr.\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(engine.idle_timeout(), Some(Duration::from_secs(20)));
+        let mut out = Vec::new();
+        engine.idle(&mut out).unwrap();
+        engine.idle(&mut out).unwrap();
+        assert_eq!(engine.idle_timeout(), None);
+        let pet = steps(&mut engine, &["pet"]);
+        assert_eq!(engine.idle_timeout(), Some(Duration::from_secs(20)));
+        engine.idle(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "The kitten yawns.\nZzz.\n"
+        );
+        assert_eq!(pet, "Purr.\n");
+        assert!(engine.transcript().ends_with("Zzz.\n"));
+        assert!(
+            Scene::parse(PathBuf::from("t.scene"), "@idle: soon\n").is_err()
+        );
+        // The format applies to the effect wherever it is declared.
+        assert!(Scene::parse(PathBuf::from("t.scene"), "@idle: 5 -> yawn\n")
+            .is_ok());
+        for text in [
+            "@idle: 5 -> yawn\n@format: 2\n",
+            "@format: 2\n@idle: 5 -> yawn\n",
+        ] {
+            assert!(Scene::parse(PathBuf::from("t.scene"), text).is_err());
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let mut engine = kitten_engine();
+        let start = engine.start_frame().unwrap();
+        let meow = engine.frame("meow\n").unwrap();
+        let hug = engine.frame("hug").unwrap();
+        assert_eq!(
+            start.description.as_deref(),
+            Some("There's a little kitten in front of you!\n")
+        );
+        assert_eq!(meow.input, "meow");
+        assert_eq!(meow.output, "\"Meow!\" =^.^=\n");
+        assert_eq!(meow.description, None);
+        assert_eq!(meow.prompt, DEFAULT_PROMPT);
+        assert_eq!(
+            hug.output,
+            "*purr*\nThere's a kitten purring in your arms!\n"
+        );
+        assert_eq!(hug.scene, "cuddle_cat");
+        assert_eq!(hug.description.as_ref(), Some(&hug.output));
+        assert_eq!(
+            hug.changes,
+            [Change::Visits("cuddle_cat".to_string(), 0, 1)]
+        );
+        let json = serde_json::to_value(&hug).unwrap();
+        assert_eq!(json["scene"], "cuddle_cat");
+    }
+
+    #[test]
+    fn restore_changed_adventure() {
+        let fixture = TempAdventure::new(
+            "engine-changed",
+            &[
+                ("street.scene", "A street.\n!kw:in -> scene shop\n"),
+                ("shop.scene", "A shop.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let mut engine =
+            Engine::new(Scene::load(dir.join("street.scene")).unwrap());
+        steps(&mut engine, &["in"]);
+        let save = engine.save_game();
+        let same = engine.restore(save.clone()).unwrap();
+        std::fs::write(dir.join("shop.scene"), "A bakery.\n").unwrap();
+        let changed = engine.restore(save.clone()).unwrap();
+        let changed_scene = engine.scene().name().to_string();
+        std::fs::remove_file(dir.join("shop.scene")).unwrap();
+        let gone = engine.restore(save.clone()).unwrap();
+
+        assert!(save.content.is_some());
+        assert!(same.is_empty());
+        assert_eq!(
+            changed,
+            ["The adventure has changed since this game was saved, some \
+              things may not be as you left them."]
+        );
+        assert_eq!(changed_scene, "shop");
+        assert_eq!(gone.len(), 2);
+        assert_eq!(
+            gone[1],
+            "The place you saved in no longer exists, continuing at the \
+             start."
+        );
+        assert_eq!(engine.scene().name(), "street");
+    }
+
+    #[test]
+    fn scene_provider() {
+        #[derive(Debug)]
+        struct Maze;
+
+        impl SceneProvider for Maze {
+            fn load_next(
+                &self,
+                current: &Scene,
+                name: &str,
+            ) -> Result<Scene, Box<dyn Error>> {
+                let Some(n) = name.strip_prefix("maze_") else {
+                    return current.load_next(name);
+                };
+                let n: u32 = n.parse()?;
+                let path: PathBuf = [
+                    env!("CARGO_MANIFEST_DIR"),
+                    "resources",
+                    &format!("{}.scene", name),
+                ]
+                .iter()
+                .collect();
+                let text = format!(
+                    "Maze room {}.\n!kw:on -> scene maze_{}\n\
+                     !kw:out -> scene kitten\n",
+                    n,
+                    n + 1
+                );
+                Scene::parse(path, &text)
+            }
+        }
+
+        let mut engine = kitten_engine();
+        engine.set_scene_provider(Maze);
+        engine.scene = Scene::parse(
+            PathBuf::from("entrance.scene"),
+            "A maze entrance.\n!kw:in -> scene maze_1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps(&mut engine, &["in", "on", "on", "out"]),
+            "Maze room 1.\nMaze room 2.\nMaze room 3.\n\
+             There's a little kitten in front of you!\n"
+        );
+        assert_eq!(engine.state().visits("maze_2"), 1);
+    }
+
+    #[cfg(feature = "rhai")]
+    #[test]
+    fn script_effect() {
+        let fixture = TempAdventure::new(
+            "engine-script",
+            &[
+                (
+                    "cellar.scene",
+                    "@format: 2\nA dark cellar.\n\
+                     !kw:pull lever -> script lever.rhai\n",
+                ),
+                (
+                    "lever.rhai",
+                    "if has(\"key\") { print(\"Nothing happens.\"); } \
+                     else { give(\"key\"); print(\"Clonk!\"); \
+                     scene(\"cellar\"); }",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let mut engine =
+            Engine::new(Scene::load(dir.join("cellar.scene")).unwrap());
+        let out = steps(&mut engine, &["pull lever", "pull lever"]);
+        assert_eq!(out, "Clonk!\nA dark cellar.\nNothing happens.\n");
+        assert!(engine.state().has("key"));
+        assert_eq!(engine.state().visits("cellar"), 2);
+    }
+
+    #[test]
+    fn entry_condition() {
+        let fixture = TempAdventure::new(
+            "engine-enter",
+            &[
+                (
+                    "hall.scene",
+                    "@format: 2\nA hall.\n!kw:take lamp -> give lamp\n\
+                     !kw:cave -> scene cave\n!kw:attic -> scene attic\n",
+                ),
+                (
+                    "cave.scene",
+                    "@enter: has(lamp) && visits(hall) >= 1 \
+                     | It's too dark.\n\
+                     A cave.\n",
+                ),
+                ("attic.scene", "@enter: visits(attic) > 5\nAn attic.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let mut engine =
+            Engine::new(Scene::load(dir.join("hall.scene")).unwrap());
+        let out = steps(&mut engine, &["cave", "attic", "take lamp", "cave"]);
+        assert_eq!(
+            out,
+            "It's too dark.\nYou can't go there.\nGot lamp.\nA cave.\n"
+        );
+    }
+
+    #[test]
+    fn debug_state() {
+        let mut engine = kitten_engine();
+        assert_eq!(steps(&mut engine, &["debug state"]), "");
+        engine.set_debug(true);
+        engine.state.set("mood", "playful");
+        let out = steps(&mut engine, &["hug", "debug state", "debug state"]);
+        assert_eq!(
+            out,
+            "*purr*\nThere's a kitten purring in your arms!\n\
+             Scene: cuddle_cat, turn 3\n\
+             Variable mood = playful\n\
+             Visits: cuddle_cat 1, kitten 1\n\
+             Changes since the last check:\n  \
+             variable mood: unset -> playful\n  \
+             visits cuddle_cat: 0 -> 1\n\
+             Scene: cuddle_cat, turn 4\n\
+             Variable mood = playful\n\
+             Visits: cuddle_cat 1, kitten 1\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["debug purr"]),
+            "Unknown debug command: purr\n"
+        );
+    }
+
+    #[test]
+    fn author_commands() {
+        let fixture = TempAdventure::new(
+            "engine-author",
+            &[
+                ("hall.scene", "A hall.\n!kw:@set -> print Hi\n"),
+                (
+                    "cave.scene",
+                    "@enter: has(lamp) | It's too dark.\nA cave.\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let mut engine =
+            Engine::new(Scene::load(dir.join("hall.scene")).unwrap());
+        // Without debug mode the scene's actions apply
+        let normal = steps(&mut engine, &["@set"]);
+        engine.set_debug(true);
+        let out = steps(
+            &mut engine,
+            &[
+                "@goto cave",
+                "@goto nowhere",
+                "@set mood = sleepy",
+                "@set",
+                "@give lamp",
+                "@dance",
+            ],
+        );
+        std::fs::write(dir.join("cave.scene"), "A bright cave.\n").unwrap();
+        let reloaded = steps(&mut engine, &["@reload"]);
+        assert_eq!(normal, "Hi\n");
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("A cave."));
+        assert!(lines.next().unwrap().starts_with("Cannot load nowhere: "));
+        assert_eq!(
+            lines.collect::<Vec<_>>(),
+            [
+                "Set mood = sleepy.",
+                "Usage: @set var=value",
+                "Gave lamp.",
+                "Unknown author command: @dance"
+            ]
+        );
+        assert_eq!(reloaded, "A bright cave.\n");
+        assert_eq!(engine.state().get("mood"), Some("sleepy"));
+        assert!(engine.state().has("lamp"));
+        assert_eq!(engine.state().visits("cave"), 1);
+        assert_eq!(engine.state().turns(), 1);
+    }
+
+    #[test]
+    fn export_scene() {
+        let fixture = TempAdventure::new(
+            "export",
+            &[
+                (
+                    "about.yaml",
+                    "name: Kitten\nauthor: Fiona\nstyles:\n  purr: italic\n",
+                ),
+                (
+                    "start.scene",
+                    "@item: yarn = A ball of yarn.\n\
+                     A kitten {purr}purrs{/purr} at ${name}.\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let adventure = crate::adventure::load(dir);
+        let mut engine = Engine::from_adventure(adventure.unwrap()).unwrap();
+        engine.set_wrap(Some(10));
+        engine.set_debug(true);
+        engine.state.set("name", "<you>");
+        let file = dir.join("start.html");
+        let out =
+            steps(&mut engine, &[&format!("@export {}", file.display())]);
+        let html = std::fs::read_to_string(&file).unwrap();
+        let missing = steps(&mut engine, &["@export /nonexistent/x.html"]);
+
+        assert_eq!(
+            out,
+            format!("Exported the scene to {}.\n", file.display())
+        );
+        assert!(html.contains("<title>Kitten: start</title>"));
+        assert!(html.contains(".style-purr { font-style: italic; }"));
+        assert!(html.contains(
+            "<p>A kitten <span class=\"style-purr\">purrs</span> at \
+             &lt;you&gt;.<br>\nYou can see: yarn.</p>"
+        ));
+        assert!(missing.starts_with("Cannot export: "));
+        // The text filters are back
+        assert_eq!(engine.render("A tiny kitten"), "A tiny\nkitten");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn trace_matching() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        /// Collects the fields of events as text.
+        struct Collector(Arc<Mutex<Vec<String>>>);
+
+        struct Fields(String);
+
+        impl Visit for Fields {
+            fn record_debug(
+                &mut self,
+                field: &Field,
+                value: &dyn std::fmt::Debug,
+            ) {
+                self.0.push_str(&format!(" {}={:?}", field.name(), value));
+            }
+        }
+
+        impl tracing::Subscriber for Collector {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(String::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = kitten_engine();
+        tracing::subscriber::with_default(
+            Collector(Arc::clone(&events)),
+            || steps(&mut engine, &["hug"]),
+        );
+        let events = events.lock().unwrap();
+        assert_eq!(
+            events[0],
+            " message=processing input input=\"hug\" scene=\"kitten\""
+        );
+        assert!(events.contains(
+            &" message=tested action scene=\"kitten\" action=0 \
+              expression=\"^meow$\" matched=false"
+                .to_string()
+        ));
+        assert!(events.iter().any(|e| e.contains("matched=true")));
+        // Applying the effect loads the next scene.
+        let n = events.len();
+        assert!(events[n - 2].starts_with(
+            " message=applying effect scene=\"kitten\" action=1 \
+             effect=Change(\"cuddle_cat\")"
+        ));
+        assert_eq!(events[n - 1], " message=scene loaded actions=4");
+    }
+
+    #[test]
+    fn clock() {
+        let mut engine = Engine::new(
+            Scene::parse(
+                PathBuf::from("camp.scene"),
+                "@format: 2\nA camp. It is ${time} on day ${day}.\n\
+                 !kw:look -> print The stars shine. [if time in night \
+                 | The sun shines.]\n\
+                 !kw:sleep -> advance 1d2h You sleep.\n",
+            )
+            .unwrap(),
+        );
+        let mut clock = Clock::default();
+        clock.set_start("19:50").unwrap();
+        clock.set_turn("5m").unwrap();
+        engine.set_clock(Some(clock));
+        let mut out = Vec::new();
+        engine.describe(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "A camp. It is 19:50 on day 1.\n"
+        );
+        assert_eq!(
+            steps(&mut engine, &["look", "sleep", "look"]),
+            "The sun shines.\nYou sleep.\nThe stars shine.\n"
+        );
+        // Three turns of 5 minutes and 26 hours of sleep
+        assert_eq!(engine.state().elapsed(), 1575);
+        assert_eq!(engine.render("${day} ${time}"), "2 22:05");
+    }
+
+    #[test]
+    fn ambient_events() {
+        let engine = |seed| {
+            let mut engine = Engine::new(
+                Scene::parse(
+                    PathBuf::from("camp.scene"),
+                    "A camp.\n!kw:wait -> print You wait.\n",
+                )
+                .unwrap(),
+            );
+            engine.set_seed(seed);
+            engine.set_events(vec![
+                Event::new("A wolf howls.", 100).in_scenes(&["forest"]),
+                Event::new("The fire crackles.", 50),
+                Event::new("An owl hoots.", 100),
+            ]);
+            engine
+        };
+        let inputs = ["wait"; 10];
+        let mut a = engine(7);
+        let out = steps(&mut a, &inputs);
+        // The same seed gives the same events.
+        assert_eq!(steps(&mut engine(7), &inputs), out);
+        let events: Vec<&str> =
+            out.lines().filter(|l| *l != "You wait.").collect();
+        assert_eq!(events.len(), 10);
+        assert!(events.contains(&"The fire crackles."));
+        assert!(events.contains(&"An owl hoots."));
+        assert!(!events.contains(&"A wolf howls."));
+        // Empty input is not a turn.
+        assert_eq!(steps(&mut a, &[""]), "");
+    }
+
+    #[test]
+    fn scene_tag_condition() {
+        let scene = |name: &str, tags: &str| {
+            Scene::parse(
+                PathBuf::from(format!("{}.scene", name)),
+                &format!(
+                    "@format: 2\n@tags: {}\nA place.\n\
+                     !kw:look up -> print The sky. [if scene has outdoors \
+                     | A ceiling.]\n",
+                    tags
+                ),
+            )
+            .unwrap()
+        };
+        let mut engine = Engine::new(scene("field", "outdoors, grass"));
+        assert_eq!(steps(&mut engine, &["look up"]), "The sky.\n");
+        engine.goto(scene("hall", "indoors"));
+        assert_eq!(steps(&mut engine, &["look up"]), "A ceiling.\n");
+    }
+
+    #[test]
+    fn change_adventure() {
+        let fixture = TempAdventure::new(
+            "saga",
+            &[
+                (
+                    "episode1/about.yaml",
+                    "name: Box\nauthor: Fiona\n\
+                     variables:\n  score: 3\n  mood: happy\n",
+                ),
+                (
+                    "episode1/start.scene",
+                    "@format: 2\nA box.\n!kw:take coin -> give coin\n\
+                     !kw:take yarn -> give yarn\n\
+                     !kw:leave -> adventure garden\n",
+                ),
+                (
+                    "episode2/about.yaml",
+                    "name: Garden\nauthor: Fiona\n\
+                     variables:\n  score: 0\n  bonus: 1\n",
+                ),
+                (
+                    "episode2/start.scene",
+                    "@format: 2\n\
+                     A garden, score ${score}${mood}, bonus ${bonus}.\n\
+                     !kw:check -> print Coin! [needs coin | No coin.]\n",
+                ),
+                (
+                    "campaign.yaml",
+                    "name: Saga\nadventures:\n  box: episode1\n  \
+                     garden: episode2\n\
+                     carry:\n  variables: [score]\n  inventory: true\n",
+                ),
+            ],
+        );
+        let adventure = crate::adventure::load(&fixture.path("episode1"));
+        let mut engine = Engine::from_adventure(adventure.unwrap()).unwrap();
+        let out = steps(&mut engine, &["take coin", "leave", "check"]);
+
+        assert_eq!(
+            out,
+            "Got coin.\nA garden, score 3${mood}, bonus 1.\nCoin!\n"
+        );
+        assert_eq!(engine.adventure(), "Garden");
+        assert_eq!(engine.campaign().unwrap().name(), "Saga");
+        assert!(engine.adventure_dir().unwrap().ends_with("episode2"));
+        assert_eq!(engine.state().visits("start"), 1);
+    }
+
+    #[test]
+    fn scene_stack() {
+        #[derive(Debug)]
+        struct Town;
+
+        impl SceneProvider for Town {
+            fn load_next(
+                &self,
+                _current: &Scene,
+                name: &str,
+            ) -> Result<Scene, Box<dyn Error>> {
+                let text = match name {
+                    "street" => {
+                        "@format: 2\nA street.\n!kw:enter -> pushscene shop\n\
+                                 !kw:leave -> popscene\n"
+                    }
+                    "shop" => {
+                        "@format: 2\n\
+                         A shop.\n!kw:read book -> pushscene book\n\
+                               !kw:leave -> popscene\n"
+                    }
+                    "book" => "@format: 2\nA book.\n!kw:close -> popscene\n",
+                    _ => return Err("no such scene".into()),
+                };
+                Scene::parse(PathBuf::from(format!("{}.scene", name)), text)
+            }
+        }
+
+        let street = Town.load_next(&Scene::default(), "street").unwrap();
+        let mut engine = Engine::new(street);
+        engine.set_scene_provider(Town);
+        let events = Events::default();
+        let recorded = Rc::clone(&events.0);
+        engine.set_analytics(events);
+        assert_eq!(
+            steps(&mut engine, &["enter", "read book"]),
+            "A shop.\nA book.\n"
+        );
+        assert_eq!(engine.state().scene_stack(), ["street", "shop"]);
+        assert_eq!(
+            steps(&mut engine, &["close", "leave", "leave"]),
+            "A shop.\nA street.\nThere is nowhere to return to.\n"
+        );
+        // Returning doesn't count as a visit, but is a scene change.
+        assert_eq!(engine.state().visits("shop"), 1);
+        assert_eq!(engine.state().visits("street"), 1);
+        assert_eq!(
+            *recorded.borrow(),
+            [
+                "street -> shop (1)",
+                "shop -> book (2)",
+                "book -> shop (3)",
+                "shop -> street (4)"
+            ]
+        );
+        // A scene that fails to load stays on the stack.
+        engine.state.push_scene("cellar".to_string());
+        assert!(engine.step("leave", &mut Vec::new()).is_err());
+        assert_eq!(engine.state().scene_stack(), ["cellar"]);
+        assert_eq!(engine.scene.name(), "street");
+    }
+
+    #[test]
+    fn crlf_input() {
+        let mut engine = kitten_engine();
+        assert_eq!(
+            steps(&mut engine, &["meow\r\n", "meow\n"]),
+            "\"Meow!\" =^.^=\n".repeat(2)
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! E-book version of an adventure, for reading gamebook-style
+//! adventures offline: `rustventure export epub <file>` writes an
+//! EPUB 3 book with a chapter for each scene reachable from the start
+//! scene, in the order the scenes are found from there. Scene changes
+//! become links to their chapters, with the condition of the action
+//! if it has one.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::adventure::Adventure;
+use crate::output::html::{self, escape};
+use crate::save;
+use crate::scene::graph::SceneGraph;
+use crate::scene::Scene;
+use crate::twee::link_text;
+
+/// CRC-32 of `data`, as used by zip files.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// A zip archive of uncompressed files, which is all an EPUB needs.
+#[derive(Debug, Default)]
+struct Zip {
+    data: Vec<u8>,
+    /// Central directory entries
+    directory: Vec<u8>,
+    files: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, content: &[u8]) {
+        // Version 1.0, no flags, stored, 1980-01-01 00:00
+        let mut header = Vec::new();
+        header.extend(10u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(0x21u16.to_le_bytes());
+        header.extend(crc32(content).to_le_bytes());
+        header.extend((content.len() as u32).to_le_bytes());
+        header.extend((content.len() as u32).to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+
+        self.directory.extend(b"PK\x01\x02");
+        self.directory.extend(10u16.to_le_bytes());
+        self.directory.extend(&header);
+        // No comment, disk 0, no attributes
+        self.directory.extend([0; 10]);
+        self.directory
+            .extend((self.data.len() as u32).to_le_bytes());
+        self.directory.extend(name.as_bytes());
+
+        self.data.extend(b"PK\x03\x04");
+        self.data.extend(&header);
+        self.data.extend(name.as_bytes());
+        self.data.extend(content);
+        self.files += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        self.data.extend(b"PK\x05\x06");
+        self.data.extend([0; 4]);
+        self.data.extend(self.files.to_le_bytes());
+        self.data.extend(self.files.to_le_bytes());
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(offset.to_le_bytes());
+        self.data.extend([0; 2]);
+        self.data
+    }
+}
+
+/// The current time in the format of `dcterms:modified`.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // Civil date from days since 1970-01-01, after Howard Hinnant's
+    // civil_from_days algorithm
+    let days = secs / 86400 + 719_468;
+    let era = days / 146_097;
+    let doe = days % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// File name of the chapter for the scene `name`, without extension.
+fn chapter_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Unique chapter file names for the scenes of `graph`, by scene
+/// name. Scenes whose names differ only in characters replaced by
+/// [`chapter_name`], or only in case, get a number added.
+fn chapter_files(graph: &SceneGraph) -> HashMap<&str, String> {
+    // The table of contents is a document of its own.
+    let mut taken = vec!["nav".to_string()];
+    let mut files = HashMap::new();
+    for scene in graph.scenes() {
+        let base = chapter_name(scene.name());
+        let mut name = base.clone();
+        let mut i = 1;
+        while taken
+            .iter()
+            .any(|t| t.to_lowercase() == name.to_lowercase())
+        {
+            i += 1;
+            name = format!("{}_{}", base, i);
+        }
+        files.insert(scene.name(), format!("{}.xhtml", name));
+        taken.push(name);
+    }
+    files
+}
+
+/// Language of the adventure for the book metadata: its dictionary
+/// name like `en_US` as language tag `en-US`, English if it has none.
+fn language(adventure: &Adventure) -> String {
+    adventure.language().unwrap_or("en").replace('_', "-")
+}
+
+/// An XHTML document with `title` and `body`.
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" \
+         xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+         <head>\n<title>{}</title>\n\
+         <link rel=\"stylesheet\" href=\"style.css\"/>\n</head>\n\
+         <body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+/// The chapter of `scene`, with links to the chapters of the scenes
+/// it leads to.
+fn chapter(
+    graph: &SceneGraph,
+    scene: &Scene,
+    files: &HashMap<&str, String>,
+    adventure: &Adventure,
+) -> String {
+    let text = html::to_html(&scene.to_string(), adventure.styles());
+    let mut body = format!(
+        "<section epub:type=\"chapter\">\n<h1>{}</h1>\n{}",
+        escape(scene.name()),
+        text.replace("<br>", "<br/>")
+    );
+    let links = graph.links(scene.name());
+    if !links.is_empty() {
+        body.push_str("<ul>\n");
+        for link in links {
+            let action = &scene.actions()[link.action];
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>",
+                files[link.to()],
+                escape(link_text(action, &link.target))
+            ));
+            if let Some(c) = action.condition() {
+                body.push_str(&format!(" (if {})", escape(&c.to_string())));
+            }
+            body.push_str("</li>\n");
+        }
+        body.push_str("</ul>\n");
+    }
+    body.push_str("</section>\n");
+    xhtml(scene.name(), &body)
+}
+
+/// Convert the adventure to an EPUB book. Fails if a scene change
+/// leads to a scene that cannot be loaded.
+pub fn export(adventure: Adventure) -> Result<Vec<u8>, Box<dyn Error>> {
+    let graph = SceneGraph::crawl(adventure.clone().start()?);
+    if let Some((_, e)) = graph.missing().first_key_value() {
+        return Err(e.as_str().into());
+    }
+    let id = save::content_hash(adventure.dir())?;
+
+    let mut zip = Zip::default();
+    // The mimetype must come first, uncompressed.
+    zip.add("mimetype", b"application/epub+zip");
+    zip.add(
+        "META-INF/container.xml",
+        b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+          <container version=\"1.0\" \
+          xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+          <rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" \
+          media-type=\"application/oebps-package+xml\"/>\n\
+          </rootfiles>\n</container>\n",
+    );
+    zip.add("OEBPS/style.css", html::css(adventure.styles()).as_bytes());
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut toc = String::new();
+    let files = chapter_files(&graph);
+    for (i, scene) in graph.scenes().enumerate() {
+        let file = &files[scene.name()];
+        zip.add(
+            &format!("OEBPS/{}", file),
+            chapter(&graph, scene, &files, &adventure).as_bytes(),
+        );
+        manifest.push_str(&format!(
+            "<item id=\"s{}\" href=\"{}\" \
+             media-type=\"application/xhtml+xml\"/>\n",
+            i, file
+        ));
+        spine.push_str(&format!("<itemref idref=\"s{}\"/>\n", i));
+        toc.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            file,
+            escape(scene.name())
+        ));
+    }
+    let nav = format!(
+        "<nav epub:type=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n",
+        escape(adventure.name()),
+        toc
+    );
+    zip.add("OEBPS/nav.xhtml", xhtml(adventure.name(), &nav).as_bytes());
+    let opf = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" \
+         unique-identifier=\"id\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:identifier id=\"id\">urn:rustventure:{:016x}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n<dc:creator>{}</dc:creator>\n\
+         <dc:language>{}</dc:language>\n\
+         <meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n\
+         <manifest>\n<item id=\"nav\" href=\"nav.xhtml\" \
+         media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n\
+         {}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+        id,
+        escape(adventure.name()),
+        escape(adventure.author()),
+        escape(&language(&adventure)),
+        timestamp(),
+        manifest,
+        spine
+    );
+    zip.add("OEBPS/content.opf", opf.as_bytes());
+    Ok(zip.finish())
+}
+
+/// Export the adventure in `path` as an EPUB book to `file`.
+pub fn write(path: &Path, file: &Path) -> Result<(), Box<dyn Error>> {
+    let book = export(crate::adventure::load(path)?)?;
+    fs::write(file, book)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adventure;
+    use crate::fixture::TempAdventure;
+
+    /// Names and contents of the files in a zip archive written by
+    /// [`Zip`], found like zip readers do from the central directory.
+    fn unzip(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let end = data.len() - 22;
+        assert_eq!(&data[end..end + 4], b"PK\x05\x06");
+        let mut entry = u32_at(end + 16) as usize;
+        let mut files = Vec::new();
+        for _ in 0..u16_at(end + 10) {
+            assert_eq!(&data[entry..entry + 4], b"PK\x01\x02");
+            let name_len = u16_at(entry + 28) as usize;
+            let name = &data[entry + 46..entry + 46 + name_len];
+            let local = u32_at(entry + 42) as usize;
+            assert_eq!(&data[local..local + 4], b"PK\x03\x04");
+            let start = local + 30 + name_len;
+            let content = &data[start..start + u32_at(entry + 20) as usize];
+            assert_eq!(u32_at(entry + 16), crc32(content));
+            let name = String::from_utf8(name.to_vec()).unwrap();
+            files.push((name, content.to_vec()));
+            entry += 46 + name_len;
+        }
+        files
+    }
+
+    #[test]
+    fn export_epub() {
+        let fixture = TempAdventure::new(
+            "epub",
+            &[
+                (
+                    "about.yaml",
+                    "name: Cat & Mouse\nauthor: Fiona\nstyles:\n  \
+                     purr: italic\n",
+                ),
+                (
+                    "start.scene",
+                    "@format: 2\n\
+                     A {purr}cozy{/purr}\nhall.\n!door:in -> den\n\
+                     !kw:climb -> scene attic [if has ladder]\n",
+                ),
+                ("den.scene", "A den.\n"),
+                ("attic.scene", "An attic.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let book = export(adventure::load(dir).unwrap());
+
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let files = unzip(&book.unwrap());
+        let names: Vec<&str> =
+            files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/style.css",
+                "OEBPS/start.xhtml",
+                "OEBPS/den.xhtml",
+                "OEBPS/attic.xhtml",
+                "OEBPS/nav.xhtml",
+                "OEBPS/content.opf"
+            ]
+        );
+        assert_eq!(files[0].1, b"application/epub+zip");
+        let start = String::from_utf8(files[3].1.clone()).unwrap();
+        assert!(start.contains(
+            "<h1>start</h1>\n\
+             <p>A <span class=\"style-purr\">cozy</span><br/>\nhall.</p>\n\
+             <ul>\n<li><a href=\"den.xhtml\">in</a></li>\n\
+             <li><a href=\"attic.xhtml\">climb</a> (if has ladder)</li>\n\
+             </ul>\n"
+        ));
+        let opf = String::from_utf8(files[7].1.clone()).unwrap();
+        assert!(opf.contains("<dc:title>Cat &amp; Mouse</dc:title>"));
+        assert!(opf.contains("<itemref idref=\"s2\"/>"));
+        assert!(opf.contains("<dc:language>en</dc:language>"));
+        assert_eq!(timestamp().len(), "2026-01-01T00:00:00Z".len());
+    }
+
+    #[test]
+    fn unique_chapters() {
+        let fixture = TempAdventure::new(
+            "epub-names",
+            &[
+                (
+                    "about.yaml",
+                    "name: Café\nauthor: Fiona\nstart: café.scene\n\
+                     language: fr_FR\n",
+                ),
+                (
+                    "café.scene",
+                    "Un café.\n!kw:ici -> scene cafè\n!kw:là -> scene nav\n",
+                ),
+                ("cafè.scene", "Un autre café.\n!kw:retour -> scene café\n"),
+                ("nav.scene", "Navigation.\n"),
+            ],
+        );
+        let book = export(adventure::load(fixture.dir()).unwrap());
+
+        let files = unzip(&book.unwrap());
+        let names: Vec<&str> =
+            files.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names[3..7],
+            [
+                "OEBPS/caf_.xhtml",
+                "OEBPS/caf__2.xhtml",
+                "OEBPS/nav_2.xhtml",
+                "OEBPS/nav.xhtml"
+            ]
+        );
+        let other = String::from_utf8(files[4].1.clone()).unwrap();
+        assert!(other.contains("<a href=\"caf_.xhtml\">retour</a>"));
+        let opf = String::from_utf8(files[7].1.clone()).unwrap();
+        assert!(opf.contains("<dc:language>fr-FR</dc:language>"));
+    }
+}
@@ -0,0 +1,578 @@
+//! A tiny expression language for conditions, e.g.
+//! `has(key) && visits(cave) > 2 || score >= 10`.
+//!
+//! Expressions combine comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`)
+//! with `&&`, `||`, `!` and parentheses. Operands are numbers, quoted
+//! strings, `true` and `false`, variables (by name), and functions:
+//!
+//! * `has(item)`: whether the player carries the item
+//! * `visits(scene)`: number of times the player entered the scene
+//! * `npc(name)`: state of the NPC, unset if it hasn't changed
+//! * `var(name)`: value of the variable, for names that clash with
+//!   keywords or functions
+//!
+//! Function arguments are names, so they don't need quotes.
+//! Comparisons are numeric if both sides are numbers, otherwise
+//! values are compared as text. Unset variables are not equal to
+//! anything, and neither less nor greater.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustventure::expr::Expr;
+//! let e = Expr::parse("has(key) && visits(cave) > 2 || score >= 10").unwrap();
+//! assert_eq!(
+//!     e.to_string(),
+//!     "has(key) && visits(cave) > 2 || score >= 10"
+//! );
+//! ```
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use crate::condition::Context;
+
+/// Value of an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Number(i64),
+    Text(String),
+    /// Value of unset variables
+    Unset,
+}
+
+impl Value {
+    /// Whether the value counts as true: `false`, 0, empty text,
+    /// the texts "false" and "0", and unset values don't.
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0,
+            Value::Text(t) => !t.is_empty() && t != "false" && t != "0",
+            Value::Unset => false,
+        }
+    }
+
+    fn number(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(t) => t.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Compare two values, `None` if they can't be compared.
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        if *self == Value::Unset || *other == Value::Unset {
+            return None;
+        }
+        match (self.number(), other.number()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => Some(self.to_string().cmp(&other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(t) => write!(f, "{}", t),
+            Value::Unset => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn parse(op: &str) -> Option<CompareOp> {
+        Some(match op {
+            "==" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, a: &Value, b: &Value) -> bool {
+        match (self, a.compare(b)) {
+            (CompareOp::Ne, o) => o != Some(Ordering::Equal),
+            (_, None) => false,
+            (CompareOp::Eq, Some(o)) => o == Ordering::Equal,
+            (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+            (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+            (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+            (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        })
+    }
+}
+
+/// Functions expressions can call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Has,
+    Visits,
+    Npc,
+    Var,
+}
+
+impl Function {
+    pub const ALL: [Function; 4] = [
+        Function::Has,
+        Function::Visits,
+        Function::Npc,
+        Function::Var,
+    ];
+
+    fn parse(name: &str) -> Option<Function> {
+        Function::ALL.into_iter().find(|f| f.to_string() == name)
+    }
+
+    fn call(self, ctx: &dyn Context, arg: &str) -> Value {
+        let text = |v: Option<&str>| {
+            v.map_or(Value::Unset, |v| Value::Text(v.to_string()))
+        };
+        match self {
+            Function::Has => Value::Bool(ctx.has(arg)),
+            Function::Visits => Value::Number(i64::from(ctx.visits(arg))),
+            Function::Npc => text(ctx.npc_state(arg)),
+            Function::Var => text(ctx.variable(arg)),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Function::Has => "has",
+            Function::Visits => "visits",
+            Function::Npc => "npc",
+            Function::Var => "var",
+        })
+    }
+}
+
+/// A parsed expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Literal(Value),
+    Variable(String),
+    Call(Function, String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, Box<dyn Error>> {
+        let tokens = tokenize(s)
+            .map_err(|e| format!("invalid expression: {}: {}", s, e))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser
+            .or()
+            .and_then(|e| match parser.peek() {
+                None => Ok(e),
+                Some(t) => Err(format!("unexpected {}", t)),
+            })
+            .map_err(|e| format!("invalid expression: {}: {}", s, e))?;
+        Ok(expr)
+    }
+
+    pub fn eval(&self, ctx: &dyn Context) -> Value {
+        match self {
+            Expr::Literal(v) => v.clone(),
+            Expr::Variable(name) => ctx
+                .variable(name)
+                .map_or(Value::Unset, |v| Value::Text(v.to_string())),
+            Expr::Call(f, arg) => f.call(ctx, arg),
+            Expr::Not(e) => Value::Bool(!e.check(ctx)),
+            Expr::And(a, b) => Value::Bool(a.check(ctx) && b.check(ctx)),
+            Expr::Or(a, b) => Value::Bool(a.check(ctx) || b.check(ctx)),
+            Expr::Compare(a, op, b) => {
+                Value::Bool(op.apply(&a.eval(ctx), &b.eval(ctx)))
+            }
+        }
+    }
+
+    /// Whether the expression is true in `ctx`.
+    pub fn check(&self, ctx: &dyn Context) -> bool {
+        self.eval(ctx).truthy()
+    }
+
+    /// Binding strength, to decide where parentheses are needed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 1,
+            Expr::And(..) => 2,
+            Expr::Compare(..) => 3,
+            Expr::Not(..) => 4,
+            _ => 5,
+        }
+    }
+
+    fn fmt_operand(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        min: u8,
+    ) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(Value::Text(t)) => write!(f, "{:?}", t),
+            Expr::Literal(v) => write!(f, "{}", v),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Call(func, arg) if is_name(arg) => {
+                write!(f, "{}({})", func, arg)
+            }
+            Expr::Call(func, arg) => write!(f, "{}({:?})", func, arg),
+            Expr::Not(e) => {
+                write!(f, "!")?;
+                e.fmt_operand(f, 4)
+            }
+            Expr::And(a, b) => {
+                a.fmt_operand(f, 2)?;
+                write!(f, " && ")?;
+                b.fmt_operand(f, 3)
+            }
+            Expr::Or(a, b) => {
+                a.fmt_operand(f, 1)?;
+                write!(f, " || ")?;
+                b.fmt_operand(f, 2)
+            }
+            Expr::Compare(a, op, b) => {
+                a.fmt_operand(f, 4)?;
+                write!(f, " {} ", op)?;
+                b.fmt_operand(f, 4)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Number(i64),
+    Text(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(n) => write!(f, "'{}'", n),
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Text(t) => write!(f, "{:?}", t),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+const OPERATORS: [&str; 13] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")", ",", "-",
+];
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_name_char)
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if c == '"' {
+            let end = rest[1..].find('"').ok_or("unterminated string")?;
+            tokens.push(Token::Text(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let n = rest[..end].parse().map_err(|_| "number too large")?;
+            tokens.push(Token::Number(n));
+            rest = &rest[end..];
+        } else if is_name_char(c) {
+            let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per precedence level.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    /// Consume the operator `op` if it is next.
+    fn accept(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(o)) if o == op => Ok(()),
+            Some(t) => Err(format!("expected '{}', found {}", op, t)),
+            None => Err(format!("expected '{}'", op)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut e = self.and()?;
+        while self.accept("||") {
+            e = Expr::Or(Box::new(e), Box::new(self.and()?));
+        }
+        Ok(e)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut e = self.comparison()?;
+        while self.accept("&&") {
+            e = Expr::And(Box::new(e), Box::new(self.comparison()?));
+        }
+        Ok(e)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let e = self.unary()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) => CompareOp::parse(op),
+            _ => None,
+        };
+        match op {
+            Some(op) => {
+                self.pos += 1;
+                Ok(Expr::Compare(Box::new(e), op, Box::new(self.unary()?)))
+            }
+            None => Ok(e),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.accept("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op("(")) => {
+                let e = self.or()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Some(Token::Op("-")) => match self.next() {
+                Some(Token::Number(n)) => {
+                    Ok(Expr::Literal(Value::Number(-n)))
+                }
+                _ => Err("expected a number after '-'".to_string()),
+            },
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Text(t)) => Ok(Expr::Literal(Value::Text(t))),
+            Some(Token::Name(n)) if n == "true" || n == "false" => {
+                Ok(Expr::Literal(Value::Bool(n == "true")))
+            }
+            Some(Token::Name(n)) => {
+                if !self.accept("(") {
+                    return Ok(Expr::Variable(n));
+                }
+                let f = Function::parse(&n)
+                    .ok_or(format!("unknown function '{}'", n))?;
+                let arg = match self.next() {
+                    Some(Token::Name(a)) | Some(Token::Text(a)) => a,
+                    Some(Token::Number(a)) => a.to_string(),
+                    _ => return Err(format!("expected a name for {}()", n)),
+                };
+                self.expect(")")?;
+                Ok(Expr::Call(f, arg))
+            }
+            Some(t) => Err(format!("unexpected {}", t)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::GameState;
+
+    fn state() -> GameState {
+        let mut state = GameState::default();
+        state.set("score", "12");
+        state.set("mood", "happy");
+        state.set("dark", "false");
+        state.give("key");
+        for _ in 0..3 {
+            state.visit("cave");
+        }
+        state.set_npc("guard", "asleep");
+        state
+    }
+
+    fn eval(s: &str) -> Value {
+        Expr::parse(s).unwrap().eval(&state())
+    }
+
+    fn check(s: &str) -> bool {
+        Expr::parse(s).unwrap().check(&state())
+    }
+
+    #[test]
+    fn precedence() {
+        let var = |n: &str| Box::new(Expr::Variable(n.to_string()));
+        assert_eq!(
+            Expr::parse("a || b && c").unwrap(),
+            Expr::Or(var("a"), Box::new(Expr::And(var("b"), var("c"))))
+        );
+        assert_eq!(
+            Expr::parse("!a == b").unwrap(),
+            Expr::Compare(
+                Box::new(Expr::Not(var("a"))),
+                CompareOp::Eq,
+                var("b")
+            )
+        );
+        for s in [
+            "(a || b) && c",
+            "a || b && c",
+            "!(a == 1)",
+            "!has(key) || npc(guard) != \"alert\"",
+            "a && (b && c)",
+            "score >= -5",
+            "has(\"red key\")",
+        ] {
+            assert_eq!(Expr::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(Expr::parse("((a))&&(b)").unwrap().to_string(), "a && b");
+    }
+
+    #[test]
+    fn evaluate() {
+        assert_eq!(eval("score"), Value::Text("12".to_string()));
+        assert_eq!(eval("visits(cave)"), Value::Number(3));
+        assert_eq!(eval("npc(guard)"), Value::Text("asleep".to_string()));
+        assert_eq!(eval("npc(cat)"), Value::Unset);
+        assert_eq!(eval("var(mood)"), Value::Text("happy".to_string()));
+        assert_eq!(eval("unknown"), Value::Unset);
+        assert!(check("has(key) && visits(cave) > 2 || score >= 100"));
+        assert!(check("has(yarn) || score >= 10"));
+        assert!(!check("has(yarn) || score >= 100"));
+        assert!(!check("has(key) && (visits(cave) > 3 || score < 12)"));
+        assert!(check("!has(yarn) && npc(guard) == \"asleep\""));
+        assert!(check("visits(garden) == 0"));
+    }
+
+    #[test]
+    fn comparisons() {
+        // Numeric if both sides are numbers
+        assert!(check("score > 9"));
+        assert!(check("score <= 12 && score >= 12 && score == 12"));
+        assert!(check("score != 13"));
+        assert!(check("-1 < 0"));
+        // Text otherwise
+        assert!(check("mood == \"happy\""));
+        assert!(check("mood < \"sad\""));
+        assert!(check("\"10\" == 10"));
+        // Unset values only differ
+        assert!(!check("unknown == 0"));
+        assert!(!check("unknown < 1"));
+        assert!(!check("unknown >= 1"));
+        assert!(check("unknown != 1"));
+    }
+
+    #[test]
+    fn truthiness() {
+        assert!(check("mood"));
+        assert!(!check("dark"));
+        assert!(!check("unknown"));
+        assert!(check("true && !false"));
+        assert!(!check("0"));
+        assert!(check("visits(cave)"));
+        assert!(!check("\"\""));
+    }
+
+    #[test]
+    fn errors() {
+        for (s, e) in [
+            ("has(key", "expected ')'"),
+            ("fly(away)", "unknown function 'fly'"),
+            ("score ==", "unexpected end"),
+            ("\"open", "unterminated string"),
+            ("a b", "unexpected 'b'"),
+            ("a @ b", "unexpected '@'"),
+            ("has()", "expected a name for has()"),
+            ("a == b == c", "unexpected '=='"),
+            ("- x", "expected a number after '-'"),
+        ] {
+            let err = Expr::parse(s).unwrap_err().to_string();
+            assert_eq!(err, format!("invalid expression: {}: {}", s, e));
+        }
+    }
+}
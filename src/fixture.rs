@@ -0,0 +1,59 @@
+//! Adventures written to a temporary directory, for tests that need
+//! files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `about.yaml` of a minimal adventure starting in `box.scene`.
+pub const ABOUT: &str = "name: Test\nauthor: Fiona\nstart: box.scene\n";
+
+/// A temporary directory with adventure files, removed when dropped,
+/// also if the test fails.
+pub struct TempAdventure {
+    dir: PathBuf,
+}
+
+impl TempAdventure {
+    /// Create a directory unique to the test `name` and this process,
+    /// with `files` as pairs of a path relative to the directory and
+    /// its text.
+    pub fn new(name: &str, files: &[(&str, &str)]) -> TempAdventure {
+        let dir = std::env::temp_dir().join(format!(
+            "rustventure-{}-{}",
+            name,
+            std::process::id()
+        ));
+        // Left over from an aborted run
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let adventure = TempAdventure { dir };
+        for (file, text) in files {
+            adventure.write(file, text);
+        }
+        adventure
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of `file` in the directory.
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    /// Write `text` to `file`, creating directories as needed.
+    pub fn write(&self, file: &str, text: &str) {
+        let path = self.path(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, text).unwrap();
+    }
+}
+
+impl Drop for TempAdventure {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
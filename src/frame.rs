@@ -0,0 +1,28 @@
+//! Output of one turn as a single value, for front ends that send one
+//! message per turn, like network servers, instead of passing on a
+//! stream of text. See [`Engine::frame`](crate::engine::Engine::frame).
+
+use serde::Serialize;
+
+use crate::state::Change;
+
+/// Everything one line of input produced.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Frame {
+    /// The input, empty for the start of the game
+    pub input: String,
+    /// All text written for the input, including any description
+    pub output: String,
+    /// Name of the current scene, with parameters if any
+    pub scene: String,
+    /// Description of the scene if the input led to another one, and
+    /// at the start of the game
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Prompt for the next input
+    pub prompt: String,
+    /// Labels of the exits of the current scene
+    pub exits: Vec<String>,
+    /// How the game state changed
+    pub changes: Vec<Change>,
+}
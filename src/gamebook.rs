@@ -0,0 +1,328 @@
+//! Classic gamebook version of an adventure: `rustventure export pdf
+//! <file>` numbers the scenes reachable from the start scene and
+//! writes them to a PDF as numbered sections, with "turn to 47" for
+//! each scene change, followed by an index of the scene names.
+//!
+//! The start scene is section 1, the others follow in the order they
+//! are found from there, so the numbers only change when the scene
+//! graph does.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::adventure::Adventure;
+use crate::output;
+use crate::scene::graph::SceneGraph;
+use crate::twee::link_text;
+
+/// Page size (A4) in points.
+const PAGE: (usize, usize) = (595, 842);
+/// Margin on all sides in points.
+const MARGIN: usize = 72;
+/// Font size in points.
+const FONT_SIZE: usize = 10;
+/// Line height in points.
+const LEADING: usize = 12;
+/// Characters per line: Courier characters are 0.6 em wide.
+const COLUMNS: usize = (PAGE.0 - 2 * MARGIN) * 10 / (FONT_SIZE * 6);
+/// Lines per page.
+const LINES: usize = (PAGE.1 - 2 * MARGIN) / LEADING;
+
+/// Section numbers of the scenes in `graph`.
+pub fn numbers(graph: &SceneGraph) -> BTreeMap<&str, usize> {
+    graph
+        .scenes()
+        .enumerate()
+        .map(|(i, s)| (s.name(), i + 1))
+        .collect()
+}
+
+/// A line of the book, in the bold font if `bold` is set.
+struct Line {
+    bold: bool,
+    text: String,
+}
+
+impl Line {
+    fn new(text: impl Into<String>) -> Line {
+        Line {
+            bold: false,
+            text: text.into(),
+        }
+    }
+
+    fn bold(text: impl Into<String>) -> Line {
+        Line {
+            bold: true,
+            text: text.into(),
+        }
+    }
+}
+
+/// Centered `text`, padded with spaces.
+fn center(text: &str) -> String {
+    let pad = COLUMNS.saturating_sub(text.chars().count()) / 2;
+    format!("{}{}", " ".repeat(pad), text)
+}
+
+/// Lines of the sections and the index.
+fn lines(graph: &SceneGraph, adventure: &Adventure) -> Vec<Vec<Line>> {
+    let numbers = numbers(graph);
+    let mut book = vec![Line::bold(center(adventure.name()))];
+    book.push(Line::new(center(&format!("by {}", adventure.author()))));
+    for scene in graph.scenes() {
+        book.push(Line::new(""));
+        book.push(Line::bold(center(&numbers[scene.name()].to_string())));
+        let text = adventure.styles().strip(&scene.to_string());
+        let text = output::wrap(text.trim_end(), COLUMNS);
+        book.extend(text.lines().map(Line::new));
+        let links = graph.links(scene.name());
+        if !links.is_empty() {
+            book.push(Line::new(""));
+        }
+        for link in links {
+            let action = &scene.actions()[link.action];
+            let mut choice = link_text(action, &link.target).to_string();
+            if let Some(c) = action.condition() {
+                choice.push_str(&format!(" (if {})", c));
+            }
+            let choice =
+                format!("{}: turn to {}.", choice, numbers[link.to()]);
+            book.extend(
+                output::wrap(&choice, COLUMNS).lines().map(Line::new),
+            );
+        }
+    }
+    let mut index = vec![Line::bold(center("Index")), Line::new("")];
+    for (name, n) in &numbers {
+        let n = n.to_string();
+        let dots = COLUMNS.saturating_sub(name.chars().count() + n.len() + 2);
+        index.push(Line::new(format!("{} {} {}", name, ".".repeat(dots), n)));
+    }
+    vec![book, index]
+}
+
+/// Split `lines` into pages, without empty lines at the top.
+fn paginate(lines: Vec<Line>) -> Vec<Vec<Line>> {
+    let mut pages: Vec<Vec<Line>> = vec![Vec::new()];
+    for line in lines {
+        let page = pages.last_mut().unwrap();
+        if page.is_empty() && line.text.is_empty() {
+            continue;
+        }
+        page.push(line);
+        if page.len() == LINES {
+            pages.push(Vec::new());
+        }
+    }
+    pages.retain(|p| !p.is_empty());
+    pages
+}
+
+/// A PDF string literal of `text`, in the WinAnsi encoding of the
+/// standard fonts. Characters it doesn't have become `?`.
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut s = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => s.extend([b'\\', c as u8]),
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => s.push(c as u8),
+            '\u{2014}' => s.push(0x97),
+            '\u{2018}' | '\u{2019}' => s.push(b'\''),
+            '\u{201c}' | '\u{201d}' => s.push(b'"'),
+            _ => s.push(b'?'),
+        }
+    }
+    s.push(b')');
+    s
+}
+
+/// Content stream drawing the lines of a page.
+fn content(page: &[Line]) -> Vec<u8> {
+    let mut out = format!(
+        "BT\n{} TL\n{} {} Td\n",
+        LEADING,
+        MARGIN,
+        PAGE.1 - MARGIN - FONT_SIZE
+    )
+    .into_bytes();
+    let mut bold = None;
+    for line in page {
+        if bold != Some(line.bold) {
+            let font = if line.bold { "F2" } else { "F1" };
+            out.extend(format!("/{} {} Tf\n", font, FONT_SIZE).bytes());
+            bold = Some(line.bold);
+        }
+        out.extend(pdf_string(&line.text));
+        out.extend(b" Tj T*\n");
+    }
+    out.extend(b"ET\n");
+    out
+}
+
+/// A PDF file with `pages`, using the standard Courier fonts.
+fn pdf(pages: &[Vec<Line>]) -> Vec<u8> {
+    // Objects 1 and 2 are the catalog and the page tree, 3 and 4 the
+    // fonts, then each page and its content.
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 5 + 2 * i))
+        .collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    ];
+    for font in ["Courier", "Courier-Bold"] {
+        objects.push(
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} \
+                 /Encoding /WinAnsiEncoding >>",
+                font
+            )
+            .into_bytes(),
+        );
+    }
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> \
+                 /Contents {} 0 R >>",
+                PAGE.0,
+                PAGE.1,
+                6 + 2 * i
+            )
+            .into_bytes(),
+        );
+        let stream = content(page);
+        let mut obj =
+            format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+        obj.extend(stream);
+        obj.extend(b"endstream");
+        objects.push(obj);
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n", i + 1).bytes());
+        out.extend(obj);
+        out.extend(b"\nendobj\n");
+    }
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n", objects.len() + 1).bytes());
+    out.extend(b"0000000000 65535 f \n");
+    for offset in offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .bytes(),
+    );
+    out
+}
+
+/// Convert the adventure to a gamebook PDF. Fails if a scene change
+/// leads to a scene that cannot be loaded.
+pub fn export(adventure: Adventure) -> Result<Vec<u8>, Box<dyn Error>> {
+    let graph = SceneGraph::crawl(adventure.clone().start()?);
+    if let Some((_, e)) = graph.missing().first_key_value() {
+        return Err(e.as_str().into());
+    }
+    // The index starts on a new page.
+    let pages: Vec<Vec<Line>> = lines(&graph, &adventure)
+        .into_iter()
+        .flat_map(paginate)
+        .collect();
+    Ok(pdf(&pages))
+}
+
+/// Export the adventure in `path` as a gamebook PDF to `file`.
+pub fn write(path: &Path, file: &Path) -> Result<(), Box<dyn Error>> {
+    let book = export(crate::adventure::load(path)?)?;
+    fs::write(file, book)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adventure;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn export_pdf() {
+        let fixture = TempAdventure::new(
+            "gamebook",
+            &[
+                (
+                    "about.yaml",
+                    "name: Cat (and Mouse)\nauthor: Fiona\nstyles:\n  \
+                     purr: italic\n",
+                ),
+                (
+                    "start.scene",
+                    "@format: 2\nA {purr}cozy{/purr} hall.\n!door:in -> den\n\
+                     !kw:climb -> scene attic [if has ladder]\n",
+                ),
+                ("den.scene", "A den.\n!door:out -> start\n"),
+                ("attic.scene", "An attic \u{2014} dusty.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let adventure = adventure::load(dir).unwrap();
+        let graph = SceneGraph::crawl(adventure.clone().start().unwrap());
+        let book = export(adventure);
+
+        assert_eq!(
+            numbers(&graph),
+            BTreeMap::from([("attic", 3), ("den", 2), ("start", 1)])
+        );
+        let book = book.unwrap();
+        assert!(book.starts_with(b"%PDF-1.4\n"));
+        assert!(book.ends_with(b"%%EOF\n"));
+        let text = String::from_utf8_lossy(&book);
+        for line in [
+            "Cat \\(and Mouse\\)) Tj",
+            "(A cozy hall.) Tj",
+            "(in: turn to 2.) Tj",
+            "(climb \\(if has ladder\\): turn to 3.) Tj",
+            "(out: turn to 1.) Tj",
+            "/Count 2",
+        ] {
+            assert!(text.contains(line), "missing {}", line);
+        }
+        assert!(book.windows(14).any(|w| w == b"(An attic \x97 du"));
+        // The cross-reference table points to the objects.
+        let tail = String::from_utf8(book[book.len() - 40..].to_vec());
+        let xref: usize = tail
+            .unwrap()
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|s| s.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let table = String::from_utf8(book[xref..].to_vec()).unwrap();
+        assert!(table.starts_with("xref\n0 9\n0000000000 65535 f \n"));
+        for (i, entry) in table.lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let obj = format!("{} 0 obj\n", i + 1);
+            assert!(book[offset..].starts_with(obj.as_bytes()));
+        }
+        assert!(
+            text.contains(&format!("(attic {} 3)", ".".repeat(COLUMNS - 8)))
+        );
+    }
+}
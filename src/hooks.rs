@@ -0,0 +1,91 @@
+//! Hook points for front ends in the game loop, see [`play`]: run
+//! code before each prompt, e.g. to redraw a status bar, and after
+//! each batch of output, e.g. to scroll, without writing their own
+//! loop.
+//!
+//! [`play`]: crate::play
+
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::engine::Engine;
+
+/// Callbacks for the game loop. Both do nothing by default.
+///
+/// # Examples
+///
+/// A status line with the scene name before each prompt:
+///
+/// ```
+/// use rustventure::engine::Engine;
+/// use rustventure::hooks::Hooks;
+/// use rustventure::scene::Scene;
+/// use std::error::Error;
+/// use std::io::Write;
+/// use std::path::PathBuf;
+///
+/// #[derive(Debug)]
+/// struct StatusLine;
+///
+/// impl Hooks for StatusLine {
+///     fn before_prompt(
+///         &mut self,
+///         engine: &Engine,
+///         output: &mut dyn Write,
+///     ) -> Result<(), Box<dyn Error>> {
+///         writeln!(output, "[{}]", engine.scene().name())?;
+///         Ok(())
+///     }
+/// }
+///
+/// let scene = Scene::parse(PathBuf::from("garden.scene"), "A garden.\n")?;
+/// let engine = Engine::new(scene);
+/// let mut output = Vec::new();
+/// StatusLine.before_prompt(&engine, &mut output)?;
+/// assert_eq!(output, b"[garden]\n");
+/// # Ok::<(), Box<dyn Error>>(())
+/// ```
+pub trait Hooks: fmt::Debug {
+    /// Called before the game asks for input.
+    fn before_prompt(
+        &mut self,
+        _engine: &Engine,
+        _output: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Called after the output for the start of the game or one line
+    /// of input is written.
+    fn after_output(
+        &mut self,
+        _engine: &Engine,
+        _output: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Sets the time limit for reading input to the idle time of the
+/// current scene before each prompt, see
+/// [`Engine::idle_timeout`]. The limit is in milliseconds, like
+/// [`ThreadedInput::timeout`](crate::input::ThreadedInput::timeout).
+#[derive(Debug)]
+pub struct IdleTimer(pub Arc<AtomicU64>);
+
+impl Hooks for IdleTimer {
+    fn before_prompt(
+        &mut self,
+        engine: &Engine,
+        _output: &mut dyn Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let ms = engine
+            .idle_timeout()
+            .map_or(0, |t| (t.as_millis() as u64).max(1));
+        self.0.store(ms, Ordering::SeqCst);
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Experimental import of stories written in Ink. Only a subset is
+//! supported: knots, stitches, diverts, and (nested) choices with
+//! gathers. Logic, variables, and conditional text are left as they
+//! are.
+//!
+//! Knots and stitches become scenes, choices are shown as a numbered
+//! menu and selected by number. Content after a choice gets its own
+//! scene that leads on to the following gather, if any.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::twee::{Passage, Story};
+
+/// Remove one level of choice or gather markers from `line`.
+fn strip_marker(line: &str) -> &str {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some('*') | Some('+') => chars.as_str().trim_start(),
+        Some('-') if !line.starts_with("->") => chars.as_str().trim_start(),
+        _ => line,
+    }
+}
+
+fn is_choice(line: &str) -> bool {
+    line.starts_with(['*', '+'])
+        && !strip_marker(line).starts_with(['*', '+'])
+}
+
+fn has_gather_marker(line: &str) -> bool {
+    line.starts_with('-') && !line.starts_with("->")
+}
+
+fn is_gather(line: &str) -> bool {
+    has_gather_marker(line) && !has_gather_marker(strip_marker(line))
+}
+
+/// Split a line at a divert, returning the text before it and the
+/// target.
+fn split_divert(line: &str) -> (&str, Option<&str>) {
+    match line.split_once("->") {
+        Some((text, target)) => (text.trim_end(), Some(target.trim())),
+        None => (line, None),
+    }
+}
+
+/// Parses knots into passages, resolving divert targets.
+struct Parser {
+    /// Stitches of each knot
+    stitches: BTreeMap<String, Vec<String>>,
+    passages: Vec<Passage>,
+}
+
+impl Parser {
+    /// Resolve a divert target inside `knot` to a passage name, or
+    /// `None` if the story ends.
+    fn target(&self, knot: &str, target: &str) -> Option<String> {
+        if target == "END" || target == "DONE" {
+            return None;
+        }
+        if let Some((k, s)) = target.split_once('.') {
+            return Some(format!("{}_{}", k, s));
+        }
+        match self.stitches.get(knot) {
+            Some(s) if s.iter().any(|s| s == target) => {
+                Some(format!("{}_{}", knot, target))
+            }
+            _ => Some(target.to_string()),
+        }
+    }
+
+    /// Turn a block of lines into the passage `name` and its
+    /// sub-passages. If the block ends without a divert, the story
+    /// continues with `next`. Fails on choices that can't be parsed.
+    fn block(
+        &mut self,
+        knot: &str,
+        name: &str,
+        lines: &[&str],
+        next: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut text = String::new();
+        let mut i = 0;
+        while i < lines.len() && !is_choice(lines[i]) && !is_gather(lines[i])
+        {
+            let (t, divert) = split_divert(lines[i]);
+            if !t.is_empty() {
+                text.push_str(t);
+                text.push('\n');
+            }
+            if let Some(target) = divert {
+                if let Some(target) = self.target(knot, target) {
+                    text.push_str(&format!("[[Continue->{}]]\n", target));
+                }
+                self.passages.push(Passage {
+                    name: name.to_string(),
+                    tags: Vec::new(),
+                    text: text.trim().to_string(),
+                });
+                return Ok(());
+            }
+            i += 1;
+        }
+
+        let mut choices = Vec::new();
+        while i < lines.len() && is_choice(lines[i]) {
+            let start = i;
+            i += 1;
+            while i < lines.len()
+                && !is_choice(lines[i])
+                && !is_gather(lines[i])
+            {
+                i += 1;
+            }
+            choices.push(&lines[start..i]);
+        }
+
+        let gather = if i < lines.len() {
+            let gather = format!("{}_gather", name);
+            let rest: Vec<&str> =
+                lines[i..].iter().map(|l| strip_marker(l)).collect();
+            self.block(knot, &gather, &rest, next)?;
+            Some(gather)
+        } else {
+            None
+        };
+        let next = gather.as_deref().or(next);
+
+        for (n, choice) in choices.iter().enumerate() {
+            lazy_static! {
+                static ref CHOICE_RE: Regex = Regex::new(
+                    r"^(?:\(\w+\)\s*)?(?:\{[^}]*\}\s*)?([^\[]*)(?:\[([^\]]*)\](.*))?$"
+                )
+                .unwrap();
+            }
+            let line = strip_marker(choice[0]);
+            let (line, divert) = split_divert(line);
+            let c = CHOICE_RE
+                .captures(line)
+                .ok_or_else(|| format!("invalid choice: {}", line))?;
+            let before = &c[1];
+            let inside = c.get(2).map_or("", |m| m.as_str());
+            let after = c.get(3).map_or("", |m| m.as_str());
+            let label = format!("{}{}", before, inside);
+            let output = format!("{}{}", before, after).trim().to_string();
+
+            let body: Vec<&str> =
+                choice[1..].iter().map(|l| strip_marker(l)).collect();
+            let direct = if output.is_empty() && body.is_empty() {
+                match divert {
+                    Some(d) => self.target(knot, d),
+                    None => next.map(String::from),
+                }
+            } else {
+                None
+            };
+            // Choices with content, or that end the story, get a
+            // passage of their own.
+            let target = match direct {
+                Some(target) => target,
+                None => {
+                    let sub = format!("{}_{}", name, n + 1);
+                    let divert_line = divert.map(|d| format!("-> {}", d));
+                    let mut lines: Vec<&str> = Some(output.as_str())
+                        .filter(|o| !o.is_empty())
+                        .into_iter()
+                        .collect();
+                    lines.extend(body);
+                    lines.extend(divert_line.as_deref());
+                    self.block(knot, &sub, &lines, next)?;
+                    sub
+                }
+            };
+            text.push_str(&format!(
+                "[[{}->{}]]: {}\n",
+                n + 1,
+                target,
+                label.trim()
+            ));
+        }
+        if choices.is_empty() {
+            if let Some(next) = next {
+                text.push_str(&format!("[[Continue->{}]]\n", next));
+            }
+        }
+        self.passages.push(Passage {
+            name: name.to_string(),
+            tags: Vec::new(),
+            text: text.trim().to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// Parse an Ink story into a [`Story`] that can be imported with
+/// [`crate::twee::import`].
+pub fn parse(text: &str) -> Result<Story, Box<dyn Error>> {
+    lazy_static! {
+        static ref KNOT_RE: Regex = Regex::new(
+            r"^={2,}\s*(function\s+)?(\w+)\s*(?:\([^)]*\))?\s*=*$"
+        )
+        .unwrap();
+        static ref STITCH_RE: Regex = Regex::new(r"^=\s*(\w+)\s*$").unwrap();
+        static ref COMMENT_RE: Regex =
+            Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").unwrap();
+    }
+    let text = COMMENT_RE.replace_all(text, "");
+
+    // Collect the lines of each knot and stitch, in order.
+    let mut title = None;
+    let mut sections: Vec<(String, String, Vec<&str>)> =
+        vec![(String::new(), String::new(), Vec::new())];
+    let mut stitches: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut knot = String::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(c) = KNOT_RE.captures(line) {
+            if c.get(1).is_some() {
+                return Err(
+                    format!("functions are not supported: {}", line).into()
+                );
+            }
+            knot = c[2].to_string();
+            sections.push((knot.clone(), knot.clone(), Vec::new()));
+        } else if let Some(c) = STITCH_RE.captures(line) {
+            let name = format!("{}_{}", knot, &c[1]);
+            stitches
+                .entry(knot.clone())
+                .or_default()
+                .push(c[1].to_string());
+            sections.push((knot.clone(), name, Vec::new()));
+        } else if let Some(t) = line.strip_prefix("# title:") {
+            title = Some(t.trim().to_string());
+        } else if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with('~')
+            || line.starts_with("VAR ")
+            || line.starts_with("CONST ")
+            || line.starts_with("INCLUDE ")
+            || line.starts_with("TODO")
+        {
+            continue;
+        } else {
+            sections.last_mut().unwrap().2.push(line);
+        }
+    }
+
+    let mut parser = Parser {
+        stitches,
+        passages: Vec::new(),
+    };
+    let (_, _, top) = sections.remove(0);
+    let start = match top.as_slice() {
+        [] => sections.first().map(|(_, name, _)| name.clone()),
+        [line] if line.starts_with("->") => {
+            parser.target("", line[2..].trim())
+        }
+        _ => {
+            parser.block("", "start", &top, None)?;
+            Some("start".to_string())
+        }
+    };
+    for (knot, name, lines) in &sections {
+        // A knot without content of its own flows into its first
+        // stitch.
+        let first_stitch;
+        let lines = match parser.stitches.get(knot) {
+            Some(s) if lines.is_empty() && knot == name => {
+                first_stitch = format!("-> {}", s[0]);
+                vec![first_stitch.as_str()]
+            }
+            _ => lines.clone(),
+        };
+        parser.block(knot, name, &lines, None)?;
+    }
+    if parser.passages.is_empty() {
+        return Err("no content in story".into());
+    }
+    Ok(Story {
+        title,
+        start,
+        passages: parser.passages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORY: &str = "\
+# title: Kitten Ink
+// The kitten is waiting.
+-> box
+
+=== box ===
+A kitten peeks out of a box.
+* [Pet the kitten] You pet the kitten.
+  ** Purr[.] it says.
+  ** [Leave it] -> END
+* Leave -> outside
+- The kitten yawns.
+-> END
+
+=== outside ===
+= door
+You are outside.
+-> DONE
+";
+
+    fn passage<'a>(story: &'a Story, name: &str) -> &'a Passage {
+        story.passages.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn parse_ink() {
+        let story = parse(STORY).unwrap();
+        assert_eq!(story.title.as_deref(), Some("Kitten Ink"));
+        assert_eq!(story.start.as_deref(), Some("box"));
+        assert_eq!(
+            passage(&story, "box").text,
+            "A kitten peeks out of a box.\n\
+             [[1->box_1]]: Pet the kitten\n\
+             [[2->box_2]]: Leave"
+        );
+        assert_eq!(
+            passage(&story, "box_1").text,
+            "You pet the kitten.\n\
+             [[1->box_1_1]]: Purr.\n\
+             [[2->box_1_2]]: Leave it"
+        );
+        assert_eq!(
+            passage(&story, "box_1_1").text,
+            "Purr it says.\n[[Continue->box_gather]]"
+        );
+        assert_eq!(
+            passage(&story, "box_2").text,
+            "Leave\n[[Continue->outside]]"
+        );
+        assert_eq!(passage(&story, "box_1_2").text, "");
+        assert_eq!(passage(&story, "box_gather").text, "The kitten yawns.");
+        assert_eq!(
+            passage(&story, "outside").text,
+            "[[Continue->outside_door]]"
+        );
+        assert_eq!(passage(&story, "outside_door").text, "You are outside.");
+    }
+
+    #[test]
+    #[should_panic(expected = "functions are not supported")]
+    fn function() {
+        parse("=== function f(x) ===\n~ return x\n").unwrap();
+    }
+
+    #[test]
+    fn invalid_choice() {
+        let err = parse("=== box ===\n* Look [inside\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid choice: Look [inside");
+    }
+}
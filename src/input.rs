@@ -0,0 +1,282 @@
+//! Input helpers for interactive front ends.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+enum Message {
+    /// A line, empty at the end of the source
+    Line(io::Result<String>),
+    Interrupt,
+}
+
+/// Reads lines from a source on a background thread, so the front
+/// end can notice input arriving while it is still busy writing
+/// output (e.g. to skip a [`crate::output::Typewriter`] animation).
+///
+/// Reading can be interrupted using an [`Interrupter`], which makes
+/// the current read return end of file once.
+///
+/// With [`set_skip_lines`](ThreadedInput::set_skip_lines) lines that
+/// only served to skip output are dropped, see there.
+///
+/// Waiting for a line can be limited with
+/// [`timeout`](ThreadedInput::timeout).
+pub struct ThreadedInput {
+    lines: Receiver<Message>,
+    /// Messages received while polling, before waiting for more
+    queue: VecDeque<Message>,
+    skip_lines: bool,
+    tx: Sender<Message>,
+    pending: Arc<AtomicUsize>,
+    interrupted: Arc<AtomicBool>,
+    /// Time limit for waiting for a line in milliseconds, 0 for none
+    timeout: Arc<AtomicU64>,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+/// Interrupts reading from a [`ThreadedInput`], e.g. from a signal
+/// handler.
+#[derive(Clone)]
+pub struct Interrupter {
+    tx: Sender<Message>,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Interrupter {
+    /// Set the interrupted flag and wake up the reader.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+        // If the input is gone there is nothing to wake up.
+        let _ = self.tx.send(Message::Interrupt);
+    }
+}
+
+impl ThreadedInput {
+    pub fn spawn<R>(mut source: R) -> ThreadedInput
+    where
+        R: BufRead + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let p = Arc::clone(&pending);
+        let lines = tx.clone();
+        thread::spawn(move || loop {
+            let mut line = String::new();
+            let res = match source.read_line(&mut line) {
+                Ok(0) => Ok(line),
+                Ok(_) => {
+                    p.fetch_add(1, Ordering::SeqCst);
+                    Ok(line)
+                }
+                Err(e) => Err(e),
+            };
+            // Interrupters keep the channel open, so the end must be
+            // sent explicitly.
+            let done = !matches!(&res, Ok(l) if !l.is_empty());
+            if lines.send(Message::Line(res)).is_err() || done {
+                break;
+            }
+        });
+        ThreadedInput {
+            lines: rx,
+            queue: VecDeque::new(),
+            skip_lines: false,
+            tx,
+            pending,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            timeout: Arc::new(AtomicU64::new(0)),
+            buf: Vec::new(),
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    /// Flag that is set when reading is interrupted. The reader
+    /// should reset it after handling the interruption.
+    pub fn interrupted(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    pub fn interrupter(&self) -> Interrupter {
+        Interrupter {
+            tx: self.tx.clone(),
+            interrupted: Arc::clone(&self.interrupted),
+        }
+    }
+
+    /// Counter of lines that have been read from the source, but
+    /// not consumed yet.
+    pub fn pending(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.pending)
+    }
+
+    /// Time limit for waiting for the next line in milliseconds, 0
+    /// (the default) for none. When it runs out reading fails with
+    /// [`io::ErrorKind::TimedOut`], and can be tried again.
+    pub fn timeout(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.timeout)
+    }
+
+    /// Wait for the next message, up to the time limit.
+    fn receive(&mut self) -> io::Result<Option<Message>> {
+        let message = match self.timeout.load(Ordering::SeqCst) {
+            0 => self.lines.recv().ok(),
+            ms => match self.lines.recv_timeout(Duration::from_millis(ms)) {
+                Ok(m) => Some(m),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::ErrorKind::TimedOut.into())
+                }
+                Err(RecvTimeoutError::Disconnected) => None,
+            },
+        };
+        Ok(message)
+    }
+
+    /// Drop empty lines and `skip` that arrived before the next line
+    /// is requested: The player entered them while output was still
+    /// being written, to skip to the end of it, and they shouldn't
+    /// count as commands. Other lines are kept.
+    pub fn set_skip_lines(&mut self, skip: bool) {
+        self.skip_lines = skip;
+    }
+
+    /// Collect the messages that have arrived so far without waiting,
+    /// and drop skip lines among them.
+    fn discard_skips(&mut self) {
+        while let Ok(m) = self.lines.try_recv() {
+            self.queue.push_back(m);
+        }
+        self.queue.retain(|m| match m {
+            Message::Line(Ok(l))
+                if l.ends_with('\n') && matches!(l.trim(), "" | "skip") =>
+            {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+                false
+            }
+            _ => true,
+        });
+    }
+}
+
+impl Read for ThreadedInput {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let buf = self.fill_buf()?;
+            let n = buf.len().min(out.len());
+            out[..n].copy_from_slice(&buf[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for ThreadedInput {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buf.len() && !self.eof {
+            if self.skip_lines {
+                self.discard_skips();
+            }
+            let message = match self.queue.pop_front() {
+                Some(m) => Some(m),
+                None => self.receive()?,
+            };
+            match message {
+                Some(Message::Line(Ok(line))) if !line.is_empty() => {
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
+                    self.buf = line.into_bytes();
+                    self.pos = 0;
+                }
+                Some(Message::Line(Err(e))) => {
+                    self.eof = true;
+                    return Err(e);
+                }
+                Some(Message::Interrupt) => return Ok(&[]),
+                // The reader thread is done, so this is the end.
+                _ => self.eof = true,
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threaded_lines() {
+        let mut input = ThreadedInput::spawn(&b"meow\nhug\npet"[..]);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while input.read_line(&mut line).unwrap() > 0 {
+            lines.push(line.clone());
+            line.clear();
+        }
+        assert_eq!(lines, vec!["meow\n", "hug\n", "pet"]);
+        assert_eq!(input.pending().load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn interrupt() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut input = ThreadedInput::spawn(io::BufReader::new(reader));
+        let interrupted = input.interrupted();
+        input.interrupter().interrupt();
+        let mut line = String::new();
+        assert_eq!(input.read_line(&mut line).unwrap(), 0);
+        assert!(interrupted.swap(false, Ordering::SeqCst));
+        // Reading continues after the interruption.
+        std::io::Write::write_all(&mut writer, b"y\n").unwrap();
+        drop(writer);
+        assert_eq!(input.read_line(&mut line).unwrap(), 2);
+        assert_eq!(line, "y\n");
+        assert_eq!(input.read_line(&mut line).unwrap(), 0);
+        assert_eq!(input.read_line(&mut line).unwrap(), 0);
+    }
+
+    #[test]
+    fn timeout() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut input = ThreadedInput::spawn(io::BufReader::new(reader));
+        input.timeout().store(10, Ordering::SeqCst);
+        let mut line = String::new();
+        let err = input.read_line(&mut line).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        std::io::Write::write_all(&mut writer, b"y\n").unwrap();
+        input.timeout().store(0, Ordering::SeqCst);
+        assert_eq!(input.read_line(&mut line).unwrap(), 2);
+        assert_eq!(line, "y\n");
+    }
+
+    #[test]
+    fn skip_lines() {
+        let mut input =
+            ThreadedInput::spawn(&b"look\n\nskip\n\r\nnorth\n"[..]);
+        input.set_skip_lines(true);
+        let pending = input.pending();
+        // Wait for all lines to arrive, as if typed during output.
+        while pending.load(Ordering::SeqCst) < 5 {
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while input.read_line(&mut line).unwrap() > 0 {
+            lines.push(line.clone());
+            line.clear();
+        }
+        assert_eq!(lines, vec!["look\n", "north\n"]);
+        assert_eq!(pending.load(Ordering::SeqCst), 0);
+    }
+}
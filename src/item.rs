@@ -0,0 +1,135 @@
+//! Item definitions, loaded from the `items.yaml` file of an
+//! adventure. The file maps item ids to their definitions:
+//!
+//! ```yaml
+//! key:
+//!   name: rusty key
+//!   description: A small, rusty key.
+//!   aliases: [small key]
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    id: String,
+    name: String,
+    description: String,
+    aliases: Vec<String>,
+}
+
+impl Item {
+    /// Create an item without description or aliases, using the id
+    /// as name.
+    pub fn new(id: &str) -> Item {
+        Item {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Name to show the player.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Check if the player may mean this item when typing `s`: its
+    /// id, name, or one of the aliases.
+    pub fn matches(&self, s: &str) -> bool {
+        s == self.id || s == self.name || self.aliases.iter().any(|a| a == s)
+    }
+
+    fn from_yaml(id: &str, def: &Yaml) -> Result<Item, Box<dyn Error>> {
+        let mut item = Item::new(id);
+        if def.is_null() {
+            return Ok(item);
+        }
+        if def.as_hash().is_none() {
+            return Err(format!("invalid item definition: {}", id).into());
+        }
+        if let Some(name) = def["name"].as_str() {
+            item.name = name.to_string();
+        }
+        if let Some(desc) = def["description"].as_str() {
+            item.description = desc.to_string();
+        }
+        item.aliases = match &def["aliases"] {
+            Yaml::Array(a) => a
+                .iter()
+                .filter_map(|a| a.as_str().map(|a| a.to_string()))
+                .collect(),
+            Yaml::String(a) => vec![a.clone()],
+            _ => Vec::new(),
+        };
+        Ok(item)
+    }
+}
+
+/// Parse item definitions from YAML text.
+pub fn parse(s: &str) -> Result<BTreeMap<String, Item>, Box<dyn Error>> {
+    let docs = YamlLoader::load_from_str(s)?;
+    let mut items = BTreeMap::new();
+    let defs = match docs.first() {
+        Some(Yaml::Hash(h)) => h,
+        Some(_) => return Err("invalid item data, must be hash".into()),
+        None => return Ok(items),
+    };
+    for (id, def) in defs {
+        let id = id.as_str().ok_or("item ids must be strings")?;
+        items.insert(id.to_string(), Item::from_yaml(id, def)?);
+    }
+    Ok(items)
+}
+
+/// Load item definitions from a file.
+pub fn load(path: &Path) -> Result<BTreeMap<String, Item>, Box<dyn Error>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_items() {
+        let items = parse(
+            "key:\n  name: rusty key\n  description: A small, rusty key.\n  \
+             aliases: [small key, old key]\nyarn:\n  aliases: ball\ntreat:\n",
+        )
+        .unwrap();
+        let key = &items["key"];
+        assert_eq!(key.name(), "rusty key");
+        assert_eq!(key.description(), "A small, rusty key.");
+        assert!(key.matches("key"));
+        assert!(key.matches("rusty key"));
+        assert!(key.matches("old key"));
+        assert!(!key.matches("yarn"));
+        assert_eq!(items["yarn"].aliases(), ["ball"]);
+        assert_eq!(items["treat"], Item::new("treat"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid item definition: key")]
+    fn invalid_item() {
+        parse("key: rusty\n").unwrap();
+    }
+}
@@ -2,24 +2,220 @@
 
 use std::error;
 use std::fmt;
-use std::io::{BufRead, Write};
+use std::io::{self, BufRead, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
 
 use clap::Parser;
 
+use commands::Command;
+
+/// Log a trace event if the `tracing` feature is enabled, arguments
+/// are the same as for [`tracing::trace!`](https://docs.rs/tracing).
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+/// Log a debug event if the `tracing` feature is enabled.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Log an info event if the `tracing` feature is enabled.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+    };
+}
+
+/// Log a warning if the `tracing` feature is enabled.
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+/// Enter an info level span if the `tracing` feature is enabled, the
+/// span lasts until the end of the enclosing block.
+macro_rules! enter_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($($arg)*).entered();
+    };
+}
+
 pub mod adventure;
+pub mod ambient;
+pub mod analytics;
+pub mod bundle;
+pub mod cache;
+pub mod campaign;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod clock;
+pub mod commands;
+pub mod condition;
+pub mod diagnose;
+pub mod engine;
+pub mod epub;
+pub mod expr;
+#[cfg(test)]
+mod fixture;
+pub mod frame;
+pub mod gamebook;
+pub mod hooks;
+#[cfg(feature = "ink")]
+pub mod ink;
+pub mod input;
+pub mod item;
+pub mod matcher;
+pub mod menu;
+pub mod migrate;
+pub mod npc;
+pub mod output;
+pub mod pool;
+pub mod preprocess;
+pub mod profile;
+pub mod prompt;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod save;
 pub mod scene;
+#[cfg(feature = "rhai")]
+pub mod script;
+pub mod site;
+pub mod spelling;
+pub mod state;
+pub mod syntax;
+pub mod testing;
+pub mod twee;
+pub mod warning;
 
-use scene::{Effect, Scene};
+use adventure::Adventure;
+use engine::{Engine, AUTOSAVE};
+use hooks::Hooks;
+use output::Profile;
+use profile::{LastPlayed, PlayerProfile};
+use prompt::{Prompter, Terminal};
+use save::FsStore;
+use scene::Scene;
 
 /// Runtime configuration data
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
 pub struct Config {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
     /// Path of the initial scene file to load, or directory to search
     /// for adventures
     #[clap(default_value = ".")]
     pub scene: PathBuf,
+    /// Print output like a typewriter with the given number of
+    /// characters per second, press Enter to skip ahead
+    #[clap(long, value_name = "CPS")]
+    pub typewriter: Option<u32>,
+    /// Adjust how output is presented
+    #[clap(long, value_enum, default_value = "plain")]
+    pub output_profile: Profile,
+    /// Confirm recognized commands and announce scene changes, e.g.
+    /// for screen readers
+    #[clap(long)]
+    pub echo: bool,
+    /// Player profile file to store aliases and other preferences
+    /// in, defaults to one in the user configuration directory
+    #[clap(long, value_name = "FILE")]
+    pub profile: Option<PathBuf>,
+    /// Directory to keep saved games in, defaults to one in the user
+    /// data directory
+    #[clap(long, value_name = "DIR")]
+    pub saves: Option<PathBuf>,
+    /// Wrap text to the given number of columns
+    #[clap(long, value_name = "COLUMNS")]
+    pub wrap: Option<usize>,
+    /// Record scene changes and the end of the game to the given file
+    /// for playtest analysis, as CSV if the name ends in `.csv`,
+    /// otherwise as JSON lines
+    #[clap(long, value_name = "FILE")]
+    pub analytics: Option<PathBuf>,
+    /// Append inputs that matched nothing to `unmatched.log` in the
+    /// adventure directory when the game ends, for authors
+    #[clap(long)]
+    pub author_feedback: bool,
+    /// Seed for random responses, to make a game repeatable
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Hot-seat mode: the number of players taking turns, each with
+    /// their own inventory and variables
+    #[clap(long, value_name = "N")]
+    pub players: Option<usize>,
+    /// Enable commands for adventure authors: `debug state`, and
+    /// `@goto`, `@set`, `@give`, `@reload`, `@export`
+    #[clap(long)]
+    pub debug: bool,
+    /// Skip malformed action lines in scenes instead of failing, so
+    /// authors can keep playtesting while fixing them. The skipped
+    /// lines are logged as warnings.
+    #[clap(long)]
+    pub lenient: bool,
+    /// Don't show the cover art of adventures, it is also skipped if
+    /// the output isn't a terminal
+    #[clap(long)]
+    pub no_art: bool,
+    /// Remove text styles from the output, overriding the player's
+    /// options
+    #[clap(long)]
+    pub no_color: bool,
+    /// Log engine events (scene loads, saves, parse warnings) to
+    /// stderr, repeat for more detail
+    #[cfg(feature = "tracing")]
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Write the log to the given file instead of stderr
+    #[cfg(feature = "tracing")]
+    #[clap(long, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+    /// Read the output aloud with a speech synthesizer, the command
+    /// gets the text as its input
+    #[cfg(feature = "speak")]
+    #[clap(
+        long,
+        value_name = "COMMAND",
+        num_args = 0..=1,
+        default_missing_value = output::speech::DEFAULT_COMMAND
+    )]
+    pub speak: Option<String>,
+    /// Set when the player interrupts the game (e.g. with Ctrl-C),
+    /// the input must then return end of file. The game offers to
+    /// save before quitting.
+    #[clap(skip)]
+    pub interrupted: Option<Arc<AtomicBool>>,
+    /// Speed of the typewriter the output is written to, see
+    /// [`Typewriter::speed`](output::Typewriter::speed). Lets the
+    /// player change it with the `options` command.
+    #[clap(skip)]
+    pub text_speed: Option<Arc<AtomicU32>>,
+    /// Text filters for front ends to add presentation features,
+    /// applied after the default ones
+    #[clap(skip)]
+    pub filters: Vec<Box<dyn output::TextFilter>>,
+    /// Callbacks for front ends in the game loop, see [`play`]
+    #[clap(skip)]
+    pub hooks: Vec<Box<dyn Hooks>>,
+    /// Time limit for reading input in milliseconds, which the game
+    /// sets to the idle time of the current scene, see
+    /// [`hooks::IdleTimer`]. Only for interactive input.
+    #[clap(skip)]
+    pub idle_timer: Option<Arc<AtomicU64>>,
 }
 
 #[derive(Debug)]
@@ -45,7 +241,7 @@ impl fmt::Display for Error {
 ///
 /// The last two arguments exist primarily to make the function
 /// testable, but could also be used to implement some other user
-/// interface.
+/// interface. For more control over prompts use [`run_with`].
 pub fn run<R, W>(
     config: Config,
     input: &mut R,
@@ -55,73 +251,268 @@ where
     R: BufRead,
     W: Write,
 {
+    run_with(config, &mut Terminal::new(input, output))
+}
+
+/// Show the cover art of `adventure` and its name in large letters,
+/// if enabled for the adventure, within `width` columns. Without a
+/// width art is disabled. Cover art that can't be read is skipped.
+fn show_cover(
+    adventure: &Adventure,
+    width: Option<usize>,
+    output: &mut dyn Write,
+) -> Result<(), Box<dyn error::Error>> {
+    let Some(width) = width else {
+        return Ok(());
+    };
+    if let Some(banner) = adventure
+        .banner()
+        .then(|| output::banner::render(adventure.name(), width))
+        .flatten()
+    {
+        writeln!(output, "{}", banner)?;
+    }
+    let Some(path) = adventure.cover() else {
+        return Ok(());
+    };
+    let art = match std::fs::read(path) {
+        Ok(art) => art,
+        Err(_e) => {
+            warn!(
+                cover = %path.display(),
+                error = %_e,
+                "cannot read cover art"
+            );
+            return Ok(());
+        }
+    };
+    let art = output::crop(&String::from_utf8_lossy(&art), width);
+    write!(output, "{}", art)?;
+    if !art.ends_with('\n') {
+        writeln!(output)?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Whether reading input failed because the time limit ran out.
+fn timed_out(e: &(dyn error::Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// The game loop: describe the current scene, then process input
+/// from `prompter` until it ends, calling `hooks` along the way. If
+/// reading input times out, the idle effect of the scene happens,
+/// see [`Engine::idle`].
+pub fn play(
+    engine: &mut Engine,
+    prompter: &mut dyn Prompter,
+    hooks: &mut [Box<dyn Hooks>],
+) -> Result<(), Box<dyn error::Error>> {
+    engine.describe(&mut prompter.output())?;
+    for h in hooks.iter_mut() {
+        h.after_output(engine, prompter.output())?;
+    }
+    loop {
+        for h in hooks.iter_mut() {
+            h.before_prompt(engine, prompter.output())?;
+        }
+        let prompt = engine.formatted_prompt()?;
+        let line = match prompter.read_line(&prompt) {
+            Err(e) if timed_out(&*e) => {
+                writeln!(prompter.output())?;
+                engine.idle(&mut prompter.output())?;
+                None
+            }
+            res => match res? {
+                Some(line) => Some(line),
+                None => {
+                    writeln!(prompter.output())?;
+                    return Ok(());
+                }
+            },
+        };
+        if let Some(line) = line {
+            engine.step(line.trim(), &mut prompter.output())?;
+        }
+        for h in hooks.iter_mut() {
+            h.after_output(engine, prompter.output())?;
+        }
+    }
+}
+
+/// Run a game based on the given [`Config`], reading input and
+/// writing output through `prompter`.
+pub fn run_with(
+    config: Config,
+    prompter: &mut dyn Prompter,
+) -> Result<(), Box<dyn error::Error>> {
+    if let Some(command) = config.command {
+        return command.run(&mut prompter.output());
+    }
+
+    let mut profile = config.profile.map(PlayerProfile::load).transpose()?;
+    // Settings from the command line override the player's options.
+    let options = profile.as_ref().map(|p| p.options()).unwrap_or_default();
+    let wrap = config.wrap.or(options.width);
+    // Cover art is cropped to the wrap width, or the terminal width
+    // if text isn't wrapped. Screen readers would only read symbols.
+    let art = (!config.no_art
+        && config.output_profile != Profile::ScreenReader)
+        .then(|| wrap.unwrap_or_else(output::terminal_width));
+
     // If the configured path is a directory, search it for
     // adventures. Otherwise try to load it as a scene file.
-    let mut scene = if config.scene.is_dir() {
+    let mut resume = None;
+    let lenient = config.lenient;
+    let start = |a| {
+        if lenient {
+            Engine::from_adventure_lenient(a)
+        } else {
+            Engine::from_adventure(a)
+        }
+    };
+    let mut engine = if config.scene.is_dir() {
         let mut adventures = adventure::search(&config.scene)?;
-        if adventures.is_empty() {
+        let last = profile.as_ref().and_then(|p| p.last()).cloned();
+        if adventures.is_empty() && last.is_none() {
             return Err(Box::new(Error {
                 msg: "no adventures found".to_string(),
             }) as Box<dyn error::Error>);
-        } else if adventures.len() == 1 {
+        } else if adventures.len() == 1 && last.is_none() {
             let a = adventures.swap_remove(0);
-            writeln!(output, "Starting adventure: {}\n", a)?;
-            a.start()?
+            show_cover(&a, art, prompter.output())?;
+            writeln!(prompter.output(), "Starting adventure: {}\n", a)?;
+            start(a)?
         } else {
-            writeln!(output, "Please select an adventure by number:")?;
-            for (i, a) in adventures.iter().enumerate() {
-                writeln!(output, "{}: {}", i + 1, a)?;
-            }
-            let mut i: Option<usize> = None;
-            let mut line = String::new();
-            while i.is_none() {
-                write!(output, "> ")?;
-                output.flush()?;
-                input.read_line(&mut line)?;
-                i = line
-                    .trim()
-                    .parse()
-                    .ok()
-                    .filter(|i| i > &0 && i <= &adventures.len());
-                if i.is_none() {
-                    line.clear();
-                    writeln!(
-                        output,
-                        "Please select a valid number (1 to {})!",
-                        adventures.len()
-                    )?;
+            // Offer to continue the last adventure first, and
+            // preselect the adventure chosen last time.
+            let mut options: Vec<String> =
+                last.iter().map(|l| format!("Continue: {}", l)).collect();
+            options.extend(adventures.iter().map(|a| a.to_string()));
+            let search = config.scene.canonicalize()?;
+            let selected =
+                profile.as_ref().and_then(|p| p.selection(&search));
+            let default = selected
+                .and_then(|s| {
+                    adventures.iter().position(|a| {
+                        a.dir().canonicalize().is_ok_and(|d| d == s)
+                    })
+                })
+                .map(|i| i + last.iter().count());
+            let title = "Please select an adventure by number:";
+            match (last, prompter.select(title, &options, default)?) {
+                (Some(last), 0) => {
+                    let a = adventure::load(&last.path)?;
+                    show_cover(&a, art, prompter.output())?;
+                    resume = Some(last.scene);
+                    start(a)?
+                }
+                (last, i) => {
+                    let a = adventures.swap_remove(i - last.iter().count());
+                    if let Some(p) = &mut profile {
+                        p.set_selection(search, a.dir().canonicalize()?);
+                    }
+                    show_cover(&a, art, prompter.output())?;
+                    start(a)?
                 }
             }
-            adventures.swap_remove(i.unwrap() - 1).start()?
         }
+    } else if lenient {
+        Engine::new(Scene::load_lenient(config.scene)?)
     } else {
-        Scene::load(config.scene)?
+        Engine::new(Scene::load(config.scene)?)
     };
 
-    write!(output, "{}", scene)?;
-    output.flush()?;
-
-    loop {
-        write!(output, "> ")?;
-        output.flush()?;
-
-        let mut line = String::new();
-        if input.read_line(&mut line)? == 0 {
-            writeln!(output)?;
-            break;
+    engine.set_output_profile(config.output_profile);
+    if config.echo {
+        engine.set_decorator(output::Echo);
+    }
+    engine.set_wrap(wrap);
+    for filter in config.filters {
+        engine.add_filter(filter);
+    }
+    engine.set_color(options.color && !config.no_color);
+    if let Some(speed) = config.text_speed {
+        if config.typewriter.is_none() {
+            speed.store(options.speed.unwrap_or(0), Ordering::SeqCst);
         }
-
-        if let Some(a) = scene.get_action(line.trim()) {
-            match a.effect() {
-                Effect::Output(s) => writeln!(output, "{}", s)?,
-                Effect::Change(s) => {
-                    scene = scene.load_next(s)?;
-                    write!(output, "{}", scene)?;
-                    output.flush()?;
+        engine.set_text_speed(speed);
+    }
+    if let Some(seed) = config.seed {
+        engine.set_seed(seed);
+    }
+    if let Some(players) = config.players {
+        engine.set_players(players);
+    }
+    engine.set_debug(config.debug);
+    if let Some(path) = &config.analytics {
+        engine.set_analytics(analytics::FileLog::open(path)?);
+    }
+    if let Some(dir) = config.saves {
+        // Keep saves of each adventure separate, adventures of a
+        // campaign share them so the game can continue after changing
+        // to the next adventure.
+        let name = match (engine.campaign(), engine.adventure()) {
+            (Some(c), _) => c.name(),
+            (None, "") => engine.scene().name(),
+            (None, a) => a,
+        };
+        let sub: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        engine.set_save_store(FsStore::new(dir.join(sub)));
+    }
+    if let Some(p) = profile {
+        engine.set_player_profile(p);
+    }
+    if let Some(scene) = resume {
+        // Without an autosave at least return to the scene.
+        match engine.restore_from(AUTOSAVE) {
+            Ok(notes) => {
+                for note in notes {
+                    writeln!(prompter.output(), "{}", note)?;
                 }
             }
+            Err(_) => {
+                let scene = engine.scene().load_next(&scene)?;
+                engine.goto(scene);
+            }
+        }
+    }
+    let mut hooks = config.hooks;
+    if let Some(timer) = config.idle_timer {
+        hooks.push(Box::new(hooks::IdleTimer(timer)));
+    }
+    play(&mut engine, prompter, &mut hooks)?;
+    if let Some(i) = &config.interrupted {
+        if i.swap(false, Ordering::SeqCst) {
+            engine.confirm_save(prompter)?;
         }
     }
+    engine.finish();
+    let feedback = engine.adventure_dir().filter(|_| config.author_feedback);
+    if let Some(dir) = feedback {
+        analytics::append_unmatched(
+            &dir.join(analytics::UNMATCHED_LOG),
+            engine.unmatched(),
+        )?;
+    }
+
+    // Remember where the player stopped, so they can continue.
+    if let Some(dir) = engine.adventure_dir().map(|d| d.to_path_buf()) {
+        engine.save_as(AUTOSAVE)?;
+        let last = LastPlayed {
+            path: dir.canonicalize()?,
+            name: engine.adventure().to_string(),
+            scene: engine.scene().name().to_string(),
+        };
+        let profile = engine.player_profile_mut();
+        profile.set_last(last);
+        profile.save()?;
+    }
 
     Ok(())
 }
@@ -129,6 +520,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture::TempAdventure;
 
     #[test]
     fn kitten() {
@@ -136,7 +528,10 @@ mod tests {
             [env!("CARGO_MANIFEST_DIR"), "resources", "kitten.scene"]
                 .iter()
                 .collect();
-        let config = Config { scene: path };
+        let config = Config {
+            scene: path,
+            ..Default::default()
+        };
 
         let input = b"meow\nhug\npet";
         let mut slice = &input[..];
@@ -158,4 +553,297 @@ mod tests {
                 .collect::<Vec<&str>>()
         );
     }
+
+    #[test]
+    fn screen_reader() {
+        let path: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources", "kitten.scene"]
+                .iter()
+                .collect();
+        let config = Config {
+            scene: path,
+            output_profile: Profile::ScreenReader,
+            ..Default::default()
+        };
+
+        let input = b"hug";
+        let mut slice = &input[..];
+        let mut output = Vec::new();
+
+        run(config, &mut slice, &mut output).unwrap();
+        assert_eq!(
+            vec![
+                "There's a little kitten in front of you!",
+                "Command: New scene: cuddle cat",
+                "*purr*",
+                "There's a kitten purring in your arms!",
+                "Command: ",
+            ],
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn scripted_prompter() {
+        let config = Config {
+            scene: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("resources")
+                .join("kitten.scene"),
+            ..Default::default()
+        };
+        let mut prompter = prompt::Scripted::new(&["hug", "pet"]);
+        run_with(config, &mut prompter).unwrap();
+        assert_eq!(prompter.prompts, vec!["> ", "> ", "> "]);
+        assert_eq!(
+            String::from_utf8(prompter.output).unwrap(),
+            "There's a little kitten in front of you!\n*purr*\n\
+             There's a kitten purring in your arms!\n*purr, purr*\n\n"
+        );
+    }
+
+    #[test]
+    fn lifecycle_hooks() {
+        #[derive(Debug)]
+        struct Marks;
+
+        impl Hooks for Marks {
+            fn before_prompt(
+                &mut self,
+                engine: &Engine,
+                output: &mut dyn Write,
+            ) -> Result<(), Box<dyn error::Error>> {
+                write!(output, "[{}]", engine.scene().name())?;
+                Ok(())
+            }
+
+            fn after_output(
+                &mut self,
+                _engine: &Engine,
+                output: &mut dyn Write,
+            ) -> Result<(), Box<dyn error::Error>> {
+                writeln!(output, "--")?;
+                Ok(())
+            }
+        }
+
+        let config = Config {
+            scene: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("resources")
+                .join("kitten.scene"),
+            hooks: vec![Box::new(Marks)],
+            ..Default::default()
+        };
+        let mut prompter = prompt::Scripted::new(&["hug"]);
+        run_with(config, &mut prompter).unwrap();
+        assert_eq!(
+            String::from_utf8(prompter.output).unwrap(),
+            "There's a little kitten in front of you!\n--\n[kitten]*purr*\n\
+             There's a kitten purring in your arms!\n--\n[cuddle_cat]\n"
+        );
+    }
+
+    #[test]
+    fn idle_timeout() {
+        /// Times out once before each answer.
+        struct Sleepy(prompt::Scripted, bool);
+
+        impl Prompter for Sleepy {
+            fn output(&mut self) -> &mut dyn Write {
+                self.0.output()
+            }
+
+            fn read_line(
+                &mut self,
+                prompt: &str,
+            ) -> Result<Option<String>, Box<dyn error::Error>> {
+                self.1 = !self.1;
+                match self.1 {
+                    true => Err(io::Error::from(io::ErrorKind::TimedOut))?,
+                    false => self.0.read_line(prompt),
+                }
+            }
+        }
+
+        let fixture = TempAdventure::new(
+            "idle",
+            &[("nap.scene", "@idle: 5 -> print Zzz.\nA nap.\n")],
+        );
+        let config = Config {
+            scene: fixture.path("nap.scene"),
+            ..Default::default()
+        };
+        let mut prompter = Sleepy(prompt::Scripted::new(&["wake"]), false);
+        let res = run_with(config, &mut prompter);
+        res.unwrap();
+        assert_eq!(
+            String::from_utf8(prompter.0.output).unwrap(),
+            "A nap.\n\nZzz.\n\nZzz.\n\n"
+        );
+    }
+
+    #[test]
+    fn continue_last() {
+        let tmp = TempAdventure::new("continue", &[]);
+        let empty = tmp.path("empty");
+        std::fs::create_dir(&empty).unwrap();
+        let config = || Config {
+            profile: Some(tmp.path("profile.yaml")),
+            saves: Some(tmp.path("saves")),
+            ..Default::default()
+        };
+
+        let mut first = Vec::new();
+        let res = run(
+            Config {
+                scene: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("resources"),
+                ..config()
+            },
+            &mut &b"hug"[..],
+            &mut first,
+        );
+        let mut second = Vec::new();
+        let res2 = run(
+            Config {
+                scene: empty.clone(),
+                ..config()
+            },
+            &mut &b"2\n1\npet"[..],
+            &mut second,
+        );
+        res.unwrap();
+        res2.unwrap();
+        assert_eq!(
+            String::from_utf8(second).unwrap(),
+            "Please select an adventure by number:\n\
+             1: Continue: A cuddly kitten (cuddle_cat)\n\
+             > Please select a valid number (1 to 1)!\n\
+             > *purr*\nThere's a kitten purring in your arms!\n\
+             > *purr, purr*\n> \n"
+        );
+    }
+
+    #[test]
+    fn remember_selection() {
+        let tmp = TempAdventure::new(
+            "remember",
+            &[
+                (
+                    "adventures/attic/about.yaml",
+                    "name: The attic\nauthor: Fiona\n",
+                ),
+                ("adventures/attic/start.scene", "A attic.\n"),
+                (
+                    "adventures/cellar/about.yaml",
+                    "name: The cellar\nauthor: Fiona\n",
+                ),
+                ("adventures/cellar/start.scene", "A cellar.\n"),
+            ],
+        );
+        let config = || Config {
+            scene: tmp.path("adventures"),
+            profile: Some(tmp.path("profile.yaml")),
+            ..Default::default()
+        };
+
+        let mut first = prompt::Scripted::new(&["\"The cellar\" by Fiona"]);
+        let res = run_with(config(), &mut first);
+        let mut second = Vec::new();
+        let res2 = run(config(), &mut &b"\n"[..], &mut second);
+        res.unwrap();
+        res2.unwrap();
+        let second = String::from_utf8(second).unwrap();
+        assert!(
+            second.contains(
+                ": \"The cellar\" by Fiona (default, press Enter)\n"
+            ),
+            "{}",
+            second
+        );
+        assert!(second.ends_with("> A cellar.\n> \n"), "{}", second);
+    }
+
+    #[test]
+    fn no_color() {
+        let fixture = TempAdventure::new(
+            "no-color",
+            &[
+                (
+                    "about.yaml",
+                    "name: Den\nauthor: Fiona\nstyles:\n  danger: red\n",
+                ),
+                ("start.scene", "A {danger}dragon{/danger} sleeps.\n"),
+            ],
+        );
+        let config = |no_color| Config {
+            scene: fixture.dir().to_path_buf(),
+            no_color,
+            ..Default::default()
+        };
+
+        let mut color = Vec::new();
+        run(config(false), &mut &b""[..], &mut color).unwrap();
+        let mut plain = Vec::new();
+        run(config(true), &mut &b""[..], &mut plain).unwrap();
+        let color = String::from_utf8(color).unwrap();
+        let plain = String::from_utf8(plain).unwrap();
+        assert!(color.contains("A \x1b[0;31mdragon\x1b[0m"), "{}", color);
+        assert!(plain.contains("A dragon sleeps."), "{}", plain);
+    }
+
+    #[test]
+    fn cover_art() {
+        let fixture = TempAdventure::new(
+            "cover",
+            &[
+                (
+                    "about.yaml",
+                    "name: Hi\nauthor: Fiona\ncover: cover.txt\n\
+                     banner: true\n",
+                ),
+                ("start.scene", "A kitten.\n"),
+                ("cover.txt", " /\\_/\\\n( o.o ) meow"),
+            ],
+        );
+        let dir = fixture.dir();
+        let config = |no_art| Config {
+            scene: dir.to_path_buf(),
+            wrap: Some(9),
+            no_art,
+            ..Default::default()
+        };
+
+        let mut art = Vec::new();
+        let res = run(config(false), &mut &b""[..], &mut art);
+        let mut no_art = Vec::new();
+        let res2 = run(config(true), &mut &b""[..], &mut no_art);
+        // The game starts without missing cover art.
+        std::fs::remove_file(fixture.path("cover.txt")).unwrap();
+        let mut missing = Vec::new();
+        let res3 = run(config(false), &mut &b""[..], &mut missing);
+        res.unwrap();
+        res2.unwrap();
+        res3.unwrap();
+        let art = String::from_utf8(art).unwrap();
+        let banner = output::banner::render("Hi", 9).unwrap();
+        assert!(
+            art.starts_with(&format!(
+                "{}\n /\\_/\\\n( o.o ) m\n\nStarting adventure:",
+                banner
+            )),
+            "{}",
+            art
+        );
+        let no_art = String::from_utf8(no_art).unwrap();
+        assert!(no_art.starts_with("Starting adventure:"), "{}", no_art);
+        let missing = String::from_utf8(missing).unwrap();
+        assert!(
+            missing.starts_with(&format!("{}\nStarting adventure:", banner)),
+            "{}",
+            missing
+        );
+    }
 }
@@ -1,17 +1,117 @@
 use clap::Parser;
-use std::io;
+use std::env;
+use std::io::{self, BufReader, IsTerminal};
 use std::process;
 
+use rustventure::bundle::Launcher;
+use rustventure::input::ThreadedInput;
+#[cfg(feature = "speak")]
+use rustventure::output::speech::Speaker;
+use rustventure::output::{Profile, Typewriter};
+use rustventure::profile::PlayerProfile;
+use rustventure::save::FsStore;
 use rustventure::Config;
 
+mod console;
+
+/// Send log events to stderr or the configured log file, depending
+/// on the verbosity level. Without `-v` or `--log` nothing is logged.
+#[cfg(feature = "tracing")]
+fn init_logging(config: &Config) -> io::Result<()> {
+    use std::fs::File;
+    use std::sync::Mutex;
+    use tracing::Level;
+
+    let level = match config.verbose {
+        0 if config.log.is_none() => return Ok(()),
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let builder = tracing_subscriber::fmt().with_max_level(level);
+    match &config.log {
+        Some(path) => builder
+            .with_ansi(false)
+            .with_writer(Mutex::new(File::create(path)?))
+            .init(),
+        None => builder.with_writer(io::stderr).init(),
+    }
+    Ok(())
+}
+
 fn main() {
-    let config = Config::parse();
+    // Bundled games start their adventure when run without arguments.
+    let mut args: Vec<_> = env::args_os().collect();
+    if args.len() == 1 {
+        match Launcher::find() {
+            Ok(Some(launcher)) => args.extend(launcher.args()),
+            Ok(None) => (),
+            Err(err) => {
+                eprintln!("Warning: could not load the launcher: {}", err)
+            }
+        }
+    }
+    let mut config = Config::parse_from(args);
+    #[cfg(feature = "tracing")]
+    if let Err(err) = init_logging(&config) {
+        eprintln!("Error: could not open log: {}", err);
+        process::exit(1);
+    }
+    if config.profile.is_none() {
+        config.profile = PlayerProfile::default_path();
+    }
+    if config.saves.is_none() {
+        config.saves = FsStore::default_dir();
+    }
+    if !console::setup() {
+        eprintln!("Warning: the console does not support colors");
+        // Escape sequences would show up as garbage.
+        config.no_color = true;
+        if config.output_profile == Profile::HighContrast {
+            config.output_profile = Profile::Plain;
+        }
+    }
+    // Cover art would only clutter redirected output.
+    config.no_art |= !io::stdout().is_terminal();
+
+    // Ctrl-C interrupts reading input, so the game can offer to save
+    // and quit cleanly.
+    let mut input = ThreadedInput::spawn(BufReader::new(io::stdin()));
+    // Enter skips the typewriter animation, without counting as a
+    // command.
+    input.set_skip_lines(
+        config.typewriter.is_some() || io::stdin().is_terminal(),
+    );
+    let interrupter = input.interrupter();
+    if let Err(err) = ctrlc::set_handler(move || interrupter.interrupt()) {
+        eprintln!("Warning: could not handle Ctrl-C: {}", err);
+    }
+    config.interrupted = Some(input.interrupted());
+    // Scenes can react to the player doing nothing for a while.
+    if io::stdin().is_terminal() {
+        config.idle_timer = Some(input.timeout());
+    }
 
-    let stdin = io::stdin();
-    let mut input = stdin.lock();
-    let mut stdout = io::stdout();
+    // The typewriter writes text at once with speed 0, the player can
+    // change the speed with the `options` command.
+    let cps = config.typewriter.unwrap_or(0);
+    let mut output = Typewriter::new(io::stdout(), cps, input.pending());
+    config.text_speed = Some(output.speed());
+    #[cfg(feature = "speak")]
+    let mut output: Box<dyn io::Write> = match &config.speak {
+        Some(command) => match Speaker::new(&mut output, command) {
+            Ok(speaker) => Box::new(speaker),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        },
+        None => Box::new(&mut output),
+    };
+    let res = rustventure::run(config, &mut input, &mut output);
 
-    if let Err(err) = rustventure::run(config, &mut input, &mut stdout) {
+    if let Err(err) = res {
         eprintln!("Error: {}", err);
         process::exit(1);
     }
@@ -0,0 +1,275 @@
+//! Matching player input to actions. The [`Engine`](crate::engine::Engine)
+//! uses a [`RegexMatcher`] by default, other matchers can be set with
+//! [`Engine::set_matcher`](crate::engine::Engine::set_matcher).
+
+use std::borrow::Cow;
+use std::fmt;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::scene::Action;
+
+/// Decides which actions the player's input triggers.
+///
+/// # Examples
+///
+/// A matcher that ignores case:
+///
+/// ```
+/// use rustventure::matcher::Matcher;
+/// use rustventure::scene::Action;
+///
+/// #[derive(Debug)]
+/// struct IgnoreCase;
+///
+/// impl Matcher for IgnoreCase {
+///     fn matches(&self, action: &Action, input: &str) -> bool {
+///         action.expression().is_match(&input.to_lowercase())
+///     }
+/// }
+///
+/// let a = Action::new("!kw:meow -> print Meow!").unwrap();
+/// assert!(IgnoreCase.matches(&a, "MEOW"));
+/// ```
+pub trait Matcher: fmt::Debug {
+    /// Whether `input` triggers `action`. If several actions match,
+    /// the first one in the scene whose condition is met wins.
+    fn matches(&self, action: &Action, input: &str) -> bool;
+
+    /// Whether `input` is close enough to trigger `action`, used only
+    /// if no action [`matches`](Matcher::matches) exactly. The
+    /// default is to accept no approximate matches.
+    fn matches_approximately(&self, _action: &Action, _input: &str) -> bool {
+        false
+    }
+
+    /// Suggest what the player may have meant if `input` matched none
+    /// of the `actions`. The default is to make no suggestions.
+    fn suggest<'a>(
+        &self,
+        _actions: &'a [Action],
+        _input: &str,
+    ) -> Option<&'a str> {
+        None
+    }
+}
+
+/// Default maximum edit distance for suggestions.
+pub const SUGGEST_DISTANCE: usize = 2;
+
+/// Match input against the regular expressions of actions, which
+/// for keyword actions means exact matches. If nothing matches the
+/// closest keyword is suggested, if it is within the configured edit
+/// distance.
+///
+/// Optionally keyword actions tolerate typos: they also accept input
+/// within the given edit distance, if no action matches exactly.
+///
+/// Input is normalized to Unicode normalization form C before
+/// matching (like the patterns, when the scene is loaded), so
+/// e.g. "café" matches regardless of whether the "é" was entered as
+/// one character or with a combining accent.
+#[derive(Debug)]
+pub struct RegexMatcher {
+    suggest_distance: usize,
+    tolerance: usize,
+    case_fold: bool,
+}
+
+impl Default for RegexMatcher {
+    fn default() -> Self {
+        RegexMatcher {
+            suggest_distance: SUGGEST_DISTANCE,
+            tolerance: 0,
+            case_fold: false,
+        }
+    }
+}
+
+impl RegexMatcher {
+    /// Ignore case in any script: input is converted to lower case,
+    /// and keywords are compared in lower case. Regular expressions
+    /// must be written in lower case to match.
+    pub fn case_fold(mut self, case_fold: bool) -> Self {
+        self.case_fold = case_fold;
+        self
+    }
+
+    fn normalize<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let input = if is_nfc(input) {
+            Cow::Borrowed(input)
+        } else {
+            Cow::Owned(input.nfc().collect())
+        };
+        if self.case_fold {
+            Cow::Owned(input.to_lowercase())
+        } else {
+            input
+        }
+    }
+
+    /// Keyword of `action` as compared to normalized input.
+    fn keyword<'a>(&self, action: &'a Action) -> Option<Cow<'a, str>> {
+        let k = action.keyword()?;
+        Some(if self.case_fold {
+            Cow::Owned(k.to_lowercase())
+        } else {
+            Cow::Borrowed(k)
+        })
+    }
+
+    /// Set the edit distance keyword actions tolerate, 0 (the
+    /// default) requires exact matches.
+    pub fn tolerance(mut self, distance: usize) -> Self {
+        self.tolerance = distance;
+        self
+    }
+
+    /// Set the maximum edit distance for suggestions, 0 disables
+    /// them.
+    pub fn suggest_distance(mut self, distance: usize) -> Self {
+        self.suggest_distance = distance;
+        self
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn matches(&self, action: &Action, input: &str) -> bool {
+        let input = self.normalize(input);
+        match self.keyword(action) {
+            Some(k) if self.case_fold => k == input,
+            _ => action.expression().is_match(&input),
+        }
+    }
+
+    fn matches_approximately(&self, action: &Action, input: &str) -> bool {
+        let input = self.normalize(input);
+        self.keyword(action)
+            .is_some_and(|k| levenshtein(&k, &input) <= self.tolerance)
+    }
+
+    fn suggest<'a>(
+        &self,
+        actions: &'a [Action],
+        input: &str,
+    ) -> Option<&'a str> {
+        let input = self.normalize(input);
+        actions
+            .iter()
+            .filter_map(|a| a.keyword())
+            .map(|k| (levenshtein(&self.normalize(k), &input), k))
+            .filter(|(d, _)| *d > 0 && *d <= self.suggest_distance)
+            .min_by_key(|(d, _)| *d)
+            .map(|(_, k)| k)
+    }
+}
+
+/// Edit distance between `a` and `b`: the number of characters that
+/// have to be inserted, deleted, or replaced to turn one into the
+/// other. Characters are counted as the user perceives them
+/// (extended grapheme clusters), so e.g. a letter with a combining
+/// accent is one character.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<&str> = b.graphemes(true).collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.graphemes(true).enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replace = prev[j] + usize::from(ca != *cb);
+            row.push(replace.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_matcher() {
+        let kw = Action::new("!kw:meow -> print Meow!").unwrap();
+        let re = Action::new("!regex:^(hug|cuddle)$ -> scene arms").unwrap();
+        let m = RegexMatcher::default();
+        assert!(m.matches(&kw, "meow"));
+        assert!(!m.matches(&kw, "meow!"));
+        assert!(m.matches(&re, "cuddle"));
+        assert!(!m.matches(&re, "pet"));
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(levenshtein("meow", "meow"), 0);
+        assert_eq!(levenshtein("meow", "mwow"), 1);
+        assert_eq!(levenshtein("meow", "mew"), 1);
+        assert_eq!(levenshtein("meow", "meoww"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "pet"), 3);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+        // "e" with combining acute accent
+        assert_eq!(levenshtein("cafe\u{301}", "cafe"), 1);
+        // Devanagari syllables consisting of several code points
+        assert_eq!(levenshtein("नमस्ते", "नमस्"), 1);
+    }
+
+    #[test]
+    fn unicode_normalization() {
+        let m = RegexMatcher::default();
+        let cafe = Action::new("!kw:café -> print Coffee!").unwrap();
+        assert!(m.matches(&cafe, "café"));
+        assert!(m.matches(&cafe, "cafe\u{301}"));
+        // Decomposed keyword, precomposed input
+        let greek = Action::new("!kw:γα\u{301}τα -> print Νιάου!").unwrap();
+        assert!(m.matches(&greek, "γάτα"));
+        let hangul = Action::new("!regex:^고양이$ -> print 야옹").unwrap();
+        // Hangul syllables as conjoining jamo
+        let jamo: String = "고양이".nfd().collect();
+        assert_ne!(jamo, "고양이");
+        assert!(m.matches(&hangul, &jamo));
+    }
+
+    #[test]
+    fn case_folding() {
+        let greek = Action::new("!kw:Γάτα -> print Νιάου!").unwrap();
+        let re = Action::new("!regex:^(кошка|кот)$ -> print Мяу!").unwrap();
+        let m = RegexMatcher::default();
+        assert!(!m.matches(&greek, "γάτα"));
+        assert!(!m.matches(&re, "КОТ"));
+        let m = RegexMatcher::default().case_fold(true);
+        assert!(m.matches(&greek, "γάτα"));
+        assert!(m.matches(&greek, "ΓΆΤΑ"));
+        assert!(m.matches(&re, "КОТ"));
+        assert!(!m.matches(&greek, "γάτ"));
+        assert_eq!(m.suggest(&[greek], "ΓΑΤΑ"), Some("Γάτα"));
+    }
+
+    #[test]
+    fn suggestions() {
+        let actions = [
+            Action::new("!kw:meow -> print Meow!").unwrap(),
+            Action::new("!kw:pet -> print *purr*").unwrap(),
+            Action::new("!regex:^(hug|cuddle)$ -> scene arms").unwrap(),
+        ];
+        let m = RegexMatcher::default();
+        assert_eq!(m.suggest(&actions, "mow"), Some("meow"));
+        assert_eq!(m.suggest(&actions, "pat"), Some("pet"));
+        assert_eq!(m.suggest(&actions, "hugg"), None);
+        assert_eq!(m.suggest(&actions, "bark"), None);
+        let m = RegexMatcher::default().suggest_distance(0);
+        assert_eq!(m.suggest(&actions, "mow"), None);
+    }
+
+    #[test]
+    fn tolerance() {
+        let kw = Action::new("!kw:meow -> print Meow!").unwrap();
+        let re = Action::new("!regex:^(hug|cuddle)$ -> scene arms").unwrap();
+        let m = RegexMatcher::default();
+        assert!(!m.matches_approximately(&kw, "mwow"));
+        let m = RegexMatcher::default().tolerance(1);
+        assert!(m.matches_approximately(&kw, "mwow"));
+        assert!(m.matches_approximately(&kw, "meoww"));
+        assert!(!m.matches_approximately(&kw, "mwoww"));
+        assert!(!m.matches_approximately(&re, "hugg"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Selection menus for long lists, like all adventures found in a
+//! directory. Options are shown in pages, typing text filters them,
+//! and Enter selects the first option shown.
+
+use std::io::{self, Write};
+
+/// Number of options per page, unless set otherwise.
+pub const PAGE_SIZE: usize = 10;
+
+/// What to do after input to a [`Menu`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Choice {
+    /// The option with this index was selected
+    Selected(usize),
+    /// The options shown changed, show the menu again
+    Show,
+    /// Show this message and ask again
+    Message(String),
+}
+
+#[derive(Debug)]
+pub struct Menu<'a> {
+    options: &'a [String],
+    page_size: usize,
+    /// Current page, starting at 0
+    page: usize,
+    filter: String,
+    /// Indices of the options matching the filter
+    matches: Vec<usize>,
+    /// Index of the option Enter selects while there is no filter
+    default: Option<usize>,
+}
+
+impl<'a> Menu<'a> {
+    pub fn new(options: &'a [String]) -> Self {
+        Menu {
+            options,
+            page_size: PAGE_SIZE,
+            page: 0,
+            filter: String::new(),
+            matches: (0..options.len()).collect(),
+            default: None,
+        }
+    }
+
+    /// Make the option with `index` the default, and start on its
+    /// page. Enter selects it unless the options are filtered.
+    pub fn preselect(mut self, index: usize) -> Self {
+        if index < self.options.len() {
+            self.default = Some(index);
+            self.page = index / self.page_size;
+        }
+        self
+    }
+
+    pub fn page_size(mut self, size: usize) -> Self {
+        self.page_size = size.max(1);
+        self.page = self.default.map_or(0, |i| i / self.page_size);
+        self
+    }
+
+    fn pages(&self) -> usize {
+        self.matches.len().div_ceil(self.page_size).max(1)
+    }
+
+    /// Options on the current page: their number in the menu, index,
+    /// and text.
+    pub fn visible(&self) -> impl Iterator<Item = (usize, usize, &str)> {
+        self.matches
+            .iter()
+            .enumerate()
+            .skip(self.page * self.page_size)
+            .take(self.page_size)
+            .map(|(n, &i)| (n + 1, i, self.options[i].as_str()))
+    }
+
+    /// Handle a line of input: a number selects the option, `>` and
+    /// `<` turn the page, `*` clears the filter, an empty line
+    /// selects the default or the first option shown, and any other
+    /// text filters the options.
+    pub fn input(&mut self, line: &str) -> Choice {
+        let line = line.trim();
+        match line {
+            "" => match (self.default, self.visible().next()) {
+                (Some(d), _) if self.filter.is_empty() => Choice::Selected(d),
+                (_, Some((_, i, _))) => Choice::Selected(i),
+                _ => Choice::Message("There is nothing to select.".into()),
+            },
+            ">" => {
+                if self.page + 1 < self.pages() {
+                    self.page += 1;
+                    Choice::Show
+                } else {
+                    Choice::Message("This is the last page.".into())
+                }
+            }
+            "<" => {
+                if self.page > 0 {
+                    self.page -= 1;
+                    Choice::Show
+                } else {
+                    Choice::Message("This is the first page.".into())
+                }
+            }
+            "*" => {
+                self.filter.clear();
+                self.matches = (0..self.options.len()).collect();
+                self.page = 0;
+                Choice::Show
+            }
+            _ => match line.parse::<usize>() {
+                Ok(n) => {
+                    match n.checked_sub(1).and_then(|k| self.matches.get(k)) {
+                        Some(&i) => Choice::Selected(i),
+                        None => Choice::Message(format!(
+                            "Please select a valid number (1 to {})!",
+                            self.matches.len()
+                        )),
+                    }
+                }
+                Err(_) => self.set_filter(line),
+            },
+        }
+    }
+
+    /// Show only options containing `text`, ignoring case. Keeps the
+    /// current filter if nothing matches.
+    fn set_filter(&mut self, text: &str) -> Choice {
+        let lower = text.to_lowercase();
+        let matches: Vec<usize> = (0..self.options.len())
+            .filter(|&i| self.options[i].to_lowercase().contains(&lower))
+            .collect();
+        if matches.is_empty() {
+            return Choice::Message(format!("Nothing matches \"{}\".", text));
+        }
+        self.filter = text.to_string();
+        self.matches = matches;
+        self.page = 0;
+        Choice::Show
+    }
+
+    /// Write the `title` and the options shown, with hints for paging
+    /// and filtering when they are in use.
+    pub fn render(&self, title: &str, w: &mut dyn Write) -> io::Result<()> {
+        writeln!(w, "{}", title)?;
+        for (n, i, text) in self.visible() {
+            if self.filter.is_empty() && self.default == Some(i) {
+                writeln!(w, "{}: {} (default, press Enter)", n, text)?;
+            } else {
+                writeln!(w, "{}: {}", n, text)?;
+            }
+        }
+        if self.pages() > 1 {
+            writeln!(
+                w,
+                "Page {} of {}, enter > or < to turn the page, or text \
+                 to filter.",
+                self.page + 1,
+                self.pages()
+            )?;
+        }
+        if !self.filter.is_empty() {
+            writeln!(
+                w,
+                "Showing matches for \"{}\", enter * to show all.",
+                self.filter
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adventures() -> Vec<String> {
+        (1..=25).map(|i| format!("Adventure {}", i)).collect()
+    }
+
+    fn rendered(menu: &Menu) -> String {
+        let mut out = Vec::new();
+        menu.render("Select:", &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn paging() {
+        let options = adventures();
+        let mut menu = Menu::new(&options);
+        assert_eq!(menu.visible().count(), 10);
+        assert_eq!(
+            menu.input("<"),
+            Choice::Message("This is the first page.".into())
+        );
+        assert_eq!(menu.input(">"), Choice::Show);
+        assert_eq!(menu.input(">"), Choice::Show);
+        assert_eq!(
+            rendered(&menu),
+            "Select:\n\
+             21: Adventure 21\n22: Adventure 22\n23: Adventure 23\n\
+             24: Adventure 24\n25: Adventure 25\n\
+             Page 3 of 3, enter > or < to turn the page, or text to filter.\n"
+        );
+        assert_eq!(
+            menu.input(">"),
+            Choice::Message("This is the last page.".into())
+        );
+        assert_eq!(menu.input(""), Choice::Selected(20));
+        assert_eq!(menu.input("<"), Choice::Show);
+        assert_eq!(menu.input(""), Choice::Selected(10));
+        // Numbers work on any page.
+        assert_eq!(menu.input("3"), Choice::Selected(2));
+        assert_eq!(
+            menu.input("26"),
+            Choice::Message("Please select a valid number (1 to 25)!".into())
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let options = adventures();
+        let mut menu = Menu::new(&options).page_size(2);
+        assert_eq!(menu.input(">"), Choice::Show);
+        assert_eq!(menu.input("URE 2"), Choice::Show);
+        assert_eq!(
+            rendered(&menu),
+            "Select:\n1: Adventure 2\n2: Adventure 20\n\
+             Page 1 of 4, enter > or < to turn the page, or text to filter.\n\
+             Showing matches for \"URE 2\", enter * to show all.\n"
+        );
+        assert_eq!(menu.input("3"), Choice::Selected(20));
+        assert_eq!(
+            menu.input("dragon"),
+            Choice::Message("Nothing matches \"dragon\".".into())
+        );
+        assert_eq!(menu.input("e 25"), Choice::Show);
+        assert_eq!(menu.input(""), Choice::Selected(24));
+        assert_eq!(menu.input("*"), Choice::Show);
+        assert_eq!(menu.input(""), Choice::Selected(0));
+    }
+
+    #[test]
+    fn default() {
+        let options = adventures();
+        let mut menu = Menu::new(&options).preselect(12);
+        assert!(rendered(&menu).contains(
+            "\n12: Adventure 12\n13: Adventure 13 (default, press Enter)\n"
+        ));
+        assert_eq!(menu.input(""), Choice::Selected(12));
+        assert_eq!(menu.input("<"), Choice::Show);
+        assert_eq!(menu.input(""), Choice::Selected(12));
+        assert_eq!(menu.input("ure 2"), Choice::Show);
+        assert_eq!(menu.input(""), Choice::Selected(1));
+        assert_eq!(Menu::new(&options).preselect(30).default, None);
+    }
+
+    #[test]
+    fn small_menu() {
+        let options = vec!["Box".to_string()];
+        let mut menu = Menu::new(&options);
+        assert_eq!(rendered(&menu), "Select:\n1: Box\n");
+        assert_eq!(menu.input(""), Choice::Selected(0));
+        let mut empty = Menu::new(&[]);
+        assert_eq!(
+            empty.input(""),
+            Choice::Message("There is nothing to select.".into())
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Upgrade scene files to the newest scene format, see [`FORMAT`].
+//! `rustventure migrate` rewrites the scene files of an adventure in
+//! place, with `--dry-run` it only prints what would change:
+//!
+//! ```text
+//! --- cave.scene (format 1)
+//! +++ cave.scene (format 2)
+//! @@ line 1 @@
+//! +@format: 2
+//! @@ line 3 @@
+//! -!kw:shout -> say Hello?
+//! +!kw:shout -> print Hello?
+//! ```
+//!
+//! Only lines that need changes are touched, so comments, macros and
+//! formatting stay as they are. Lines format 2 reads differently
+//! either way, like `-> scene attic [needs ladder]` (a scene called
+//! "attic [needs ladder]" in format 1), stop the migration and need
+//! to be changed by hand.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::scene::{Action, Scene, FORMAT};
+use crate::testing::scene_files;
+use crate::warning::Warning;
+
+/// A line of a scene file changed by the migration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// Line in the original file, counting from 1
+    pub line: usize,
+    /// The original line, `None` for inserted lines
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// The migrated text of a scene file and what changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migration {
+    pub path: PathBuf,
+    /// Format of the original file
+    pub from: u32,
+    pub text: String,
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for Migration {
+    /// Show the changes as a diff.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.path.display();
+        writeln!(f, "--- {} (format {})", name, self.from)?;
+        writeln!(f, "+++ {} (format {})", name, FORMAT)?;
+        for c in &self.changes {
+            writeln!(f, "@@ line {} @@", c.line)?;
+            if let Some(old) = &c.old {
+                writeln!(f, "-{}", old)?;
+            }
+            writeln!(f, "+{}", c.new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `line` means the same in format 2 as `old` in format 1.
+fn same_meaning(old: &Action, line: &str) -> bool {
+    Action::parse(line, FORMAT).is_ok_and(|a| {
+        a.effect() == old.effect()
+            && a.condition().is_none()
+            && a.cooldown() == 0
+    })
+}
+
+/// Rewrite an action line from format 1 to format 2, if it needs it:
+/// format 1 prints the rest of the line for every effect word but
+/// `scene`, so they become `print`. Fails for lines format 2 reads
+/// differently either way, like a `[if ...]` at the end, which format
+/// 1 prints as written.
+fn migrate_action(line: &str) -> Result<Option<String>, String> {
+    lazy_static! {
+        static ref EFFECT_RE: Regex = Regex::new(r"^(.*?\s->\s)\w+").unwrap();
+    }
+    let action = line.trim();
+    let Ok(old) = Action::parse(action, 1) else {
+        return Ok(None);
+    };
+    if same_meaning(&old, action) {
+        return Ok(None);
+    }
+    let new = EFFECT_RE.replace(line, "${1}print").into_owned();
+    if same_meaning(&old, new.trim()) {
+        Ok(Some(new))
+    } else {
+        Err(format!(
+            "would change meaning in format {}: {}",
+            FORMAT, action
+        ))
+    }
+}
+
+/// Warn about the action lines of the older format scene in `text`
+/// that the newest format reads differently, and that need to be
+/// changed by hand before migrating.
+pub(crate) fn meaning_changes(path: &Path, text: &str) -> Vec<Warning> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let e = migrate_action(line).err()?;
+            Some(Warning::new(path, format!("line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+/// Migrate the scene in `text` to the newest format. Returns `None`
+/// if the scene already uses it.
+pub fn migrate_scene(
+    path: &Path,
+    text: &str,
+) -> Result<Option<Migration>, Box<dyn Error>> {
+    let scene = Scene::parse(path.to_path_buf(), text)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if scene.format() >= FORMAT {
+        return Ok(None);
+    }
+    let header = format!("@format: {}", FORMAT);
+    let mut changes = vec![Change {
+        line: 1,
+        old: None,
+        new: header.clone(),
+    }];
+    let mut migrated = format!("{}\n", header);
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let migrated_line = migrate_action(content).map_err(|e| {
+            format!("cannot migrate {} line {}: {}", path.display(), i + 1, e)
+        })?;
+        match migrated_line {
+            Some(new) => {
+                migrated.push_str(&new);
+                migrated.push_str(&line[content.len()..]);
+                changes.push(Change {
+                    line: i + 1,
+                    old: Some(content.to_string()),
+                    new,
+                });
+            }
+            None => migrated.push_str(line),
+        }
+    }
+    Scene::parse(path.to_path_buf(), &migrated)
+        .map_err(|e| format!("cannot migrate {}: {}", path.display(), e))?;
+    Ok(Some(Migration {
+        path: path.to_path_buf(),
+        from: scene.format(),
+        text: migrated,
+        changes,
+    }))
+}
+
+/// Migrate the scene files of the adventure in `path`, or the single
+/// scene file `path`. Nothing is written if any file fails to
+/// migrate, or with `dry_run`.
+pub fn migrate(
+    path: &Path,
+    dry_run: bool,
+) -> Result<Vec<Migration>, Box<dyn Error>> {
+    let mut migrations = Vec::new();
+    for file in scene_files(path)? {
+        let text = fs::read_to_string(&file)?;
+        migrations.extend(migrate_scene(&file, &text)?);
+    }
+    if !dry_run {
+        for m in &migrations {
+            fs::write(&m.path, &m.text)?;
+        }
+    }
+    Ok(migrations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn migrate_scenes() {
+        let fixture = TempAdventure::new("migrate", &[]);
+        let dir = fixture.dir();
+        let cave = dir.join("cave.scene");
+        fs::write(
+            &cave,
+            "@tags: dark\nA cave.\n!kw:shout -> say Hello?\r\n\
+             !door:out -> field\n!kw:look -> print Rocks.\n\
+             !kw:quit -> give up and leave\n",
+        )
+        .unwrap();
+        fs::write(dir.join("field.scene"), "@format: 2\nA field.\n").unwrap();
+        let dry = migrate(dir, true).unwrap();
+        let unchanged = fs::read_to_string(&cave).unwrap();
+        let migrated = migrate(dir, false).unwrap();
+        let text = fs::read_to_string(&cave).unwrap();
+        let again = migrate(dir, false).unwrap();
+
+        assert_eq!(dry, migrated);
+        assert!(unchanged.starts_with("@tags"));
+        assert_eq!(
+            text,
+            "@format: 2\n@tags: dark\nA cave.\n\
+             !kw:shout -> print Hello?\r\n\
+             !door:out -> field\n!kw:look -> print Rocks.\n\
+             !kw:quit -> print up and leave\n"
+        );
+        assert_eq!(
+            dry[0].to_string(),
+            format!(
+                "--- {0} (format 1)\n+++ {0} (format 2)\n\
+                 @@ line 1 @@\n+@format: 2\n\
+                 @@ line 3 @@\n-!kw:shout -> say Hello?\n\
+                 +!kw:shout -> print Hello?\n\
+                 @@ line 6 @@\n-!kw:quit -> give up and leave\n\
+                 +!kw:quit -> print up and leave\n",
+                cave.display()
+            )
+        );
+        assert!(again.is_empty());
+
+        // Format 2 would make these conditional.
+        for line in [
+            "!kw:look -> print Nothing [if you dare]",
+            "!kw:up -> scene attic [needs ladder]",
+        ] {
+            let text = format!("A hall.\n{}\n", line);
+            let err = migrate_scene(&cave, &text).unwrap_err().to_string();
+            assert!(err.contains("line 2: would change meaning"), "{}", err);
+            assert_eq!(
+                meaning_changes(&cave, &text)
+                    .iter()
+                    .map(|w| w.message.as_str())
+                    .collect::<Vec<_>>(),
+                [format!(
+                    "line 2: would change meaning in format 2: {}",
+                    line
+                )]
+            );
+        }
+        assert!(
+            meaning_changes(&cave, "A hall.\n!kw:a -> say A\n").is_empty()
+        );
+    }
+}
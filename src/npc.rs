@@ -0,0 +1,149 @@
+//! NPC definitions, loaded from the `npcs.yaml` file of an
+//! adventure. Each NPC has a set of states and starts in the first
+//! one unless `initial` says otherwise. States can be a list, or a
+//! hash listing the states each state may change to:
+//!
+//! ```yaml
+//! guard:
+//!   initial: idle
+//!   states:
+//!     idle: [alerted, asleep]
+//!     alerted: [idle]
+//!     asleep: [alerted]
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Npc {
+    id: String,
+    initial: String,
+    states: Vec<String>,
+    /// Allowed state changes, any change is allowed if `None`
+    transitions: Option<BTreeMap<String, Vec<String>>>,
+}
+
+fn strings(value: &Yaml) -> Vec<String> {
+    match value {
+        Yaml::Array(a) => a
+            .iter()
+            .filter_map(|s| s.as_str().map(|s| s.to_string()))
+            .collect(),
+        Yaml::String(s) => vec![s.clone()],
+        _ => Vec::new(),
+    }
+}
+
+impl Npc {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn initial(&self) -> &str {
+        &self.initial
+    }
+
+    pub fn states(&self) -> &[String] {
+        &self.states
+    }
+
+    /// Check if the NPC may change from state `from` to `to`.
+    pub fn can_change(&self, from: &str, to: &str) -> bool {
+        self.states.iter().any(|s| s == to)
+            && self.transitions.as_ref().is_none_or(|t| {
+                t.get(from).is_some_and(|next| next.iter().any(|s| s == to))
+            })
+    }
+
+    fn from_yaml(id: &str, def: &Yaml) -> Result<Npc, Box<dyn Error>> {
+        let (states, transitions) = match &def["states"] {
+            Yaml::Hash(h) => {
+                let mut states = Vec::new();
+                let mut transitions = BTreeMap::new();
+                for (k, v) in h {
+                    let k = k.as_str().ok_or("NPC states must be strings")?;
+                    states.push(k.to_string());
+                    transitions.insert(k.to_string(), strings(v));
+                }
+                (states, Some(transitions))
+            }
+            other => (strings(other), None),
+        };
+        let initial = match def["initial"].as_str() {
+            Some(s) => s.to_string(),
+            None => states
+                .first()
+                .ok_or(format!("NPC {} has no states", id))?
+                .clone(),
+        };
+        if !states.contains(&initial) {
+            return Err(format!(
+                "unknown initial state of NPC {}: {}",
+                id, initial
+            )
+            .into());
+        }
+        Ok(Npc {
+            id: id.to_string(),
+            initial,
+            states,
+            transitions,
+        })
+    }
+}
+
+/// Parse NPC definitions from YAML text.
+pub fn parse(s: &str) -> Result<BTreeMap<String, Npc>, Box<dyn Error>> {
+    let docs = YamlLoader::load_from_str(s)?;
+    let mut npcs = BTreeMap::new();
+    let defs = match docs.first() {
+        Some(Yaml::Hash(h)) => h,
+        Some(_) => return Err("invalid NPC data, must be hash".into()),
+        None => return Ok(npcs),
+    };
+    for (id, def) in defs {
+        let id = id.as_str().ok_or("NPC ids must be strings")?;
+        npcs.insert(id.to_string(), Npc::from_yaml(id, def)?);
+    }
+    Ok(npcs)
+}
+
+/// Load NPC definitions from a file.
+pub fn load(path: &Path) -> Result<BTreeMap<String, Npc>, Box<dyn Error>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_npcs() {
+        let npcs = parse(
+            "guard:\n  initial: asleep\n  states:\n    idle: [alerted]\n    \
+             alerted: [idle]\n    asleep: [alerted]\n\
+             cat:\n  states: [sleepy, playful]\n",
+        )
+        .unwrap();
+        let guard = &npcs["guard"];
+        assert_eq!(guard.initial(), "asleep");
+        assert!(guard.can_change("asleep", "alerted"));
+        assert!(!guard.can_change("alerted", "asleep"));
+        assert!(!guard.can_change("idle", "dancing"));
+        let cat = &npcs["cat"];
+        assert_eq!(cat.initial(), "sleepy");
+        assert!(cat.can_change("playful", "sleepy"));
+        assert!(!cat.can_change("playful", "grumpy"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown initial state of NPC cat: grumpy")]
+    fn invalid_initial() {
+        parse("cat:\n  initial: grumpy\n  states: [sleepy]\n").unwrap();
+    }
+}
@@ -0,0 +1,660 @@
+//! Output helpers for front ends. The game logic writes to any plain
+//! [`Write`], everything in here decides how the text ends up in
+//! front of the player.
+//!
+//! Adventure text passes through a pipeline: the engine applies its
+//! [`TextFilter`]s (by default [`Interpolate`], [`Markup`], and
+//! [`Wrap`]), then writes the result to the writer the front end
+//! passed in, which may be a [`Typewriter`] around the actual sink,
+//! or an [`ssml::Ssml`] sink for voice output. Front ends add
+//! presentation features with their own filters or writers.
+
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::engine::Engine;
+
+pub mod banner;
+pub mod html;
+#[cfg(feature = "speak")]
+pub mod speech;
+pub mod ssml;
+
+/// Output profiles adjust presentation to the needs of the player.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Profile {
+    /// Text as written by the adventure
+    #[default]
+    Plain,
+    /// Emphasize the prompt, show colors as bold text
+    HighContrast,
+    /// Announce scene changes, avoid symbol-only prompts and text
+    /// styles
+    ScreenReader,
+}
+
+impl Profile {
+    /// Write the input prompt.
+    pub fn prompt<W: Write>(
+        &self,
+        w: &mut W,
+        prompt: &str,
+    ) -> io::Result<()> {
+        match self {
+            Profile::Plain => write!(w, "{}", prompt),
+            Profile::HighContrast => write!(w, "\x1b[1m{}\x1b[0m", prompt),
+            Profile::ScreenReader => {
+                if prompt.trim().chars().all(|c| c.is_ascii_punctuation()) {
+                    write!(w, "Command: ")
+                } else {
+                    write!(w, "{}", prompt)
+                }
+            }
+        }
+    }
+
+    /// Announce that the player has entered the scene `name`, if the
+    /// profile wants that.
+    pub fn scene_change<W: Write>(
+        &self,
+        w: &mut W,
+        name: &str,
+    ) -> io::Result<()> {
+        match self {
+            Profile::ScreenReader => {
+                writeln!(w, "New scene: {}", name.replace('_', " "))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Adds announcements to the output of the engine, set with
+/// [`Engine::set_decorator`](crate::engine::Engine::set_decorator).
+/// The default methods write nothing.
+pub trait Decorator: fmt::Debug {
+    /// Called before the response to `input`, if it triggered an
+    /// action of the scene.
+    fn command(&self, _w: &mut dyn Write, _input: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when the player enters the scene `name`, before its
+    /// description.
+    fn scene_change(
+        &self,
+        _w: &mut dyn Write,
+        _name: &str,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decorator that adds nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Undecorated;
+
+impl Decorator for Undecorated {}
+
+/// Confirms recognized commands like "(You meow.)" and announces
+/// scene changes like "— New location: Clearing —", so players
+/// using screen readers know what happened.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Echo;
+
+impl Decorator for Echo {
+    fn command(&self, w: &mut dyn Write, input: &str) -> io::Result<()> {
+        let input = input.trim().trim_end_matches(['.', '!', '?']);
+        writeln!(w, "(You {}.)", input.to_lowercase())
+    }
+
+    fn scene_change(&self, w: &mut dyn Write, name: &str) -> io::Result<()> {
+        let name = name.replace('_', " ");
+        let mut chars = name.chars();
+        let name: String = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => name,
+        };
+        writeln!(w, "\u{2014} New location: {} \u{2014}", name)
+    }
+}
+
+/// A step of the text pipeline, see
+/// [`Engine::add_filter`](crate::engine::Engine::add_filter).
+///
+/// # Examples
+///
+/// ```
+/// use rustventure::engine::Engine;
+/// use rustventure::output::TextFilter;
+/// use rustventure::scene::Scene;
+///
+/// #[derive(Debug)]
+/// struct Shout;
+///
+/// impl TextFilter for Shout {
+///     fn filter(&self, _engine: &Engine, text: String) -> String {
+///         text.to_uppercase()
+///     }
+/// }
+///
+/// let scene = Scene::parse("room.scene".into(), "A room.").unwrap();
+/// let mut engine = Engine::new(scene);
+/// engine.add_filter(Box::new(Shout));
+/// assert_eq!(engine.render("Meow!"), "MEOW!");
+/// ```
+pub trait TextFilter: fmt::Debug {
+    /// Transform `text`, using settings and state of `engine`.
+    fn filter(&self, engine: &Engine, text: String) -> String;
+}
+
+/// Replace variable references, see [`Engine::interpolate`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Interpolate;
+
+impl TextFilter for Interpolate {
+    fn filter(&self, engine: &Engine, text: String) -> String {
+        engine.interpolate(&text).into_owned()
+    }
+}
+
+/// Apply the text styles of the adventure for the output profile, or
+/// remove them if colors are off.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Markup;
+
+impl TextFilter for Markup {
+    fn filter(&self, engine: &Engine, text: String) -> String {
+        match engine.color() {
+            true => engine.styles().render(&text, engine.output_profile()),
+            false => engine.styles().strip(&text),
+        }
+    }
+}
+
+/// Wrap text to the width set for the engine, if any.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Wrap;
+
+impl TextFilter for Wrap {
+    fn filter(&self, engine: &Engine, text: String) -> String {
+        match engine.wrap() {
+            Some(width) => wrap(&text, width),
+            None => text,
+        }
+    }
+}
+
+/// The filters the engine starts with.
+pub fn default_filters() -> Vec<Box<dyn TextFilter>> {
+    vec![Box::new(Interpolate), Box::new(Markup), Box::new(Wrap)]
+}
+
+/// Named text styles, so adventures can mark up text like
+/// `{danger}Watch out!{/danger}` without hardcoding ANSI escape
+/// codes. Tags that don't name a style are left alone.
+///
+/// # Examples
+///
+/// ```
+/// use rustventure::output::{Profile, Stylesheet};
+/// let mut styles = Stylesheet::default();
+/// styles.add("danger", "bold red").unwrap();
+/// assert_eq!(
+///     styles.render("{danger}Hiss!{/danger}", Profile::Plain),
+///     "\x1b[0;1;31mHiss!\x1b[0m"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stylesheet {
+    /// SGR parameters for each style
+    styles: BTreeMap<String, Vec<u8>>,
+}
+
+const COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl Stylesheet {
+    /// Add a style from a list of attributes separated by spaces or
+    /// commas: `bold`, `dim`, `italic`, `underline`, a color name,
+    /// or a color name prefixed with `bright_` or `on_` (background).
+    pub fn add(
+        &mut self,
+        name: &str,
+        attributes: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut codes = Vec::new();
+        for attr in attributes.split([' ', ',']).filter(|a| !a.is_empty()) {
+            let color = |c: &str| COLORS.iter().position(|n| *n == c);
+            let code = match attr {
+                "bold" => Some(1),
+                "dim" => Some(2),
+                "italic" => Some(3),
+                "underline" => Some(4),
+                _ => {
+                    if let Some(c) = attr.strip_prefix("bright_") {
+                        color(c).map(|c| 90 + c)
+                    } else if let Some(c) = attr.strip_prefix("on_") {
+                        color(c).map(|c| 40 + c)
+                    } else {
+                        color(attr).map(|c| 30 + c)
+                    }
+                }
+            };
+            codes.push(
+                code.ok_or(format!("unknown text attribute: {}", attr))?
+                    as u8,
+            );
+        }
+        self.styles.insert(name.to_string(), codes);
+        Ok(())
+    }
+
+    /// Whether there is a style called `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.styles.contains_key(name)
+    }
+
+    /// Remove style tags from `text`, for players who turned colors
+    /// off.
+    pub fn strip(&self, text: &str) -> String {
+        // Screen readers get no escape codes.
+        self.render(text, Profile::ScreenReader)
+    }
+
+    /// Replace style tags in `text` with escape codes as suitable for
+    /// the output `profile`.
+    pub fn render(&self, text: &str, profile: Profile) -> String {
+        let mut out = String::new();
+        let mut open: Vec<&str> = Vec::new();
+        let mut last = 0;
+        for c in TAG_RE.captures_iter(text) {
+            let name = c.get(2).unwrap().as_str();
+            if !self.styles.contains_key(name) {
+                continue;
+            }
+            let tag = c.get(0).unwrap();
+            out.push_str(&text[last..tag.start()]);
+            last = tag.end();
+            if c[1].is_empty() {
+                open.push(name);
+            } else if let Some(i) = open.iter().rposition(|n| *n == name) {
+                open.remove(i);
+            }
+            out.push_str(&self.escape(&open, profile));
+        }
+        out.push_str(&text[last..]);
+        if !open.is_empty() {
+            out.push_str(&self.escape(&[], profile));
+        }
+        out
+    }
+
+    /// Escape sequence to switch to the combination of `styles`.
+    fn escape(&self, styles: &[&str], profile: Profile) -> String {
+        if profile == Profile::ScreenReader {
+            return String::new();
+        }
+        let mut seq = String::from("\x1b[0");
+        for code in styles.iter().flat_map(|s| &self.styles[*s]) {
+            let code = match (profile, code) {
+                (Profile::HighContrast, 30..=49 | 90..=97) => 1,
+                _ => *code,
+            };
+            seq.push_str(&format!(";{}", code));
+        }
+        seq.push('m');
+        seq
+    }
+}
+
+/// Writes text one character at a time with a fixed delay, like a
+/// typewriter. The speed can be changed while writing through
+/// [`speed`](Typewriter::speed), a speed of 0 turns the animation
+/// off.
+///
+/// The animation is skipped (the rest of the text written at once)
+/// while the `skip` counter is non-zero. [`crate::input::ThreadedInput`]
+/// provides a suitable counter of pending input lines, so pressing
+/// Enter skips ahead.
+pub struct Typewriter<W: Write> {
+    inner: W,
+    /// Characters per second
+    speed: Arc<AtomicU32>,
+    skip: Arc<AtomicUsize>,
+}
+
+impl<W: Write> Typewriter<W> {
+    pub fn new(
+        inner: W,
+        chars_per_second: u32,
+        skip: Arc<AtomicUsize>,
+    ) -> Typewriter<W> {
+        Typewriter {
+            inner,
+            speed: Arc::new(AtomicU32::new(chars_per_second)),
+            skip,
+        }
+    }
+
+    /// Speed in characters per second, for changing it while the
+    /// typewriter is in use.
+    pub fn speed(&self) -> Arc<AtomicU32> {
+        Arc::clone(&self.speed)
+    }
+
+    fn skipping(&self) -> bool {
+        self.skip.load(Ordering::SeqCst) > 0
+    }
+}
+
+impl<W: Write> Write for Typewriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let speed = self.speed.load(Ordering::SeqCst);
+        if speed == 0 {
+            self.inner.write_all(buf)?;
+            return Ok(buf.len());
+        }
+        let delay = Duration::from_secs(1) / speed;
+        for (i, b) in buf.iter().enumerate() {
+            if self.skipping() {
+                self.inner.write_all(&buf[i..])?;
+                break;
+            }
+            self.inner.write_all(&[*b])?;
+            // Only pause after complete UTF-8 characters, the next
+            // byte must not be a continuation byte.
+            if buf.get(i + 1).is_none_or(|n| n & 0xc0 != 0x80) {
+                self.inner.flush()?;
+                thread::sleep(delay);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+lazy_static! {
+    static ref ESCAPE_RE: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    /// Style tags like `{danger}` and `{/danger}`
+    static ref TAG_RE: Regex = Regex::new(r"\{(/?)(\w+)\}").unwrap();
+}
+
+/// Width of `text` in terminal columns. Wide characters (e.g. CJK)
+/// take two columns, combining marks and escape sequences none.
+pub fn display_width(text: &str) -> usize {
+    ESCAPE_RE.split(text).map(|t| t.width()).sum()
+}
+
+/// Crop lines of `text` that are wider than `width` columns, for
+/// text like ASCII art that wrapping would garble. Escape sequences
+/// after the cut are kept, so styles are reset as in the original.
+pub fn crop(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    for line in text.split_inclusive('\n') {
+        let mut col = 0;
+        let mut last = 0;
+        let escapes = ESCAPE_RE
+            .find_iter(line)
+            .map(|m| (m.start(), m.end()))
+            .chain([(line.len(), line.len())]);
+        for (start, end) in escapes {
+            for g in line[last..start].graphemes(true) {
+                if g == "\n" || g == "\r\n" {
+                    out.push_str(g);
+                } else if col + g.width() <= width {
+                    out.push_str(g);
+                    col += g.width();
+                } else {
+                    col = width + 1;
+                }
+            }
+            out.push_str(&line[start..end]);
+            last = end;
+        }
+    }
+    out
+}
+
+/// Width of the terminal in columns as reported by the `COLUMNS`
+/// environment variable, or 80 if it isn't set.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(80)
+}
+
+/// Pieces of a line for wrapping.
+enum Token<'a> {
+    Space(&'a str),
+    Word(&'a str),
+    Escape(&'a str),
+}
+
+/// Split `line` into whitespace, words, and escape sequences. Wide
+/// characters are separate words, because lines may break between
+/// them even without spaces.
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut last = 0;
+    let escapes = ESCAPE_RE
+        .find_iter(line)
+        .map(|m| (m.start(), m.end()))
+        .chain([(line.len(), line.len())]);
+    for (start, end) in escapes {
+        let text = &line[last..start];
+        let mut word: Option<usize> = None;
+        for (i, g) in text.grapheme_indices(true) {
+            let space = g.chars().all(char::is_whitespace);
+            if space || g.width() > 1 {
+                if let Some(w) = word.take() {
+                    tokens.push(Token::Word(&text[w..i]));
+                }
+                tokens.push(if space {
+                    Token::Space(g)
+                } else {
+                    Token::Word(g)
+                });
+            } else if word.is_none() {
+                word = Some(i);
+            }
+        }
+        if let Some(w) = word {
+            tokens.push(Token::Word(&text[w..]));
+        }
+        if start < end {
+            tokens.push(Token::Escape(&line[start..end]));
+        }
+        last = end;
+    }
+    tokens
+}
+
+/// Wrap lines of `text` that are wider than `width` columns, breaking
+/// at whitespace or between wide characters. Words wider than a line
+/// are not split. Right-to-left text stays in logical order, so the
+/// terminal can display each line correctly.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    for line in text.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(l) => (l, "\n"),
+            None => (line, ""),
+        };
+        let mut col = 0;
+        let mut space = "";
+        let mut escapes = String::new();
+        for token in tokenize(line) {
+            match token {
+                // Keep indentation
+                Token::Space(s) if col == 0 && space.is_empty() => {
+                    out.push_str(s);
+                    col += s.width();
+                }
+                Token::Space(s) => space = s,
+                // Escape sequences belong to the following word if
+                // separated from the previous one by whitespace.
+                Token::Escape(e)
+                    if space.is_empty() && escapes.is_empty() =>
+                {
+                    out.push_str(e)
+                }
+                Token::Escape(e) => escapes.push_str(e),
+                Token::Word(w) => {
+                    let w_width = w.width();
+                    if col > 0 && col + space.width() + w_width > width {
+                        out.push('\n');
+                        col = 0;
+                    } else {
+                        out.push_str(space);
+                        col += space.width();
+                    }
+                    space = "";
+                    out.push_str(&escapes);
+                    escapes.clear();
+                    out.push_str(w);
+                    col += w_width;
+                }
+            }
+        }
+        out.push_str(space);
+        out.push_str(&escapes);
+        out.push_str(newline);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn profile_prompt() {
+        let mut out = Vec::new();
+        Profile::Plain.prompt(&mut out, "> ").unwrap();
+        Profile::HighContrast.prompt(&mut out, "> ").unwrap();
+        Profile::ScreenReader.prompt(&mut out, "> ").unwrap();
+        Profile::ScreenReader.prompt(&mut out, "You? ").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "> \x1b[1m> \x1b[0mCommand: You? "
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let mut styles = Stylesheet::default();
+        styles.add("danger", "bold, red").unwrap();
+        styles.add("calm", "on_blue").unwrap();
+        let text = "{danger}Hiss! {calm}Purr{/calm}{/danger} {unknown}";
+        assert_eq!(
+            styles.render(text, Profile::Plain),
+            "\x1b[0;1;31mHiss! \x1b[0;1;31;44mPurr\x1b[0;1;31m\x1b[0m {unknown}"
+        );
+        assert_eq!(
+            styles.render(text, Profile::HighContrast),
+            "\x1b[0;1;1mHiss! \x1b[0;1;1;1mPurr\x1b[0;1;1m\x1b[0m {unknown}"
+        );
+        assert_eq!(
+            styles.render(text, Profile::ScreenReader),
+            "Hiss! Purr {unknown}"
+        );
+        assert_eq!(
+            styles.render("{danger}unclosed", Profile::Plain),
+            "\x1b[0;1;31munclosed\x1b[0m"
+        );
+        assert!(styles.add("bad", "sparkly").is_err());
+    }
+
+    #[test]
+    fn typewriter() {
+        let skip = Arc::new(AtomicUsize::new(0));
+        let mut t = Typewriter::new(Vec::new(), 1000, skip);
+        let start = Instant::now();
+        write!(t, "Meow! 😸").unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(7));
+        assert_eq!(String::from_utf8(t.inner).unwrap(), "Meow! 😸");
+    }
+
+    #[test]
+    fn typewriter_skip() {
+        let skip = Arc::new(AtomicUsize::new(1));
+        let mut t = Typewriter::new(Vec::new(), 1, skip);
+        let start = Instant::now();
+        write!(t, "*purr, purr*").unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(String::from_utf8(t.inner).unwrap(), "*purr, purr*");
+
+        // Speed 0 turns the typewriter off.
+        let skip = Arc::new(AtomicUsize::new(0));
+        let mut t = Typewriter::new(Vec::new(), 1, skip);
+        t.speed().store(0, Ordering::SeqCst);
+        let start = Instant::now();
+        write!(t, "*purr*").unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn width() {
+        assert_eq!(display_width("meow"), 4);
+        assert_eq!(display_width("子猫"), 4);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("\x1b[0;1mmeow\x1b[0m"), 4);
+        assert_eq!(display_width("חתול"), 4);
+    }
+
+    #[test]
+    fn wrap_text() {
+        assert_eq!(
+            wrap("The kitten looks at you curiously.\n", 12),
+            "The kitten\nlooks at you\ncuriously.\n"
+        );
+        // Indentation and short lines are kept.
+        assert_eq!(wrap("  Meow!\n\nPurr.", 12), "  Meow!\n\nPurr.");
+        // Long words are not split.
+        assert_eq!(wrap("a meeeeeeow b", 5), "a\nmeeeeeeow\nb");
+        // Wide characters take two columns and may be broken between.
+        assert_eq!(
+            wrap("小さな子猫がいます。", 8),
+            "小さな子\n猫がいま\nす。"
+        );
+        assert_eq!(wrap("kitten 子猫", 9), "kitten 子\n猫");
+        // Right-to-left text stays in logical order.
+        assert_eq!(wrap("יש חתול קטן לפניך", 10), "יש חתול\nקטן לפניך");
+        // Escape sequences take no space and stay with their word.
+        assert_eq!(
+            wrap("a \x1b[0;1mbold\x1b[0m kitten", 6),
+            "a \x1b[0;1mbold\x1b[0m\nkitten"
+        );
+    }
+
+    #[test]
+    fn crop_text() {
+        assert_eq!(crop(" /\\_/\\\n( o.o )\n", 5), " /\\_/\n( o.o\n");
+        // Wide characters are not split, escapes after the cut stay.
+        assert_eq!(crop("子猫子猫\n", 5), "子猫\n");
+        assert_eq!(
+            crop("\x1b[31m=^.^=\x1b[0m\nmeow", 3),
+            "\x1b[31m=^.\x1b[0m\nmeo"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Large letters made of `#` characters, in the style of figlet's
+//! banner font, for adventure titles.
+
+/// Height of every glyph in lines.
+const HEIGHT: usize = 5;
+
+/// Glyphs of the font. Lowercase letters use the uppercase glyphs.
+const FONT: &[(char, [&str; HEIGHT])] = &[
+    (' ', ["   ", "   ", "   ", "   ", "   "]),
+    ('A', [" ### ", "#   #", "#####", "#   #", "#   #"]),
+    ('B', ["#### ", "#   #", "#### ", "#   #", "#### "]),
+    ('C', [" ####", "#    ", "#    ", "#    ", " ####"]),
+    ('D', ["#### ", "#   #", "#   #", "#   #", "#### "]),
+    ('E', ["#####", "#    ", "#### ", "#    ", "#####"]),
+    ('F', ["#####", "#    ", "#### ", "#    ", "#    "]),
+    ('G', [" ####", "#    ", "#  ##", "#   #", " ####"]),
+    ('H', ["#   #", "#   #", "#####", "#   #", "#   #"]),
+    ('I', ["###", " # ", " # ", " # ", "###"]),
+    ('J', ["  ###", "    #", "    #", "#   #", " ### "]),
+    ('K', ["#   #", "#  # ", "###  ", "#  # ", "#   #"]),
+    ('L', ["#    ", "#    ", "#    ", "#    ", "#####"]),
+    ('M', ["#   #", "## ##", "# # #", "#   #", "#   #"]),
+    ('N', ["#   #", "##  #", "# # #", "#  ##", "#   #"]),
+    ('O', [" ### ", "#   #", "#   #", "#   #", " ### "]),
+    ('P', ["#### ", "#   #", "#### ", "#    ", "#    "]),
+    ('Q', [" ### ", "#   #", "# # #", "#  # ", " ## #"]),
+    ('R', ["#### ", "#   #", "#### ", "#  # ", "#   #"]),
+    ('S', [" ####", "#    ", " ### ", "    #", "#### "]),
+    ('T', ["#####", "  #  ", "  #  ", "  #  ", "  #  "]),
+    ('U', ["#   #", "#   #", "#   #", "#   #", " ### "]),
+    ('V', ["#   #", "#   #", "#   #", " # # ", "  #  "]),
+    ('W', ["#   #", "#   #", "# # #", "## ##", "#   #"]),
+    ('X', ["#   #", " # # ", "  #  ", " # # ", "#   #"]),
+    ('Y', ["#   #", " # # ", "  #  ", "  #  ", "  #  "]),
+    ('Z', ["#####", "   # ", "  #  ", " #   ", "#####"]),
+    ('0', [" ### ", "#  ##", "# # #", "##  #", " ### "]),
+    ('1', [" # ", "## ", " # ", " # ", "###"]),
+    ('2', ["#### ", "    #", " ### ", "#    ", "#####"]),
+    ('3', ["#### ", "    #", " ### ", "    #", "#### "]),
+    ('4', ["#   #", "#   #", "#####", "    #", "    #"]),
+    ('5', ["#####", "#    ", "#### ", "    #", "#### "]),
+    ('6', [" ### ", "#    ", "#### ", "#   #", " ### "]),
+    ('7', ["#####", "    #", "   # ", "  #  ", "  #  "]),
+    ('8', [" ### ", "#   #", " ### ", "#   #", " ### "]),
+    ('9', [" ### ", "#   #", " ####", "    #", " ### "]),
+    ('.', [" ", " ", " ", " ", "#"]),
+    (',', ["  ", "  ", "  ", " #", "# "]),
+    ('!', ["#", "#", "#", " ", "#"]),
+    ('?', [" ### ", "#   #", "  ## ", "     ", "  #  "]),
+    ('\'', ["#", "#", " ", " ", " "]),
+    ('-', ["   ", "   ", "###", "   ", "   "]),
+    (':', [" ", "#", " ", "#", " "]),
+];
+
+fn glyph(c: char) -> Option<&'static [&'static str; HEIGHT]> {
+    let c = c.to_ascii_uppercase();
+    FONT.iter().find(|(g, _)| *g == c).map(|(_, lines)| lines)
+}
+
+/// Width of `word` in large letters, including the column between
+/// letters.
+fn word_width(word: &[&[&str; HEIGHT]]) -> usize {
+    word.iter()
+        .map(|g| g[0].len() + 1)
+        .sum::<usize>()
+        .saturating_sub(1)
+}
+
+/// Render `text` in large letters, breaking lines between words to
+/// fit into `width` columns. Returns `None` if the font lacks a
+/// character of `text`, or a word is wider than `width`.
+pub fn render(text: &str, width: usize) -> Option<String> {
+    let space = glyph(' ')?;
+    let mut lines: Vec<Vec<&[&str; HEIGHT]>> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.chars().map(glyph).collect::<Option<Vec<_>>>()?;
+        if word_width(&word) > width {
+            return None;
+        }
+        match lines.last_mut() {
+            Some(line)
+                if word_width(line) + word_width(&word) + 5 <= width =>
+            {
+                line.push(space);
+                line.extend(word);
+            }
+            _ => lines.push(word),
+        }
+    }
+    let mut out = String::new();
+    for line in lines {
+        for row in 0..HEIGHT {
+            let row: Vec<&str> = line.iter().map(|g| g[row]).collect();
+            out.push_str(row.join(" ").trim_end());
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_text() {
+        assert_eq!(
+            render("Hi!", 20).unwrap(),
+            "#   # ### #\n#   #  #  #\n#####  #  #\n#   #  #\n#   # ### #\n"
+        );
+        // Words that don't fit go on the next line.
+        assert_eq!(render("a b", 9).unwrap().lines().count(), 10);
+        assert_eq!(render("a b", 15).unwrap().lines().count(), 5);
+        assert_eq!(render("kitten", 10), None);
+        assert_eq!(render("Kätzchen", 80), None);
+    }
+
+    #[test]
+    fn glyph_width() {
+        for (c, lines) in FONT {
+            assert!(
+                lines.iter().all(|l| l.len() == lines[0].len()),
+                "uneven glyph: {}",
+                c
+            );
+        }
+    }
+}
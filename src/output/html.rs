@@ -0,0 +1,143 @@
+//! HTML versions of adventure text, e.g. to show a scene on a web
+//! page. Text styles become CSS classes named after the style with a
+//! `style-` prefix, so `{danger}Hiss!{/danger}` turns into
+//! `<span class="style-danger">Hiss!</span>`.
+
+use super::{Stylesheet, ESCAPE_RE, TAG_RE};
+
+/// Colors of the terminal color names, like the VGA palette.
+const COLORS: [&str; 8] = [
+    "#000000", "#aa0000", "#00aa00", "#aa5500", "#0000aa", "#aa00aa",
+    "#00aaaa", "#aaaaaa",
+];
+
+/// Bright variants of [`COLORS`].
+const BRIGHT: [&str; 8] = [
+    "#555555", "#ff5555", "#55ff55", "#ffff55", "#5555ff", "#ff55ff",
+    "#55ffff", "#ffffff",
+];
+
+/// Escape characters that have a meaning in HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// CSS rules for the classes of `styles`.
+pub fn css(styles: &Stylesheet) -> String {
+    let mut css = String::new();
+    for (name, codes) in &styles.styles {
+        let rules: Vec<String> = codes
+            .iter()
+            .map(|code| match *code as usize {
+                1 => "font-weight: bold".to_string(),
+                2 => "opacity: 0.6".to_string(),
+                3 => "font-style: italic".to_string(),
+                4 => "text-decoration: underline".to_string(),
+                c @ 30..=37 => format!("color: {}", COLORS[c - 30]),
+                c @ 40..=47 => format!("background: {}", COLORS[c - 40]),
+                c => format!("color: {}", BRIGHT[c - 90]),
+            })
+            .collect();
+        css.push_str(&format!(
+            ".style-{} {{ {}; }}\n",
+            name,
+            rules.join("; ")
+        ));
+    }
+    css
+}
+
+/// Convert a paragraph to HTML, closing styles left open at its end.
+fn paragraph(text: &str, styles: &Stylesheet) -> String {
+    let mut out = String::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut last = 0;
+    for c in TAG_RE.captures_iter(text) {
+        let name = c.get(2).unwrap().as_str();
+        if !styles.contains(name) {
+            continue;
+        }
+        let tag = c.get(0).unwrap();
+        out.push_str(&text[last..tag.start()]);
+        last = tag.end();
+        if c[1].is_empty() {
+            open.push(name);
+            out.push_str(&format!("<span class=\"style-{}\">", name));
+        } else if let Some(i) = open.iter().rposition(|n| *n == name) {
+            // Spans must nest, so close the ones opened later too,
+            // and open them again.
+            out.push_str(&"</span>".repeat(open.len() - i));
+            open.remove(i);
+            for n in &open[i..] {
+                out.push_str(&format!("<span class=\"style-{}\">", n));
+            }
+        }
+    }
+    out.push_str(&text[last..]);
+    out.push_str(&"</span>".repeat(open.len()));
+    out.replace('\n', "<br>\n")
+}
+
+/// Convert `text` to HTML paragraphs, separated by empty lines in
+/// the text. Tags of `styles` become spans, escape sequences are
+/// removed, and other tags are left alone, like
+/// [`Stylesheet::render`] does.
+pub fn to_html(text: &str, styles: &Stylesheet) -> String {
+    let text = escape(&ESCAPE_RE.replace_all(text, ""));
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>\n", paragraph(p, styles)))
+        .collect()
+}
+
+/// A complete HTML page with `title` and `body`, which is HTML
+/// already, e.g. from [`to_html`]. It looks like a terminal.
+pub fn page(title: &str, body: &str, styles: &Stylesheet) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>\n\
+         body {{ background: #000000; color: #aaaaaa; \
+         font-family: monospace; max-width: 80ch; margin: 2em auto; }}\n\
+         {}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        css(styles),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html() {
+        let mut styles = Stylesheet::default();
+        styles.add("danger", "bold red").unwrap();
+        styles.add("soft", "italic bright_blue on_black").unwrap();
+        assert_eq!(
+            css(&styles),
+            ".style-danger { font-weight: bold; color: #aa0000; }\n\
+             .style-soft { font-style: italic; color: #5555ff; \
+             background: #000000; }\n"
+        );
+        assert_eq!(
+            to_html(
+                "A {danger}big {soft}cat{/danger} & a{/soft} {mouse}.\n\
+                 It\nhisses.\n\n\n{soft}\x1b[1mPurr\x1b[0m\n",
+                &styles
+            ),
+            "<p>A <span class=\"style-danger\">big \
+             <span class=\"style-soft\">cat</span></span>\
+             <span class=\"style-soft\"> &amp; a</span> {mouse}.<br>\n\
+             It<br>\nhisses.</p>\n\
+             <p><span class=\"style-soft\">Purr</span></p>\n"
+        );
+        let page = page("<Cat>", &to_html("Meow!", &styles), &styles);
+        assert!(page.contains("<title>&lt;Cat&gt;</title>"));
+        assert!(page.contains("<body>\n<p>Meow!</p>\n</body>"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Read the output aloud with an external speech synthesizer, like
+//! `espeak` or `say`, for playing without looking at the screen. The
+//! text still goes to the terminal as well.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use super::ESCAPE_RE;
+
+/// Speech synthesizer to use if the player doesn't name one.
+#[cfg(target_os = "macos")]
+pub const DEFAULT_COMMAND: &str = "say";
+/// Speech synthesizer to use if the player doesn't name one.
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_COMMAND: &str = "espeak";
+
+/// Run `command` once for each text received, with the text as its
+/// input. Stops at the first failure, so a missing synthesizer is
+/// reported only once.
+fn speak(command: Vec<String>, texts: mpsc::Receiver<String>) {
+    for text in texts {
+        let res = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .and_then(|mut child| {
+                child.stdin.take().unwrap().write_all(text.as_bytes())?;
+                child.wait()
+            });
+        if let Err(e) = res {
+            eprintln!("Warning: could not run {}: {}", command[0], e);
+            return;
+        }
+    }
+}
+
+/// Writes to `inner` and speaks the text written since the last
+/// flush, the engine flushes at the end of each step. Speech runs in
+/// the background, one text after another. Text without any letters
+/// or digits, like a `> ` prompt, isn't spoken.
+pub struct Speaker<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    sender: Option<Sender<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<W: Write> Speaker<W> {
+    /// Speak with `command`, a program followed by its arguments
+    /// separated by spaces. It gets the text to speak as its input.
+    pub fn new(inner: W, command: &str) -> io::Result<Speaker<W>> {
+        let command: Vec<String> =
+            command.split_whitespace().map(str::to_string).collect();
+        if command.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty speech command",
+            ));
+        }
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || speak(command, receiver));
+        Ok(Speaker {
+            inner,
+            buffer: Vec::new(),
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl<W: Write> Write for Speaker<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.buffer);
+        let text = ESCAPE_RE.replace_all(&text, "");
+        if text.chars().any(char::is_alphanumeric) {
+            if let Some(sender) = &self.sender {
+                // If the synthesizer failed there's nobody listening
+                // any more, that has been reported already.
+                let _ = sender.send(text.into_owned());
+            }
+        }
+        self.buffer.clear();
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Speaker<W> {
+    /// Finish speaking before the game ends.
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+    use std::fs;
+
+    #[cfg(unix)]
+    #[test]
+    fn speak_output() {
+        let fixture = TempAdventure::new("speech", &[]);
+        let path = fixture.path("spoken.txt");
+        let mut output = Vec::new();
+        let mut speaker =
+            Speaker::new(&mut output, &format!("tee -a {}", path.display()))
+                .unwrap();
+        writeln!(speaker, "\x1b[0;1mMeow!\x1b[0m").unwrap();
+        speaker.flush().unwrap();
+        write!(speaker, "> ").unwrap();
+        speaker.flush().unwrap();
+        writeln!(speaker, "*purr*").unwrap();
+        speaker.flush().unwrap();
+        drop(speaker);
+        let spoken = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(output, b"\x1b[0;1mMeow!\x1b[0m\n> *purr*\n");
+        assert_eq!(spoken, "Meow!\n*purr*\n");
+        assert!(Speaker::new(Vec::new(), " ").is_err());
+    }
+}
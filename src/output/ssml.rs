@@ -0,0 +1,146 @@
+//! SSML output for voice assistants and text-to-speech pipelines.
+//! [`Ssml`] is a sink to use instead of the terminal: it turns the
+//! text of each step into one `<speak>` document per line, with a
+//! pause at paragraph breaks and emphasis for text in style tags:
+//!
+//! ```text
+//! <speak>A <emphasis>fierce</emphasis> cat.<break strength="strong"/>It hisses.</speak>
+//! ```
+//!
+//! The style tags must reach the sink, so use the engine with
+//! [`filters`] instead of the default ones.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::io::{self, Write};
+
+use super::{Interpolate, Stylesheet, TextFilter, ESCAPE_RE, TAG_RE};
+
+/// The text filters to use with [`Ssml`]: variables are replaced,
+/// but style tags are left for the sink, and wrapping lines means
+/// nothing for speech.
+pub fn filters() -> Vec<Box<dyn TextFilter>> {
+    vec![Box::new(Interpolate)]
+}
+
+/// Escape characters that have a meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Convert `text` to the content of a `<speak>` element: paragraphs
+/// are separated by breaks and joined into one line, tags of
+/// `styles` become `<emphasis>`, and escape sequences are removed.
+/// Other tags are left alone, like [`Stylesheet::render`] does.
+pub fn to_ssml(text: &str, styles: &Stylesheet) -> String {
+    lazy_static! {
+        static ref PARAGRAPH_RE: Regex = Regex::new(r"\n\s*\n").unwrap();
+    }
+    let text = escape(&ESCAPE_RE.replace_all(text, ""));
+    let mut out = String::new();
+    // All styles sound the same, so only the number of open ones
+    // matters.
+    let mut open = Vec::new();
+    let mut last = 0;
+    for c in TAG_RE.captures_iter(&text) {
+        let name = c.get(2).unwrap().as_str();
+        if !styles.contains(name) {
+            continue;
+        }
+        let tag = c.get(0).unwrap();
+        out.push_str(&text[last..tag.start()]);
+        last = tag.end();
+        if c[1].is_empty() {
+            open.push(name);
+            out.push_str("<emphasis>");
+        } else if let Some(i) = open.iter().rposition(|n| *n == name) {
+            open.remove(i);
+            out.push_str("</emphasis>");
+        }
+    }
+    out.push_str(text[last..].trim_end());
+    out.push_str(&"</emphasis>".repeat(open.len()));
+    PARAGRAPH_RE
+        .split(out.trim_start())
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("<break strength=\"strong\"/>")
+}
+
+/// Writes the text written to it as SSML, see the [module
+/// documentation](self). Text is collected until the next flush, the
+/// engine flushes at the end of each step.
+#[derive(Debug)]
+pub struct Ssml<W: Write> {
+    inner: W,
+    styles: Stylesheet,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Ssml<W> {
+    /// Write SSML to `inner`, with emphasis for the `styles` of the
+    /// adventure.
+    pub fn new(inner: W, styles: Stylesheet) -> Ssml<W> {
+        Ssml {
+            inner,
+            styles,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Ssml<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.buffer);
+        let ssml = to_ssml(&text, &self.styles);
+        self.buffer.clear();
+        if !ssml.is_empty() {
+            writeln!(self.inner, "<speak>{}</speak>", ssml)?;
+        }
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::scene::Scene;
+    use std::path::PathBuf;
+
+    #[test]
+    fn ssml() {
+        let mut styles = Stylesheet::default();
+        styles.add("danger", "bold red").unwrap();
+        let scene = Scene::parse(
+            PathBuf::from("room.scene"),
+            "A {danger}fierce{/danger} cat & a {mouse}.\n\n\
+             It\nhisses.\n\
+             !kw:pet -> print {danger}Ouch!\n",
+        )
+        .unwrap();
+        let mut engine = Engine::new(scene);
+        engine.set_filters(filters());
+        let mut ssml = Ssml::new(Vec::new(), styles);
+        engine.describe(&mut ssml).unwrap();
+        ssml.flush().unwrap();
+        engine.step("pet", &mut ssml).unwrap();
+        write!(ssml, "\x1b[1m> \x1b[0m").unwrap();
+        ssml.flush().unwrap();
+
+        assert_eq!(
+            String::from_utf8(ssml.inner).unwrap(),
+            "<speak>A <emphasis>fierce</emphasis> cat &amp; a {mouse}.\
+             <break strength=\"strong\"/>It hisses.</speak>\n\
+             <speak><emphasis>Ouch!</emphasis></speak>\n\
+             <speak>&gt;</speak>\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Preprocessing of scene files before parsing, currently simple
+//! text macros: `!define NAME text` defines a macro, and `!use NAME`
+//! anywhere in a later line is replaced by its text.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustventure::preprocess::preprocess;
+//! let text = "!define PURR print *purr*\n!kw:pet -> !use PURR\n";
+//! assert_eq!(preprocess(text).unwrap(), "!kw:pet -> print *purr*\n");
+//! ```
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Expand macros in `text`. Errors mention the line number in the
+/// original text.
+pub fn preprocess(text: &str) -> Result<String, Box<dyn Error>> {
+    Ok(preprocess_lines(text)?
+        .into_iter()
+        .map(|(_, l)| l)
+        .collect())
+}
+
+/// Expand macros in `text` like [`preprocess`], returning each line
+/// (with its line break) and its number in the original text.
+pub fn preprocess_lines(
+    text: &str,
+) -> Result<Vec<(usize, String)>, Box<dyn Error>> {
+    lazy_static! {
+        static ref DEFINE_RE: Regex =
+            Regex::new(r"^!define\s+(\w+)(?:\s(.*))?$").unwrap();
+        static ref USE_RE: Regex = Regex::new(r"!use\s+(\w+)").unwrap();
+    }
+    let mut macros: HashMap<String, String> = HashMap::new();
+    let mut out = Vec::new();
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let mut undefined = None;
+        let expanded = USE_RE.replace_all(line, |c: &Captures| {
+            macros.get(&c[1]).cloned().unwrap_or_else(|| {
+                undefined.get_or_insert_with(|| c[1].to_string());
+                String::new()
+            })
+        });
+        if let Some(name) = undefined {
+            return Err(
+                format!("line {}: undefined macro: {}", i + 1, name).into()
+            );
+        }
+
+        let content = expanded.trim_end_matches(['\r', '\n']);
+        if content.starts_with("!define") {
+            let c = DEFINE_RE.captures(content).ok_or(format!(
+                "line {}: invalid macro definition: {}",
+                i + 1,
+                content
+            ))?;
+            let body = c.get(2).map_or("", |m| m.as_str().trim());
+            macros.insert(c[1].to_string(), body.to_string());
+        } else {
+            out.push((i + 1, expanded.into_owned()));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_macros() {
+        let text = "A kitten.\n\
+                    !define MEOW \"Meow!\"\n\
+                    !define GREET print !use MEOW\n\
+                    !kw:hi -> !use GREET\n\
+                    !kw:hello -> !use GREET =^.^=\n";
+        assert_eq!(
+            preprocess(text).unwrap(),
+            "A kitten.\n!kw:hi -> print \"Meow!\"\n\
+             !kw:hello -> print \"Meow!\" =^.^=\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2: undefined macro: PURR")]
+    fn undefined_macro() {
+        preprocess("A kitten.\n!kw:pet -> !use PURR\n").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "line 1: invalid macro definition")]
+    fn invalid_definition() {
+        preprocess("!define\n").unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+//! The player profile stores preferences that apply across
+//! adventures, like input aliases and output options.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PlayerProfile {
+    /// Where to save the profile, if anywhere
+    path: Option<PathBuf>,
+    aliases: BTreeMap<String, String>,
+    last: Option<LastPlayed>,
+    /// Adventure directory the player selected last, by the directory
+    /// searched for adventures
+    selections: BTreeMap<PathBuf, PathBuf>,
+    options: Options,
+}
+
+/// Output settings the player can change during the game with the
+/// `options` command. Command line options take precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// Typewriter speed in characters per second, `None` to show
+    /// text at once
+    pub speed: Option<u32>,
+    /// Whether to show text styles
+    pub color: bool,
+    /// Width to wrap text to, `None` to not wrap
+    pub width: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            speed: None,
+            color: true,
+            width: None,
+        }
+    }
+}
+
+/// The adventure the player played most recently, so they can
+/// continue where they left off.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastPlayed {
+    /// Directory of the adventure
+    pub path: PathBuf,
+    pub name: String,
+    /// Scene the player was in when they stopped
+    pub scene: String,
+}
+
+impl fmt::Display for LastPlayed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.scene)
+    }
+}
+
+impl PlayerProfile {
+    /// Default location of the profile file, in the user's
+    /// configuration directory.
+    pub fn default_path() -> Option<PathBuf> {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|h| PathBuf::from(h).join(".config"))
+            })?;
+        Some(config.join("rustventure").join("profile.yaml"))
+    }
+
+    /// Load the profile from `path`. If the file doesn't exist yet an
+    /// empty profile is returned, and will be created on save.
+    pub fn load(path: PathBuf) -> Result<PlayerProfile, Box<dyn Error>> {
+        let s = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut profile = PlayerProfile {
+            path: Some(path),
+            ..Default::default()
+        };
+        let docs = YamlLoader::load_from_str(&s)?;
+        if let Some(doc) = docs.first() {
+            if let Some(aliases) = doc["aliases"].as_hash() {
+                for (k, v) in aliases {
+                    if let (Some(k), Some(v)) = (k.as_str(), v.as_str()) {
+                        profile.aliases.insert(k.to_string(), v.to_string());
+                    }
+                }
+            }
+            let last = &doc["last"];
+            if let (Some(path), Some(name), Some(scene)) = (
+                last["path"].as_str(),
+                last["name"].as_str(),
+                last["scene"].as_str(),
+            ) {
+                profile.last = Some(LastPlayed {
+                    path: PathBuf::from(path),
+                    name: name.to_string(),
+                    scene: scene.to_string(),
+                });
+            }
+            let options = &doc["options"];
+            profile.options = Options {
+                speed: options["speed"].as_i64().map(|s| s as u32),
+                color: options["color"].as_bool().unwrap_or(true),
+                width: options["width"].as_i64().map(|w| w as usize),
+            };
+            if let Some(selections) = doc["selections"].as_hash() {
+                for (k, v) in selections {
+                    if let (Some(k), Some(v)) = (k.as_str(), v.as_str()) {
+                        profile
+                            .selections
+                            .insert(PathBuf::from(k), PathBuf::from(v));
+                    }
+                }
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Write the profile to its file, if it has one.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let mut aliases = Hash::new();
+        for (k, v) in &self.aliases {
+            aliases.insert(Yaml::String(k.clone()), Yaml::String(v.clone()));
+        }
+        let mut doc = Hash::new();
+        doc.insert(Yaml::String("aliases".to_string()), Yaml::Hash(aliases));
+        if let Some(l) = &self.last {
+            let mut last = Hash::new();
+            for (k, v) in [
+                ("path", l.path.to_string_lossy().into_owned()),
+                ("name", l.name.clone()),
+                ("scene", l.scene.clone()),
+            ] {
+                last.insert(Yaml::String(k.to_string()), Yaml::String(v));
+            }
+            doc.insert(Yaml::String("last".to_string()), Yaml::Hash(last));
+        }
+        if self.options != Options::default() {
+            let mut options = Hash::new();
+            let o = &self.options;
+            for (k, v) in [
+                ("speed", o.speed.map(|s| Yaml::Integer(s.into()))),
+                ("color", Some(Yaml::Boolean(o.color))),
+                ("width", o.width.map(|w| Yaml::Integer(w as i64))),
+            ] {
+                if let Some(v) = v {
+                    options.insert(Yaml::String(k.to_string()), v);
+                }
+            }
+            doc.insert(
+                Yaml::String("options".to_string()),
+                Yaml::Hash(options),
+            );
+        }
+        if !self.selections.is_empty() {
+            let mut selections = Hash::new();
+            for (k, v) in &self.selections {
+                selections.insert(
+                    Yaml::String(k.to_string_lossy().into_owned()),
+                    Yaml::String(v.to_string_lossy().into_owned()),
+                );
+            }
+            doc.insert(
+                Yaml::String("selections".to_string()),
+                Yaml::Hash(selections),
+            );
+        }
+
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&Yaml::Hash(doc))?;
+        out.push('\n');
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, out)?;
+        Ok(())
+    }
+
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    pub fn set_alias(&mut self, name: &str, command: &str) {
+        self.aliases.insert(name.to_string(), command.to_string());
+    }
+
+    pub fn remove_alias(&mut self, name: &str) -> Option<String> {
+        self.aliases.remove(name)
+    }
+
+    /// The adventure played most recently, if any.
+    pub fn last(&self) -> Option<&LastPlayed> {
+        self.last.as_ref()
+    }
+
+    pub fn set_last(&mut self, last: LastPlayed) {
+        self.last = Some(last);
+    }
+
+    /// The adventure directory the player selected last when
+    /// searching `dir`.
+    pub fn selection(&self, dir: &Path) -> Option<&Path> {
+        self.selections.get(dir).map(|p| p.as_path())
+    }
+
+    pub fn set_selection(&mut self, dir: PathBuf, adventure: PathBuf) {
+        self.selections.insert(dir, adventure);
+    }
+
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    /// Replace the first word of `input` if it is an alias.
+    pub fn expand<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let (first, rest) = match input.split_once(' ') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (input, None),
+        };
+        match (self.aliases.get(first), rest) {
+            (Some(a), Some(rest)) => Cow::Owned(format!("{} {}", a, rest)),
+            (Some(a), None) => Cow::Owned(a.clone()),
+            (None, _) => Cow::Borrowed(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn expand_alias() {
+        let mut p = PlayerProfile::default();
+        p.set_alias("n", "go north");
+        p.set_alias("x", "examine");
+        assert_eq!(p.expand("n"), "go north");
+        assert_eq!(p.expand("x kitten"), "examine kitten");
+        assert_eq!(p.expand("meow"), "meow");
+        assert_eq!(p.remove_alias("n"), Some("go north".to_string()));
+        assert_eq!(p.expand("n"), "n");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let fixture = TempAdventure::new("profile", &[]);
+        let path = fixture.path("profile.yaml");
+        let mut p = PlayerProfile::load(path.clone()).unwrap();
+        assert!(p.aliases().is_empty());
+        p.set_alias("p", "pet");
+        p.set_last(LastPlayed {
+            path: PathBuf::from("/adventures/kitten"),
+            name: "A cuddly kitten".to_string(),
+            scene: "cuddle_cat".to_string(),
+        });
+        assert_eq!(
+            p.last().unwrap().to_string(),
+            "A cuddly kitten (cuddle_cat)"
+        );
+        p.set_selection(
+            PathBuf::from("/adventures"),
+            PathBuf::from("/adventures/kitten"),
+        );
+        p.options_mut().speed = Some(40);
+        p.options_mut().color = false;
+        p.save().unwrap();
+        let loaded = PlayerProfile::load(path.clone()).unwrap();
+        assert_eq!(loaded, p);
+    }
+}
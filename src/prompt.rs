@@ -0,0 +1,201 @@
+//! Asking the player for input: the game prompt, selection menus and
+//! yes/no questions. [`run_with`](crate::run_with) does all of that
+//! through a [`Prompter`], so front ends can show their own dialogs.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use crate::menu::{Choice, Menu};
+
+/// Input and output of a game. Only [`output`](Prompter::output) and
+/// [`read_line`](Prompter::read_line) are required, menus and
+/// questions default to text using those two.
+pub trait Prompter {
+    /// Where game output goes.
+    fn output(&mut self) -> &mut dyn Write;
+
+    /// Show `prompt` and read a line of input, without the line end.
+    /// Returns `None` at the end of input.
+    fn read_line(
+        &mut self,
+        prompt: &str,
+    ) -> Result<Option<String>, Box<dyn Error>>;
+
+    /// Ask the player to select one of `options`, returns the index
+    /// of the selected option. The option with index `default` is
+    /// preselected, if any. The default shows a [`Menu`].
+    fn select(
+        &mut self,
+        title: &str,
+        options: &[String],
+        default: Option<usize>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut menu = Menu::new(options);
+        if let Some(d) = default {
+            menu = menu.preselect(d);
+        }
+        menu.render(title, self.output())?;
+        loop {
+            let line = self.read_line("> ")?.ok_or("no selection")?;
+            match menu.input(&line) {
+                Choice::Selected(i) => return Ok(i),
+                Choice::Show => menu.render(title, self.output())?,
+                Choice::Message(m) => writeln!(self.output(), "{}", m)?,
+            }
+        }
+    }
+
+    /// Ask a yes/no `question`, the end of input counts as no.
+    fn confirm(&mut self, question: &str) -> Result<bool, Box<dyn Error>> {
+        match self.read_line(&format!("{} [y/n] ", question))? {
+            Some(answer) => Ok(matches!(
+                answer.trim().to_lowercase().as_str(),
+                "y" | "yes"
+            )),
+            None => {
+                writeln!(self.output())?;
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Prompts on a text terminal, or any other pair of input and output
+/// streams.
+#[derive(Debug)]
+pub struct Terminal<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Terminal<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Terminal { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> Prompter for Terminal<R, W> {
+    fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    fn read_line(
+        &mut self,
+        prompt: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        write!(self.output, "{}", prompt)?;
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
+
+/// Prompter for tests: answers come from a list, output and prompts
+/// are collected.
+#[derive(Debug, Default)]
+pub struct Scripted {
+    answers: VecDeque<String>,
+    /// Everything written to the output
+    pub output: Vec<u8>,
+    /// Prompts, menu titles and questions, in order
+    pub prompts: Vec<String>,
+}
+
+impl Scripted {
+    pub fn new(answers: &[&str]) -> Self {
+        Scripted {
+            answers: answers.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Prompter for Scripted {
+    fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    fn read_line(
+        &mut self,
+        prompt: &str,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        self.prompts.push(prompt.to_string());
+        Ok(self.answers.pop_front())
+    }
+
+    /// Selects the option with the text of the next answer, or its
+    /// number. An empty answer selects the default.
+    fn select(
+        &mut self,
+        title: &str,
+        options: &[String],
+        default: Option<usize>,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.prompts.push(title.to_string());
+        let answer = self.answers.pop_front().ok_or("no selection")?;
+        if let (Some(d), "") = (default, answer.as_str()) {
+            return Ok(d);
+        }
+        options
+            .iter()
+            .position(|o| *o == answer)
+            .or_else(|| {
+                answer
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&i| i > 0 && i <= options.len())
+                    .map(|i| i - 1)
+            })
+            .ok_or(format!("no such option: {}", answer).into())
+    }
+
+    fn confirm(&mut self, question: &str) -> Result<bool, Box<dyn Error>> {
+        self.prompts.push(question.to_string());
+        Ok(self
+            .answers
+            .pop_front()
+            .is_some_and(|a| matches!(a.as_str(), "y" | "yes")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal() {
+        let mut output = Vec::new();
+        let mut t = Terminal::new(&b"0\n2\nYes\r\nlook\n"[..], &mut output);
+        let options = ["Box".to_string(), "Basket".to_string()];
+        assert_eq!(t.select("Where?", &options, None).unwrap(), 1);
+        assert!(t.confirm("Sure?").unwrap());
+        assert_eq!(t.read_line("> ").unwrap().as_deref(), Some("look"));
+        assert!(!t.confirm("Again?").unwrap());
+        assert_eq!(t.read_line("> ").unwrap(), None);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Where?\n1: Box\n2: Basket\n\
+             > Please select a valid number (1 to 2)!\n> \
+             Sure? [y/n] > Again? [y/n] \n> "
+        );
+    }
+
+    #[test]
+    fn scripted() {
+        let mut s = Scripted::new(&["Basket", "3", "", "y"]);
+        let options = ["Box".to_string(), "Basket".to_string()];
+        assert_eq!(s.select("Where?", &options, None).unwrap(), 1);
+        assert!(s.select("Where?", &options, None).is_err());
+        assert_eq!(s.select("Where?", &options, Some(1)).unwrap(), 1);
+        assert!(s.confirm("Sure?").unwrap());
+        assert!(!s.confirm("Really?").unwrap());
+        assert_eq!(
+            s.prompts,
+            vec!["Where?", "Where?", "Where?", "Sure?", "Really?"]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Python bindings, enabled with the `python` feature. The module
+//! lets tools load adventures, inspect scenes, and play through an
+//! [`Engine`]:
+//!
+//! ```python
+//! import rustventure
+//!
+//! adventure = rustventure.Adventure.load("kitten")
+//! engine = rustventure.Engine.from_adventure(adventure)
+//! print(engine.describe(), end="")
+//! print(engine.step("look"), end="")
+//! print([a for a in engine.scene.actions])
+//! ```
+//!
+//! To build the module for Python, also enable the
+//! `pyo3/extension-module` feature and build the library as
+//! `cdylib`, e.g. with maturin.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::adventure::{self, Adventure};
+use crate::engine::Engine;
+use crate::scene::Scene;
+
+fn err(e: Box<dyn Error>) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+#[pyclass(name = "Adventure", frozen)]
+struct PyAdventure {
+    adventure: Adventure,
+}
+
+#[pymethods]
+impl PyAdventure {
+    /// Load the adventure in `path`, the adventure directory or its
+    /// `about.yaml` file.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<PyAdventure> {
+        let adventure = adventure::load(&path).map_err(err)?;
+        Ok(PyAdventure { adventure })
+    }
+
+    /// Find the adventures inside `dir`.
+    #[staticmethod]
+    fn search(dir: PathBuf) -> PyResult<Vec<PyAdventure>> {
+        let found = adventure::search(&dir).map_err(err)?;
+        Ok(found
+            .into_iter()
+            .map(|adventure| PyAdventure { adventure })
+            .collect())
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        self.adventure.name()
+    }
+
+    #[getter]
+    fn dir(&self) -> PathBuf {
+        self.adventure.dir().to_path_buf()
+    }
+
+    /// The start scene of the adventure.
+    fn start(&self) -> PyResult<PyScene> {
+        let path = self.adventure.start_path().to_path_buf();
+        Ok(PyScene::from(&Scene::load(path).map_err(err)?))
+    }
+
+    fn __str__(&self) -> String {
+        self.adventure.to_string()
+    }
+}
+
+/// A snapshot of a scene for inspection, changes don't affect the
+/// game.
+#[pyclass(name = "Scene", frozen, get_all)]
+struct PyScene {
+    name: String,
+    path: PathBuf,
+    description: String,
+    prompt: Option<String>,
+    tags: Vec<String>,
+    /// Source lines of the actions
+    actions: Vec<String>,
+    /// Labels of the exits
+    exits: Vec<String>,
+}
+
+impl From<&Scene> for PyScene {
+    fn from(scene: &Scene) -> Self {
+        PyScene {
+            name: scene.name().to_string(),
+            path: scene.path().to_path_buf(),
+            description: scene.to_string(),
+            prompt: scene.prompt().map(|p| p.to_string()),
+            tags: scene.meta().tags().map(|t| t.to_string()).collect(),
+            actions: scene
+                .actions()
+                .iter()
+                .map(|a| a.source().to_string())
+                .collect(),
+            exits: scene
+                .exits()
+                .filter_map(|a| a.exit_label().map(|l| l.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyScene {
+    /// Load the scene file at `path`.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<PyScene> {
+        Ok(PyScene::from(&Scene::load(path).map_err(err)?))
+    }
+
+    /// Parse a scene from `text`, `path` is only used for the name.
+    #[staticmethod]
+    #[pyo3(signature = (text, path = PathBuf::from("scene.scene")))]
+    fn parse(text: &str, path: PathBuf) -> PyResult<PyScene> {
+        Ok(PyScene::from(&Scene::parse(path, text).map_err(err)?))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Scene {}>", self.name)
+    }
+}
+
+#[pyclass(name = "Engine", unsendable)]
+struct PyEngine {
+    engine: Engine,
+}
+
+#[pymethods]
+impl PyEngine {
+    /// Create an engine starting in the scene file at `path`.
+    #[new]
+    fn new(path: PathBuf) -> PyResult<PyEngine> {
+        let scene = Scene::load(path).map_err(err)?;
+        Ok(PyEngine {
+            engine: Engine::new(scene),
+        })
+    }
+
+    /// Create an engine for `adventure`, starting at its start
+    /// scene.
+    #[staticmethod]
+    fn from_adventure(adventure: &PyAdventure) -> PyResult<PyEngine> {
+        let engine = Engine::from_adventure(adventure.adventure.clone())
+            .map_err(err)?;
+        Ok(PyEngine { engine })
+    }
+
+    /// The description of the current scene.
+    fn describe(&mut self) -> PyResult<String> {
+        let mut output = Vec::new();
+        self.engine.describe(&mut output).map_err(err)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Process one line of player input, returns the output.
+    fn step(&mut self, input: &str) -> PyResult<String> {
+        let mut output = Vec::new();
+        self.engine.step(input, &mut output).map_err(err)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    #[getter]
+    fn scene(&self) -> PyScene {
+        PyScene::from(self.engine.scene())
+    }
+
+    fn variable(&self, name: &str) -> Option<String> {
+        self.engine.variable(name).map(|v| v.to_string())
+    }
+
+    #[getter]
+    fn transcript(&self) -> &str {
+        self.engine.transcript()
+    }
+}
+
+#[pymodule]
+fn rustventure(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyAdventure>()?;
+    m.add_class::<PyScene>()?;
+    m.add_class::<PyEngine>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::ffi::c_str;
+
+    #[test]
+    fn play() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "rustventure").unwrap();
+            rustventure(&module).unwrap();
+            let path: PathBuf =
+                [env!("CARGO_MANIFEST_DIR"), "resources", "kitten.scene"]
+                    .iter()
+                    .collect();
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("rustventure", module).unwrap();
+            locals.set_item("path", path).unwrap();
+            py.run(
+                c_str!(
+                    "engine = rustventure.Engine(path)\n\
+                     out = engine.step('meow')\n\
+                     scene = engine.scene\n\
+                     parsed = rustventure.Scene.parse(\
+                     '@tags: cave\\nA cave.\\n!kw[exit]:out -> scene field\\n')"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let get = |name: &str| locals.get_item(name).unwrap().unwrap();
+            assert_eq!(
+                get("out").extract::<String>().unwrap(),
+                "\"Meow!\" =^.^=\n"
+            );
+            assert_eq!(
+                get("scene")
+                    .getattr("name")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "kitten"
+            );
+            let parsed = get("parsed");
+            let attr = |name: &str| -> Vec<String> {
+                parsed.getattr(name).unwrap().extract().unwrap()
+            };
+            assert_eq!(attr("tags"), ["cave"]);
+            assert_eq!(attr("exits"), ["out"]);
+        });
+    }
+}
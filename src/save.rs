@@ -0,0 +1,390 @@
+//! Saved games and where to keep them. The engine works with any
+//! [`SaveStore`]: [`FsStore`] keeps one JSON file per save, with the
+//! `sqlite` feature `SqliteStore` keeps many saves in one database.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cache;
+use crate::state::GameState;
+
+/// Current format of saved games. Saves without a version are format
+/// 1, older formats are migrated when loading.
+pub const SAVE_VERSION: u32 = 3;
+
+type Migration = fn(&mut serde_json::Value) -> Result<(), Box<dyn Error>>;
+
+/// Migrations from each save format to the next, starting at format 1.
+const MIGRATIONS: &[Migration] = &[
+    // Format 2 only added the version.
+    |_| Ok(()),
+    // Format 3 added the players of hot-seat games, older saves have
+    // one player.
+    |_| Ok(()),
+];
+
+/// Everything needed to continue a game later.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveGame {
+    /// Format of the save, see [`SAVE_VERSION`]
+    pub version: u32,
+    /// Name of the adventure, empty when playing a single scene file
+    pub adventure: String,
+    /// Name of the current scene, with parameters if any
+    pub scene: String,
+    pub state: GameState,
+    /// Hash of the adventure files when the game was saved, see
+    /// [`content_hash`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<u64>,
+}
+
+impl SaveGame {
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Read a save, migrating it from older formats if needed.
+    pub fn from_json(json: &str) -> Result<SaveGame, Box<dyn Error>> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if !value.is_object() {
+            return Err("invalid save: not a JSON object".into());
+        }
+        let version = match value.get("version") {
+            None => 1,
+            Some(v) => v
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .filter(|v| *v > 0)
+                .ok_or(format!("invalid save version: {}", v))?,
+        };
+        if version > SAVE_VERSION {
+            return Err(format!(
+                "the save is from a newer version of rustventure (save \
+                 format {}, this version reads up to format {})",
+                version, SAVE_VERSION
+            )
+            .into());
+        }
+        for (from, migrate) in
+            MIGRATIONS.iter().enumerate().skip(version as usize - 1)
+        {
+            migrate(&mut value)?;
+            value["version"] = (from as u32 + 2).into();
+        }
+        serde_json::from_value(value).map_err(|e| {
+            format!("cannot read save in format {}: {}", version, e).into()
+        })
+    }
+}
+
+/// Hash the scene files and `about.yaml` in `dir`, to notice when an
+/// adventure changed since a game was saved.
+pub fn content_hash(dir: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "scene")
+            || path.file_name().is_some_and(|n| n == "about.yaml")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    let mut data = Vec::new();
+    for f in files {
+        data.extend(f.file_name().unwrap_or_default().as_encoded_bytes());
+        data.push(0);
+        data.extend(fs::read(&f)?);
+        data.push(0);
+    }
+    Ok(cache::hash(&data))
+}
+
+/// Storage for saved games, identified by name.
+pub trait SaveStore: fmt::Debug {
+    /// Store `save` as `name`, replacing any existing save of that
+    /// name.
+    fn save(
+        &mut self,
+        name: &str,
+        save: &SaveGame,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn load(&self, name: &str) -> Result<SaveGame, Box<dyn Error>>;
+
+    /// Names of all saves, sorted.
+    fn list(&self) -> Result<Vec<String>, Box<dyn Error>>;
+
+    fn delete(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// Stores each save as `<name>.json` in a directory.
+#[derive(Debug)]
+pub struct FsStore {
+    dir: PathBuf,
+}
+
+impl FsStore {
+    /// Store saves in `dir`, which is created on the first save.
+    pub fn new(dir: PathBuf) -> FsStore {
+        FsStore { dir }
+    }
+
+    /// Default directory for saves, in the user's data directory.
+    pub fn default_dir() -> Option<PathBuf> {
+        let data = env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(
+            || {
+                env::var_os("HOME")
+                    .map(|h| PathBuf::from(h).join(".local").join("share"))
+            },
+        )?;
+        Some(data.join("rustventure").join("saves"))
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-".contains(c))
+        {
+            return Err(format!("invalid save name: {}", name).into());
+        }
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+}
+
+/// Turn "not found" errors into a readable message about the save.
+fn not_found(name: &str, e: io::Error) -> Box<dyn Error> {
+    if e.kind() == io::ErrorKind::NotFound {
+        format!("no save named {}", name).into()
+    } else {
+        e.into()
+    }
+}
+
+impl SaveStore for FsStore {
+    fn save(
+        &mut self,
+        name: &str,
+        save: &SaveGame,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, save.to_json()?)?;
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<SaveGame, Box<dyn Error>> {
+        let json = fs::read_to_string(self.path(name)?)
+            .map_err(|e| not_found(name, e))?;
+        SaveGame::from_json(&json)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str())
+                {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn delete(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(self.path(name)?).map_err(|e| not_found(name, e))?;
+        Ok(())
+    }
+}
+
+/// Stores saves as JSON in an SQLite database, in a table `saves`.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open or create the database at `path`. Use `":memory:"` for a
+    /// temporary in-memory database.
+    pub fn open(path: &str) -> Result<SqliteStore, Box<dyn Error>> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS saves \
+             (name TEXT PRIMARY KEY, data TEXT NOT NULL)",
+            (),
+        )?;
+        Ok(SqliteStore { conn })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SaveStore for SqliteStore {
+    fn save(
+        &mut self,
+        name: &str,
+        save: &SaveGame,
+    ) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO saves (name, data) VALUES (?1, ?2)",
+            (name, save.to_json()?),
+        )?;
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<SaveGame, Box<dyn Error>> {
+        let json: String = self
+            .conn
+            .query_row(
+                "SELECT data FROM saves WHERE name = ?1",
+                [name],
+                |r| r.get(0),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => {
+                    format!("no save named {}", name).into()
+                }
+                e => Box::new(e) as Box<dyn Error>,
+            })?;
+        SaveGame::from_json(&json)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt =
+            self.conn.prepare("SELECT name FROM saves ORDER BY name")?;
+        let names = stmt
+            .query_map((), |r| r.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(names)
+    }
+
+    fn delete(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let n = self
+            .conn
+            .execute("DELETE FROM saves WHERE name = ?1", [name])?;
+        if n == 0 {
+            return Err(format!("no save named {}", name).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    fn example() -> SaveGame {
+        let mut state = GameState::default();
+        state.set("mood", "sleepy");
+        state.give("yarn");
+        state.visit("kitten");
+        SaveGame {
+            version: SAVE_VERSION,
+            adventure: "A cuddly kitten".to_string(),
+            scene: "kitten".to_string(),
+            state,
+            content: Some(7),
+        }
+    }
+
+    /// Run the same checks on any store.
+    fn check_store(store: &mut dyn SaveStore) {
+        let save = example();
+        assert!(store.list().unwrap().is_empty());
+        store.save("slot1", &save).unwrap();
+        store.save("auto", &save).unwrap();
+        assert_eq!(store.list().unwrap(), ["auto", "slot1"]);
+        assert_eq!(store.load("slot1").unwrap(), save);
+        store.delete("slot1").unwrap();
+        assert_eq!(store.list().unwrap(), ["auto"]);
+        assert_eq!(
+            store.load("slot1").unwrap_err().to_string(),
+            "no save named slot1"
+        );
+        assert!(store.delete("slot1").is_err());
+    }
+
+    #[test]
+    fn fs_store() {
+        let fixture = TempAdventure::new("saves", &[]);
+        let mut store = FsStore::new(fixture.path("saves"));
+        check_store(&mut store);
+        let invalid = store.save("../escape", &example());
+        assert!(invalid.is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store() {
+        let mut store = SqliteStore::open(":memory:").unwrap();
+        check_store(&mut store);
+    }
+
+    #[test]
+    fn older_saves() {
+        // Saves from before turns were counted
+        let json = r#"{"adventure": "", "scene": "kitten", "state": {
+            "variables": {}, "items": {}, "npcs": {},
+            "visits": {"kitten": 1}, "exits": {}}}"#;
+        let save = SaveGame::from_json(json).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.state.turns(), 0);
+        assert_eq!(save.state.visits("kitten"), 1);
+    }
+
+    #[test]
+    fn save_versions() {
+        let save = example();
+        let json = save.to_json().unwrap();
+        assert!(json.contains(&format!("\"version\": {}", SAVE_VERSION)));
+        assert_eq!(SaveGame::from_json(&json).unwrap(), save);
+
+        let newer = json.replace(
+            &format!("\"version\": {}", SAVE_VERSION),
+            "\"version\": 99",
+        );
+        assert_eq!(
+            SaveGame::from_json(&newer).unwrap_err().to_string(),
+            format!(
+                "the save is from a newer version of rustventure (save \
+                 format 99, this version reads up to format {})",
+                SAVE_VERSION
+            )
+        );
+        let older = json.replace(
+            &format!("\"version\": {}", SAVE_VERSION),
+            "\"version\": 2",
+        );
+        assert_eq!(SaveGame::from_json(&older).unwrap(), save);
+        let broken = r#"{"version": 1, "adventure": "", "scene": 3}"#;
+        assert!(SaveGame::from_json(broken)
+            .unwrap_err()
+            .to_string()
+            .starts_with("cannot read save in format 1: "));
+        for json in ["[1, 2]", "\"kitten\"", "null"] {
+            assert_eq!(
+                SaveGame::from_json(json).unwrap_err().to_string(),
+                "invalid save: not a JSON object"
+            );
+        }
+    }
+}
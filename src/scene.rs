@@ -2,74 +2,771 @@
 
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
 
-#[derive(Debug)]
+use crate::condition::Condition;
+use crate::preprocess::preprocess_lines;
+use crate::warning::Warning;
+
+pub mod graph;
+
+/// Header directives, like `@prompt: >`.
+pub const DIRECTIVES: &[&str] = &[
+    "prompt",
+    "set",
+    "item",
+    "container",
+    "tags",
+    "enter",
+    "format",
+    "idle",
+];
+
+/// Newest scene format this engine reads. Scenes declare their format
+/// with a `@format: 2` header, scenes without one are format 1. In
+/// format 1 `scene` changes the scene and any other effect word prints
+/// the rest of the line as written, like the first versions of the
+/// engine did. The other [`EFFECTS`], lists of responses, and the
+/// [`MODIFIERS`] need format 2, which also rejects unknown effect
+/// words. `rustventure migrate` upgrades old scenes.
+pub const FORMAT: u32 = 2;
+
+/// Kinds of actions, the word after `!`. `kw` and `door` match
+/// keywords, other kinds a regular expression, by convention `regex`.
+pub const ACTION_KINDS: &[&str] = &["kw", "door", "regex"];
+
+/// Effects, the word after `->`. Any other word prints the rest of
+/// the line, by convention `print`.
+pub const EFFECTS: &[&str] = &[
+    "scene",
+    "pushscene",
+    "popscene",
+    "give",
+    "take",
+    "advance",
+    "adventure",
+    "script",
+    "npc",
+    "print",
+];
+
+/// Words starting the bracketed modifiers at the end of actions, like
+/// `[if has key]`.
+pub const MODIFIERS: &[&str] = &["if", "needs", "cooldown"];
+
+#[derive(Debug, Default)]
 pub struct Scene {
     path: PathBuf,
     description: String,
+    prompt: Option<String>,
+    variables: BTreeMap<String, String>,
+    /// Parameters passed when entering the scene
+    params: BTreeMap<String, String>,
+    items: Vec<SceneItem>,
+    containers: BTreeMap<String, String>,
+    meta: SceneMeta,
+    /// Condition to enter the scene, and text to show if it isn't met
+    entry: Option<(Condition, Option<String>)>,
+    /// Seconds without input before the effect happens
+    idle: Option<(u64, Effect)>,
     actions: Vec<Action>,
+    tests: Vec<SceneTest>,
+    /// Whether malformed action lines were skipped instead of failing
+    lenient: bool,
+    /// Format declared with `@format`
+    format: Option<u32>,
 }
 
 impl Scene {
     pub fn load(path: PathBuf) -> Result<Scene, Box<dyn Error>> {
-        let f = File::open(&path)?;
-        let mut reader = BufReader::new(f);
+        Ok(Scene::load_with_warnings(path)?.0)
+    }
+
+    /// Load a scene, also returning likely mistakes found in it.
+    pub fn load_with_warnings(
+        path: PathBuf,
+    ) -> Result<(Scene, Vec<Warning>), Box<dyn Error>> {
+        enter_span!("load_scene", path = %path.display());
+        let text = fs::read_to_string(&path)?;
+        let (scene, warnings) = Scene::parse_with_warnings(path, &text)?;
+        debug!(actions = scene.actions.len(), "scene loaded");
+        Ok((scene, warnings))
+    }
+
+    /// Load a scene like [`parse_lenient`](Scene::parse_lenient)
+    /// does, the warnings are only logged.
+    pub fn load_lenient(path: PathBuf) -> Result<Scene, Box<dyn Error>> {
+        enter_span!("load_scene", path = %path.display());
+        let text = fs::read_to_string(&path)?;
+        let (scene, _) = Scene::parse_lenient(path, &text)?;
+        debug!(actions = scene.actions.len(), "scene loaded");
+        Ok(scene)
+    }
 
+    /// Parse a scene from `text`. The `path` is used to find other
+    /// scenes relative to this one.
+    pub fn parse(path: PathBuf, text: &str) -> Result<Scene, Box<dyn Error>> {
+        Ok(Scene::parse_with_warnings(path, text)?.0)
+    }
+
+    /// Parse a scene from `text`, also returning likely mistakes:
+    /// lines in the description that look like broken actions, an
+    /// empty description, and unreachable actions because an earlier
+    /// action already takes their keyword unconditionally.
+    pub fn parse_with_warnings(
+        path: PathBuf,
+        text: &str,
+    ) -> Result<(Scene, Vec<Warning>), Box<dyn Error>> {
+        Scene::parse_mode(path, text, false)
+    }
+
+    /// Parse a scene like
+    /// [`parse_with_warnings`](Scene::parse_with_warnings), but skip
+    /// malformed action lines with a warning instead of failing, so
+    /// authors can keep playtesting while fixing them. Scenes loaded
+    /// from a lenient scene with [`load_next`](Scene::load_next) are
+    /// lenient, too.
+    pub fn parse_lenient(
+        path: PathBuf,
+        text: &str,
+    ) -> Result<(Scene, Vec<Warning>), Box<dyn Error>> {
+        Scene::parse_mode(path, text, true)
+    }
+
+    fn parse_mode(
+        path: PathBuf,
+        text: &str,
+        lenient: bool,
+    ) -> Result<(Scene, Vec<Warning>), Box<dyn Error>> {
+        let mut warnings = Vec::new();
+        let lines = preprocess_lines(text)?;
+        let mut lines =
+            lines.iter().map(|(n, l)| (*n, l.as_str())).peekable();
+
+        let mut prompt = None;
+        let mut variables = BTreeMap::new();
+        let mut items = Vec::new();
+        let mut containers = BTreeMap::new();
+        let mut meta = SceneMeta::default();
+        let mut entry = None;
+        let mut format = None;
+        let mut idle = None;
         let mut desc = String::new();
         let mut actions = Vec::new();
+        let mut tests = Vec::new();
+
+        // Read header directives: lines starting with "@" at the very
+        // beginning of the file.
+        while let Some((n, line)) = lines.next_if(|(_, l)| l.starts_with('@'))
+        {
+            let mut directive = || -> Result<(), Box<dyn Error>> {
+                let (name, value) = parse_directive(line)?;
+                match name {
+                    "prompt" => prompt = Some(value.to_string()),
+                    "set" => {
+                        let (k, v) = value
+                            .split_once('=')
+                            .ok_or(format!("invalid variable: {}", value))?;
+                        variables.insert(
+                            k.trim().to_string(),
+                            v.trim().to_string(),
+                        );
+                    }
+                    "item" => {
+                        let (id, description) = split_declaration(value);
+                        let (id, container) = match id.split_once(" in ") {
+                            Some((i, c)) => {
+                                (i.trim(), Some(c.trim().to_string()))
+                            }
+                            None => (id, None),
+                        };
+                        items.push(SceneItem {
+                            id: id.to_string(),
+                            container,
+                            description: description.to_string(),
+                        });
+                    }
+                    "container" => {
+                        let (id, description) = split_declaration(value);
+                        containers
+                            .insert(id.to_string(), description.to_string());
+                    }
+                    "tags" => meta.tags.extend(
+                        value
+                            .split(',')
+                            .map(|t| t.trim().to_string())
+                            .filter(|t| !t.is_empty()),
+                    ),
+                    "enter" => {
+                        let (condition, text) = split_alternative(value);
+                        entry = Some((
+                            Condition::parse(condition)?,
+                            text.map(|t| t.to_string()),
+                        ));
+                    }
+                    "idle" => {
+                        let (seconds, effect) = value
+                            .split_once(" -> ")
+                            .and_then(|(s, e)| {
+                                Some((s.trim().parse().ok()?, e))
+                            })
+                            .ok_or(format!(
+                                "invalid idle effect: {}",
+                                value
+                            ))?;
+                        // Parsed after the header, @format may come later.
+                        idle = Some((n, seconds, effect));
+                    }
+                    "format" => {
+                        let n: u32 = value
+                            .trim()
+                            .parse()
+                            .ok()
+                            .filter(|n| *n > 0)
+                            .ok_or(format!("invalid format: {}", value))?;
+                        if n > FORMAT {
+                            return Err(format!(
+                                "engine too old for scene format {}, it reads \
+                                 formats up to {}",
+                                n, FORMAT
+                            )
+                            .into());
+                        }
+                        format = Some(n);
+                    }
+                    _ => {
+                        return Err(format!(
+                            "unknown directive: {}",
+                            line.trim()
+                        )
+                        .into())
+                    }
+                }
+                Ok(())
+            };
+            directive().map_err(|e| format!("line {}: {}", n, e))?;
+        }
+
+        let version = format.unwrap_or(1);
+        let idle = match idle {
+            Some((n, seconds, effect)) => {
+                let action = Action::parse(
+                    &format!("!kw:idle -> {}", effect),
+                    version,
+                )
+                .map_err(|e| format!("line {}: {}", n, e))?;
+                Some((seconds, action.effect().clone()))
+            }
+            None => None,
+        };
 
         // Read the scene description: Everything until the first line
         // that can be parsed as an action.
-        loop {
-            let mut line = String::new();
-            if reader.read_line(&mut line)? == 0 {
-                break;
+        for (n, line) in lines.by_ref() {
+            if let Some(t) = line.trim().strip_prefix("!test:") {
+                tests.push(SceneTest::parse(t)?);
+                continue;
             }
-            match Action::new(line.trim()) {
+            match Action::at_line(line.trim(), n, version) {
                 Ok(a) => {
                     actions.push(a);
                     break;
                 }
-                Err(_) => desc.push_str(&line),
+                Err(e) => {
+                    if line.starts_with('!') {
+                        warnings.push(Warning::new(
+                            &path,
+                            format!("looks like an action, but isn't: {}", e),
+                        ));
+                    }
+                    desc.push_str(line)
+                }
             }
         }
 
         // Read remaining actions
-        loop {
-            let mut line = String::new();
-            if reader.read_line(&mut line)? == 0 {
-                break;
-            }
+        for (n, line) in lines {
             let line = line.trim();
             if line.is_empty() {
                 continue;
             }
-            actions.push(Action::new(line)?);
+            if let Some(t) = line.strip_prefix("!test:") {
+                tests.push(SceneTest::parse(t)?);
+                continue;
+            }
+            match Action::at_line(line, n, version) {
+                Ok(a) => actions.push(a),
+                Err(e) if lenient => warnings.push(Warning::new(
+                    &path,
+                    format!("line {}: skipped invalid action: {}", n, e),
+                )),
+                Err(e) => return Err(format!("line {}: {}", n, e).into()),
+            }
+        }
+
+        if desc.trim().is_empty() {
+            warnings.push(Warning::new(&path, "empty description".into()));
+        }
+        for (i, a) in actions.iter().enumerate() {
+            let Some(kw) = a.keyword() else { continue };
+            let shadowed = actions[..i].iter().find(|b| {
+                b.keyword() == Some(kw)
+                    && b.condition().is_none()
+                    && !b.once()
+                    && b.cooldown() == 0
+            });
+            if let Some(b) = shadowed {
+                warnings.push(Warning::new(
+                    &path,
+                    format!(
+                        "line {}: unreachable action, keyword {} is \
+                         already taken on line {}",
+                        a.line.unwrap_or_default(),
+                        kw,
+                        b.line.unwrap_or_default()
+                    ),
+                ));
+            }
         }
 
-        Ok(Scene {
+        let scene = Scene {
             path,
             description: desc,
+            prompt,
+            variables,
+            params: BTreeMap::new(),
+            items,
+            containers,
+            meta,
+            entry,
+            idle,
             actions,
-        })
+            tests,
+            lenient,
+            format,
+        };
+        Ok((scene, warnings))
+    }
+
+    /// Path of the scene file, other files are found relative to it.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether malformed action lines were skipped, see
+    /// [`parse_lenient`](Scene::parse_lenient).
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Format of the scene file, see [`FORMAT`].
+    pub fn format(&self) -> u32 {
+        self.format.unwrap_or(1)
+    }
+
+    /// Name of the scene, as used to refer to it in actions.
+    pub fn name(&self) -> &str {
+        self.path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
     }
 
+    /// Prompt to show in this scene, if it overrides the default.
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// Variables set by `@set name=value` directives or passed as
+    /// parameters, these override the game state while the player is
+    /// in the scene.
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    /// Parameters the scene was entered with, see
+    /// [`load_next`](Scene::load_next).
+    pub fn params(&self) -> &BTreeMap<String, String> {
+        &self.params
+    }
+
+    /// Name of the scene including its parameters, as used with
+    /// [`load_next`](Scene::load_next) to load the same scene again.
+    pub fn reference(&self) -> String {
+        let mut reference = self.name().to_string();
+        for (i, (k, v)) in self.params.iter().enumerate() {
+            reference.push(if i == 0 { '?' } else { '&' });
+            reference.push_str(&format!("{}={}", k, v));
+        }
+        reference
+    }
+
+    /// Items placed in the scene by `@item` directives.
+    pub fn items(&self) -> &[SceneItem] {
+        &self.items
+    }
+
+    /// Containers in the scene, mapped to their descriptions.
+    pub fn containers(&self) -> &BTreeMap<String, String> {
+        &self.containers
+    }
+
+    /// Information about the scene, like its tags.
+    pub fn meta(&self) -> &SceneMeta {
+        &self.meta
+    }
+
+    /// Condition the player must meet to enter the scene, set with
+    /// `@enter: has(lamp) || visits(cave) > 0 | It's too dark.`, and
+    /// the text to show instead if any.
+    pub fn entry_condition(&self) -> Option<(&Condition, Option<&str>)> {
+        self.entry.as_ref().map(|(c, t)| (c, t.as_deref()))
+    }
+
+    /// Effect to apply when the player doesn't enter anything for a
+    /// while, set with `@idle: 30 -> print The kitten yawns.` for 30
+    /// seconds. Only interactive front ends support it, see
+    /// [`Engine::idle`](crate::engine::Engine::idle).
+    pub fn idle(&self) -> Option<(Duration, &Effect)> {
+        self.idle
+            .as_ref()
+            .map(|(s, e)| (Duration::from_secs(*s), e))
+    }
+
+    /// Test assertions written in the scene file.
+    pub fn tests(&self) -> &[SceneTest] {
+        &self.tests
+    }
+
+    /// All actions of the scene, in order of priority.
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// Get the first action matching `input`, ignoring conditions.
     pub fn get_action(&self, input: &str) -> Option<&Action> {
         self.actions.iter().find(|a| a.expression().is_match(input))
     }
 
+    /// Actions tagged as exits.
+    pub fn exits(&self) -> impl Iterator<Item = &Action> {
+        self.actions.iter().filter(|a| a.exit_label().is_some())
+    }
+
+    /// All actions matching `input`, in order of priority.
+    pub fn matching_actions<'a>(
+        &'a self,
+        input: &'a str,
+    ) -> impl Iterator<Item = &'a Action> + 'a {
+        self.actions
+            .iter()
+            .filter(move |a| a.expression().is_match(input))
+    }
+
+    /// Load the scene called `name` from the same directory as this
+    /// one. Parameters can be passed like `shop?item=sword&price=10`,
+    /// they become variables in the scene (overriding its `@set`
+    /// directives), so one scene file can serve several purposes.
     pub fn load_next(&self, name: &str) -> Result<Scene, Box<dyn Error>> {
+        self.load_next_from(name, |path| fs::read_to_string(path))
+    }
+
+    /// Like [`load_next`](Scene::load_next), but get the text of the
+    /// scene from `read` instead of the file system.
+    pub fn load_next_from<F>(
+        &self,
+        name: &str,
+        read: F,
+    ) -> Result<Scene, Box<dyn Error>>
+    where
+        F: FnOnce(&Path) -> io::Result<String>,
+    {
+        let (name, params) = name.split_once('?').unwrap_or((name, ""));
         let mut path = self.path.clone();
         path.set_file_name(format!("{}.scene", name));
-        Scene::load(path)
+        enter_span!("load_scene", path = %path.display());
+        let text = read(&path)?;
+        let mut scene = Scene::parse_mode(path, &text, self.lenient)?.0;
+        debug!(actions = scene.actions.len(), "scene loaded");
+        for param in params.split('&').filter(|p| !p.trim().is_empty()) {
+            let (k, v) = param
+                .split_once('=')
+                .ok_or(format!("invalid scene parameter: {}", param))?;
+            let (k, v) = (k.trim().to_string(), v.trim().to_string());
+            scene.variables.insert(k.clone(), v.clone());
+            scene.params.insert(k, v);
+        }
+        Ok(scene)
     }
 }
 
+/// Loads the scenes the player moves to. By default the
+/// [`Engine`](crate::engine::Engine) uses a [`FileProvider`], other
+/// providers can be set with
+/// [`Engine::set_scene_provider`](crate::engine::Engine::set_scene_provider)
+/// to generate scenes programmatically.
+///
+/// # Examples
+///
+/// A provider that generates an endless corridor, and loads other
+/// scenes from files:
+///
+/// ```
+/// use rustventure::scene::{Scene, SceneProvider};
+/// use std::error::Error;
+/// use std::path::PathBuf;
+///
+/// #[derive(Debug)]
+/// struct Corridor;
+///
+/// impl SceneProvider for Corridor {
+///     fn load_next(
+///         &self,
+///         current: &Scene,
+///         name: &str,
+///     ) -> Result<Scene, Box<dyn Error>> {
+///         match name.strip_prefix("corridor_") {
+///             Some(n) => {
+///                 let n: u32 = n.parse()?;
+///                 let text = format!(
+///                     "Corridor section {}.\n!kw:on -> scene corridor_{}\n",
+///                     n,
+///                     n + 1
+///                 );
+///                 Scene::parse(PathBuf::from(format!("{}.scene", name)), &text)
+///             }
+///             None => current.load_next(name),
+///         }
+///     }
+/// }
+///
+/// let start = Scene::parse(PathBuf::from("start.scene"), "Start.\n")?;
+/// let next = Corridor.load_next(&start, "corridor_7")?;
+/// assert_eq!(next.to_string(), "Corridor section 7.\n");
+/// # Ok::<(), Box<dyn Error>>(())
+/// ```
+pub trait SceneProvider: fmt::Debug {
+    /// Load the scene called `name` (possibly with parameters), which
+    /// the player moves to from `current`.
+    fn load_next(
+        &self,
+        current: &Scene,
+        name: &str,
+    ) -> Result<Scene, Box<dyn Error>>;
+}
+
+/// Loads scenes from files next to the current scene, see
+/// [`Scene::load_next`].
+#[derive(Debug, Default)]
+pub struct FileProvider;
+
+impl SceneProvider for FileProvider {
+    fn load_next(
+        &self,
+        current: &Scene,
+        name: &str,
+    ) -> Result<Scene, Box<dyn Error>> {
+        current.load_next(name)
+    }
+}
+
+/// Loads scenes with a function that reads the text of the scene at
+/// a path, for hosts that keep adventures somewhere other than the
+/// file system, like memory or flash storage of a handheld.
+///
+/// # Examples
+///
+/// ```
+/// use rustventure::scene::{ReadProvider, Scene, SceneProvider};
+/// use std::collections::HashMap;
+/// use std::io;
+/// use std::path::{Path, PathBuf};
+///
+/// let files = HashMap::from([("cave.scene", "A dark cave.\n")]);
+/// let provider = ReadProvider::new(move |path: &Path| {
+///     files
+///         .get(path.to_str().unwrap_or_default())
+///         .map(|text| text.to_string())
+///         .ok_or(io::ErrorKind::NotFound.into())
+/// });
+/// let start = Scene::parse(PathBuf::from("start.scene"), "Start.\n")?;
+/// let cave = provider.load_next(&start, "cave")?;
+/// assert_eq!(cave.to_string(), "A dark cave.\n");
+/// assert!(provider.load_next(&start, "attic").is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ReadProvider<F> {
+    read: F,
+}
+
+impl<F> ReadProvider<F>
+where
+    F: Fn(&Path) -> io::Result<String>,
+{
+    pub fn new(read: F) -> ReadProvider<F> {
+        ReadProvider { read }
+    }
+}
+
+impl<F> fmt::Debug for ReadProvider<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadProvider").finish_non_exhaustive()
+    }
+}
+
+impl<F> SceneProvider for ReadProvider<F>
+where
+    F: Fn(&Path) -> io::Result<String>,
+{
+    fn load_next(
+        &self,
+        current: &Scene,
+        name: &str,
+    ) -> Result<Scene, Box<dyn Error>> {
+        current.load_next_from(name, &self.read)
+    }
+}
+
+/// Information about a scene that other parts of the adventure can
+/// refer to, so they can apply to groups of scenes without listing
+/// them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneMeta {
+    tags: BTreeSet<String>,
+}
+
+impl SceneMeta {
+    /// Tags set with `@tags: forest, outdoors`, in alphabetical
+    /// order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(|t| t.as_str())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+}
+
+/// An item as declared in a scene: `@item: key = A rusty key.`, or
+/// `@item: coin in chest = A shiny coin.` if it is inside a container.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SceneItem {
+    id: String,
+    container: Option<String>,
+    description: String,
+}
+
+impl SceneItem {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn container(&self) -> Option<&str> {
+        self.container.as_deref()
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// A test written by the adventure author:
+/// `!test: hug; pet => "*purr, purr*"` enters the inputs separated
+/// by semicolons in the scene, and passes if the output for the last
+/// one contains the expected text. Quotes around the expected text
+/// are optional.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SceneTest {
+    inputs: Vec<String>,
+    expected: String,
+}
+
+impl SceneTest {
+    fn parse(s: &str) -> Result<SceneTest, Box<dyn Error>> {
+        let (inputs, expected) = s
+            .split_once("=>")
+            .ok_or(format!("invalid test: {}", s.trim()))?;
+        let inputs: Vec<String> =
+            inputs.split(';').map(|i| i.trim().to_string()).collect();
+        if inputs.iter().any(|i| i.is_empty()) {
+            return Err(format!("empty input in test: {}", s.trim()).into());
+        }
+        let expected = expected.trim();
+        let expected = expected
+            .strip_prefix('"')
+            .and_then(|e| e.strip_suffix('"'))
+            .unwrap_or(expected);
+        Ok(SceneTest {
+            inputs,
+            expected: expected.to_string(),
+        })
+    }
+
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+impl fmt::Display for SceneTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} => {:?}", self.inputs.join("; "), self.expected)
+    }
+}
+
+/// Split `condition | text` at the first `|` that isn't part of an
+/// `||` operator, returning the condition and the trimmed text.
+fn split_alternative(s: &str) -> (&str, Option<&str>) {
+    let b = s.as_bytes();
+    for (i, c) in b.iter().enumerate() {
+        if *c == b'|'
+            && b.get(i + 1) != Some(&b'|')
+            && (i == 0 || b[i - 1] != b'|')
+        {
+            return (&s[..i], Some(s[i + 1..].trim()));
+        }
+    }
+    (s, None)
+}
+
+/// Split declarations like `key = A rusty key.` into the trimmed
+/// name and (possibly empty) description.
+fn split_declaration(value: &str) -> (&str, &str) {
+    match value.split_once('=') {
+        Some((id, desc)) => (id.trim(), desc.trim()),
+        None => (value.trim(), ""),
+    }
+}
+
+/// Split a header directive line like `@prompt: > ` into name and
+/// value. Only the line break is removed from the value, so it may
+/// end with whitespace.
+fn parse_directive(line: &str) -> Result<(&str, &str), Box<dyn Error>> {
+    lazy_static! {
+        static ref DIRECTIVE_RE: Regex =
+            Regex::new(r"^@(\w+):?(?:\s(.*))?$").unwrap();
+    }
+    let line = line.trim_end_matches(['\r', '\n']);
+    let c = DIRECTIVE_RE
+        .captures(line)
+        .ok_or(format!("invalid directive: {}", line))?;
+    Ok((
+        c.get(1).unwrap().as_str(),
+        c.get(2).map_or("", |m| m.as_str()),
+    ))
+}
+
 impl fmt::Display for Scene {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.description)
@@ -78,6 +775,40 @@ impl fmt::Display for Scene {
 
 /// A possible action in a scene.
 ///
+/// An action may have a condition in brackets at the end of the
+/// line, e.g. `[if has key]`, and is ignored if the condition is not
+/// met. With an alternative text like `[if has key | It's locked.]`
+/// the text is printed instead. `[needs key]` is short for
+/// `[if has key]`.
+///
+/// Tags in brackets after the kind mark special actions, e.g.
+/// `!kw[exit]:north -> scene cave` marks a way to another scene for
+/// the `exits` command. Exits are listed by keyword, use
+/// `[exit=north]` to set a label for `regex` actions.
+///
+/// Actions tagged `once` stop matching after they fired, e.g.
+/// `!kw[once]:take key -> give key`. An action with a cooldown like
+/// `!kw:pet -> print *purr* [cooldown 3 | The kitten has had enough.]`
+/// can't fire again until the player entered three other commands,
+/// trying earlier prints the alternative text if there is one. The
+/// cooldown goes after the condition, if any.
+///
+/// Instead of a single text an action can print alternative
+/// responses, written as a list of quoted strings: with
+/// `!kw:meow -> print ["Meow!", "Mrrp?", "*ignores you*"]` each
+/// `meow` gets the next response, starting over after the last.
+/// `print random [...]` picks one at random instead.
+///
+/// The `door` kind is a shortcut for locked passages:
+/// `!door:north -> cave [needs key | The door is locked.]` is the
+/// same as `!kw:north -> scene cave [if has key | The door is
+/// locked.]`.
+///
+/// Conditions, cooldowns, lists of responses, and effects other than
+/// `scene` and `print` need scene format 2. In format 1 any effect
+/// word but `scene` prints the rest of the line as written, so a typo
+/// like `-> scnee cave` prints "cave", see [`FORMAT`].
+///
 /// # Examples
 ///
 /// ```
@@ -85,44 +816,272 @@ impl fmt::Display for Scene {
 /// let a = Action::new("!kw:meow -> print \"Meow!\" =^.^=").unwrap();
 /// assert!(a.expression().is_match("meow"));
 /// ```
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "ActionDef", into = "ActionDef")]
 pub struct Action {
     expression: Regex,
     effect: Effect,
+    condition: Option<Condition>,
+    otherwise: Option<Effect>,
+    tags: Vec<String>,
+    /// Keyword or regular expression as written in the scene, in
+    /// Unicode normalization form C
+    source: String,
+    /// Whether `source` is a keyword rather than a regular expression
+    keyword: bool,
+    /// Number of turns before the action can fire again
+    cooldown: u64,
+    /// Effect to use instead during the cooldown
+    cooling: Option<Effect>,
+    /// Line in the scene file, if parsed from one
+    line: Option<usize>,
+}
+
+/// Serialized form of an [`Action`], with the regular expression as
+/// its source.
+#[derive(Serialize, Deserialize)]
+struct ActionDef {
+    source: String,
+    keyword: bool,
+    expression: String,
+    effect: Effect,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<Condition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    otherwise: Option<Effect>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    cooldown: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cooling: Option<Effect>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl From<Action> for ActionDef {
+    fn from(a: Action) -> Self {
+        ActionDef {
+            source: a.source,
+            keyword: a.keyword,
+            expression: a.expression.as_str().to_string(),
+            effect: a.effect,
+            condition: a.condition,
+            otherwise: a.otherwise,
+            tags: a.tags,
+            cooldown: a.cooldown,
+            cooling: a.cooling,
+        }
+    }
+}
+
+impl TryFrom<ActionDef> for Action {
+    type Error = regex::Error;
+
+    fn try_from(a: ActionDef) -> Result<Self, Self::Error> {
+        Ok(Action {
+            expression: Regex::new(&a.expression)?,
+            effect: a.effect,
+            condition: a.condition,
+            otherwise: a.otherwise,
+            tags: a.tags,
+            source: a.source,
+            keyword: a.keyword,
+            cooldown: a.cooldown,
+            cooling: a.cooling,
+            line: None,
+        })
+    }
 }
 
 impl Action {
+    /// Parse an action in the newest scene format, [`FORMAT`].
     pub fn new(line: &str) -> Result<Action, Box<dyn Error>> {
+        Action::parse(line, FORMAT)
+    }
+
+    /// Parse an action in the scene format `format`.
+    pub fn parse(line: &str, format: u32) -> Result<Action, Box<dyn Error>> {
         lazy_static! {
             static ref ACTION_RE: Regex =
-                Regex::new(r"^!(\w+):(.*)\s->\s(\w+)\s(.*)$").unwrap();
+                Regex::new(r"^!(\w+)(?:\[([^\]]*)\])?:(.*?)\s->\s(\w+)(?:\s(.*))?$")
+                    .unwrap();
+            static ref NPC_RE: Regex =
+                Regex::new(r"^(\w+)\s*->\s*(\w+)(?:\s+(.*))?$").unwrap();
+            static ref COOLDOWN_RE: Regex = Regex::new(
+                r"^(.*?)\s*\[cooldown\s+(\d+)\s*(?:\|\s*([^\]]*?))?\s*\]$"
+            )
+            .unwrap();
+            static ref CONDITION_RE: Regex = Regex::new(
+                r"^(.*?)\s*\[(if|needs)\s((?:[^|\]]|\|\|)*?)\s*(?:\|([^|\]][^\]]*?))?\s*\]$"
+            )
+            .unwrap();
         }
         let c = ACTION_RE
             .captures(line)
             .ok_or(format!("invalid action line: {}", line))?;
         let kind = &c[1];
-        let expression = &c[2];
-        let action = &c[3];
-        let argument = &c[4];
+        let tags = c.get(2).map_or_else(Vec::new, |t| {
+            t.as_str()
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect()
+        });
+        // Input is normalized to NFC before matching, see
+        // RegexMatcher, so patterns must be too.
+        let expression: String = c[3].nfc().collect();
+        let expression = expression.as_str();
+        let action = &c[4];
+        let mut argument = c.get(5).map_or("", |m| m.as_str());
+
+        let mut cooldown = 0;
+        let mut cooling = None;
+        if let Some(cc) =
+            COOLDOWN_RE.captures(argument).filter(|_| format >= 2)
+        {
+            argument = cc.get(1).unwrap().as_str();
+            cooldown = cc[2].parse()?;
+            cooling =
+                cc.get(3).map(|m| Effect::Output(m.as_str().to_string()));
+        }
 
-        let expr = if kind == "kw" {
+        let mut condition = None;
+        let mut otherwise = None;
+        if let Some(cc) =
+            CONDITION_RE.captures(argument).filter(|_| format >= 2)
+        {
+            argument = cc.get(1).unwrap().as_str();
+            condition = Some(if &cc[2] == "needs" {
+                Condition::Has(cc[3].to_string())
+            } else {
+                Condition::parse(&cc[3])?
+            });
+            otherwise = cc
+                .get(4)
+                .map(|m| Effect::Output(m.as_str().trim().to_string()));
+        }
+
+        let expr = if kind == "kw" || kind == "door" {
             Regex::new(&format!("^{}$", regex::escape(expression)))?
         } else {
             Regex::new(expression)?
         };
 
-        let effect = if action == "scene" {
+        let effect = if kind == "door" {
+            if !argument.is_empty() {
+                return Err(format!("invalid door: {}", line).into());
+            }
+            if condition.is_some() && otherwise.is_none() {
+                otherwise = Some(Effect::Output("It's locked.".to_string()));
+            }
+            Effect::Change(action.to_string())
+        } else if action == "scene" {
             Effect::Change(argument.to_string())
-        } else {
+        } else if format < 2 {
             Effect::Output(argument.to_string())
+        } else if action == "pushscene" {
+            if argument.is_empty() {
+                return Err(format!("missing scene: {}", line).into());
+            }
+            Effect::PushScene(argument.to_string())
+        } else if action == "popscene" {
+            if !argument.is_empty() {
+                return Err(format!("invalid popscene: {}", line).into());
+            }
+            Effect::PopScene
+        } else if action == "give" || action == "take" {
+            let (item, msg) = match argument.split_once(' ') {
+                Some((item, msg)) => (item, Some(msg.to_string())),
+                None => (argument, None),
+            };
+            if item.is_empty() {
+                return Err(format!("missing item: {}", line).into());
+            }
+            if action == "give" {
+                Effect::Give(item.to_string(), msg)
+            } else {
+                Effect::Take(item.to_string(), msg)
+            }
+        } else if action == "advance" {
+            let (duration, msg) = match argument.split_once(' ') {
+                Some((d, msg)) => (d, Some(msg.to_string())),
+                None => (argument, None),
+            };
+            Effect::Advance(
+                crate::clock::parse_duration(duration)
+                    .map_err(|e| format!("{}: {}", e, line))?,
+                msg,
+            )
+        } else if action == "adventure" {
+            let mut words = argument.split_whitespace();
+            let name =
+                words.next().ok_or(format!("missing adventure: {}", line))?;
+            Effect::ChangeAdventure(
+                name.to_string(),
+                words.next().map(|s| s.to_string()),
+            )
+        } else if action == "script" {
+            if argument.is_empty() {
+                return Err(format!("missing script: {}", line).into());
+            }
+            #[cfg(feature = "rhai")]
+            {
+                Effect::Script(argument.to_string())
+            }
+            #[cfg(not(feature = "rhai"))]
+            return Err(
+                format!("scripts need the rhai feature: {}", line).into()
+            );
+        } else if action == "npc" {
+            let nc = NPC_RE
+                .captures(argument)
+                .ok_or(format!("invalid NPC effect: {}", line))?;
+            Effect::Npc(
+                nc[1].to_string(),
+                nc[2].to_string(),
+                nc.get(3).map(|m| m.as_str().to_string()),
+            )
+        } else if action != "print" {
+            return Err(format!("unknown effect {}: {}", action, line).into());
+        } else {
+            parse_responses(argument)
+                .unwrap_or_else(|| Effect::Output(argument.to_string()))
         };
 
         Ok(Action {
             expression: expr,
             effect,
+            condition,
+            otherwise,
+            tags: if kind == "door"
+                && !tags.iter().any(|t| t.starts_with("exit"))
+            {
+                [vec!["exit".to_string()], tags].concat()
+            } else {
+                tags
+            },
+            source: expression.to_string(),
+            keyword: kind == "kw" || kind == "door",
+            cooldown,
+            cooling,
+            line: None,
         })
     }
 
+    /// Parse an action on line `n` of a scene file in `format`.
+    fn at_line(
+        line: &str,
+        n: usize,
+        format: u32,
+    ) -> Result<Action, Box<dyn Error>> {
+        let mut action = Action::parse(line, format)?;
+        action.line = Some(n);
+        Ok(action)
+    }
+
     pub fn effect(&self) -> &Effect {
         &self.effect
     }
@@ -130,12 +1089,174 @@ impl Action {
     pub fn expression(&self) -> &Regex {
         &self.expression
     }
+
+    /// The keyword or regular expression as written in the scene.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Line of the action in its scene file, counting from 1, `None`
+    /// if it wasn't parsed from a scene file.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// The keyword for `kw` and `door` actions, `None` for actions
+    /// using regular expressions.
+    pub fn keyword(&self) -> Option<&str> {
+        Some(self.source.as_str()).filter(|_| self.keyword)
+    }
+
+    /// An input that triggers the action: the keyword, or for regular
+    /// expressions a guess based on the first alternative. `None` if
+    /// the guess doesn't match.
+    pub fn example_input(&self) -> Option<String> {
+        if let Some(kw) = self.keyword() {
+            return Some(kw.to_string());
+        }
+        let re = self.source.trim_start_matches('^').trim_end_matches('$');
+        let re = re
+            .strip_prefix('(')
+            .and_then(|r| r.strip_suffix(')'))
+            .unwrap_or(re);
+        let guess = re.split('|').next()?.replace(r"\s+", " ");
+        Some(guess).filter(|g| self.expression.is_match(g))
+    }
+
+    pub fn condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
+    }
+
+    /// Tags of the action as written, e.g. `exit=north`.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Get the value of a tag: `Some("")` for tags without value,
+    /// `None` if the action doesn't have the tag.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find_map(|t| match t.split_once('=') {
+            Some((n, v)) if n.trim() == name => Some(v.trim()),
+            None if t == name => Some(""),
+            _ => None,
+        })
+    }
+
+    /// Label of the action if it is tagged as exit.
+    pub fn exit_label(&self) -> Option<&str> {
+        self.tag("exit")
+            .map(|l| if l.is_empty() { &self.source } else { l })
+    }
+
+    /// Effect to use instead if the condition is not met.
+    pub fn otherwise(&self) -> Option<&Effect> {
+        self.otherwise.as_ref()
+    }
+
+    /// Whether the action is tagged to fire only once.
+    pub fn once(&self) -> bool {
+        self.tag("once").is_some()
+    }
+
+    /// Number of turns after firing during which the action can't
+    /// fire again, 0 if there is no cooldown.
+    pub fn cooldown(&self) -> u64 {
+        self.cooldown
+    }
+
+    /// Effect to use instead during the cooldown.
+    pub fn cooling(&self) -> Option<&Effect> {
+        self.cooling.as_ref()
+    }
+}
+
+/// Parse a list of alternative responses like `["Meow!", "Mrrp?"]`,
+/// optionally preceded by `random`. Returns `None` if `argument`
+/// isn't one, so it's printed as is.
+fn parse_responses(argument: &str) -> Option<Effect> {
+    let (random, list) = match argument.strip_prefix("random ") {
+        Some(list) => (true, list.trim_start()),
+        None => (false, argument),
+    };
+    if !list.starts_with('[') {
+        return None;
+    }
+    let responses: Vec<String> = serde_json::from_str(list).ok()?;
+    if responses.is_empty() {
+        None
+    } else if random {
+        Some(Effect::Random(responses))
+    } else {
+        Some(Effect::Cycle(responses))
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Effect {
     Output(String),
+    /// Print alternative responses in turn, starting over after the
+    /// last one
+    Cycle(Vec<String>),
+    /// Print one of the alternative responses, chosen at random
+    Random(Vec<String>),
     Change(String),
+    /// Change to a scene, remembering the current one to return to
+    PushScene(String),
+    /// Return to the scene the last `pushscene` left
+    PopScene,
+    /// Give an item to the player, with an optional message
+    Give(String, Option<String>),
+    /// Take an item from the player, with an optional message
+    Take(String, Option<String>),
+    /// Change the state of an NPC, with an optional message
+    Npc(String, String, Option<String>),
+    /// Advance the in-game clock by minutes, with an optional message
+    Advance(u64, Option<String>),
+    /// Continue with another adventure of the campaign, optionally in
+    /// the given scene instead of its start scene
+    ChangeAdventure(String, Option<String>),
+    /// Run a script, relative to the scene file
+    #[cfg(feature = "rhai")]
+    Script(String),
+}
+
+impl Effect {
+    /// Name of the kind of effect, as in the serialized form, e.g.
+    /// `change` for a scene change.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Effect::Output(_) => "output",
+            Effect::Cycle(_) => "cycle",
+            Effect::Random(_) => "random",
+            Effect::Change(_) => "change",
+            Effect::PushScene(_) => "push_scene",
+            Effect::PopScene => "pop_scene",
+            Effect::Give(..) => "give",
+            Effect::Take(..) => "take",
+            Effect::Npc(..) => "npc",
+            Effect::Advance(..) => "advance",
+            Effect::ChangeAdventure(..) => "change_adventure",
+            #[cfg(feature = "rhai")]
+            Effect::Script(_) => "script",
+        }
+    }
+
+    /// What the effect applies to: the scene, item, NPC, adventure,
+    /// or script file. Scene targets may include parameters.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Effect::Change(t)
+            | Effect::PushScene(t)
+            | Effect::Give(t, _)
+            | Effect::Take(t, _)
+            | Effect::Npc(t, ..)
+            | Effect::ChangeAdventure(t, _) => Some(t),
+            #[cfg(feature = "rhai")]
+            Effect::Script(t) => Some(t),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -156,6 +1277,254 @@ mod tests {
         assert_eq!(a.effect, Effect::Output("\"Meow!\" =^.^=".to_string()));
         assert_eq!(a.expression().as_str(), r"^meow$");
         assert!(a.expression().is_match("meow"));
+        assert_eq!(a.keyword(), Some("meow"));
+        let a = Action::new("!regex:^(hug|cuddle)$ -> scene arms").unwrap();
+        assert_eq!(a.keyword(), None);
+    }
+
+    #[test]
+    fn parse_conditional_action() {
+        let a = Action::new(
+            "!kw:north -> scene cave [if lamp == lit | It's too dark.]",
+        )
+        .unwrap();
+        assert_eq!(a.effect, Effect::Change("cave".to_string()));
+        assert_eq!(a.condition().unwrap().to_string(), "lamp == lit");
+        assert_eq!(
+            a.otherwise(),
+            Some(&Effect::Output("It's too dark.".to_string()))
+        );
+
+        let a =
+            Action::new("!kw:take yarn -> give yarn [needs paws]").unwrap();
+        assert_eq!(a.effect, Effect::Give("yarn".to_string(), None));
+        assert_eq!(a.condition(), Some(&Condition::Has("paws".to_string())));
+        assert!(a.otherwise().is_none());
+    }
+
+    #[test]
+    fn parse_expression_condition() {
+        let a = Action::new(
+            "!kw:north -> scene cave [if has(lamp) || visits(cave) > 0 \
+             | It's too dark.]",
+        )
+        .unwrap();
+        assert_eq!(
+            a.condition().unwrap().to_string(),
+            "has(lamp) || visits(cave) > 0"
+        );
+        assert_eq!(
+            a.otherwise(),
+            Some(&Effect::Output("It's too dark.".to_string()))
+        );
+        let a = Action::new("!kw:pet -> print *purr* [if a || b]").unwrap();
+        assert_eq!(a.condition().unwrap().to_string(), "a || b");
+        assert!(a.otherwise().is_none());
+    }
+
+    #[test]
+    fn entry_condition() {
+        let s = Scene::parse(
+            PathBuf::from("cave.scene"),
+            "@enter: has(lamp) || score >= 10 | It's too dark.\nA cave.\n",
+        )
+        .unwrap();
+        let (c, text) = s.entry_condition().unwrap();
+        assert_eq!(c.to_string(), "has(lamp) || score >= 10");
+        assert_eq!(text, Some("It's too dark."));
+        let s =
+            Scene::parse(PathBuf::from("cave.scene"), "@enter: has lamp\n")
+                .unwrap();
+        assert_eq!(s.entry_condition().unwrap().1, None);
+    }
+
+    #[test]
+    fn parse_npc_effect() {
+        let a = Action::new(
+            "!kw:shout -> npc guard -> alerted The guard spins around!",
+        )
+        .unwrap();
+        assert!(a.expression().is_match("shout"));
+        assert_eq!(
+            a.effect,
+            Effect::Npc(
+                "guard".to_string(),
+                "alerted".to_string(),
+                Some("The guard spins around!".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn parse_tags() {
+        let a = Action::new("!kw[exit]:north -> scene cave").unwrap();
+        assert_eq!(a.tag("exit"), Some(""));
+        assert_eq!(a.exit_label(), Some("north"));
+        assert!(a.expression().is_match("north"));
+
+        let a =
+            Action::new("!regex[exit=up, quiet]:^(up|climb)$ -> scene tree")
+                .unwrap();
+        assert_eq!(a.exit_label(), Some("up"));
+        assert_eq!(a.tag("quiet"), Some(""));
+        assert_eq!(a.tag("once"), None);
+
+        let a = Action::new("!door:west -> garden").unwrap();
+        assert_eq!(a.exit_label(), Some("west"));
+        assert!(Action::new("!kw:meow -> print Mew")
+            .unwrap()
+            .tag("exit")
+            .is_none());
+    }
+
+    #[test]
+    fn parse_responses() {
+        let a =
+            Action::new(r#"!kw:meow -> print ["Meow!", "Mrrp?"]"#).unwrap();
+        assert_eq!(
+            a.effect,
+            Effect::Cycle(vec!["Meow!".to_string(), "Mrrp?".to_string()])
+        );
+        let a = Action::new(
+            r#"!kw:meow -> print random ["Meow!", "*ignores you*"] [needs yarn]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            a.effect,
+            Effect::Random(vec![
+                "Meow!".to_string(),
+                "*ignores you*".to_string()
+            ])
+        );
+        assert!(a.condition().is_some());
+        // Anything else is printed as is.
+        for text in ["[citation needed]", "random [] stuff", "[]"] {
+            let a =
+                Action::new(&format!("!kw:meow -> print {}", text)).unwrap();
+            assert_eq!(a.effect, Effect::Output(text.to_string()));
+        }
+    }
+
+    #[test]
+    fn parse_script() {
+        let line = "!kw:pull lever -> script lever.rhai";
+        let a = Action::parse(line, 2);
+        #[cfg(feature = "rhai")]
+        assert_eq!(
+            a.unwrap().effect,
+            Effect::Script("lever.rhai".to_string())
+        );
+        #[cfg(not(feature = "rhai"))]
+        assert!(a
+            .unwrap_err()
+            .to_string()
+            .starts_with("scripts need the rhai feature"));
+        assert!(Action::parse("!kw:pull lever -> script", 2).is_err());
+        // Before format 2 "script" was just another word for print.
+        assert_eq!(
+            Action::parse(line, 1).unwrap().effect,
+            Effect::Output("lever.rhai".to_string())
+        );
+    }
+
+    #[test]
+    fn baseline_format() {
+        let scene = Scene::parse(
+            PathBuf::from("chapel.scene"),
+            "A chapel.\n\
+             !kw:look -> print Nothing [if you dare]\n\
+             !kw:pray -> script Our Father\n\
+             !kw:quit -> give up and leave\n\
+             !kw:sing -> print [\"la\", \"la\"]\n\
+             !kw:out -> scene yard [needs key]\n",
+        )
+        .unwrap();
+        let output = |s: &str| Effect::Output(s.to_string());
+        assert_eq!(
+            scene
+                .actions()
+                .iter()
+                .map(Action::effect)
+                .collect::<Vec<_>>(),
+            [
+                &output("Nothing [if you dare]"),
+                &output("Our Father"),
+                &output("up and leave"),
+                &output("[\"la\", \"la\"]"),
+                &Effect::Change("yard [needs key]".to_string()),
+            ]
+        );
+        assert!(scene.actions().iter().all(|a| a.condition().is_none()));
+        // Format 2 reads the same lines with the newer grammar.
+        assert_eq!(
+            Action::parse("!kw:quit -> give up and leave", 2)
+                .unwrap()
+                .effect,
+            Effect::Give("up".to_string(), Some("and leave".to_string()))
+        );
+        assert!(Action::parse("!kw:look -> print Nothing [if you dare]", 2)
+            .is_err());
+    }
+
+    #[test]
+    fn parse_once_and_cooldown() {
+        let a = Action::new("!kw[once]:take key -> give key").unwrap();
+        assert!(a.once());
+        assert_eq!(a.cooldown(), 0);
+
+        let a = Action::new(
+            "!kw:pet -> print *purr* [if mood == happy | Hiss!] \
+             [cooldown 3 | The kitten has had enough.]",
+        )
+        .unwrap();
+        assert!(!a.once());
+        assert_eq!(a.effect, Effect::Output("*purr*".to_string()));
+        assert_eq!(a.condition().unwrap().to_string(), "mood == happy");
+        assert_eq!(a.otherwise(), Some(&Effect::Output("Hiss!".to_string())));
+        assert_eq!(a.cooldown(), 3);
+        assert_eq!(
+            a.cooling(),
+            Some(&Effect::Output("The kitten has had enough.".to_string()))
+        );
+
+        let a = Action::new("!kw:meow -> print Meow! [cooldown 1]").unwrap();
+        assert_eq!(a.effect, Effect::Output("Meow!".to_string()));
+        assert_eq!(a.cooldown(), 1);
+        assert!(a.cooling().is_none());
+    }
+
+    #[test]
+    fn example_input() {
+        let input = |line: &str| Action::new(line).unwrap().example_input();
+        assert_eq!(input("!kw:pet cat -> print *purr*").unwrap(), "pet cat");
+        assert_eq!(
+            input("!regex:^(hug|cuddle)$ -> scene arms").unwrap(),
+            "hug"
+        );
+        assert_eq!(
+            input(r"!regex:^look\s+up$ -> print Stars.").unwrap(),
+            "look up"
+        );
+        assert_eq!(input(r"!regex:^go (north|n)$ -> scene cave"), None);
+    }
+
+    #[test]
+    fn parse_door() {
+        let a = Action::new(
+            "!door:north -> cave [needs key | The door is locked.]",
+        )
+        .unwrap();
+        assert!(a.expression().is_match("north"));
+        assert_eq!(a.effect, Effect::Change("cave".to_string()));
+        assert_eq!(a.condition(), Some(&Condition::Has("key".to_string())));
+        assert_eq!(
+            a.otherwise(),
+            Some(&Effect::Output("The door is locked.".to_string()))
+        );
+
+        let a = Action::new("!door:south -> meadow").unwrap();
+        assert_eq!(a.effect, Effect::Change("meadow".to_string()));
+        assert!(a.condition().is_none());
     }
 
     #[test]
@@ -182,6 +1551,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scene_prompt() {
+        let s = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@prompt: Meow? \nA kitten looks at you.\n!kw:meow -> print Mew!\n",
+        )
+        .unwrap();
+        assert_eq!(s.prompt(), Some("Meow? "));
+        assert_eq!(s.description, "A kitten looks at you.\n");
+        assert!(kitten_scene().prompt().is_none());
+    }
+
+    #[test]
+    fn scene_variables() {
+        let s = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@set dark=true\n@set mood = sleepy\nIt's dark.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            s.variables().iter().collect::<Vec<_>>(),
+            [
+                (&"dark".to_string(), &"true".to_string()),
+                (&"mood".to_string(), &"sleepy".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn scene_items() {
+        let s = Scene::parse(
+            PathBuf::from("test.scene"),
+            "@item: yarn = A ball of yarn.\n@container: basket = A basket.\n\
+             @item: treat in basket\nA cozy room.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            s.items(),
+            [
+                SceneItem {
+                    id: "yarn".to_string(),
+                    container: None,
+                    description: "A ball of yarn.".to_string(),
+                },
+                SceneItem {
+                    id: "treat".to_string(),
+                    container: Some("basket".to_string()),
+                    description: "".to_string(),
+                },
+            ]
+        );
+        assert_eq!(s.containers().get("basket").unwrap(), "A basket.");
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown directive: @purr")]
+    fn unknown_directive() {
+        Scene::parse(PathBuf::from("test.scene"), "@purr\nMeow!\n").unwrap();
+    }
+
     #[test]
     fn change_scene() {
         let mut s = kitten_scene();
@@ -212,4 +1641,281 @@ mod tests {
             Effect::Change("kitten".to_string())
         );
     }
+
+    #[test]
+    fn scene_tests() {
+        let s = Scene::parse(
+            PathBuf::from("test.scene"),
+            "A kitten.\n!test: meow => \"Meow!\"\n\
+             !kw:meow -> print Meow!\n\
+             !test: hug; pet => *purr*\n",
+        )
+        .unwrap();
+        assert_eq!(s.description, "A kitten.\n");
+        assert_eq!(s.actions().len(), 1);
+        let tests = s.tests();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].inputs(), ["meow"]);
+        assert_eq!(tests[0].expected(), "Meow!");
+        assert_eq!(tests[1].inputs(), ["hug", "pet"]);
+        assert_eq!(tests[1].to_string(), "hug; pet => \"*purr*\"");
+        assert!(
+            Scene::parse(PathBuf::from("t.scene"), "!test: meow\n").is_err()
+        );
+    }
+
+    #[test]
+    fn scene_format() {
+        let parse = |text: &str| Scene::parse(PathBuf::from("t.scene"), text);
+        let old = parse("A cat.\n!kw:meow -> say Meow!\n").unwrap();
+        assert_eq!(old.format(), 1);
+        assert_eq!(
+            old.actions()[0].effect(),
+            &Effect::Output("Meow!".to_string())
+        );
+        let new =
+            parse("@format: 2\nA cat.\n!kw:meow -> print Meow!\n").unwrap();
+        assert_eq!(new.format(), 2);
+        assert_eq!(
+            parse(
+                "@format: 2\nA cat.\n!kw:meow -> print Meow!\n\
+                 !kw:nap -> scnee bed\n"
+            )
+            .unwrap_err()
+            .to_string(),
+            "line 4: unknown effect scnee: !kw:nap -> scnee bed"
+        );
+        assert_eq!(
+            parse("@format: 3\nA cat.\n").unwrap_err().to_string(),
+            "line 1: engine too old for scene format 3, it reads formats \
+             up to 2"
+        );
+        assert_eq!(
+            parse("@prompt: ?\n@bogus: 1\n").unwrap_err().to_string(),
+            "line 2: unknown directive: @bogus: 1"
+        );
+        assert!(parse("@format: 0\nA cat.\n").is_err());
+    }
+
+    #[test]
+    fn lenient_scene() {
+        let text = "A cat.\n!kw:meow -> print Meow!\n\
+                    !kw:pet print Purr\n!kw:nap -> scene bed\n";
+        assert!(Scene::parse(PathBuf::from("t.scene"), text).is_err());
+        let (scene, warnings) =
+            Scene::parse_lenient(PathBuf::from("t.scene"), text).unwrap();
+        assert!(scene.lenient());
+        assert_eq!(
+            scene
+                .actions()
+                .iter()
+                .map(|a| a.source())
+                .collect::<Vec<_>>(),
+            ["meow", "nap"]
+        );
+        assert_eq!(
+            warnings[0].message,
+            "line 3: skipped invalid action: \
+             invalid action line: !kw:pet print Purr"
+        );
+        let next = scene
+            .load_next_from("bed", |_| Ok("A bed.\n!kw:up\n".to_string()))
+            .unwrap();
+        assert!(next.lenient());
+        assert!(next.actions().is_empty());
+    }
+
+    #[test]
+    fn scene_warnings() {
+        let (_, warnings) = Scene::parse_with_warnings(
+            PathBuf::from("t.scene"),
+            "@format: 2\n!kw meow -> print Meow!\n\
+             !kw:pet -> print Purr\n\
+             !kw:meow -> print Mew [needs yarn]\n\
+             !kw:meow -> print Meow!\n\
+             !kw:pet -> print Purr!\n",
+        )
+        .unwrap();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "t.scene: looks like an action, but isn't: \
+                 invalid action line: !kw meow -> print Meow!",
+                "t.scene: line 6: unreachable action, keyword pet is \
+                 already taken on line 3",
+            ]
+        );
+        let (_, warnings) =
+            Scene::parse_with_warnings(PathBuf::from("t.scene"), "\n")
+                .unwrap();
+        assert_eq!(warnings[0].message, "empty description");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn parse_warning() {
+        use std::io::{self, Write};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer(Arc::new(Mutex::new(Vec::new())));
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            Scene::parse(
+                PathBuf::from("t.scene"),
+                "A cat.\n!kw meow -> print Meow!\n!kw:pet -> print Purr\n",
+            )
+            .unwrap()
+        });
+        let log =
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("WARN"));
+        assert!(log.contains("looks like an action, but isn't"));
+        assert!(log.contains("!kw meow -> print Meow!"));
+        assert!(log.contains("file=t.scene"));
+    }
+
+    #[test]
+    fn scene_tags() {
+        let s = Scene::parse(
+            PathBuf::from("glade.scene"),
+            "@tags: forest, outdoors\n@tags: quiet\nA glade.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            s.meta().tags().collect::<Vec<_>>(),
+            ["forest", "outdoors", "quiet"]
+        );
+        assert!(s.meta().has_tag("forest"));
+        assert!(!s.meta().has_tag("cave"));
+    }
+
+    #[test]
+    fn serialize_action() {
+        let a = Action::new(
+            "!kw[exit]:north -> scene cave [if lamp == lit | Too dark.]",
+        )
+        .unwrap();
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":"north","keyword":true,"expression":"^north$","#
+                .to_owned()
+                + r#""effect":{"change":"cave"},"condition":"lamp == lit","#
+                + r#""otherwise":{"output":"Too dark."},"tags":["exit"]}"#
+        );
+        let b: Action = serde_json::from_str(&json).unwrap();
+        assert!(b.expression().is_match("north"));
+        assert_eq!(b.condition(), a.condition());
+        assert_eq!(b.exit_label(), Some("north"));
+        assert!(serde_json::from_str::<Action>(
+            r#"{"source":"(","keyword":false,"expression":"(",
+                "effect":"pop_scene"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn serialize_effects() {
+        for (effect, json) in [
+            (Effect::PopScene, r#""pop_scene""#),
+            (
+                Effect::Give("yarn".to_string(), None),
+                r#"{"give":["yarn",null]}"#,
+            ),
+            (
+                Effect::Cycle(vec!["a".to_string(), "b".to_string()]),
+                r#"{"cycle":["a","b"]}"#,
+            ),
+            (Effect::Advance(90, None), r#"{"advance":[90,null]}"#),
+        ] {
+            assert_eq!(serde_json::to_string(&effect).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Effect>(json).unwrap(), effect);
+        }
+        let meta =
+            Scene::parse(PathBuf::from("glade.scene"), "@tags: b, a\n")
+                .unwrap()
+                .meta()
+                .clone();
+        let json = serde_json::to_string(&meta).unwrap();
+        assert_eq!(json, r#"{"tags":["a","b"]}"#);
+        assert_eq!(serde_json::from_str::<SceneMeta>(&json).unwrap(), meta);
+    }
+
+    #[test]
+    fn inspect_actions() {
+        let scene = Scene::parse(
+            PathBuf::from("hall.scene"),
+            "@format: 2\nA hall.\n\
+             !door[exit=upstairs]:up -> attic [if has key]\n\
+             !kw:take key -> give key You take the key.\n\
+             !regex:^(wave|greet)$ -> print Hello!\n",
+        )
+        .unwrap();
+        let summary: Vec<_> = scene
+            .actions()
+            .iter()
+            .map(|a| (a.source(), a.effect().kind(), a.effect().target()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("up", "change", Some("attic")),
+                ("take key", "give", Some("key")),
+                ("^(wave|greet)$", "output", None),
+            ]
+        );
+        assert_eq!(scene.actions()[0].tags(), ["exit=upstairs"]);
+        assert_eq!(scene.actions()[0].otherwise().unwrap().kind(), "output");
+    }
+
+    #[test]
+    fn grammar_tables() {
+        for d in DIRECTIVES {
+            let value = match *d {
+                "format" => "1",
+                "idle" => "30 -> print Yawn.",
+                _ => "has key = 1",
+            };
+            let text = format!("@{}: {}\nText.\n", d, value);
+            assert!(Scene::parse(PathBuf::from("t.scene"), &text).is_ok());
+        }
+        assert!(
+            Scene::parse(PathBuf::from("t.scene"), "@bogus: x\n").is_err()
+        );
+        for k in ACTION_KINDS {
+            let a = Action::new(&format!("!{}:x -> print", k)).unwrap();
+            assert_eq!(a.keyword().is_some(), *k != "regex");
+        }
+        // Effects must not fall back to printing, unless they fail.
+        for e in EFFECTS.iter().filter(|e| **e != "print") {
+            if let Ok(a) = Action::parse(&format!("!kw:x -> {} 1h", e), 2) {
+                assert_ne!(a.effect().kind(), "output", "{}", e);
+            }
+        }
+        for m in MODIFIERS {
+            let arg = if *m == "cooldown" { "2" } else { "has key" };
+            let a = Action::new(&format!("!kw:x -> print a [{} {}]", m, arg))
+                .unwrap();
+            assert!(a.condition().is_some() || a.cooldown() > 0, "{}", m);
+        }
+    }
 }
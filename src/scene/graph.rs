@@ -0,0 +1,379 @@
+//! The graph of scenes in an adventure: scenes are the nodes, actions
+//! that change the scene are the edges. A [`SceneGraph`] is built by
+//! loading every scene reachable from the start scene.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use super::{Effect, FileProvider, Scene, SceneProvider};
+use crate::pool;
+
+/// A scene change from one scene to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Name of the scene containing the action
+    pub from: String,
+    /// Index of the action in its scene
+    pub action: usize,
+    /// Target as written in the action, may include scene parameters
+    pub target: String,
+}
+
+impl Link {
+    /// Name of the target scene, without parameters.
+    pub fn to(&self) -> &str {
+        target_name(&self.target)
+    }
+}
+
+/// Links out of `scene`, in the order of its actions. Returns from a
+/// pushed scene are not links, because they depend on the game.
+pub fn scene_links(scene: &Scene) -> Vec<Link> {
+    let mut links = Vec::new();
+    for (a, action) in scene.actions().iter().enumerate() {
+        let effects = [Some(action.effect()), action.otherwise()];
+        for effect in effects.into_iter().flatten() {
+            if let Effect::Change(target) | Effect::PushScene(target) = effect
+            {
+                links.push(Link {
+                    from: scene.name().to_string(),
+                    action: a,
+                    target: target.to_string(),
+                });
+            }
+        }
+    }
+    links
+}
+
+/// Scene name of a scene change `target`, without parameters.
+fn target_name(target: &str) -> &str {
+    target.split_once('?').map_or(target, |(name, _)| name)
+}
+
+/// Load `target` with `provider`, converting errors to messages so
+/// they can be sent between threads.
+fn load(
+    provider: &dyn SceneProvider,
+    current: &Scene,
+    target: &str,
+) -> Result<Scene, String> {
+    provider
+        .load_next(current, target)
+        .map_err(|e| e.to_string())
+}
+
+/// Scenes reachable from a start scene, and the links between them.
+#[derive(Debug)]
+pub struct SceneGraph {
+    /// Scenes in the order they were found, breadth first
+    scenes: Vec<Scene>,
+    /// Index in `scenes` and `links` by scene name
+    index: BTreeMap<String, usize>,
+    /// Outgoing links of each scene
+    links: Vec<Vec<Link>>,
+    /// Link targets that could not be loaded, with the error
+    missing: BTreeMap<String, String>,
+}
+
+impl SceneGraph {
+    /// Crawl scene files, starting from `start`, loading them in
+    /// parallel with the default number of workers.
+    pub fn crawl(start: Scene) -> SceneGraph {
+        SceneGraph::crawl_parallel(
+            start,
+            &FileProvider,
+            pool::default_workers(),
+        )
+    }
+
+    /// Crawl scenes loaded by `provider`, starting from `start`.
+    /// Scenes that fail to load are recorded as
+    /// [missing](SceneGraph::missing), the crawl continues with the
+    /// others.
+    pub fn crawl_with(
+        start: Scene,
+        provider: &dyn SceneProvider,
+    ) -> SceneGraph {
+        SceneGraph::build(start, |requests| {
+            requests
+                .iter()
+                .map(|(current, target)| load(provider, current, target))
+                .collect()
+        })
+    }
+
+    /// Like [`crawl_with`](SceneGraph::crawl_with), but load the
+    /// scenes linked from each level of the graph with up to
+    /// `workers` threads. The result is the same, including the order
+    /// of scenes.
+    pub fn crawl_parallel(
+        start: Scene,
+        provider: &(dyn SceneProvider + Sync),
+        workers: usize,
+    ) -> SceneGraph {
+        SceneGraph::build(start, |requests| {
+            pool::map(requests, workers, |(current, target)| {
+                load(provider, current, target)
+            })
+        })
+    }
+
+    /// Crawl breadth first. For each level of the graph, `load` gets
+    /// the targets of all links to scenes not seen yet, with the
+    /// scene linking to them, and returns the loaded scenes in the
+    /// same order.
+    fn build<F>(start: Scene, load: F) -> SceneGraph
+    where
+        F: Fn(&[(&Scene, String)]) -> Vec<Result<Scene, String>>,
+    {
+        let mut graph = SceneGraph {
+            index: BTreeMap::from([(start.name().to_string(), 0)]),
+            scenes: vec![start],
+            links: Vec::new(),
+            missing: BTreeMap::new(),
+        };
+        let mut level = 0..1;
+        while !level.is_empty() {
+            let mut requests = Vec::new();
+            let mut names = BTreeSet::new();
+            for scene in &graph.scenes[level.clone()] {
+                let links = scene_links(scene);
+                for link in &links {
+                    let name = link.to().to_string();
+                    if !graph.index.contains_key(&name)
+                        && !graph.missing.contains_key(&name)
+                        && names.insert(name)
+                    {
+                        requests.push((scene, link.target.clone()));
+                    }
+                }
+                graph.links.push(links);
+            }
+
+            let loaded = load(&requests);
+            let names: Vec<String> = requests
+                .iter()
+                .map(|(_, t)| target_name(t).to_string())
+                .collect();
+            level = graph.scenes.len()..graph.scenes.len();
+            for (name, scene) in names.into_iter().zip(loaded) {
+                match scene {
+                    Ok(s) => {
+                        graph.index.insert(s.name().to_string(), level.end);
+                        graph.scenes.push(s);
+                        level.end += 1;
+                    }
+                    Err(e) => {
+                        graph.missing.insert(name, e);
+                    }
+                }
+            }
+        }
+        debug!(
+            scenes = graph.scenes.len(),
+            missing = graph.missing.len(),
+            "scene graph crawled"
+        );
+        graph
+    }
+
+    /// The scene the crawl started from.
+    pub fn start(&self) -> &Scene {
+        &self.scenes[0]
+    }
+
+    /// All scenes, in breadth first order from the start scene.
+    pub fn scenes(&self) -> impl Iterator<Item = &Scene> {
+        self.scenes.iter()
+    }
+
+    pub fn scene(&self, name: &str) -> Option<&Scene> {
+        self.index.get(name).map(|&i| &self.scenes[i])
+    }
+
+    /// Links out of the scene `name`, in the order of its actions.
+    /// Empty if there is no such scene.
+    pub fn links(&self, name: &str) -> &[Link] {
+        self.index.get(name).map_or(&[], |&i| &self.links[i])
+    }
+
+    /// Names of the scenes the scene `name` links to directly,
+    /// including missing ones, without duplicates.
+    pub fn successors(&self, name: &str) -> Vec<&str> {
+        let mut seen = BTreeSet::new();
+        self.links(name)
+            .iter()
+            .map(|l| l.to())
+            .filter(|&t| seen.insert(t))
+            .collect()
+    }
+
+    /// Link targets that could not be loaded, with the error message.
+    pub fn missing(&self) -> &BTreeMap<String, String> {
+        &self.missing
+    }
+
+    /// Names of all scenes reachable from the scene `from`, including
+    /// itself. Missing scenes are not included.
+    pub fn reachable(&self, from: &str) -> BTreeSet<&str> {
+        let mut seen = BTreeSet::new();
+        let Some(start) = self.scene(from) else {
+            return seen;
+        };
+        let mut queue = VecDeque::from([start.name()]);
+        seen.insert(start.name());
+        while let Some(name) = queue.pop_front() {
+            for next in self.successors(name) {
+                if self.index.contains_key(next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Fewest links to follow to get from the scene `from` to the
+    /// scene `to`, ignoring conditions. Empty if `from` and `to` are
+    /// the same, `None` if `to` isn't reachable.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<&Link>> {
+        self.scene(from)?;
+        // The link each scene was first reached by
+        let mut via: BTreeMap<&str, Option<&Link>> =
+            BTreeMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(name) = queue.pop_front() {
+            if name == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while let Some(link) = via[current] {
+                    path.push(link);
+                    current = &link.from;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for link in self.links(name) {
+                let next = link.to();
+                if self.index.contains_key(next) && !via.contains_key(next) {
+                    via.insert(next, Some(link));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::path::PathBuf;
+
+    /// Scenes from memory instead of files.
+    #[derive(Debug)]
+    struct Scenes(BTreeMap<&'static str, &'static str>);
+
+    impl SceneProvider for Scenes {
+        fn load_next(
+            &self,
+            _current: &Scene,
+            name: &str,
+        ) -> Result<Scene, Box<dyn Error>> {
+            let name = name.split_once('?').map_or(name, |(n, _)| n);
+            let text = self.0.get(name).ok_or("no such scene")?;
+            Scene::parse(PathBuf::from(format!("{}.scene", name)), text)
+        }
+    }
+
+    fn scenes() -> Scenes {
+        Scenes(BTreeMap::from([
+            (
+                "hall",
+                "A hall.\n!kw:north -> scene library\n\
+                 !kw:west -> scene kitchen?light=on\n",
+            ),
+            ("library", "Books.\n!kw:south -> scene hall\n"),
+            (
+                "kitchen",
+                "@format: 2\nA kitchen.\n!kw:east -> scene hall\n\
+                 !kw:down -> scene cellar [needs lamp | Too dark.]\n\
+                 !kw:jump -> scene pit\n",
+            ),
+            ("cellar", "Dark.\n!kw:up -> scene kitchen\n"),
+        ]))
+    }
+
+    fn graph() -> SceneGraph {
+        let scenes = scenes();
+        let start = scenes.load_next(&Scene::default(), "hall").unwrap();
+        SceneGraph::crawl_with(start, &scenes)
+    }
+
+    #[test]
+    fn crawl() {
+        let g = graph();
+        assert_eq!(g.start().name(), "hall");
+        assert_eq!(
+            g.scenes().map(|s| s.name()).collect::<Vec<_>>(),
+            vec!["hall", "library", "kitchen", "cellar"]
+        );
+        assert_eq!(g.successors("hall"), vec!["library", "kitchen"]);
+        assert_eq!(g.successors("kitchen"), vec!["hall", "cellar", "pit"]);
+        assert!(g.successors("attic").is_empty());
+        assert_eq!(g.links("hall")[1].target, "kitchen?light=on");
+        assert_eq!(g.links("kitchen")[2].action, 2);
+        assert_eq!(
+            g.missing(),
+            &BTreeMap::from([(
+                "pit".to_string(),
+                "no such scene".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn parallel_crawl() {
+        let g = graph();
+        let scenes = scenes();
+        for workers in [1, 2, 8] {
+            let start = scenes.load_next(&Scene::default(), "hall").unwrap();
+            let p = SceneGraph::crawl_parallel(start, &scenes, workers);
+            assert_eq!(
+                p.scenes().map(|s| s.name()).collect::<Vec<_>>(),
+                g.scenes().map(|s| s.name()).collect::<Vec<_>>()
+            );
+            for s in g.scenes() {
+                assert_eq!(p.links(s.name()), g.links(s.name()));
+            }
+            assert_eq!(p.missing(), g.missing());
+        }
+    }
+
+    #[test]
+    fn reachability() {
+        let g = graph();
+        assert_eq!(
+            g.reachable("library"),
+            BTreeSet::from(["cellar", "hall", "kitchen", "library"])
+        );
+        assert_eq!(
+            g.reachable("cellar"),
+            BTreeSet::from(["cellar", "hall", "kitchen", "library"])
+        );
+        assert!(g.reachable("pit").is_empty());
+    }
+
+    #[test]
+    fn shortest_path() {
+        let g = graph();
+        let path = g.shortest_path("library", "cellar").unwrap();
+        assert_eq!(
+            path.iter().map(|l| l.to()).collect::<Vec<_>>(),
+            vec!["hall", "kitchen", "cellar"]
+        );
+        assert_eq!(path[2].action, 1);
+        assert_eq!(g.shortest_path("hall", "hall"), Some(vec![]));
+        assert_eq!(g.shortest_path("hall", "pit"), None);
+    }
+}
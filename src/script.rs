@@ -0,0 +1,173 @@
+//! Actions running [Rhai](https://rhai.rs) scripts, for logic the
+//! scene format can't express: `!kw:pull lever -> script lever.rhai`
+//! runs `lever.rhai` from the directory of the scene. Scripts need
+//! scene format 2 (`@format: 2`), before that `script` prints the
+//! rest of the line like any other unknown effect word.
+//!
+//! Scripts can use these functions to access the game state:
+//!
+//! * `has(item)`, `give(item)`, `take(item)`: check, add, or remove
+//!   an item in the inventory
+//! * `get(name)` and `set(name, value)`: read and write variables,
+//!   `get` returns `()` for unset variables
+//! * `visits(scene)`: number of times the player entered a scene
+//! * `npc(name)` and `set_npc(name, state)`: state of an NPC, `npc`
+//!   returns `()` if it hasn't changed
+//! * `scene(name)`: move the player to another scene after the
+//!   script is done
+//!
+//! Output of `print` is shown to the player. Scripts that run too
+//! long or recurse too deep are stopped with an error, so a loop in a
+//! script can't hang the game.
+
+use rhai::Dynamic;
+use std::cell::RefCell;
+use std::error::Error;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::state::GameState;
+
+/// Operations a script may run before it is stopped.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Nesting depth of function calls in scripts.
+const MAX_CALL_LEVELS: usize = 32;
+
+/// What a script did besides changing the game state.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// Printed lines
+    pub output: Vec<String>,
+    /// Scene to move to
+    pub scene: Option<String>,
+}
+
+/// Run the script at `path` with access to `state`. If the script
+/// fails, changes it made to the state are kept.
+pub fn run(
+    path: &Path,
+    state: &mut GameState,
+) -> Result<Outcome, Box<dyn Error>> {
+    let shared = Rc::new(RefCell::new(std::mem::take(state)));
+    let outcome = Rc::new(RefCell::new(Outcome::default()));
+    let res = {
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        let s = Rc::clone(&shared);
+        engine.register_fn("has", move |item: &str| s.borrow().has(item));
+        let s = Rc::clone(&shared);
+        engine.register_fn("give", move |item: &str| {
+            s.borrow_mut().give(item);
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("take", move |item: &str| {
+            s.borrow_mut().take(item);
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("get", move |name: &str| {
+            s.borrow()
+                .get(name)
+                .map_or(Dynamic::UNIT, |v| v.to_string().into())
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("set", move |name: &str, value: Dynamic| {
+            s.borrow_mut().set(name, &value.to_string());
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("visits", move |scene: &str| {
+            i64::from(s.borrow().visits(scene))
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("npc", move |npc: &str| {
+            s.borrow()
+                .npc(npc)
+                .map_or(Dynamic::UNIT, |v| v.to_string().into())
+        });
+        let s = Rc::clone(&shared);
+        engine.register_fn("set_npc", move |npc: &str, state: &str| {
+            s.borrow_mut().set_npc(npc, state);
+        });
+        let o = Rc::clone(&outcome);
+        engine.register_fn("scene", move |name: &str| {
+            o.borrow_mut().scene = Some(name.to_string());
+        });
+        let o = Rc::clone(&outcome);
+        engine.on_print(move |text| {
+            o.borrow_mut().output.push(text.to_string())
+        });
+        engine.run_file(path.to_path_buf())
+    };
+    *state = shared.take();
+    res.map_err(|e| format!("script {} failed: {}", path.display(), e))?;
+    Ok(outcome.take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+    use std::fs;
+
+    #[test]
+    fn run_script() {
+        let fixture = TempAdventure::new("script", &[]);
+        let dir = fixture.dir();
+        let path = dir.join("lever.rhai");
+        fs::write(
+            &path,
+            r#"
+            let pulls = get("pulls");
+            let pulls = if pulls == () { 1 } else { parse_int(pulls) + 1 };
+            set("pulls", pulls);
+            if pulls >= 2 && !has("key") {
+                give("key");
+                print("A key drops out!");
+            } else {
+                print(`Click (${pulls}).`);
+            }
+            if visits("cellar") == 0 && npc("rat") == () {
+                set_npc("rat", "awake");
+                scene("cellar");
+            }
+            "#,
+        )
+        .unwrap();
+        let broken = dir.join("broken.rhai");
+        fs::write(&broken, "give(\"key\");\nlet x = ;\n").unwrap();
+        let endless = dir.join("endless.rhai");
+        fs::write(&endless, "loop { set(\"spin\", 1); }").unwrap();
+        let deep = dir.join("deep.rhai");
+        fs::write(&deep, "fn down(n) { down(n + 1) }\ndown(0);").unwrap();
+
+        let mut state = GameState::default();
+        let first = run(&path, &mut state);
+        let second = run(&path, &mut state);
+        let failed = run(&broken, &mut state);
+        let stopped = run(&endless, &mut state);
+        let too_deep = run(&deep, &mut state);
+
+        assert_eq!(
+            first.unwrap(),
+            Outcome {
+                output: vec!["Click (1).".to_string()],
+                scene: Some("cellar".to_string()),
+            }
+        );
+        assert_eq!(
+            second.unwrap(),
+            Outcome {
+                output: vec!["A key drops out!".to_string()],
+                scene: None,
+            }
+        );
+        assert!(state.has("key"));
+        assert_eq!(state.get("pulls"), Some("2"));
+        assert_eq!(state.npc("rat"), Some("awake"));
+        assert!(failed.unwrap_err().to_string().starts_with("script "));
+        assert!(stopped.is_err());
+        assert_eq!(state.get("spin"), Some("1"));
+        assert!(too_deep.is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Static web version of an adventure, for proofreading the story in
+//! a browser: `rustventure export html <dir>` writes one page per
+//! scene reachable from the start scene, with the scene text as
+//! written and links for the scene changes, plus an `index.html`
+//! listing all scenes. Links that depend on a condition are hidden
+//! behind a spoiler until clicked.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::adventure::Adventure;
+use crate::output::html::{self, escape};
+use crate::output::Stylesheet;
+use crate::scene::graph::{Link, SceneGraph};
+use crate::scene::Scene;
+use crate::twee::link_text;
+
+/// File name of the page for the scene `name`, without extension.
+pub fn page_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '-' | '_' => c,
+            c if c.is_alphanumeric() => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Unique page file names for the scenes of `graph`, by scene name.
+/// Scenes whose names differ only in characters replaced by
+/// [`page_name`], or only in case, get a number added.
+fn page_files(graph: &SceneGraph) -> HashMap<&str, String> {
+    // The index is a page of its own.
+    let mut taken = vec!["index".to_string()];
+    let mut files = HashMap::new();
+    for scene in graph.scenes() {
+        let base = page_name(scene.name());
+        let mut name = base.clone();
+        let mut i = 1;
+        while taken
+            .iter()
+            .any(|t| t.to_lowercase() == name.to_lowercase())
+        {
+            i += 1;
+            name = format!("{}_{}", base, i);
+        }
+        files.insert(scene.name(), format!("{}.html", name));
+        taken.push(name);
+    }
+    files
+}
+
+/// A list item linking to the scene of `link`.
+fn link_item(
+    scene: &Scene,
+    link: &Link,
+    files: &HashMap<&str, String>,
+) -> String {
+    let action = &scene.actions()[link.action];
+    let a = format!(
+        "<a href=\"{}\">{}</a>",
+        escape(&files[link.to()]),
+        escape(link_text(action, &link.target))
+    );
+    match action.condition() {
+        Some(c) => format!(
+            "<li><details><summary>Spoiler</summary>{} \
+             (if {})</details></li>\n",
+            a,
+            escape(&c.to_string())
+        ),
+        None => format!("<li>{}</li>\n", a),
+    }
+}
+
+/// The page of `scene`, with links to the scenes it leads to.
+fn scene_page(
+    graph: &SceneGraph,
+    scene: &Scene,
+    files: &HashMap<&str, String>,
+    styles: &Stylesheet,
+) -> String {
+    let links = graph.links(scene.name());
+    let mut body = format!(
+        "<h1>{}</h1>\n{}",
+        escape(scene.name()),
+        html::to_html(&scene.to_string(), styles)
+    );
+    if !links.is_empty() {
+        body.push_str("<ul>\n");
+        for link in links {
+            body.push_str(&link_item(scene, link, files));
+        }
+        body.push_str("</ul>\n");
+    }
+    body.push_str("<p><a href=\"index.html\">All scenes</a></p>\n");
+    html::page(scene.name(), &body, styles)
+}
+
+/// Write the pages of the adventure to `dir`, returns the paths of
+/// the written files. Fails if a scene change leads to a scene that
+/// cannot be loaded.
+pub fn export(
+    adventure: Adventure,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let styles = adventure.styles().clone();
+    let title = adventure.name().to_string();
+    let graph = SceneGraph::crawl(adventure.start()?);
+    if let Some((_, e)) = graph.missing().first_key_value() {
+        return Err(e.as_str().into());
+    }
+    let files = page_files(&graph);
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    let mut index = format!("<h1>{}</h1>\n<ul>\n", escape(&title));
+    for scene in graph.scenes() {
+        let page = &files[scene.name()];
+        let path = dir.join(page);
+        fs::write(&path, scene_page(&graph, scene, &files, &styles))?;
+        written.push(path);
+        index.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            escape(page),
+            escape(scene.name())
+        ));
+    }
+    index.push_str("</ul>\n");
+    let path = dir.join("index.html");
+    fs::write(&path, html::page(&title, &index, &styles))?;
+    written.push(path);
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adventure;
+    use crate::fixture::TempAdventure;
+
+    #[test]
+    fn export_site() {
+        let fixture = TempAdventure::new(
+            "site",
+            &[
+                (
+                    "adventure/about.yaml",
+                    "name: Cat & Mouse\nauthor: Fiona\nstyles:\n  \
+                     purr: italic\n",
+                ),
+                (
+                    "adventure/start.scene",
+                    "@format: 2\nA {purr}cozy{/purr} hall.\n!door:in -> den\n\
+                     !kw:climb -> scene attic [if has ladder]\n",
+                ),
+                ("adventure/den.scene", "A den.\n"),
+                ("adventure/attic.scene", "An attic.\n"),
+            ],
+        );
+        let site = fixture.path("site");
+        let adventure = adventure::load(&fixture.path("adventure")).unwrap();
+        let written = export(adventure, &site);
+        let start = fs::read_to_string(site.join("start.html"));
+        let index = fs::read_to_string(site.join("index.html"));
+
+        assert_eq!(
+            written.unwrap(),
+            ["start.html", "den.html", "attic.html", "index.html"]
+                .map(|p| site.join(p))
+        );
+        let start = start.unwrap();
+        assert!(start.contains(
+            "<h1>start</h1>\n\
+             <p>A <span class=\"style-purr\">cozy</span> hall.</p>\n\
+             <ul>\n<li><a href=\"den.html\">in</a></li>\n\
+             <li><details><summary>Spoiler</summary>\
+             <a href=\"attic.html\">climb</a> (if has ladder)\
+             </details></li>\n</ul>\n"
+        ));
+        let index = index.unwrap();
+        assert!(index.contains("<title>Cat &amp; Mouse</title>"));
+        assert!(index.contains("<li><a href=\"attic.html\">attic</a></li>"));
+        assert_eq!(page_name("a b?"), "a_b_");
+    }
+
+    #[test]
+    fn unique_pages() {
+        let fixture = TempAdventure::new(
+            "site-names",
+            &[
+                (
+                    "about.yaml",
+                    "name: Names\nauthor: Fiona\nstart: a b.scene\n",
+                ),
+                (
+                    "a b.scene",
+                    "Space.\n!kw:next -> scene a.b\n!kw:on -> scene A_b\n\
+                     !kw:list -> scene index\n",
+                ),
+                ("a.b.scene", "Dot.\n!kw:back -> scene a b\n"),
+                ("A_b.scene", "Capital.\n"),
+                ("index.scene", "Index.\n"),
+            ],
+        );
+        let site = fixture.path("site");
+        let adventure = adventure::load(fixture.dir()).unwrap();
+        let written = export(adventure, &site).unwrap();
+        let space = fs::read_to_string(site.join("a_b.html")).unwrap();
+        let dot = fs::read_to_string(site.join("a_b_2.html")).unwrap();
+
+        assert_eq!(
+            written,
+            [
+                "a_b.html",
+                "a_b_2.html",
+                "A_b_3.html",
+                "index_2.html",
+                "index.html"
+            ]
+            .map(|p| site.join(p))
+        );
+        assert!(space.contains("<a href=\"index_2.html\">list</a>"));
+        assert!(dot.contains("<h1>a.b</h1>"));
+        assert!(dot.contains("<a href=\"a_b.html\">back</a>"));
+    }
+}
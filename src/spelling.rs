@@ -0,0 +1,371 @@
+//! Spell-check for adventure text: with a `language` in `about.yaml`,
+//! `rustventure check` looks up the words of scene descriptions and
+//! printed responses in the Hunspell dictionary for that language,
+//! and warns about the words it can't find. Names and made-up words
+//! go in the `known_words` list of `about.yaml`.
+//!
+//! Dictionaries are the `<language>.dic` and `<language>.aff` files
+//! Hunspell uses, searched in the adventure directory, the
+//! directories in `DICPATH`, and the usual system locations. Of the
+//! affix file only plain prefix and suffix rules are used, that
+//! covers regular word forms like plurals and past tenses.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::scene::{Effect, Scene};
+
+/// Where distributions install Hunspell dictionaries.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// How the affix file writes flags, see `FLAG` in hunspell(5).
+#[derive(Clone, Copy)]
+enum Flags {
+    Char,
+    Long,
+    Num,
+}
+
+impl Flags {
+    fn split(self, flags: &str) -> Vec<String> {
+        match self {
+            Flags::Char => flags.chars().map(String::from).collect(),
+            Flags::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|c| c.iter().collect()).collect()
+            }
+            Flags::Num => flags.split(',').map(str::to_string).collect(),
+        }
+    }
+}
+
+/// A prefix or suffix rule of the affix file.
+struct Affix {
+    prefix: bool,
+    /// Whether the rule combines with rules of the other kind
+    cross: bool,
+    strip: String,
+    add: String,
+    condition: Regex,
+}
+
+impl Affix {
+    /// `word` with the affix, if the rule applies to it.
+    fn apply(&self, word: &str) -> Option<String> {
+        if !self.condition.is_match(word) {
+            return None;
+        }
+        if self.prefix {
+            let stem = word.strip_prefix(self.strip.as_str())?;
+            Some(format!("{}{}", self.add, stem))
+        } else {
+            let stem = word.strip_suffix(self.strip.as_str())?;
+            Some(format!("{}{}", stem, self.add))
+        }
+    }
+}
+
+/// Text of a dictionary file, which may use a legacy 8 bit encoding
+/// instead of UTF-8. Those are read as ISO 8859-1.
+fn read_text(path: &Path) -> Result<String, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    Ok(String::from_utf8(bytes).unwrap_or_else(|e| {
+        e.into_bytes().iter().map(|&b| char::from(b)).collect()
+    }))
+}
+
+/// Prefix and suffix rules of an affix file, by flag.
+struct Affixes {
+    flags: Flags,
+    rules: HashMap<String, Vec<Affix>>,
+}
+
+/// Parse the prefix and suffix rules of an affix file.
+fn parse_affixes(aff: &str) -> Result<Affixes, Box<dyn Error>> {
+    let mut flags = Flags::Char;
+    let mut affixes: HashMap<String, Vec<Affix>> = HashMap::new();
+    let mut cross = HashMap::new();
+    for line in aff.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["FLAG", "long", ..] => flags = Flags::Long,
+            ["FLAG", "num", ..] => flags = Flags::Num,
+            // The header of a rule group: kind, flag, cross product,
+            // and number of rules.
+            ["PFX" | "SFX", flag, c, n, ..]
+                if n.parse::<usize>().is_ok()
+                    && !cross.contains_key(flag) =>
+            {
+                cross.insert(flag.to_string(), c == "Y");
+            }
+            [kind @ ("PFX" | "SFX"), flag, strip, add, ..] => {
+                let prefix = kind == "PFX";
+                let condition = fields.get(4).copied().unwrap_or(".");
+                let condition = if prefix {
+                    format!("^(?:{})", condition)
+                } else {
+                    format!("(?:{})$", condition)
+                };
+                let zero = |s: &str| {
+                    if s == "0" {
+                        String::new()
+                    } else {
+                        s.to_string()
+                    }
+                };
+                // Continuation flags after the added text aren't
+                // supported.
+                let add = add.split('/').next().unwrap_or_default();
+                affixes.entry(flag.to_string()).or_default().push(Affix {
+                    prefix,
+                    cross: cross.get(flag).copied().unwrap_or(false),
+                    strip: zero(strip),
+                    add: zero(add),
+                    condition: Regex::new(&condition).map_err(|e| {
+                        format!(
+                            "invalid affix condition {}: {}",
+                            condition, e
+                        )
+                    })?,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(Affixes {
+        flags,
+        rules: affixes,
+    })
+}
+
+/// Words of a Hunspell dictionary, with all their forms.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Read the dictionary from the text of its `.dic` and `.aff`
+    /// files.
+    pub fn parse(dic: &str, aff: &str) -> Result<Dictionary, Box<dyn Error>> {
+        let affixes = parse_affixes(aff)?;
+        let mut words = HashSet::new();
+        for (i, line) in dic.lines().enumerate() {
+            // The first line is the number of words.
+            let entry = line.split_whitespace().next().unwrap_or_default();
+            if entry.is_empty() || (i == 0 && entry.parse::<usize>().is_ok())
+            {
+                continue;
+            }
+            let (word, word_flags) =
+                entry.split_once('/').unwrap_or((entry, ""));
+            let rules: Vec<&Affix> = affixes
+                .flags
+                .split(word_flags)
+                .iter()
+                .filter_map(|f| affixes.rules.get(f))
+                .flatten()
+                .collect();
+            for suffix in rules.iter().filter(|a| !a.prefix) {
+                let Some(form) = suffix.apply(word) else {
+                    continue;
+                };
+                if suffix.cross {
+                    for prefix in rules.iter().filter(|a| a.prefix && a.cross)
+                    {
+                        words.extend(prefix.apply(&form));
+                    }
+                }
+                words.insert(form);
+            }
+            for prefix in rules.iter().filter(|a| a.prefix) {
+                words.extend(prefix.apply(word));
+            }
+            words.insert(word.to_string());
+        }
+        Ok(Dictionary { words })
+    }
+
+    /// Load the dictionary `<language>.dic` from the first of `dirs`
+    /// that has it, or `None` if none does. The `.aff` file next to it
+    /// is optional.
+    pub fn load(
+        language: &str,
+        dirs: &[PathBuf],
+    ) -> Result<Option<Dictionary>, Box<dyn Error>> {
+        let Some(dic) = dirs
+            .iter()
+            .map(|d| d.join(format!("{}.dic", language)))
+            .find(|p| p.is_file())
+        else {
+            return Ok(None);
+        };
+        let aff = dic.with_extension("aff");
+        let aff = if aff.is_file() {
+            read_text(&aff)?
+        } else {
+            String::new()
+        };
+        Dictionary::parse(&read_text(&dic)?, &aff)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", dic.display(), e).into())
+    }
+
+    /// Accept `word` in addition to the words of the dictionary.
+    pub fn add(&mut self, word: &str) {
+        self.words.insert(word.to_lowercase());
+    }
+
+    /// Whether the dictionary has `word`. Words starting a sentence
+    /// or written in capitals may have a lowercase entry, and
+    /// capitals may stand for a capitalized entry.
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.replace('\u{2019}', "'");
+        let lower = word.to_lowercase();
+        let mut chars = lower.chars();
+        let capitalized: String = chars
+            .next()
+            .into_iter()
+            .flat_map(char::to_uppercase)
+            .collect();
+        let capitalized = capitalized + chars.as_str();
+        self.words.contains(&word)
+            || self.words.contains(&lower)
+            || (word.chars().all(|c| !c.is_lowercase())
+                && self.words.contains(&capitalized))
+    }
+}
+
+/// Directories to search for dictionaries of the adventure in `dir`:
+/// the adventure directory itself, those in `DICPATH`, and the
+/// system dictionary directories.
+pub fn search_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![dir.to_path_buf()];
+    if let Some(path) = env::var_os("DICPATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+    dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+/// Words in `text`, without variables (and a possessive `'s` after
+/// them), style tags, and escape sequences. Words with digits are
+/// skipped.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    lazy_static! {
+        static ref MARKUP_RE: Regex =
+            Regex::new(r"\$\{\w+\}(?:['’]s)?|\{/?\w+\}|\x1b\[[0-9;]*m")
+                .unwrap();
+        static ref WORD_RE: Regex =
+            Regex::new(r"[\p{Alphabetic}\d]+(?:['’][\p{Alphabetic}]+)*")
+                .unwrap();
+    }
+    let mut markup = MARKUP_RE.find_iter(text).peekable();
+    WORD_RE
+        .find_iter(text)
+        .filter(move |w| {
+            while markup.next_if(|m| m.end() <= w.start()).is_some() {}
+            markup.peek().is_none_or(|m| m.start() >= w.end())
+        })
+        .map(|w| w.as_str())
+        .filter(|w| !w.chars().any(|c| c.is_ascii_digit()))
+}
+
+/// Texts `effect` prints.
+fn effect_texts(effect: &Effect) -> Vec<&str> {
+    match effect {
+        Effect::Output(t) => vec![t],
+        Effect::Cycle(ts) | Effect::Random(ts) => {
+            ts.iter().map(String::as_str).collect()
+        }
+        Effect::Give(_, m)
+        | Effect::Take(_, m)
+        | Effect::Npc(_, _, m)
+        | Effect::Advance(_, m) => m.iter().map(String::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Words of the description of `scene` and the texts its actions
+/// print, for spell-checking.
+pub fn scene_words(scene: &Scene) -> BTreeSet<String> {
+    let description = scene.to_string();
+    let mut texts = vec![description.as_str()];
+    for action in scene.actions() {
+        texts.extend(effect_texts(action.effect()));
+        for effect in
+            [action.otherwise(), action.cooling()].into_iter().flatten()
+        {
+            texts.extend(effect_texts(effect));
+        }
+    }
+    texts
+        .into_iter()
+        .flat_map(words)
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\n\
+        PFX U Y 1\n\
+        PFX U 0 un .\n\
+        SFX S Y 2\n\
+        SFX S y ies [^aeiou]y\n\
+        SFX S 0 s [^y]\n\
+        SFX D N 1\n\
+        SFX D 0 ed/X [^e]\n";
+
+    #[test]
+    fn dictionary() {
+        let mut dict =
+            Dictionary::parse("4\ncat/S\nfly/SD\nlock/UDS\nParis\n", AFF)
+                .unwrap();
+        dict.add("Mrrp");
+        for word in [
+            "cat", "cats", "Cats", "CATS", "flies", "locked", "unlocks",
+            "unlock", "Paris", "PARIS", "mrrp",
+        ] {
+            assert!(dict.contains(word), "{}", word);
+        }
+        for word in ["cates", "flys", "unlocked", "paris", "it’s"] {
+            assert!(!dict.contains(word), "{}", word);
+        }
+        dict.add("it's");
+        assert!(dict.contains("it’s"));
+    }
+
+    #[test]
+    fn scene_text_words() {
+        let scene = Scene::parse(
+            PathBuf::from("hall.scene"),
+            "@format: 2\nA {purr}cosy{/purr} hall, ${name}'s 2nd home.\n\
+             !kw:pet -> print Purrr! [if has(cat) | No kitty.]\n\
+             !kw:open -> give key Got it.\n",
+        )
+        .unwrap();
+        assert_eq!(
+            scene_words(&scene),
+            [
+                "A", "Got", "No", "Purrr", "cosy", "hall", "home", "it",
+                "kitty"
+            ]
+            .map(String::from)
+            .into()
+        );
+    }
+}
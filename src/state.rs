@@ -0,0 +1,565 @@
+//! Game state that changes while playing.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Where an item is, if it has been moved from where the scene files
+/// put it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Location {
+    /// Carried by the player
+    Inventory,
+    /// Carried by a player in hot-seat mode, counting from 0, see
+    /// [`GameState::set_players`]
+    Player(usize),
+    /// Lying in a scene, optionally inside a container
+    Scene {
+        scene: String,
+        container: Option<String>,
+    },
+    /// Used up or otherwise gone
+    Nowhere,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Inventory => write!(f, "inventory"),
+            Location::Player(p) => write!(f, "player {}", p + 1),
+            Location::Scene {
+                scene,
+                container: Some(c),
+            } => write!(f, "{} in {}", c, scene),
+            Location::Scene {
+                scene,
+                container: None,
+            } => write!(f, "{}", scene),
+            Location::Nowhere => write!(f, "nowhere"),
+        }
+    }
+}
+
+/// A difference between two game states, see [`GameState::diff`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum Change {
+    Variable(String, Option<String>, Option<String>),
+    /// An item moved, `None` meaning where the scene files put it
+    Item(String, Option<Location>, Option<Location>),
+    Npc(String, Option<String>, Option<String>),
+    Visits(String, u32, u32),
+}
+
+/// Display an optional value, with `unset` for `None`.
+struct Opt<'a, T>(&'a Option<T>, &'static str);
+
+impl<T: fmt::Display> fmt::Display for Opt<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(v) => write!(f, "{}", v),
+            None => write!(f, "{}", self.1),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Variable(name, old, new) => write!(
+                f,
+                "variable {}: {} -> {}",
+                name,
+                Opt(old, "unset"),
+                Opt(new, "unset")
+            ),
+            Change::Item(item, old, new) => write!(
+                f,
+                "item {}: {} -> {}",
+                item,
+                Opt(old, "initial"),
+                Opt(new, "initial")
+            ),
+            Change::Npc(npc, old, new) => write!(
+                f,
+                "npc {}: {} -> {}",
+                npc,
+                Opt(old, "initial"),
+                Opt(new, "initial")
+            ),
+            Change::Visits(scene, old, new) => {
+                write!(f, "visits {}: {} -> {}", scene, old, new)
+            }
+        }
+    }
+}
+
+/// Changes between two maps, in key order.
+fn diff_maps<'a, V: Clone + PartialEq>(
+    old: &'a BTreeMap<String, V>,
+    new: &'a BTreeMap<String, V>,
+) -> impl Iterator<Item = (String, Option<V>, Option<V>)> + 'a {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter().filter_map(|k| {
+        let (o, n) = (old.get(k), new.get(k));
+        (o != n).then(|| (k.clone(), o.cloned(), n.cloned()))
+    })
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameState {
+    variables: BTreeMap<String, String>,
+    items: BTreeMap<String, Location>,
+    npcs: BTreeMap<String, String>,
+    visits: BTreeMap<String, u32>,
+    /// Known ways between scenes: scene -> input -> target scene
+    exits: BTreeMap<String, BTreeMap<String, String>>,
+    /// Number of commands the player entered
+    #[serde(default)]
+    turns: u64,
+    /// Turn each action fired on most recently: scene -> action
+    /// index -> turn
+    #[serde(default)]
+    fired: BTreeMap<String, BTreeMap<usize, u64>>,
+    /// Number of alternative responses each action gave: scene ->
+    /// action index -> count
+    #[serde(default)]
+    responses: BTreeMap<String, BTreeMap<usize, usize>>,
+    /// State of the random number generator
+    #[serde(default)]
+    rng: u64,
+    /// Minutes passed on the in-game clock
+    #[serde(default)]
+    elapsed: u64,
+    /// Scenes to return to with `popscene`, most recent last
+    #[serde(default)]
+    stack: Vec<String>,
+    /// Variables of each player in hot-seat mode, they hide those in
+    /// `variables` with the same name
+    #[serde(default)]
+    players: Vec<BTreeMap<String, String>>,
+    /// Whose turn it is in hot-seat mode, counting from 0
+    #[serde(default)]
+    player: usize,
+}
+
+impl GameState {
+    /// Get a variable, in hot-seat mode the one of the current player
+    /// if they set it.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.players
+            .get(self.player)
+            .and_then(|v| v.get(name))
+            .or_else(|| self.variables.get(name))
+            .map(|v| v.as_str())
+    }
+
+    /// Set a variable, in hot-seat mode only for the current player.
+    pub fn set(&mut self, name: &str, value: &str) {
+        let variables = match self.players.get_mut(self.player) {
+            Some(v) => v,
+            None => &mut self.variables,
+        };
+        variables.insert(name.to_string(), value.to_string());
+    }
+
+    /// Variables shared by all players.
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    /// Switch to hot-seat mode for `players` players taking turns,
+    /// each with their own inventory and variables. Variables set
+    /// before are shared, as initial values for everyone. Items still
+    /// exist only once, giving one to a player takes it from whoever
+    /// had it. With 1 it is a normal game.
+    pub fn set_players(&mut self, players: usize) {
+        let players = if players > 1 { players } else { 0 };
+        self.players.resize_with(players, BTreeMap::new);
+        self.player = 0;
+    }
+
+    /// Number of players taking turns.
+    pub fn players(&self) -> usize {
+        self.players.len().max(1)
+    }
+
+    /// Whose turn it is, counting from 0.
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Pass the turn to the next player.
+    pub fn next_player(&mut self) {
+        self.player = (self.player + 1) % self.players();
+    }
+
+    /// Variables of player number `player` in hot-seat mode.
+    pub fn player_variables(
+        &self,
+        player: usize,
+    ) -> Option<&BTreeMap<String, String>> {
+        self.players.get(player)
+    }
+
+    /// Where items the current player carries are.
+    fn carried(&self) -> Location {
+        match self.players.is_empty() {
+            true => Location::Inventory,
+            false => Location::Player(self.player),
+        }
+    }
+
+    pub fn has(&self, item: &str) -> bool {
+        self.items.get(item) == Some(&self.carried())
+    }
+
+    /// Add an item to the inventory, returns `false` if the player
+    /// already had it.
+    pub fn give(&mut self, item: &str) -> bool {
+        let carried = self.carried();
+        self.items
+            .insert(item.to_string(), carried.clone())
+            .as_ref()
+            != Some(&carried)
+    }
+
+    /// Remove an item from the inventory, returns `false` if the
+    /// player didn't have it.
+    pub fn take(&mut self, item: &str) -> bool {
+        if self.has(item) {
+            self.items.insert(item.to_string(), Location::Nowhere);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Items carried by the player.
+    pub fn inventory(&self) -> impl Iterator<Item = &str> {
+        let carried = self.carried();
+        self.items
+            .iter()
+            .filter(move |(_, l)| **l == carried)
+            .map(|(i, _)| i.as_str())
+    }
+
+    /// Location of an item, `None` if it hasn't been moved.
+    pub fn location(&self, item: &str) -> Option<&Location> {
+        self.items.get(item)
+    }
+
+    pub fn place(&mut self, item: &str, location: Location) {
+        self.items.insert(item.to_string(), location);
+    }
+
+    /// Current state of an NPC, `None` if it hasn't changed.
+    pub fn npc(&self, npc: &str) -> Option<&str> {
+        self.npcs.get(npc).map(|s| s.as_str())
+    }
+
+    /// NPCs whose state has changed, with their current state.
+    pub fn npcs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.npcs.iter().map(|(n, s)| (n.as_str(), s.as_str()))
+    }
+
+    pub fn set_npc(&mut self, npc: &str, state: &str) {
+        self.npcs.insert(npc.to_string(), state.to_string());
+    }
+
+    /// Count a visit to `scene`.
+    pub fn visit(&mut self, scene: &str) {
+        *self.visits.entry(scene.to_string()).or_default() += 1;
+    }
+
+    /// Number of times the player entered `scene`.
+    pub fn visits(&self, scene: &str) -> u32 {
+        self.visits.get(scene).copied().unwrap_or_default()
+    }
+
+    /// Visited scenes with their visit counts.
+    pub fn visited(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.visits.iter().map(|(s, v)| (s.as_str(), *v))
+    }
+
+    /// Remember that `input` in `scene` leads to `target`.
+    pub fn add_exit(&mut self, scene: &str, input: &str, target: &str) {
+        self.exits
+            .entry(scene.to_string())
+            .or_default()
+            .insert(input.to_string(), target.to_string());
+    }
+
+    /// Known exits from `scene` as (input, target) pairs.
+    pub fn known_exits(
+        &self,
+        scene: &str,
+    ) -> impl Iterator<Item = (&str, &str)> {
+        self.exits
+            .get(scene)
+            .into_iter()
+            .flatten()
+            .map(|(i, t)| (i.as_str(), t.as_str()))
+    }
+
+    /// Count a command entered by the player.
+    pub fn tick(&mut self) {
+        self.turns += 1;
+    }
+
+    /// Number of commands the player entered so far.
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    /// Remember `scene` (a scene reference, possibly with parameters)
+    /// to return to later.
+    pub fn push_scene(&mut self, scene: String) {
+        self.stack.push(scene);
+    }
+
+    /// The scene to return to, `None` if the stack is empty.
+    pub fn pop_scene(&mut self) -> Option<String> {
+        self.stack.pop()
+    }
+
+    /// Scenes to return to, most recent last.
+    pub fn scene_stack(&self) -> &[String] {
+        &self.stack
+    }
+
+    /// Advance the in-game clock by `minutes`.
+    pub fn advance(&mut self, minutes: u64) {
+        self.elapsed += minutes;
+    }
+
+    /// Minutes passed on the in-game clock.
+    pub fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
+
+    /// Remember that action number `index` of `scene` fired in the
+    /// current turn.
+    pub fn fire(&mut self, scene: &str, index: usize) {
+        self.fired
+            .entry(scene.to_string())
+            .or_default()
+            .insert(index, self.turns);
+    }
+
+    /// Turn action number `index` of `scene` last fired on, `None` if
+    /// it never did.
+    pub fn last_fired(&self, scene: &str, index: usize) -> Option<u64> {
+        self.fired.get(scene)?.get(&index).copied()
+    }
+
+    /// Count a response of action number `index` of `scene`, returns
+    /// the number of earlier responses.
+    pub fn respond(&mut self, scene: &str, index: usize) -> usize {
+        let count = self
+            .responses
+            .entry(scene.to_string())
+            .or_default()
+            .entry(index)
+            .or_default();
+        *count += 1;
+        *count - 1
+    }
+
+    /// Seed the random number generator, to make random responses
+    /// repeatable.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    /// Random number less than `n`, which must not be 0. The
+    /// generator is SplitMix64, its state is part of the game state
+    /// so restored games continue the same sequence.
+    pub fn random(&mut self, n: usize) -> usize {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z % n as u64) as usize
+    }
+
+    /// A new state for the next adventure of a campaign, keeping only
+    /// the given `variables`, the inventory if `inventory` is set,
+    /// the turn count, the players, and the random number generator.
+    pub fn carry_over(
+        &self,
+        variables: &[String],
+        inventory: bool,
+    ) -> GameState {
+        GameState {
+            variables: self
+                .variables
+                .iter()
+                .filter(|(k, _)| variables.contains(k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            items: self
+                .items
+                .iter()
+                .filter(|(_, l)| {
+                    inventory
+                        && matches!(
+                            l,
+                            Location::Inventory | Location::Player(_)
+                        )
+                })
+                .map(|(i, l)| (i.clone(), l.clone()))
+                .collect(),
+            players: self
+                .players
+                .iter()
+                .map(|p| {
+                    p.iter()
+                        .filter(|(k, _)| variables.contains(k))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect()
+                })
+                .collect(),
+            player: self.player,
+            turns: self.turns,
+            rng: self.rng,
+            ..GameState::default()
+        }
+    }
+
+    /// Copy of the current state, to compare with later using
+    /// [`diff`](GameState::diff).
+    pub fn snapshot(&self) -> GameState {
+        self.clone()
+    }
+
+    /// Changes from this state to `other` in variables, items, NPCs
+    /// and visits. Compared with a default state this lists
+    /// everything that has been set.
+    pub fn diff(&self, other: &GameState) -> Vec<Change> {
+        let mut changes: Vec<Change> =
+            diff_maps(&self.variables, &other.variables)
+                .map(|(k, o, n)| Change::Variable(k, o, n))
+                .collect();
+        let none = BTreeMap::new();
+        for p in 0..self.players.len().max(other.players.len()) {
+            let old = self.players.get(p).unwrap_or(&none);
+            let new = other.players.get(p).unwrap_or(&none);
+            changes.extend(diff_maps(old, new).map(|(k, o, n)| {
+                Change::Variable(format!("player{}.{}", p + 1, k), o, n)
+            }));
+        }
+        changes.extend(
+            diff_maps(&self.items, &other.items)
+                .map(|(k, o, n)| Change::Item(k, o, n)),
+        );
+        changes.extend(
+            diff_maps(&self.npcs, &other.npcs)
+                .map(|(k, o, n)| Change::Npc(k, o, n)),
+        );
+        changes.extend(diff_maps(&self.visits, &other.visits).map(
+            |(k, o, n)| {
+                Change::Visits(
+                    k,
+                    o.unwrap_or_default(),
+                    n.unwrap_or_default(),
+                )
+            },
+        ));
+        changes
+    }
+
+    /// Items that have been moved, with their locations.
+    pub fn items(&self) -> impl Iterator<Item = (&str, &Location)> {
+        self.items.iter().map(|(i, l)| (i.as_str(), l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let mut state = GameState::default();
+        state.set("mood", "happy");
+        state.give("key");
+        state.visit("box");
+        let before = state.snapshot();
+        state.set("mood", "sleepy");
+        state.set("lives", "9");
+        state.take("key");
+        state.place(
+            "yarn",
+            Location::Scene {
+                scene: "box".to_string(),
+                container: Some("basket".to_string()),
+            },
+        );
+        state.set_npc("guard", "alerted");
+        state.visit("box");
+        state.tick();
+        let changes: Vec<String> =
+            before.diff(&state).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "variable lives: unset -> 9",
+                "variable mood: happy -> sleepy",
+                "item key: inventory -> nowhere",
+                "item yarn: initial -> basket in box",
+                "npc guard: initial -> alerted",
+                "visits box: 1 -> 2",
+            ]
+        );
+        assert_eq!(
+            state.diff(&before)[0],
+            Change::Variable(
+                "lives".to_string(),
+                Some("9".to_string()),
+                None
+            )
+        );
+        assert!(state.diff(&state.snapshot()).is_empty());
+    }
+
+    #[test]
+    fn players() {
+        let mut state = GameState::default();
+        state.set("mood", "happy");
+        state.set_players(2);
+        let before = state.snapshot();
+        state.set("mood", "grumpy");
+        state.give("key");
+        state.next_player();
+        assert_eq!(state.player(), 1);
+        assert_eq!(state.get("mood"), Some("happy"));
+        assert!(!state.has("key"));
+        assert!(state.give("yarn"));
+        assert!(state.inventory().eq(["yarn"]));
+        state.next_player();
+        assert_eq!(state.player(), 0);
+        assert_eq!(state.get("mood"), Some("grumpy"));
+        assert!(state.inventory().eq(["key"]));
+        assert_eq!(state.location("yarn"), Some(&Location::Player(1)));
+
+        let changes: Vec<String> =
+            before.diff(&state).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "variable player1.mood: unset -> grumpy",
+                "item key: initial -> player 1",
+                "item yarn: initial -> player 2",
+            ]
+        );
+        let next = state.carry_over(&["mood".to_string()], true);
+        assert_eq!(next.get("mood"), Some("grumpy"));
+        assert_eq!(next.players(), 2);
+        assert!(next.has("key"));
+
+        state.set_players(1);
+        assert_eq!(state.players(), 1);
+        assert_eq!(state.get("mood"), Some("happy"));
+    }
+}
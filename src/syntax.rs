@@ -0,0 +1,125 @@
+//! Syntax highlighting definitions for scene files, generated from
+//! the tables the parser uses, so editors stay in sync with the
+//! grammar. `rustventure syntax` prints a TextMate grammar, and
+//! `rustventure syntax --format words` the plain lists of words, e.g.
+//! to generate a tree-sitter grammar.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::condition;
+use crate::expr::Function;
+use crate::scene::{ACTION_KINDS, DIRECTIVES, EFFECTS, MODIFIERS};
+
+/// Scope name of the TextMate grammar.
+pub const SCOPE: &str = "source.rustventure";
+
+/// The words of the scene grammar.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Words {
+    pub directives: Vec<&'static str>,
+    pub action_kinds: Vec<&'static str>,
+    pub effects: Vec<&'static str>,
+    pub modifiers: Vec<&'static str>,
+    pub conditions: Vec<&'static str>,
+    pub functions: Vec<String>,
+}
+
+pub fn words() -> Words {
+    Words {
+        directives: DIRECTIVES.to_vec(),
+        action_kinds: ACTION_KINDS.to_vec(),
+        effects: EFFECTS.to_vec(),
+        modifiers: MODIFIERS.to_vec(),
+        conditions: condition::KEYWORDS.to_vec(),
+        functions: Function::ALL.iter().map(|f| f.to_string()).collect(),
+    }
+}
+
+/// Regular expression alternation matching any of `words`.
+fn any<S: AsRef<str>>(words: &[S]) -> String {
+    let words: Vec<String> =
+        words.iter().map(|w| regex::escape(w.as_ref())).collect();
+    format!("(?:{})", words.join("|"))
+}
+
+/// A TextMate grammar for scene files, as JSON.
+pub fn textmate() -> Value {
+    let w = words();
+    json!({
+        "name": "Rustventure Scene",
+        "scopeName": SCOPE,
+        "fileTypes": ["scene"],
+        "patterns": [
+            {
+                "match": format!(r"^(@{})\b:?", any(&w.directives)),
+                "captures": {"1": {"name": "keyword.other.directive"}}
+            },
+            {
+                "match": r"^(!define)\s+(\w+)",
+                "captures": {
+                    "1": {"name": "keyword.control.macro"},
+                    "2": {"name": "entity.name.macro"}
+                }
+            },
+            {"match": r"!use\s+\w+", "name": "variable.other.macro"},
+            {"match": r"^\s*!test:", "name": "keyword.other.test"},
+            {
+                "match": format!(
+                    r"^\s*(!{})(\[[^\]]*\])?(:)",
+                    any(&w.action_kinds)
+                ),
+                "captures": {
+                    "1": {"name": "keyword.other.action"},
+                    "2": {"name": "entity.other.attribute-name.tag"},
+                    "3": {"name": "punctuation.separator"}
+                }
+            },
+            {
+                "match": format!(r"\s(->)\s+({})\b", any(&w.effects)),
+                "captures": {
+                    "1": {"name": "keyword.operator.arrow"},
+                    "2": {"name": "support.function.effect"}
+                }
+            },
+            {
+                "match": format!(r"\[({})\b", any(&w.modifiers)),
+                "captures": {"1": {"name": "keyword.control.conditional"}}
+            },
+            {
+                "match": format!(r"\b{}\b", any(&w.conditions)),
+                "name": "keyword.operator.condition"
+            },
+            {
+                "match": format!(r"\b({})\(", any(&w.functions)),
+                "captures": {"1": {"name": "support.function"}}
+            },
+            {"match": r"\$\{[^}]*\}", "name": "variable.other.interpolation"}
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn textmate_grammar() {
+        let grammar = textmate();
+        assert_eq!(grammar["scopeName"], SCOPE);
+        let patterns = grammar["patterns"].as_array().unwrap();
+        let matches = |line: &str| {
+            patterns.iter().any(|p| {
+                Regex::new(p["match"].as_str().unwrap())
+                    .unwrap()
+                    .is_match(line)
+            })
+        };
+        assert!(matches("@tags: forest"));
+        assert!(matches("!door[exit=up]:up -> attic"));
+        assert!(matches("x -> pushscene map"));
+        assert!(!matches("Just some description."));
+        assert!(words().functions.contains(&"visits".to_string()));
+    }
+}
@@ -0,0 +1,638 @@
+//! Run the test assertions adventure authors write into scene files,
+//! see [`SceneTest`](crate::scene::SceneTest), and check adventures
+//! for likely mistakes.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::adventure::{self, Adventure};
+use crate::cache::{self, CheckCache, FileCheck};
+use crate::engine::Engine;
+use crate::migrate;
+use crate::pool;
+use crate::scene::graph::{scene_links, SceneGraph};
+use crate::scene::{Effect, Scene, FORMAT};
+use crate::spelling::{self, Dictionary};
+use crate::warning::Warning;
+
+/// Number of passed and failed tests, and what they covered.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub coverage: Vec<SceneCoverage>,
+}
+
+/// How much of a scene the tests exercised.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SceneCoverage {
+    pub scene: String,
+    /// Whether any test entered the scene
+    pub visited: bool,
+    /// Number of actions in the scene
+    pub actions: usize,
+    /// Keywords or expressions of the actions no test triggered
+    pub untriggered: Vec<String>,
+}
+
+impl SceneCoverage {
+    /// Percentage of actions triggered, 100 for scenes without
+    /// actions.
+    pub fn percent(&self) -> f64 {
+        if self.actions == 0 {
+            100.0
+        } else {
+            (self.actions - self.untriggered.len()) as f64 * 100.0
+                / self.actions as f64
+        }
+    }
+}
+
+impl fmt::Display for SceneCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} actions ({:.0}%)",
+            self.scene,
+            self.actions - self.untriggered.len(),
+            self.actions,
+            self.percent()
+        )?;
+        if !self.visited {
+            write!(f, ", never visited")?;
+        }
+        if !self.untriggered.is_empty() {
+            write!(f, "\n  untriggered: {}", self.untriggered.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_scene_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|e| e == "scene")
+}
+
+/// Scene files to test: `path` itself if it is a scene file,
+/// otherwise all scene files of the adventure in `path`.
+pub(crate) fn scene_files(
+    path: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if is_scene_file(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(Path::new("."))
+    };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let p = entry?.path();
+        if is_scene_file(&p) {
+            files.push(p);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Create a fresh engine in `scene`. Scenes that are part of an
+/// adventure get the adventure's items, NPCs and variables. Random
+/// responses always use the same seed, so tests are repeatable.
+fn engine(path: &Path, scene: Scene) -> Result<Engine, Box<dyn Error>> {
+    let mut engine = if is_scene_file(path) {
+        Engine::new(scene)
+    } else {
+        let mut engine = Engine::from_adventure(adventure::load(path)?)?;
+        engine.goto(scene);
+        engine
+    };
+    engine.set_seed(0);
+    Ok(engine)
+}
+
+/// A step of a walkthrough.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Step {
+    /// Input to enter, or the regular expression of the action if no
+    /// example input could be found
+    pub input: String,
+    /// Condition that must be met for the input to work
+    pub condition: Option<String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input)?;
+        if let Some(c) = &self.condition {
+            write!(f, " [if {}]", c)?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the start scene of the adventure at `path`, or the scene
+/// file `path` itself.
+pub(crate) fn start_scene(path: &Path) -> Result<Scene, Box<dyn Error>> {
+    if is_scene_file(path) {
+        Scene::load(path.to_path_buf())
+    } else {
+        adventure::load(path)?.start()
+    }
+}
+
+/// Find the shortest sequence of inputs leading from the start of the
+/// adventure (or the scene file) at `path` to the scene `ending`.
+/// Conditions are ignored when searching, but included in the steps.
+pub fn walkthrough(
+    path: &Path,
+    ending: &str,
+) -> Result<Vec<Step>, Box<dyn Error>> {
+    let graph = SceneGraph::crawl(start_scene(path)?);
+    let start = graph.start().name();
+    let links = graph
+        .shortest_path(start, ending)
+        .ok_or(format!("{} is not reachable from {}", ending, start))?;
+    Ok(links
+        .into_iter()
+        .map(|link| {
+            let scene = graph.scene(&link.from).expect("linked scene");
+            let action = &scene.actions()[link.action];
+            Step {
+                input: action
+                    .example_input()
+                    .unwrap_or_else(|| format!("/{}/", action.source())),
+                condition: action.condition().map(|c| c.to_string()),
+            }
+        })
+        .collect())
+}
+
+/// Check the scene `file`, or take the results from `cache` if the
+/// file didn't change. Returns the key for the cache with the
+/// results.
+fn check_file(
+    file: &Path,
+    cache: &CheckCache,
+) -> Result<(PathBuf, FileCheck), Box<dyn Error>> {
+    let key = file.canonicalize()?;
+    let text = fs::read_to_string(file)?;
+    let hash = cache::hash(text.as_bytes());
+    if let Some(c) = cache.get(&key, hash) {
+        return Ok((key, c.clone()));
+    }
+    let (scene, mut warnings) =
+        Scene::parse_with_warnings(file.to_path_buf(), &text)?;
+    if scene.format() < FORMAT {
+        warnings.extend(migrate::meaning_changes(file, &text));
+    }
+    let links = scene_links(&scene)
+        .iter()
+        .map(|l| l.to().to_string())
+        .collect();
+    Ok((
+        key,
+        FileCheck {
+            hash,
+            warnings,
+            links,
+            words: spelling::scene_words(&scene).into_iter().collect(),
+            npcs: scene_npcs(&scene),
+        },
+    ))
+}
+
+/// NPCs whose state the actions of `scene` change.
+fn scene_npcs(scene: &Scene) -> Vec<String> {
+    let mut npcs = BTreeSet::new();
+    for action in scene.actions() {
+        for effect in [Some(action.effect()), action.otherwise()] {
+            if let Some(Effect::Npc(npc, _, _)) = effect {
+                npcs.insert(npc.clone());
+            }
+        }
+    }
+    npcs.into_iter().collect()
+}
+
+/// Warn about the words of scene `texts` that are neither in the
+/// dictionary for the language of `adventure` (at `path`) nor in its
+/// known words. Warns once instead if there is no such dictionary.
+fn spell_check(
+    adventure: &Adventure,
+    path: &Path,
+    texts: &[(&PathBuf, Vec<String>)],
+) -> Result<Vec<Warning>, Box<dyn Error>> {
+    let Some(language) = adventure.language() else {
+        return Ok(Vec::new());
+    };
+    let dirs = spelling::search_dirs(adventure.dir());
+    let Some(mut dictionary) = Dictionary::load(language, &dirs)? else {
+        return Ok(vec![Warning::new(
+            &adventure::about_file(path),
+            format!("no dictionary for {}, cannot check spelling", language),
+        )]);
+    };
+    for word in adventure.known_words() {
+        dictionary.add(word);
+    }
+    Ok(texts
+        .iter()
+        .flat_map(|(file, words)| {
+            words
+                .iter()
+                .filter(|w| !dictionary.contains(w))
+                .map(|w| Warning::new(file, format!("unknown word: {}", w)))
+        })
+        .collect())
+}
+
+/// Load the adventure or scene file at `path` and all scenes of the
+/// adventure, returning the warnings found, including lines of older
+/// format scenes that `migrate` can't rewrite. Fails on the first
+/// file that can't be loaded. For adventures, also warns about scene
+/// changes to scenes that don't exist, scenes that can't be reached
+/// from the start, NPCs that aren't in `npcs.yaml`, and, with a
+/// `language` in `about.yaml`, words not in the dictionary. Scene
+/// files are parsed with up to `workers` threads, unless `cache` has
+/// results for them.
+pub fn check(
+    path: &Path,
+    workers: usize,
+    cache: &mut CheckCache,
+) -> Result<Vec<Warning>, Box<dyn Error>> {
+    let mut warnings = Vec::new();
+    let adventure = if is_scene_file(path) {
+        None
+    } else {
+        let (a, w) = adventure::load_with_warnings(path)?;
+        warnings.extend(w);
+        Some(a)
+    };
+    let files = scene_files(path)?;
+    let cached: &CheckCache = cache;
+    let results = pool::map(&files, workers, |file| {
+        check_file(file, cached)
+            .map_err(|e| format!("{}: {}", file.display(), e))
+    });
+    let mut scenes = BTreeMap::new();
+    let mut texts = Vec::new();
+    let mut npcs = Vec::new();
+    for (file, result) in files.iter().zip(results) {
+        let (key, c) = result?;
+        warnings.extend(c.warnings.iter().cloned());
+        if let Some(name) = file.file_stem().and_then(|s| s.to_str()) {
+            scenes.insert(name, (file, c.links.clone()));
+        }
+        texts.push((file, c.words.clone()));
+        npcs.push((file, c.npcs.clone()));
+        cache.insert(key, c);
+    }
+
+    if let Some(a) = adventure {
+        let start = a.start_path();
+        let start = start
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|s| scenes.contains_key(s))
+            .ok_or(format!("missing start scene: {}", start.display()))?;
+        let mut reached = BTreeSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(name) = queue.pop_front() {
+            let (file, links) = &scenes[name];
+            for target in links {
+                match scenes.get_key_value(target.as_str()) {
+                    Some((&t, _)) => {
+                        if reached.insert(t) {
+                            queue.push_back(t);
+                        }
+                    }
+                    None => warnings.push(Warning::new(
+                        file,
+                        format!("cannot change to {}: no such scene", target),
+                    )),
+                }
+            }
+        }
+        for (name, (file, _)) in &scenes {
+            if !reached.contains(name) {
+                warnings.push(Warning::new(
+                    file,
+                    "not reachable from the start scene".into(),
+                ));
+            }
+        }
+        for (file, names) in &npcs {
+            for npc in names.iter().filter(|n| !a.npcs().contains_key(*n)) {
+                warnings.push(Warning::new(
+                    file,
+                    format!("unknown NPC: {}", npc),
+                ));
+            }
+        }
+        warnings.extend(spell_check(&a, path, &texts)?);
+    }
+    Ok(warnings)
+}
+
+/// Run the tests in the adventure or scene file at `path`, each
+/// starting with a new engine. Results are reported to `output`, the
+/// returned summary includes which scenes and actions the tests
+/// covered.
+pub fn run<W: Write>(
+    path: &Path,
+    output: &mut W,
+) -> Result<Summary, Box<dyn Error>> {
+    let mut summary = Summary::default();
+    let mut scenes = Vec::new();
+    let mut triggered: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    for file in scene_files(path)? {
+        let scene = Scene::load(file.clone())?;
+        for test in scene.tests() {
+            let mut engine = engine(path, Scene::load(file.clone())?)?;
+            let mut out = Vec::new();
+            for input in test.inputs() {
+                out.clear();
+                engine.step(input, &mut out)?;
+            }
+            let out = String::from_utf8_lossy(&out);
+            if out.contains(test.expected()) {
+                summary.passed += 1;
+                writeln!(output, "{}: {} ... ok", scene.name(), test)?;
+            } else {
+                summary.failed += 1;
+                writeln!(output, "{}: {} ... FAILED", scene.name(), test)?;
+                writeln!(output, "  output: {:?}", out.trim_end())?;
+            }
+            for (s, actions) in engine.triggered() {
+                triggered.entry(s.clone()).or_default().extend(actions);
+            }
+            visited
+                .extend(engine.state().visited().map(|(s, _)| s.to_string()));
+        }
+        scenes.push(scene);
+    }
+
+    let none = BTreeSet::new();
+    for scene in scenes {
+        let t = triggered.get(scene.name()).unwrap_or(&none);
+        summary.coverage.push(SceneCoverage {
+            scene: scene.name().to_string(),
+            visited: visited.contains(scene.name()),
+            actions: scene.actions().len(),
+            untriggered: scene
+                .actions()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !t.contains(i))
+                .map(|(_, a)| a.source().to_string())
+                .collect(),
+        });
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{TempAdventure, ABOUT};
+
+    #[test]
+    fn adventure_tests() {
+        let fixture = TempAdventure::new(
+            "testing",
+            &[
+                ("about.yaml", ABOUT),
+                (
+                    "box.scene",
+                    "A kitten in a box.\n\
+                     !kw:meow -> print \"Meow!\"\n\
+                     !kw:hug -> scene arms\n\
+                     !test: meow => \"Meow!\"\n\
+                     !test: hug; pet => *purr*\n\
+                     !test: hug => A kitten in a box.\n",
+                ),
+                (
+                    "arms.scene",
+                    "A purring kitten.\n!kw:pet -> print *purr*\n\
+                     !kw:squeak -> print Squeak?\n",
+                ),
+                ("lost.scene", "Nowhere.\n!kw:back -> scene box\n"),
+            ],
+        );
+        let dir = fixture.dir();
+
+        let mut output = Vec::new();
+        let summary = run(dir, &mut output);
+        let single = run(&dir.join("box.scene"), &mut Vec::new());
+        let summary = summary.unwrap();
+        assert_eq!((summary.passed, summary.failed), (2, 1));
+        let coverage: Vec<String> =
+            summary.coverage.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            coverage,
+            [
+                "arms: 1/2 actions (50%)\n  untriggered: squeak",
+                "box: 2/2 actions (100%)",
+                "lost: 0/1 actions (0%), never visited\n  untriggered: back"
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "box: meow => \"Meow!\" ... ok\n\
+             box: hug; pet => \"*purr*\" ... ok\n\
+             box: hug => \"A kitten in a box.\" ... FAILED\n  \
+             output: \"A purring kitten.\"\n"
+        );
+        // Without the adventure the scene change still works, the
+        // scene is found next to the tested one.
+        let single = single.unwrap();
+        assert_eq!(single.passed, 2);
+        assert_eq!(single.coverage.len(), 1);
+    }
+
+    #[test]
+    fn check_adventure() {
+        let fixture = TempAdventure::new(
+            "check",
+            &[
+                (
+                    "about.yaml",
+                    "name: Test\nauthor: Fiona\nstart: box.scene\n\
+                     colour: red\n",
+                ),
+                (
+                    "box.scene",
+                    "A box.\n!kw:open -> scene lid\n!kw:jump -> scene pit\n\
+                     !kw:shout -> say Hi! [if awake]\n",
+                ),
+                ("attic.scene", "Dust.\n"),
+                (
+                    "lid.scene",
+                    "@format: 2\n!kw:close -> scene box\n\
+                     !kw:wake -> npc cat -> awake\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let warnings = check(dir, 1, &mut CheckCache::default());
+        fs::write(
+            dir.join("bad.scene"),
+            "Bad.\n!kw:b -> print B\n!kw:a fly away\n",
+        )
+        .unwrap();
+        let failed = check(dir, 4, &mut CheckCache::default());
+
+        assert_eq!(
+            warnings
+                .unwrap()
+                .iter()
+                .map(|w| w.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "unknown key: colour",
+                "line 4: would change meaning in format 2: \
+                 !kw:shout -> say Hi! [if awake]",
+                "empty description",
+                "cannot change to pit: no such scene",
+                "not reachable from the start scene",
+                "unknown NPC: cat",
+            ]
+        );
+        assert!(failed.unwrap_err().to_string().ends_with(
+            "bad.scene: line 3: invalid action line: !kw:a fly away"
+        ));
+    }
+
+    #[test]
+    fn check_cache() {
+        let fixture = TempAdventure::new(
+            "check-cache",
+            &[
+                ("about.yaml", ABOUT),
+                ("box.scene", "A box.\n!kw:open -> scene lid\n"),
+                ("lid.scene", "A lid.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let messages = |warnings: Result<Vec<Warning>, _>| {
+            warnings
+                .unwrap()
+                .into_iter()
+                .map(|w| w.message)
+                .collect::<Vec<String>>()
+        };
+
+        let mut cache = CheckCache::default();
+        let first = check(dir, 2, &mut cache);
+        // A cached result is used as long as the file doesn't change.
+        let lid = dir.join("lid.scene").canonicalize().unwrap();
+        let mut c =
+            cache.get(&lid, cache::hash(b"A lid.\n")).unwrap().clone();
+        c.warnings
+            .push(Warning::new(&lid, "from the cache".to_string()));
+        cache.insert(lid.clone(), c);
+        let second = check(dir, 2, &mut cache);
+        fs::write(&lid, "\n").unwrap();
+        let third = check(dir, 2, &mut cache);
+
+        assert!(messages(first).is_empty());
+        assert_eq!(messages(second), vec!["from the cache"]);
+        assert_eq!(messages(third), vec!["empty description"]);
+    }
+
+    #[test]
+    fn check_spelling() {
+        let fixture = TempAdventure::new(
+            "spelling",
+            &[
+                (
+                    "about.yaml",
+                    "name: Test\nauthor: Fiona\nstart: box.scene\n\
+                     language: en_TEST\nknown_words: [Mrrp]\n",
+                ),
+                (
+                    "box.scene",
+                    "A big box.\n\
+                     !kw:open -> print Mrrp! A kiten jumps out.\n",
+                ),
+            ],
+        );
+        let dir = fixture.dir();
+        let missing = check(dir, 1, &mut CheckCache::default());
+        fs::write(dir.join("en_TEST.dic"), "3\na\nbig\nbox\n").unwrap();
+        fs::write(dir.join("en_TEST.aff"), "SFX S Y 1\nSFX S 0 s .\n")
+            .unwrap();
+        let misspelled = check(dir, 1, &mut CheckCache::default());
+        fs::write(dir.join("en_TEST.dic"), "5\na\nbig\nbox\njump/S\nout\n")
+            .unwrap();
+        let fixed = check(dir, 1, &mut CheckCache::default());
+
+        let messages = |warnings: Result<Vec<Warning>, _>| {
+            warnings
+                .unwrap()
+                .into_iter()
+                .map(|w| w.message)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            messages(missing),
+            vec!["no dictionary for en_TEST, cannot check spelling"]
+        );
+        assert_eq!(
+            messages(misspelled),
+            vec![
+                "unknown word: jumps",
+                "unknown word: kiten",
+                "unknown word: out"
+            ]
+        );
+        assert_eq!(messages(fixed), vec!["unknown word: kiten"]);
+    }
+
+    #[test]
+    fn walkthrough_steps() {
+        let fixture = TempAdventure::new(
+            "walk",
+            &[
+                (
+                    "about.yaml",
+                    "name: Test\nauthor: Fiona\nstart: hall.scene\n",
+                ),
+                (
+                    "hall.scene",
+                    "A hall.\n!kw:north -> scene library\n\
+                     !regex:^(go\\s+west|w)$ -> scene kitchen\n",
+                ),
+                ("library.scene", "Books.\n!kw:south -> scene hall\n"),
+                (
+                    "kitchen.scene",
+                    "@format: 2\n\
+                     A kitchen.\n!kw:down -> scene cellar [needs lamp]\n",
+                ),
+                ("cellar.scene", "Dark.\n"),
+            ],
+        );
+        let dir = fixture.dir();
+        let steps = walkthrough(dir, "cellar");
+        let lost = walkthrough(dir, "attic");
+
+        assert_eq!(
+            steps
+                .unwrap()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>(),
+            vec!["go west", "down [if has lamp]"]
+        );
+        assert_eq!(
+            lost.unwrap_err().to_string(),
+            "attic is not reachable from hall"
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! Conversion from and to Twee, the text format of Twine
+//! stories. Passages become scenes, and links between passages become
+//! keyword actions changing the scene.
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+use crate::adventure::Adventure;
+use crate::scene::graph::SceneGraph;
+use crate::scene::Action;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Passage {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Story {
+    pub title: Option<String>,
+    /// Name of the start passage, if the story data declares one
+    pub start: Option<String>,
+    pub passages: Vec<Passage>,
+}
+
+/// A link in passage text: the text shown and the target passage.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Link {
+    pub text: String,
+    pub target: String,
+}
+
+lazy_static! {
+    static ref LINK_RE: Regex =
+        Regex::new(r"\[\[(.*?)\]\](?:\[.*?\])?").unwrap();
+}
+
+/// Parse the inside of a `[[...]]` link.
+fn parse_link(inner: &str) -> Link {
+    let (text, target) = if let Some((t, target)) = inner.split_once("->") {
+        (t, target)
+    } else if let Some((target, t)) = inner.split_once("<-") {
+        (t, target)
+    } else if let Some((t, target)) = inner.split_once('|') {
+        (t, target)
+    } else {
+        (inner, inner)
+    };
+    Link {
+        text: text.trim().to_string(),
+        target: target.trim().to_string(),
+    }
+}
+
+impl Passage {
+    /// Links in the passage, in order of appearance.
+    pub fn links(&self) -> Vec<Link> {
+        LINK_RE
+            .captures_iter(&self.text)
+            .map(|c| parse_link(&c[1]))
+            .collect()
+    }
+
+    /// The passage text with links replaced by their text.
+    pub fn plain_text(&self) -> String {
+        LINK_RE
+            .replace_all(&self.text, |c: &Captures| parse_link(&c[1]).text)
+            .into_owned()
+    }
+
+    /// Special passages that contain story metadata or code, not
+    /// story text.
+    fn is_special(&self) -> bool {
+        self.name == "StoryTitle"
+            || self.name == "StoryData"
+            || self.tags.iter().any(|t| t == "script" || t == "stylesheet")
+    }
+}
+
+/// Parse a Twee 3 story.
+pub fn parse(text: &str) -> Result<Story, Box<dyn Error>> {
+    lazy_static! {
+        static ref HEADER_RE: Regex =
+            Regex::new(r"^::\s*(.*?)\s*(?:\[([^\]]*)\])?\s*(?:\{.*\})?\s*$")
+                .unwrap();
+        static ref START_RE: Regex =
+            Regex::new(r#""start"\s*:\s*"([^"]*)""#).unwrap();
+    }
+    let mut passages: Vec<Passage> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with("::") {
+            let c = HEADER_RE
+                .captures(line)
+                .ok_or(format!("invalid passage header: {}", line))?;
+            passages.push(Passage {
+                name: c[1].to_string(),
+                tags: c
+                    .get(2)
+                    .map(|t| {
+                        t.as_str()
+                            .split_whitespace()
+                            .map(String::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                text: String::new(),
+            });
+        } else if let Some(p) = passages.last_mut() {
+            p.text.push_str(line);
+            p.text.push('\n');
+        }
+    }
+    for p in passages.iter_mut() {
+        p.text = p.text.trim().to_string();
+    }
+
+    let title = passages
+        .iter()
+        .find(|p| p.name == "StoryTitle")
+        .map(|p| p.text.clone());
+    let start = passages
+        .iter()
+        .find(|p| p.name == "StoryData")
+        .and_then(|p| START_RE.captures(&p.text))
+        .map(|c| c[1].to_string());
+    passages.retain(|p| !p.is_special());
+    if passages.is_empty() {
+        return Err("no passages in story".into());
+    }
+    Ok(Story {
+        title,
+        start,
+        passages,
+    })
+}
+
+/// Turn a passage name into something usable as scene file name.
+fn scene_name(passage: &str) -> String {
+    let name: String = passage
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "passage".to_string()
+    } else {
+        name
+    }
+}
+
+/// Write the story as adventure into `dir`: one scene file per
+/// passage with the passage tags as scene tags, and an `about.yaml`.
+/// Returns the paths of the files written.
+pub fn import(
+    story: &Story,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // Assign unique scene names first, so links can be resolved.
+    let mut names: HashMap<&str, String> = HashMap::new();
+    for p in &story.passages {
+        let base = scene_name(&p.name);
+        let mut name = base.clone();
+        let mut i = 1;
+        while names.values().any(|n| *n == name) {
+            i += 1;
+            name = format!("{}_{}", base, i);
+        }
+        names.insert(&p.name, name);
+    }
+
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for p in &story.passages {
+        let mut scene = String::new();
+        if !p.tags.is_empty() {
+            scene.push_str(&format!("@tags: {}\n", p.tags.join(", ")));
+        }
+        scene.push_str(&p.plain_text());
+        scene.push('\n');
+        let mut keywords = Vec::new();
+        for link in p.links() {
+            let kw = link.text.to_lowercase();
+            if keywords.contains(&kw) {
+                continue;
+            }
+            let target = names
+                .get(link.target.as_str())
+                .ok_or(format!("link to unknown passage: {}", link.target))?;
+            scene.push_str(&format!("!kw:{} -> scene {}\n", kw, target));
+            keywords.push(kw);
+        }
+        let path = dir.join(format!("{}.scene", names[p.name.as_str()]));
+        fs::write(&path, scene)?;
+        written.push(path);
+    }
+
+    let start = story
+        .start
+        .as_deref()
+        .or_else(|| {
+            story
+                .passages
+                .iter()
+                .find(|p| p.name == "Start")
+                .map(|p| p.name.as_str())
+        })
+        .unwrap_or(&story.passages[0].name);
+    let start = names
+        .get(start)
+        .ok_or(format!("unknown start passage: {}", start))?;
+    let mut about = Hash::new();
+    for (key, value) in [
+        ("name", story.title.as_deref().unwrap_or("Imported story")),
+        ("author", "unknown"),
+        ("start", &format!("{}.scene", start)),
+    ] {
+        about.insert(
+            Yaml::String(key.to_string()),
+            Yaml::String(value.to_string()),
+        );
+    }
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&Yaml::Hash(about))?;
+    out.push('\n');
+    let path = dir.join("about.yaml");
+    fs::write(&path, out)?;
+    written.push(path);
+    Ok(written)
+}
+
+/// Text for a link representing `action`: the exit label or keyword
+/// if there is one, otherwise the name of the target scene, because
+/// regular expressions don't make good link text.
+pub(crate) fn link_text<'a>(action: &'a Action, target: &'a str) -> &'a str {
+    action
+        .exit_label()
+        .or_else(|| action.keyword())
+        .unwrap_or(target)
+}
+
+/// Convert the adventure to a Twee story, with one passage per scene
+/// reachable from the start scene. Scene changes become links and
+/// scene tags passage tags, other actions have no equivalent in plain
+/// Twee and are left out.
+pub fn export(adventure: Adventure) -> Result<String, Box<dyn Error>> {
+    let title = adventure.name().to_string();
+    let start = adventure.start()?;
+    let mut out = format!(
+        ":: StoryTitle\n{}\n\n:: StoryData\n{{\"start\": \"{}\"}}\n",
+        title,
+        start.name()
+    );
+    let graph = SceneGraph::crawl(start);
+    if let Some((_, e)) = graph.missing().first_key_value() {
+        return Err(e.as_str().into());
+    }
+    for scene in graph.scenes() {
+        let tags: Vec<&str> = scene.meta().tags().collect();
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", tags.join(" "))
+        };
+        out.push_str(&format!("\n:: {}{}\n{}", scene.name(), tags, scene));
+        for link in graph.links(scene.name()) {
+            let action = &scene.actions()[link.action];
+            out.push_str(&format!(
+                "[[{}->{}]]\n",
+                link_text(action, &link.target),
+                link.target
+            ));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::TempAdventure;
+
+    const STORY: &str = "\
+:: StoryTitle
+Kitten Tale
+
+:: StoryData
+{\"ifid\": \"D674C58C-DEFA-4F70-B7A2-27742230C0FC\", \"start\": \"The Box\"}
+
+:: The Box [cozy] {\"position\":\"100,100\"}
+A kitten peeks out of a box. You could [[pet it->Purring]],
+or [[Leave]].
+
+:: Purring
+*purr*
+[[Back to the box|The Box]]
+
+:: Leave
+The end.
+";
+
+    #[test]
+    fn parse_story() {
+        let story = parse(STORY).unwrap();
+        assert_eq!(story.title.as_deref(), Some("Kitten Tale"));
+        assert_eq!(story.start.as_deref(), Some("The Box"));
+        assert_eq!(story.passages.len(), 3);
+        let p = &story.passages[0];
+        assert_eq!(p.tags, ["cozy"]);
+        assert_eq!(
+            p.links(),
+            [
+                Link {
+                    text: "pet it".to_string(),
+                    target: "Purring".to_string()
+                },
+                Link {
+                    text: "Leave".to_string(),
+                    target: "Leave".to_string()
+                }
+            ]
+        );
+        assert_eq!(
+            p.plain_text(),
+            "A kitten peeks out of a box. You could pet it,\nor Leave."
+        );
+    }
+
+    #[test]
+    fn import_story() {
+        let fixture = TempAdventure::new("twee", &[]);
+        let dir = fixture.path("story");
+        let written = import(&parse(STORY).unwrap(), &dir).unwrap();
+        assert_eq!(written.len(), 4);
+        let box_scene =
+            fs::read_to_string(dir.join("the_box.scene")).unwrap();
+        let about = Adventure::try_from(&dir.join("about.yaml") as &Path);
+        assert_eq!(
+            box_scene,
+            "@tags: cozy\nA kitten peeks out of a box. You could pet it,\nor Leave.\n\
+             !kw:pet it -> scene purring\n!kw:leave -> scene leave\n"
+        );
+        assert_eq!(about.unwrap().to_string(), "\"Kitten Tale\" by unknown");
+    }
+
+    #[test]
+    fn import_title() {
+        let fixture = TempAdventure::new("twee-title", &[]);
+        let dir = fixture.path("story");
+        let title = "Cat\u{7} \"Tale\":\tyes \u{1f408}\\";
+        let mut story = parse(STORY).unwrap();
+        story.title = Some(title.to_string());
+        import(&story, &dir).unwrap();
+        let about = Adventure::try_from(&dir.join("about.yaml") as &Path);
+        assert_eq!(about.unwrap().name(), title);
+    }
+
+    #[test]
+    fn export_kitten() {
+        let about: PathBuf =
+            [env!("CARGO_MANIFEST_DIR"), "resources", "about.yaml"]
+                .iter()
+                .collect();
+        let adventure = Adventure::try_from(about.as_path()).unwrap();
+        let twee = export(adventure).unwrap();
+        assert!(twee.starts_with(
+            ":: StoryTitle\nA cuddly kitten\n\n\
+             :: StoryData\n{\"start\": \"kitten\"}\n"
+        ));
+
+        // The export can be imported again.
+        let story = parse(&twee).unwrap();
+        assert_eq!(story.start.as_deref(), Some("kitten"));
+        let names: Vec<&str> =
+            story.passages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["kitten", "cuddle_cat"]);
+        assert_eq!(
+            story.passages[0].links(),
+            [Link {
+                text: "cuddle_cat".to_string(),
+                target: "cuddle_cat".to_string()
+            }]
+        );
+        assert_eq!(
+            story.passages[1].plain_text(),
+            "*purr*\nThere's a kitten purring in your arms!\nkitten"
+        );
+    }
+}
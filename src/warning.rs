@@ -0,0 +1,34 @@
+//! Non-fatal problems found while loading adventures and scenes, like
+//! unknown keys in `about.yaml`. They don't stop the game, but are
+//! likely mistakes, so `rustventure check` lists them and `-v` logs
+//! them.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A likely mistake in an adventure file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+    /// File the problem was found in
+    pub file: PathBuf,
+    pub message: String,
+}
+
+impl Warning {
+    /// Create a warning, and log it if the `tracing` feature is
+    /// enabled.
+    pub fn new(file: &Path, message: String) -> Warning {
+        warn!(file = %file.display(), "{}", message);
+        Warning {
+            file: file.to_path_buf(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file.display(), self.message)
+    }
+}